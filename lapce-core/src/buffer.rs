@@ -1207,5 +1207,73 @@ pub fn rope_diff(
     Some(changes)
 }
 
+/// A block of rows of a side-by-side diff, where each row holds a line of
+/// the left side, a line of the right side, or both
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffBlock {
+    /// Unchanged lines, side by side
+    Both(Range<usize>, Range<usize>),
+    /// Unchanged lines folded into a single row
+    Skip(Range<usize>, Range<usize>),
+    /// Removed lines next to the lines added in their place, with the
+    /// shorter side padded to the longer one
+    Changed(Range<usize>, Range<usize>),
+}
+
+impl DiffBlock {
+    pub fn rows(&self) -> usize {
+        match self {
+            DiffBlock::Both(_, r) => r.len(),
+            DiffBlock::Skip(_, _) => 1,
+            DiffBlock::Changed(l, r) => l.len().max(r.len()),
+        }
+    }
+}
+
+/// Lines up the changes from [`rope_diff`] for a side-by-side view, pairing
+/// each run of removed lines with the run of added lines next to it.
+pub fn diff_blocks(changes: &[DiffLines]) -> Vec<DiffBlock> {
+    let mut blocks = Vec::with_capacity(changes.len());
+    let mut left_line = 0;
+    let mut right_line = 0;
+    for change in changes {
+        match change {
+            DiffLines::Both(l, r) => {
+                blocks.push(DiffBlock::Both(l.clone(), r.clone()));
+                left_line = l.end;
+                right_line = r.end;
+            }
+            DiffLines::Skip(l, r) => {
+                blocks.push(DiffBlock::Skip(l.clone(), r.clone()));
+                left_line = l.end;
+                right_line = r.end;
+            }
+            DiffLines::Left(l) => {
+                match blocks.last_mut() {
+                    Some(DiffBlock::Changed(left, _)) if left.start == left.end => {
+                        *left = l.clone();
+                    }
+                    _ => blocks
+                        .push(DiffBlock::Changed(l.clone(), right_line..right_line)),
+                }
+                left_line = l.end;
+            }
+            DiffLines::Right(r) => {
+                match blocks.last_mut() {
+                    Some(DiffBlock::Changed(_, right))
+                        if right.start == right.end =>
+                    {
+                        *right = r.clone();
+                    }
+                    _ => blocks
+                        .push(DiffBlock::Changed(left_line..left_line, r.clone())),
+                }
+                right_line = r.end;
+            }
+        }
+    }
+    blocks
+}
+
 #[cfg(test)]
 mod test;
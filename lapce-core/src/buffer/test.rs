@@ -1,5 +1,68 @@
 use super::Buffer;
 
+mod diff {
+    use super::super::{diff_blocks, rope_diff, DiffBlock, DiffLines};
+    use std::sync::{atomic::AtomicU64, Arc};
+    use xi_rope::Rope;
+
+    fn diff(left: &str, right: &str) -> Vec<DiffLines> {
+        rope_diff(
+            Rope::from(left),
+            Rope::from(right),
+            0,
+            Arc::new(AtomicU64::new(0)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pairs_removed_lines_with_added_lines() {
+        let changes = diff("a\nb\nc\nd\n", "a\nx\ny\nc\nd\n");
+        let blocks = diff_blocks(&changes);
+        assert_eq!(
+            blocks,
+            vec![
+                DiffBlock::Both(0..1, 0..1),
+                DiffBlock::Changed(1..2, 1..3),
+                DiffBlock::Both(2..4, 3..5),
+            ]
+        );
+        assert_eq!(blocks.iter().map(|b| b.rows()).sum::<usize>(), 5);
+    }
+
+    #[test]
+    fn pads_lines_only_on_one_side() {
+        let changes = diff("a\nb\nc\n", "a\nc\n");
+        assert_eq!(
+            diff_blocks(&changes),
+            vec![
+                DiffBlock::Both(0..1, 0..1),
+                DiffBlock::Changed(1..2, 1..1),
+                DiffBlock::Both(2..3, 1..2),
+            ]
+        );
+
+        let changes = diff("a\nc\n", "a\nb\nc\n");
+        assert_eq!(
+            diff_blocks(&changes),
+            vec![
+                DiffBlock::Both(0..1, 0..1),
+                DiffBlock::Changed(1..1, 1..2),
+                DiffBlock::Both(1..2, 2..3),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_skipped_lines_in_one_row() {
+        let changes = diff("1\n2\n3\n4\n5\n6\n7\n8\n", "1\n2\n3\n4\n5\n6\n7\nx\n");
+        let blocks = diff_blocks(&changes);
+        assert_eq!(blocks[0], DiffBlock::Skip(0..4, 0..4));
+        assert_eq!(blocks[0].rows(), 1);
+        assert_eq!(blocks.last(), Some(&DiffBlock::Changed(7..8, 7..8)));
+    }
+}
+
 mod editing {
     use super::*;
     use crate::{editor::EditType, selection::Selection};
@@ -245,6 +245,24 @@ pub enum FocusCommand {
     #[strum(message = "Go to Definition")]
     #[strum(serialize = "goto_definition")]
     GotoDefinition,
+    #[strum(message = "Go to Type Definition")]
+    #[strum(serialize = "goto_type_definition")]
+    GotoTypeDefinition,
+    #[strum(message = "Go to Implementation")]
+    #[strum(serialize = "goto_implementation")]
+    GotoImplementation,
+    #[strum(message = "Go to Declaration")]
+    #[strum(serialize = "goto_declaration")]
+    GotoDeclaration,
+    #[strum(message = "Show Call Hierarchy")]
+    #[strum(serialize = "show_call_hierarchy")]
+    ShowCallHierarchy,
+    #[strum(message = "Show Type Hierarchy")]
+    #[strum(serialize = "show_type_hierarchy")]
+    ShowTypeHierarchy,
+    #[strum(message = "Rename Symbol")]
+    #[strum(serialize = "rename_symbol")]
+    RenameSymbol,
     #[strum(serialize = "jump_location_backward")]
     JumpLocationBackward,
     #[strum(serialize = "jump_location_forward")]
@@ -262,6 +280,27 @@ pub enum FocusCommand {
     #[strum(message = "Toggle Code Lens")]
     #[strum(serialize = "toggle_code_lens")]
     ToggleCodeLens,
+    #[strum(message = "Toggle Blame Gutter")]
+    #[strum(serialize = "toggle_blame")]
+    ToggleBlame,
+    #[strum(message = "Toggle Side-by-Side Diff")]
+    #[strum(serialize = "toggle_side_by_side_diff")]
+    ToggleSideBySideDiff,
+    #[strum(message = "Toggle Inlay Hints")]
+    #[strum(serialize = "toggle_inlay_hints")]
+    ToggleInlayHints,
+    #[strum(message = "Fold")]
+    #[strum(serialize = "fold_code")]
+    FoldCode,
+    #[strum(message = "Unfold")]
+    #[strum(serialize = "unfold_code")]
+    UnfoldCode,
+    #[strum(message = "Fold All")]
+    #[strum(serialize = "fold_all")]
+    FoldAll,
+    #[strum(message = "Unfold All")]
+    #[strum(serialize = "unfold_all")]
+    UnfoldAll,
     #[strum(serialize = "format_document")]
     #[strum(message = "Format Document")]
     FormatDocument,
@@ -332,4 +371,10 @@ pub enum MultiSelectionCommand {
     SelectSkipCurrent,
     #[strum(serialize = "select_all")]
     SelectAll,
+    #[strum(message = "Expand Selection")]
+    #[strum(serialize = "expand_selection")]
+    ExpandSelection,
+    #[strum(message = "Shrink Selection")]
+    #[strum(serialize = "shrink_selection")]
+    ShrinkSelection,
 }
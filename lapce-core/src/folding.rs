@@ -0,0 +1,423 @@
+use crate::{
+    buffer::InvalLines,
+    lens::{Lens, LensBuilder},
+};
+
+/// Lines that can be folded away, leaving only their first line visible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldRange {
+    /// The line that stays visible when the range is folded
+    pub start_line: usize,
+    /// The last line hidden when the range is folded
+    pub end_line: usize,
+    pub folded: bool,
+}
+
+impl FoldRange {
+    fn contains(&self, line: usize) -> bool {
+        self.start_line <= line && line <= self.end_line
+    }
+}
+
+/// The fold ranges of a document, and the mapping between the lines of the
+/// document and the lines actually shown, which skip the folded ones.
+///
+/// A shown line with a code lens takes two rows, the code lens being drawn
+/// above its text, so the rows the editor paints are counted apart from the
+/// shown lines that cursor movements go by.
+#[derive(Clone, Default)]
+pub struct Folding {
+    /// Sorted by start line, the outer ranges before the inner ones
+    ranges: Vec<FoldRange>,
+    total_lines: usize,
+    /// Each shown line has a height of 1 and each hidden one a height of 0,
+    /// so that heights are counted in shown lines. None when nothing is
+    /// folded.
+    lens: Option<Lens>,
+    /// The sorted lines that have a code lens
+    code_lens_lines: Vec<usize>,
+    /// The row of the code lens of each shown line that has one
+    code_lens_rows: Vec<(usize, usize)>,
+}
+
+impl Folding {
+    pub fn new(total_lines: usize) -> Self {
+        Self {
+            ranges: Vec::new(),
+            total_lines,
+            lens: None,
+            code_lens_lines: Vec::new(),
+            code_lens_rows: Vec::new(),
+        }
+    }
+
+    pub fn ranges(&self) -> &[FoldRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn has_folded(&self) -> bool {
+        self.lens.is_some()
+    }
+
+    /// Replaces the ranges with the ones from the language server or the
+    /// syntax tree, keeping folded the ones that start on a folded line.
+    pub fn set_ranges(&mut self, ranges: Vec<(usize, usize)>) {
+        let mut ranges: Vec<FoldRange> = ranges
+            .into_iter()
+            .filter(|(start, end)| end > start)
+            .map(|(start_line, end_line)| FoldRange {
+                start_line,
+                end_line,
+                folded: self.ranges.iter().any(|r| {
+                    r.folded && r.start_line == start_line && r.end_line == end_line
+                }),
+            })
+            .collect();
+        ranges.sort_by(|a, b| {
+            a.start_line
+                .cmp(&b.start_line)
+                .then(b.end_line.cmp(&a.end_line))
+        });
+        ranges.dedup_by(|a, b| {
+            a.start_line == b.start_line && a.end_line == b.end_line
+        });
+        self.ranges = ranges;
+        self.update_lens();
+    }
+
+    /// Sets the lines that get a row for their code lens above them, which
+    /// the document sets again after each edit.
+    pub fn set_code_lens_lines(&mut self, mut lines: Vec<usize>) {
+        lines.sort_unstable();
+        lines.dedup();
+        self.code_lens_lines = lines;
+        self.update_lens();
+    }
+
+    /// Moves the ranges along with the lines of an edit, and drops the ones
+    /// that started in the lines it replaced.
+    pub fn update_lines(&mut self, inval_lines: &InvalLines) {
+        let start = inval_lines.start_line;
+        let end = start + inval_lines.inval_count;
+        let new_end = start + inval_lines.new_count;
+        let shift = |line: usize| {
+            if line >= end {
+                line + new_end - end
+            } else {
+                line.min(new_end.max(start + 1) - 1)
+            }
+        };
+
+        self.total_lines = self.total_lines + new_end - end;
+        self.ranges = self
+            .ranges
+            .iter()
+            .filter(|r| r.start_line <= start || r.start_line >= end)
+            .map(|r| FoldRange {
+                start_line: shift(r.start_line),
+                end_line: shift(r.end_line),
+                folded: r.folded,
+            })
+            .filter(|r| r.end_line > r.start_line)
+            .collect();
+        self.update_lens();
+    }
+
+    /// The range starting on the line that the gutter shows a marker for,
+    /// the outermost one if there are several.
+    pub fn range_at(&self, line: usize) -> Option<&FoldRange> {
+        self.ranges.iter().find(|r| r.start_line == line)
+    }
+
+    /// Folds the innermost unfolded range the line is in.
+    pub fn fold(&mut self, line: usize) -> bool {
+        match self
+            .ranges
+            .iter_mut()
+            .rev()
+            .find(|r| !r.folded && r.contains(line))
+        {
+            Some(range) => {
+                range.folded = true;
+                self.update_lens();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unfolds the ranges starting on the line, or else the innermost
+    /// folded range the line is in.
+    pub fn unfold(&mut self, line: usize) -> bool {
+        let mut changed = false;
+        for range in self.ranges.iter_mut() {
+            if range.folded && range.start_line == line {
+                range.folded = false;
+                changed = true;
+            }
+        }
+        if !changed {
+            if let Some(range) = self
+                .ranges
+                .iter_mut()
+                .rev()
+                .find(|r| r.folded && r.contains(line))
+            {
+                range.folded = false;
+                changed = true;
+            }
+        }
+        if changed {
+            self.update_lens();
+        }
+        changed
+    }
+
+    /// Folds or unfolds the outermost range starting on the line.
+    pub fn toggle(&mut self, line: usize) -> bool {
+        match self.ranges.iter_mut().find(|r| r.start_line == line) {
+            Some(range) => {
+                range.folded = !range.folded;
+                self.update_lens();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        for range in self.ranges.iter_mut() {
+            range.folded = true;
+        }
+        self.update_lens();
+    }
+
+    pub fn unfold_all(&mut self) {
+        for range in self.ranges.iter_mut() {
+            range.folded = false;
+        }
+        self.update_lens();
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        match self.lens.as_ref() {
+            Some(lens) if line < lens.len() => {
+                lens.height_of_line(line + 1) == lens.height_of_line(line)
+            }
+            _ => false,
+        }
+    }
+
+    /// The index of the line among the shown ones. A hidden line gets the
+    /// index of the next shown line.
+    pub fn visual_line(&self, line: usize) -> usize {
+        match self.lens.as_ref() {
+            Some(lens) if line > self.total_lines => {
+                lens.height_of_line(self.total_lines) + line - self.total_lines
+            }
+            Some(lens) => lens.height_of_line(line),
+            None => line,
+        }
+    }
+
+    /// The line of the document shown at the index.
+    pub fn actual_line(&self, visual_line: usize) -> usize {
+        match self.lens.as_ref() {
+            Some(lens) => {
+                let line = lens.line_of_height(visual_line);
+                if line >= self.total_lines {
+                    // Past the end, where lines count as if nothing was
+                    // folded.
+                    self.total_lines + visual_line - self.visual_lines()
+                } else {
+                    line
+                }
+            }
+            None => visual_line,
+        }
+    }
+
+    /// The number of lines shown.
+    pub fn visual_lines(&self) -> usize {
+        self.visual_line(self.total_lines)
+    }
+
+    /// The line itself if it's shown, or else the line of the fold hiding
+    /// it.
+    pub fn prev_visible_line(&self, line: usize) -> usize {
+        if !self.is_hidden(line) {
+            return line;
+        }
+        self.actual_line(self.visual_line(line) - 1)
+    }
+
+    /// The line itself if it's shown, or else the first shown line after
+    /// the fold hiding it.
+    pub fn next_visible_line(&self, line: usize) -> usize {
+        if !self.is_hidden(line) {
+            return line;
+        }
+        let next = self.visual_line(line);
+        if next >= self.visual_lines() {
+            self.prev_visible_line(line)
+        } else {
+            self.actual_line(next)
+        }
+    }
+
+    /// Whether the line is shown with a code lens above it.
+    pub fn has_code_lens(&self, line: usize) -> bool {
+        self.code_lens_rows
+            .binary_search_by_key(&line, |(l, _)| *l)
+            .is_ok()
+    }
+
+    /// The row the text of the line is drawn on, which is its shown line
+    /// moved down by the code lenses above it.
+    pub fn row_of_line(&self, line: usize) -> usize {
+        let code_lenses = self.code_lens_rows.partition_point(|(l, _)| *l <= line);
+        self.visual_line(line) + code_lenses
+    }
+
+    /// The line drawn on the row, and whether the row is the one of its code
+    /// lens rather than the one of its text.
+    pub fn line_of_row(&self, row: usize) -> (usize, bool) {
+        let code_lenses = self.code_lens_rows.partition_point(|(_, r)| *r <= row);
+        if code_lenses > 0 {
+            let (line, code_lens_row) = self.code_lens_rows[code_lenses - 1];
+            if code_lens_row == row {
+                return (line, true);
+            }
+        }
+        (self.actual_line(row - code_lenses), false)
+    }
+
+    /// The number of rows drawn.
+    pub fn rows(&self) -> usize {
+        self.visual_lines() + self.code_lens_rows.len()
+    }
+
+    fn update_lens(&mut self) {
+        self.update_folded_lens();
+
+        let mut code_lens_rows = Vec::new();
+        for &line in self.code_lens_lines.iter() {
+            if line < self.total_lines && !self.is_hidden(line) {
+                let row = self.visual_line(line) + code_lens_rows.len();
+                code_lens_rows.push((line, row));
+            }
+        }
+        self.code_lens_rows = code_lens_rows;
+    }
+
+    fn update_folded_lens(&mut self) {
+        let mut hidden: Vec<(usize, usize)> = Vec::new();
+        for range in self.ranges.iter().filter(|r| r.folded) {
+            let start = range.start_line + 1;
+            let end = (range.end_line + 1).min(self.total_lines);
+            if start >= end {
+                continue;
+            }
+            match hidden.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => hidden.push((start, end)),
+            }
+        }
+        if hidden.is_empty() {
+            self.lens = None;
+            return;
+        }
+
+        let mut builder = LensBuilder::new();
+        let mut line = 0;
+        for (start, end) in hidden {
+            if start > line {
+                builder.add_section(start - line, 1);
+            }
+            builder.add_section(end - start, 0);
+            line = end;
+        }
+        if line < self.total_lines {
+            builder.add_section(self.total_lines - line, 1);
+        }
+        self.lens = Some(builder.build());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        let mut folding = Folding::new(10);
+        folding.set_ranges(vec![(1, 7), (2, 4)]);
+        assert!(!folding.has_folded());
+
+        assert!(folding.fold(3));
+        assert!(folding.is_hidden(3));
+        assert!(!folding.is_hidden(2));
+        assert!(!folding.is_hidden(5));
+        assert_eq!(3, folding.visual_line(5));
+        assert_eq!(5, folding.actual_line(3));
+        assert_eq!(2, folding.prev_visible_line(4));
+        assert_eq!(5, folding.next_visible_line(3));
+
+        assert!(folding.fold(2));
+        assert_eq!(8, folding.actual_line(2));
+        assert_eq!(4, folding.visual_lines());
+
+        assert!(folding.unfold(1));
+        assert_eq!(8, folding.visual_lines());
+        folding.unfold_all();
+        assert!(!folding.has_folded());
+    }
+
+    #[test]
+    fn test_fold_update_lines() {
+        let mut folding = Folding::new(10);
+        folding.set_ranges(vec![(5, 8)]);
+        folding.toggle(5);
+        folding.update_lines(&InvalLines {
+            start_line: 1,
+            inval_count: 1,
+            new_count: 3,
+        });
+        assert_eq!(
+            &[FoldRange {
+                start_line: 7,
+                end_line: 10,
+                folded: true,
+            }],
+            folding.ranges()
+        );
+        assert!(folding.is_hidden(10));
+        assert!(!folding.is_hidden(11));
+    }
+
+    #[test]
+    fn test_code_lens_rows() {
+        let mut folding = Folding::new(10);
+        folding.set_ranges(vec![(2, 4)]);
+        folding.set_code_lens_lines(vec![6, 1, 3]);
+        assert_eq!(13, folding.rows());
+        assert_eq!(0, folding.row_of_line(0));
+        assert_eq!(2, folding.row_of_line(1));
+        assert_eq!((1, true), folding.line_of_row(1));
+        assert_eq!((1, false), folding.line_of_row(2));
+        assert_eq!(9, folding.row_of_line(6));
+        assert_eq!((6, true), folding.line_of_row(8));
+
+        // The code lens of a folded line isn't shown.
+        folding.toggle(2);
+        assert!(!folding.has_code_lens(3));
+        assert_eq!(10, folding.rows());
+        assert_eq!((5, false), folding.line_of_row(4));
+        assert_eq!(6, folding.row_of_line(6));
+        assert_eq!(4, folding.visual_line(6));
+    }
+}
@@ -45,6 +45,7 @@ use crate::style::HighlightConfiguration;
 //            indent: "    ",
 //            code_lens: (&[/* ... */], &[/* ... */]),
 //            extensions: &["foo"],
+//            lsp_id: "foo",
 //        },
 //    ];
 //
@@ -86,6 +87,9 @@ struct SyntaxProperties {
     /// File name extensions to determine the language.  `["py"]` for python,
     /// `["rs"]` for rust, for example.
     extensions: &'static [&'static str],
+    /// The language id the language servers know the documents by.  "rust"
+    /// for rust, "typescriptreact" for tsx, for example.
+    lsp_id: &'static str,
 }
 
 // NOTE: Keep the enum variants "fieldless" so they can cast to usize as array
@@ -147,6 +151,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
             &["source_file", "use_declaration", "line_comment"],
         ),
         extensions: &["rs"],
+        lsp_id: "rust",
     },
     #[cfg(feature = "lang-go")]
     SyntaxProperties {
@@ -166,6 +171,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
             &["source_file", "comment", "line_comment"],
         ),
         extensions: &["go"],
+        lsp_id: "go",
     },
     #[cfg(feature = "lang-javascript")]
     SyntaxProperties {
@@ -176,6 +182,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (&["source_file", "program"], &["source_file"]),
         extensions: &["js"],
+        lsp_id: "javascript",
     },
     #[cfg(feature = "lang-javascript")]
     SyntaxProperties {
@@ -186,6 +193,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (&["source_file", "program"], &["source_file"]),
         extensions: &["jsx"],
+        lsp_id: "javascriptreact",
     },
     #[cfg(feature = "lang-typescript")]
     SyntaxProperties {
@@ -196,6 +204,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (&["source_file", "program"], &["source_file"]),
         extensions: &["ts"],
+        lsp_id: "typescript",
     },
     #[cfg(feature = "lang-typescript")]
     SyntaxProperties {
@@ -206,6 +215,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (&["source_file", "program"], &["source_file"]),
         extensions: &["tsx"],
+        lsp_id: "typescriptreact",
     },
     #[cfg(feature = "lang-python")]
     SyntaxProperties {
@@ -227,6 +237,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
             &["source_file", "import_statement", "import_from_statement"],
         ),
         extensions: &["py"],
+        lsp_id: "python",
     },
     #[cfg(feature = "lang-toml")]
     SyntaxProperties {
@@ -237,6 +248,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["toml"],
+        lsp_id: "toml",
     },
     #[cfg(feature = "lang-php")]
     SyntaxProperties {
@@ -247,6 +259,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["php"],
+        lsp_id: "php",
     },
     #[cfg(feature = "lang-elixir")]
     SyntaxProperties {
@@ -257,6 +270,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["ex", "exs", "eex", "heex", "sface"],
+        lsp_id: "elixir",
     },
     #[cfg(feature = "lang-c")]
     SyntaxProperties {
@@ -267,6 +281,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["c"],
+        lsp_id: "c",
     },
     #[cfg(feature = "lang-cpp")]
     SyntaxProperties {
@@ -277,6 +292,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["cpp", "cxx", "cc", "c++", "hpp", "hxx", "hh", "h++"],
+        lsp_id: "cpp",
     },
     #[cfg(feature = "lang-json")]
     SyntaxProperties {
@@ -287,6 +303,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["json"],
+        lsp_id: "json",
     },
     #[cfg(feature = "lang-md")]
     SyntaxProperties {
@@ -297,6 +314,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["md"],
+        lsp_id: "markdown",
     },
     #[cfg(feature = "lang-ruby")]
     SyntaxProperties {
@@ -307,6 +325,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["rb"],
+        lsp_id: "ruby",
     },
     #[cfg(feature = "lang-html")]
     SyntaxProperties {
@@ -317,6 +336,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "    ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["html", "htm"],
+        lsp_id: "html",
     },
     #[cfg(feature = "lang-java")]
     SyntaxProperties {
@@ -327,6 +347,7 @@ const LANGUAGES: &[SyntaxProperties] = &[
         indent: "  ",
         code_lens: (DEFAULT_CODE_LENS_LIST, DEFAULT_CODE_LENS_IGNORE_LIST),
         extensions: &["java"],
+        lsp_id: "java",
     },
 ];
 
@@ -366,6 +387,10 @@ impl LapceLanguage {
         self.properties().indent
     }
 
+    pub fn lsp_id(&self) -> &'static str {
+        self.properties().lsp_id
+    }
+
     pub(crate) fn new_parser(&self) -> Parser {
         let language = (self.properties().language)();
         let mut parser = Parser::new();
@@ -7,6 +7,7 @@ use std::{
 
 use itertools::Itertools;
 use lapce_rpc::style::Style;
+use lsp_types::SymbolKind;
 use tree_sitter::{Node, Parser, Point, Tree};
 use xi_rope::{
     spans::{Spans, SpansBuilder},
@@ -285,6 +286,109 @@ impl Syntax {
         builder.build()
     }
 
+    pub fn rev(&self) -> u64 {
+        self.rev
+    }
+
+    /// The byte ranges of the identifiers in the document named like the
+    /// one at the offset, the offset being inside it or just after it.
+    pub fn find_identifier_occurrences(
+        &self,
+        offset: usize,
+    ) -> Option<Vec<(usize, usize)>> {
+        fn is_identifier(node: &Node) -> bool {
+            node.child_count() == 0 && node.kind().ends_with("identifier")
+        }
+
+        let tree = self.tree.as_ref()?;
+        let root = tree.root_node();
+        let node = root
+            .descendant_for_byte_range(offset, offset)
+            .filter(is_identifier)
+            .or_else(|| {
+                root.descendant_for_byte_range(offset.checked_sub(1)?, offset)
+                    .filter(is_identifier)
+            })?;
+        let name = self.text.slice_to_cow(node.start_byte()..node.end_byte());
+
+        let mut occurrences = Vec::new();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            if is_identifier(&node)
+                && node.end_byte() - node.start_byte() == name.len()
+                && self.text.slice_to_cow(node.start_byte()..node.end_byte()) == name
+            {
+                occurrences.push((node.start_byte(), node.end_byte()));
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Some(occurrences);
+                }
+            }
+        }
+    }
+
+    /// The line ranges of the bracketed blocks and the comments spanning
+    /// several lines, for folding when no language server provides them.
+    /// A block's closing bracket stays visible when it's folded.
+    pub fn folding_ranges(&self) -> Vec<(usize, usize)> {
+        let tree = match self.tree.as_ref() {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+
+        let mut ranges = Vec::new();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let start_line = node.start_position().row;
+            let end_line = node.end_position().row;
+            if end_line > start_line {
+                if node.kind().contains("comment") {
+                    ranges.push((start_line, end_line));
+                } else if let (Some(first), Some(last)) = (
+                    node.child(0),
+                    node.child(node.child_count().saturating_sub(1)),
+                ) {
+                    let is_block = matches!(
+                        (first.kind(), last.kind()),
+                        ("{", "}") | ("(", ")") | ("[", "]")
+                    );
+                    if is_block && end_line > start_line + 1 {
+                        ranges.push((start_line, end_line - 1));
+                    }
+                }
+            }
+
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return ranges;
+                }
+            }
+        }
+    }
+
+    /// The range of the smallest node around the range that is bigger than
+    /// it, for growing a selection one node at a time.
+    pub fn expand_range(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+        let tree = self.tree.as_ref()?;
+        let mut node = tree.root_node().descendant_for_byte_range(start, end)?;
+        loop {
+            let range = (node.start_byte(), node.end_byte());
+            if range.0 <= start && range.1 >= end && range != (start, end) {
+                return Some(range);
+            }
+            node = node.parent()?;
+        }
+    }
+
     pub fn find_matching_pair(&self, offset: usize) -> Option<usize> {
         let tree = self.tree.as_ref()?;
         let node = tree
@@ -367,6 +471,122 @@ impl Syntax {
     }
 }
 
+/// A definition found in the syntax tree, for when no language server can
+/// tell where the symbols are.
+#[derive(Clone, Debug)]
+pub struct SyntaxSymbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    /// The byte range of the name
+    pub start: usize,
+    pub end: usize,
+    pub container_name: Option<String>,
+}
+
+/// Finds the definitions in the text by their node kinds. Only the parse is
+/// done, so that this stays cheap enough to run over a whole workspace.
+pub fn find_symbols(language: LapceLanguage, text: &str) -> Vec<SyntaxSymbol> {
+    let tree = PARSER.with(|parsers| {
+        let mut parsers = parsers.borrow_mut();
+        parsers
+            .entry(language)
+            .or_insert_with(|| language.new_parser())
+            .parse(text, None)
+    });
+    let tree = match tree {
+        Some(tree) => tree,
+        None => return Vec::new(),
+    };
+
+    let mut symbols = Vec::new();
+    // The end byte and the name of the definitions the cursor is in.
+    let mut containers: Vec<(usize, String)> = Vec::new();
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        while containers
+            .last()
+            .map(|(end, _)| *end <= node.start_byte())
+            .unwrap_or(false)
+        {
+            containers.pop();
+        }
+
+        let kind = symbol_kind(node.kind());
+        // Rust's impl blocks aren't symbols, but they contain the methods.
+        let is_impl = node.kind() == "impl_item";
+        if kind.is_some() || is_impl {
+            let field = if is_impl { "type" } else { "name" };
+            if let Some(name_node) = node.child_by_field_name(field) {
+                let name = text
+                    .get(name_node.start_byte()..name_node.end_byte())
+                    .unwrap_or("")
+                    .to_string();
+                if let Some(kind) = kind {
+                    symbols.push(SyntaxSymbol {
+                        kind,
+                        name: name.clone(),
+                        start: name_node.start_byte(),
+                        end: name_node.end_byte(),
+                        container_name: containers
+                            .last()
+                            .map(|(_, name)| name.clone()),
+                    });
+                }
+                containers.push((node.end_byte(), name));
+            }
+        }
+
+        if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return symbols;
+            }
+        }
+    }
+}
+
+fn symbol_kind(node_kind: &str) -> Option<SymbolKind> {
+    // Expressions can have a name too, like Rust's struct expressions.
+    const DEFINITION_SUFFIXES: &[&str] = &[
+        "_item",
+        "_declaration",
+        "_definition",
+        "_specifier",
+        "_spec",
+        "variant",
+    ];
+    if !DEFINITION_SUFFIXES
+        .iter()
+        .any(|suffix| node_kind.ends_with(suffix))
+    {
+        return None;
+    }
+
+    let kind = match node_kind {
+        _ if node_kind.contains("variant") => SymbolKind::EnumMember,
+        _ if node_kind.contains("method") => SymbolKind::Method,
+        _ if node_kind.contains("function") => SymbolKind::Function,
+        _ if node_kind.contains("struct") => SymbolKind::Struct,
+        _ if node_kind.contains("class") => SymbolKind::Class,
+        _ if node_kind.contains("enum") => SymbolKind::Enum,
+        _ if node_kind.contains("trait") || node_kind.contains("interface") => {
+            SymbolKind::Interface
+        }
+        _ if node_kind.starts_with("mod") || node_kind.contains("namespace") => {
+            SymbolKind::Module
+        }
+        _ if node_kind.starts_with("const") || node_kind.starts_with("static") => {
+            SymbolKind::Constant
+        }
+        _ if node_kind.starts_with("type") => SymbolKind::TypeParameter,
+        _ => return None,
+    };
+    Some(kind)
+}
+
 pub fn matching_pair_direction(c: char) -> Option<bool> {
     Some(match c {
         '{' => true,
@@ -473,4 +693,49 @@ mod tests {
         assert_eq!(Some((90, 25)), iter.next());
         assert_eq!(None, iter.next());
     }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_expand_range() {
+        let text = Rope::from("fn a() { b(c, d); }");
+        let syntax = Syntax::from_language(LapceLanguage::Rust).parse(1, text, None);
+
+        // From the caret in `c` to `c`, the arguments, the call, the block
+        // and the function.
+        assert_eq!(Some((11, 12)), syntax.expand_range(11, 11));
+        assert_eq!(Some((10, 16)), syntax.expand_range(11, 12));
+        assert_eq!(Some((9, 16)), syntax.expand_range(10, 16));
+        assert_eq!(Some((7, 19)), syntax.expand_range(9, 16));
+        assert_eq!(Some((0, 19)), syntax.expand_range(7, 19));
+        assert_eq!(None, syntax.expand_range(0, 19));
+
+        // A range across siblings grows to their parent.
+        assert_eq!(Some((10, 16)), syntax.expand_range(11, 15));
+
+        let syntax = Syntax::from_language(LapceLanguage::Rust);
+        assert_eq!(None, syntax.expand_range(0, 0));
+    }
+
+    #[test]
+    #[cfg(feature = "lang-rust")]
+    fn test_find_identifier_occurrences() {
+        let text = Rope::from("fn a(b: u8) -> u8 { let bb = b; b + bb }");
+        let syntax = Syntax::from_language(LapceLanguage::Rust).parse(1, text, None);
+
+        // From the start of `b` or just after it, but not `bb`.
+        let b = Some(vec![(5, 6), (29, 30), (32, 33)]);
+        assert_eq!(b, syntax.find_identifier_occurrences(5));
+        assert_eq!(b, syntax.find_identifier_occurrences(30));
+        assert_eq!(
+            Some(vec![(24, 26), (36, 38)]),
+            syntax.find_identifier_occurrences(37)
+        );
+
+        // Keywords and types aren't identifiers.
+        assert_eq!(None, syntax.find_identifier_occurrences(1));
+        assert_eq!(None, syntax.find_identifier_occurrences(9));
+
+        let syntax = Syntax::from_language(LapceLanguage::Rust);
+        assert_eq!(None, syntax.find_identifier_occurrences(0));
+    }
 }
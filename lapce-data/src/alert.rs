@@ -14,6 +14,20 @@ pub struct AlertContentData {
     pub title: String,
     pub msg: String,
     pub buttons: Vec<(String, WidgetId, LapceCommand)>,
+    /// What runs when the alert is dismissed without picking a button
+    pub cancel: Option<(WidgetId, LapceCommand)>,
+}
+
+impl AlertContentData {
+    pub fn cancel(&self, ctx: &mut EventCtx) {
+        if let Some((widget_id, command)) = self.cancel.as_ref() {
+            ctx.submit_command(Command::new(
+                LAPCE_COMMAND,
+                command.clone(),
+                Target::Widget(*widget_id),
+            ));
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -42,6 +56,7 @@ impl AlertData {
                 title: "".to_string(),
                 msg: "".to_string(),
                 buttons: Vec::new(),
+                cancel: None,
             },
         }
     }
@@ -69,6 +84,7 @@ impl KeyPressFocus for AlertFocusData {
         _env: &Env,
     ) -> CommandExecuted {
         if let CommandKind::Focus(FocusCommand::ModalClose) = command.kind {
+            self.alert.content.cancel(ctx);
             ctx.submit_command(Command::new(
                 LAPCE_COMMAND,
                 LapceCommand {
@@ -9,12 +9,22 @@ use lapce_core::command::{
 };
 use lapce_core::syntax::Syntax;
 use lapce_rpc::{
-    buffer::BufferId, file::FileNodeItem, plugin::PluginDescription,
-    source_control::DiffInfo, style::Style, terminal::TermId,
+    buffer::BufferId,
+    core::LspStatus,
+    file::FileNodeItem,
+    plugin::PluginDescription,
+    proxy::InlayHint,
+    source_control::{BlameHunk, DiffInfo},
+    style::Style,
+    terminal::TermId,
+    RequestId,
 };
 use lsp_types::{
-    CodeActionOrCommand, CodeActionResponse, CompletionItem, CompletionResponse,
-    Location, Position, ProgressParams, PublishDiagnosticsParams, TextEdit,
+    CallHierarchyItem, CodeAction, CodeActionOrCommand, CodeActionResponse,
+    CodeLens, CompletionItem, CompletionResponse, DocumentHighlight, Location,
+    Position, PrepareRenameResponse, ProgressParams, PublishDiagnosticsParams,
+    SelectionRange, ShowMessageRequestParams, SignatureHelp, TextEdit,
+    WorkspaceEdit,
 };
 use serde_json::Value;
 use strum::{self, EnumMessage, IntoEnumIterator};
@@ -33,6 +43,7 @@ use crate::{
     palette::{PaletteItem, PaletteType},
     proxy::ProxyStatus,
     search::Match,
+    signature::SignatureTriggers,
     split::{SplitDirection, SplitMoveDirection},
 };
 
@@ -93,8 +104,10 @@ impl LapceCommand {
                 LapceWorkbenchCommand::Palette
                 | LapceWorkbenchCommand::PaletteLine
                 | LapceWorkbenchCommand::PaletteSymbol
+                | LapceWorkbenchCommand::PaletteWorkspaceSymbol
                 | LapceWorkbenchCommand::PaletteCommand
                 | LapceWorkbenchCommand::ChangeTheme
+                | LapceWorkbenchCommand::OpenLanguageServerLog
                 | LapceWorkbenchCommand::ConnectSshHost
                 | LapceWorkbenchCommand::ConnectWsl
                 | LapceWorkbenchCommand::PaletteWorkspace => return true,
@@ -261,6 +274,18 @@ pub enum LapceWorkbenchCommand {
     #[strum(message = "Disconnect From Remote")]
     DisconnectRemote,
 
+    #[strum(serialize = "restart_language_server")]
+    #[strum(message = "Restart Language Server")]
+    RestartLanguageServer,
+
+    #[strum(serialize = "open_language_server_log")]
+    #[strum(message = "Open Language Server Log")]
+    OpenLanguageServerLog,
+
+    #[strum(serialize = "toggle_language_server_trace")]
+    #[strum(message = "Toggle Language Server Message Trace")]
+    ToggleLanguageServerTrace,
+
     #[strum(serialize = "palette.line")]
     PaletteLine,
 
@@ -271,6 +296,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.symbol")]
     PaletteSymbol,
 
+    #[strum(message = "Go to Symbol in Workspace")]
+    #[strum(serialize = "palette.workspace_symbol")]
+    PaletteWorkspaceSymbol,
+
     #[strum(message = "Command Palette")]
     #[strum(serialize = "palette.command")]
     PaletteCommand,
@@ -279,9 +308,81 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "palette.workspace")]
     PaletteWorkspace,
 
+    #[strum(message = "Checkout Branch")]
     #[strum(serialize = "source_control.checkout_branch")]
     CheckoutBranch,
 
+    #[strum(message = "Create Branch")]
+    #[strum(serialize = "source_control_create_branch")]
+    CreateBranch,
+
+    #[strum(message = "Delete Branch")]
+    #[strum(serialize = "source_control_delete_branch")]
+    DeleteBranch,
+
+    #[strum(message = "Rename Branch")]
+    #[strum(serialize = "source_control_rename_branch")]
+    RenameBranch,
+
+    /// Lists the commits that changed the file of the active editor
+    #[strum(message = "Show File History")]
+    #[strum(serialize = "source_control_file_history")]
+    ShowFileHistory,
+
+    /// Compares the file of the active editor with the index, a branch or
+    /// any other revision
+    #[strum(message = "Compare With Revision")]
+    #[strum(serialize = "source_control_compare_with_revision")]
+    CompareWithRevision,
+
+    #[strum(message = "Fetch")]
+    #[strum(serialize = "source_control_fetch")]
+    Fetch,
+
+    /// Fast-forwards the current branch to its upstream
+    #[strum(message = "Pull")]
+    #[strum(serialize = "source_control_pull")]
+    Pull,
+
+    /// Rebases the commits of the current branch on its upstream
+    #[strum(message = "Pull (Rebase)")]
+    #[strum(serialize = "source_control_pull_rebase")]
+    PullRebase,
+
+    #[strum(message = "Push")]
+    #[strum(serialize = "source_control_push")]
+    Push,
+
+    /// Stashes the changes, with the message typed in the palette if any
+    #[strum(message = "Stash Changes")]
+    #[strum(serialize = "source_control_stash")]
+    StashChanges,
+
+    /// Stashes the untracked files along with the changes
+    #[strum(message = "Stash Changes (Include Untracked)")]
+    #[strum(serialize = "source_control_stash_include_untracked")]
+    StashAllChanges,
+
+    /// The stash commands take the index of the entry in parameter, or
+    /// pick it in the palette.
+    #[strum(message = "Apply Stash")]
+    #[strum(serialize = "source_control_stash_apply")]
+    ApplyStash,
+
+    #[strum(message = "Pop Stash")]
+    #[strum(serialize = "source_control_stash_pop")]
+    PopStash,
+
+    #[strum(message = "Drop Stash")]
+    #[strum(serialize = "source_control_stash_drop")]
+    DropStash,
+
+    /// Answers a language server's `window/showMessageRequest` with the
+    /// action passed in parameter, which is null when the alert was
+    /// dismissed.
+    #[strum(serialize = "respond_message_request")]
+    RespondMessageRequest,
+
     #[strum(serialize = "toggle_maximized_panel")]
     ToggleMaximizedPanel,
 
@@ -322,6 +423,10 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_focus")]
     ToggleSearchFocus,
 
+    #[strum(message = "Toggle Hierarchy Focus")]
+    #[strum(serialize = "toggle_hierarchy_focus")]
+    ToggleHierarchyFocus,
+
     // Visual toggle commands
     #[strum(serialize = "toggle_terminal_visual")]
     ToggleTerminalVisual,
@@ -341,6 +446,9 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "toggle_search_visual")]
     ToggleSearchVisual,
 
+    #[strum(serialize = "toggle_hierarchy_visual")]
+    ToggleHierarchyVisual,
+
     #[strum(serialize = "focus_editor")]
     FocusEditor,
 
@@ -350,6 +458,30 @@ pub enum LapceWorkbenchCommand {
     #[strum(serialize = "source_control_commit")]
     SourceControlCommit,
 
+    #[strum(message = "Stage Changes")]
+    #[strum(serialize = "source_control_stage")]
+    SourceControlStage,
+
+    #[strum(message = "Unstage Changes")]
+    #[strum(serialize = "source_control_unstage")]
+    SourceControlUnstage,
+
+    #[strum(message = "Discard Changes")]
+    #[strum(serialize = "source_control_discard")]
+    SourceControlDiscard,
+
+    #[strum(message = "Stage Hunk")]
+    #[strum(serialize = "source_control_stage_hunk")]
+    SourceControlStageHunk,
+
+    #[strum(message = "Unstage Hunk")]
+    #[strum(serialize = "source_control_unstage_hunk")]
+    SourceControlUnstageHunk,
+
+    #[strum(message = "Discard Hunk")]
+    #[strum(serialize = "source_control_discard_hunk")]
+    SourceControlDiscardHunk,
+
     #[strum(serialize = "export_current_theme_settings")]
     #[strum(message = "Export current settings to a theme file")]
     ExportCurrentThemeSettings,
@@ -385,6 +517,18 @@ pub enum LapceUICommand {
         path: PathBuf,
         content: Rope,
     },
+    OpenLspLog(String),
+    /// Opens a link, like the documentation of a diagnostic code, with the
+    /// default application of the system
+    OpenURI(String),
+    UpdateLspLog {
+        name: String,
+        content: Rope,
+    },
+    AppendLspLog {
+        name: String,
+        entry: String,
+    },
     ReloadBuffer {
         path: PathBuf,
         rev: u64,
@@ -402,6 +546,15 @@ pub enum LapceUICommand {
         location: EditorLocation,
     },
     ShowAlert(AlertContentData),
+    ShowMessageRequest(RequestId, ShowMessageRequestParams),
+    /// The proxy needs the credentials of a git remote, or the passphrase of
+    /// the SSH key
+    AskGitCredentials {
+        id: RequestId,
+        url: String,
+        username: Option<String>,
+        key: Option<PathBuf>,
+    },
     ShowMenu(Point, Arc<Vec<MenuKind>>),
     UpdateSearch(String),
     GlobalSearchResult(String, Arc<HashMap<PathBuf, Vec<Match>>>),
@@ -410,14 +563,61 @@ pub enum LapceUICommand {
     SetTheme(String, bool),
     UpdateKeymap(KeyMap, Vec<KeyPress>),
     OpenFile(PathBuf),
+    /// Compares the file with a version, which is "head", "index" or any
+    /// revision git understands
     OpenFileDiff(PathBuf, String),
     CancelCompletion(usize),
     ResolveCompletion(BufferId, u64, usize, Box<CompletionItem>),
     UpdateCompletion(usize, String, CompletionResponse),
     UpdateHover(usize, Arc<Vec<RichText>>),
+    UpdateSignature(usize, Option<SignatureHelp>),
     UpdateCodeActions(PathBuf, u64, usize, CodeActionResponse),
+    UpdateInlayHints(PathBuf, u64, Vec<InlayHint>),
+    UpdateFoldingRanges(PathBuf, u64, Vec<(usize, usize)>),
+    UpdateCodeLens(PathBuf, u64, Vec<CodeLens>),
+    ResolveCodeLens(PathBuf, CodeLens),
+    UpdateBlame(PathBuf, u64, Vec<BlameHunk>),
+    /// Runs the command line in a new terminal, named after the title
+    RunInTerminal {
+        title: String,
+        cwd: Option<PathBuf>,
+        command: String,
+    },
+    NewTerminal {
+        title: String,
+        cwd: Option<PathBuf>,
+        command: String,
+    },
+    ToggleFold(usize),
+    /// The selection ranges of the language server around the regions of
+    /// the selection at the revision and cursor offset, or None to expand
+    /// the selection by the syntax tree instead
+    ExpandSelection {
+        rev: u64,
+        offset: usize,
+        ranges: Option<Vec<SelectionRange>>,
+    },
+    UpdateDocumentHighlights {
+        view_id: WidgetId,
+        path: PathBuf,
+        rev: u64,
+        offset: usize,
+        highlights: Option<Vec<DocumentHighlight>>,
+    },
+    ShowHierarchy {
+        is_call: bool,
+        items: Vec<CallHierarchyItem>,
+    },
+    UpdateHierarchyChildren {
+        id: u64,
+        tree: usize,
+        path: Vec<usize>,
+        items: Vec<CallHierarchyItem>,
+    },
     CancelPalette,
     RunCodeAction(CodeActionOrCommand),
+    /// A code action that was resolved after it was picked
+    ApplyCodeAction(CodeAction),
     ShowCodeActions(Option<Point>),
     Hide,
     ResignFocus,
@@ -465,6 +665,18 @@ pub enum LapceUICommand {
     DocumentFormatAndSave(PathBuf, u64, Result<Value>, Option<WidgetId>),
     BufferSave(PathBuf, u64, Option<WidgetId>),
     UpdateSemanticStyles(BufferId, PathBuf, u64, Arc<Spans<Style>>),
+    /// Replaces the semantic styles in `start..end`, when they are still
+    /// the ones of `base_rev`. Without a base, they are put over whatever
+    /// styles there are.
+    EditSemanticStyles {
+        buffer_id: BufferId,
+        path: PathBuf,
+        rev: u64,
+        base_rev: Option<u64>,
+        start: usize,
+        end: usize,
+        styles: Spans<Style>,
+    },
     UpdateTerminalTitle(TermId, String),
     UpdateHistoryStyle {
         id: BufferId,
@@ -488,6 +700,8 @@ pub enum LapceUICommand {
     UpdateLineChanges(BufferId),
     PublishDiagnostics(PublishDiagnosticsParams),
     WorkDoneProgress(ProgressParams),
+    UpdateLspStatus(String, LspStatus),
+    UpdateSignatureTriggers(PathBuf, SignatureTriggers),
     UpdateDiffInfo(DiffInfo),
     EnsureVisible((Rect, (f64, f64), Option<EnsureVisiblePosition>)),
     EnsureRectVisible(Rect),
@@ -528,6 +742,20 @@ pub enum LapceUICommand {
     GotoReference(WidgetId, usize, EditorLocation),
     GotoDefinition(WidgetId, usize, EditorLocation),
     PaletteReferences(usize, Vec<Location>),
+    PrepareRename {
+        editor_view_id: WidgetId,
+        path: PathBuf,
+        rev: u64,
+        offset: usize,
+        position: Position,
+        response: Option<PrepareRenameResponse>,
+    },
+    ConfirmRename,
+    CancelRename,
+    ApplyWorkspaceEdit(WorkspaceEdit),
+    /// A `workspace/applyEdit` from a language server, which is answered
+    /// once the edit is applied
+    ApplyWorkspaceEditRequest(RequestId, WorkspaceEdit),
     GotoLocation(Location),
     ActiveFileChanged {
         path: Option<PathBuf>,
@@ -12,6 +12,7 @@ use druid::{
 };
 use indexmap::IndexMap;
 use lapce_proxy::plugin::PluginCatalog;
+use lapce_rpc::proxy::LspSettings;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -47,6 +48,9 @@ impl LapceTheme {
     pub const EDITOR_SELECTION: &'static str = "editor.selection";
     pub const EDITOR_CURRENT_LINE: &'static str = "editor.current_line";
     pub const EDITOR_LINK: &'static str = "editor.link";
+    pub const EDITOR_DOCUMENT_HIGHLIGHT: &'static str = "editor.document_highlight";
+    pub const EDITOR_DOCUMENT_HIGHLIGHT_WRITE: &'static str =
+        "editor.document_highlight_write";
 
     pub const SOURCE_CONTROL_ADDED: &'static str = "source_control.added";
     pub const SOURCE_CONTROL_REMOVED: &'static str = "source_control.removed";
@@ -151,6 +155,18 @@ pub struct EditorConfig {
         desc = "How long (in ms) it should take before the hover information appears"
     )]
     pub hover_delay: u64,
+    #[field_names(
+        desc = "If the language server should format the code as you type its trigger characters"
+    )]
+    pub format_on_type: bool,
+    #[field_names(
+        desc = "If who last changed the line of the cursor, and when, is shown at its end"
+    )]
+    pub inline_blame: bool,
+    #[field_names(
+        desc = "If diffs show the old version next to the working tree, instead of inline"
+    )]
+    pub side_by_side_diff: bool,
 }
 
 impl EditorConfig {
@@ -480,6 +496,9 @@ pub struct Config {
     pub editor: EditorConfig,
     pub terminal: TerminalConfig,
     pub theme: ThemeConfig,
+    /// The language servers started without a plugin, by language id
+    #[serde(default)]
+    pub lsp: HashMap<String, LspSettings>,
     #[serde(skip)]
     pub default_theme: ThemeConfig,
     #[serde(skip)]
@@ -27,14 +27,22 @@ use lapce_core::{
     selection::Selection,
 };
 use lapce_rpc::{
-    buffer::BufferId, plugin::PluginDescription, source_control::FileDiff,
+    buffer::BufferId,
+    core::LspStatus,
+    plugin::PluginDescription,
+    source_control::{FileDiff, GitBranchError},
     terminal::TermId,
+    Callback, RequestId,
+};
+use lsp_types::{
+    CallHierarchyItem, Diagnostic, MessageActionItem, MessageType, Position,
+    PrepareRenameResponse, ProgressToken, ShowMessageRequestParams, TextEdit,
+    WorkspaceEdit,
 };
-use lsp_types::{Diagnostic, Position, ProgressToken, TextEdit};
 use notify::Watcher;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use xi_rope::{Rope, RopeDelta, Transformer};
+use serde_json::{json, Value};
+use xi_rope::{LinesMetric, Rope, RopeDelta, Transformer};
 
 use crate::{
     alert::{AlertContentData, AlertData},
@@ -50,24 +58,36 @@ use crate::{
         SplitInfo, TabsInfo, WindowInfo, WorkspaceInfo,
     },
     document::{BufferContent, Document, LocalBufferKind},
-    editor::{EditorLocation, LapceEditorBufferData, TabRect},
+    editor::{
+        workspce_edits, DocumentHighlights, EditorLocation, LapceEditorBufferData,
+        TabRect,
+    },
     explorer::FileExplorerData,
     find::Find,
+    hierarchy::HierarchyData,
     hover::HoverData,
     keypress::KeyPressData,
-    palette::{PaletteData, PaletteType, PaletteViewData},
+    palette::{
+        GitBranchAction, GitStashAction, PaletteData, PaletteType, PaletteViewData,
+    },
     panel::PanelPosition,
     picker::FilePickerData,
     plugin::PluginData,
     problem::ProblemData,
-    proxy::{LapceProxy, ProxyStatus, TermEvent},
+    proxy::{path_from_url, LapceProxy, ProxyStatus, TermEvent},
+    rename::RenameData,
     search::SearchData,
     settings::LapceSettingsPanelData,
+    signature::SignatureData,
     source_control::SourceControlData,
     split::{SplitDirection, SplitMoveDirection},
     terminal::TerminalSplitData,
 };
 
+/// The number of lines kept in the log of a language server, as many as the
+/// entries the proxy keeps
+const LSP_LOG_CAPACITY: usize = 2000;
+
 /// `LapceData` is the topmost structure in a tree of structures that holds
 /// the application model for Lapce.
 ///
@@ -354,6 +374,7 @@ pub enum PanelKind {
     Terminal,
     Search,
     Problem,
+    Hierarchy,
 }
 
 impl PanelKind {
@@ -365,6 +386,7 @@ impl PanelKind {
             PanelKind::Terminal => "terminal.svg",
             PanelKind::Search => "search.svg",
             PanelKind::Problem => "error.svg",
+            PanelKind::Hierarchy => "symbol-method.svg",
         }
     }
 }
@@ -426,11 +448,14 @@ pub struct LapceTabData {
     pub main_split: LapceMainSplitData,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
+    pub rename: Arc<RenameData>,
     pub terminal: Arc<TerminalSplitData>,
     pub palette: Arc<PaletteData>,
     pub find: Arc<Find>,
     pub source_control: Arc<SourceControlData>,
     pub problem: Arc<ProblemData>,
+    pub hierarchy: Arc<HierarchyData>,
     pub search: Arc<SearchData>,
     pub plugin: Arc<PluginData>,
     pub picker: Arc<FilePickerData>,
@@ -453,6 +478,9 @@ pub struct LapceTabData {
     pub focus_area: FocusArea,
     pub db: Arc<LapceDb>,
     pub progresses: im::Vector<WorkProgress>,
+    /// The last reported status of each language server, by the name of the
+    /// server and the language it was started for
+    pub lsp_status: im::OrdMap<String, LspStatus>,
     pub drag: Arc<Option<(Vec2, DragContent)>>,
 }
 
@@ -461,6 +489,8 @@ impl Data for LapceTabData {
         self.main_split.same(&other.main_split)
             && self.completion.same(&other.completion)
             && self.hover.same(&other.hover)
+            && self.signature.same(&other.signature)
+            && self.rename.same(&other.rename)
             && self.palette.same(&other.palette)
             && self.workspace.same(&other.workspace)
             && self.source_control.same(&other.source_control)
@@ -475,9 +505,11 @@ impl Data for LapceTabData {
             && self.find.same(&other.find)
             && self.alert.same(&other.alert)
             && self.progresses.ptr_eq(&other.progresses)
+            && self.lsp_status.ptr_eq(&other.lsp_status)
             && self.file_explorer.same(&other.file_explorer)
             && self.plugin.same(&other.plugin)
             && self.problem.same(&other.problem)
+            && self.hierarchy.same(&other.hierarchy)
             && self.search.same(&other.search)
             && self.installed_plugins.same(&other.installed_plugins)
             && self.picker.same(&other.picker)
@@ -514,12 +546,15 @@ impl LapceTabData {
         let proxy = Arc::new(LapceProxy::new(
             tab_id,
             workspace.clone(),
+            config.lsp.clone(),
             term_sender.clone(),
             event_sink.clone(),
         ));
         let palette = Arc::new(PaletteData::new(proxy.clone()));
         let completion = Arc::new(CompletionData::new());
         let hover = Arc::new(HoverData::new());
+        let signature = Arc::new(SignatureData::new());
+        let rename = Arc::new(RenameData::new());
         let source_control = Arc::new(SourceControlData::new());
         let settings = Arc::new(LapceSettingsPanelData::new());
         let alert = Arc::new(AlertData::new());
@@ -585,9 +620,17 @@ impl LapceTabData {
             &config,
             event_sink.clone(),
         );
+        main_split.add_editor(
+            rename.view_id,
+            None,
+            LocalBufferKind::Rename,
+            &config,
+            event_sink.clone(),
+        );
 
         let terminal = Arc::new(TerminalSplitData::new(proxy.clone()));
         let problem = Arc::new(ProblemData::new());
+        let hierarchy = Arc::new(HierarchyData::new());
 
         let mut panels = im::HashMap::new();
         panels.insert(
@@ -611,6 +654,7 @@ impl LapceTabData {
                     PanelKind::Terminal,
                     PanelKind::Search,
                     PanelKind::Problem,
+                    PanelKind::Hierarchy,
                 ],
                 shown: true,
                 maximized: false,
@@ -625,9 +669,12 @@ impl LapceTabData {
             main_split,
             completion,
             hover,
+            signature,
+            rename,
             terminal,
             plugin,
             problem,
+            hierarchy,
             search,
             plugins: Arc::new(Vec::new()),
             installed_plugins: Arc::new(HashMap::new()),
@@ -658,6 +705,7 @@ impl LapceTabData {
             focus_area: FocusArea::Editor,
             db,
             progresses: im::Vector::new(),
+            lsp_status: im::OrdMap::new(),
             drag: Arc::new(None),
         };
         tab.start_update_process(event_sink);
@@ -720,12 +768,16 @@ impl LapceTabData {
             BufferContent::SettingsValue(name, ..) => {
                 self.main_split.value_docs.get(name).unwrap().clone()
             }
+            BufferContent::LspLog(name) => {
+                self.main_split.log_docs.get(name).unwrap().clone()
+            }
         };
         LapceEditorBufferData {
             view_id: editor_view_id,
             main_split: self.main_split.clone(),
             completion: self.completion.clone(),
             hover: self.hover.clone(),
+            signature: self.signature.clone(),
             source_control: self.source_control.clone(),
             proxy: self.proxy.clone(),
             find: self.find.clone(),
@@ -747,6 +799,7 @@ impl LapceTabData {
         match &editor.content {
             BufferContent::Local(_) => Size::ZERO,
             BufferContent::SettingsValue(..) => Size::ZERO,
+            BufferContent::LspLog(_) => Size::ZERO,
             BufferContent::File(path) => {
                 let doc = self.main_split.open_docs.get(path).unwrap();
                 let offset = editor.cursor.offset();
@@ -777,6 +830,7 @@ impl LapceTabData {
     ) {
         self.completion = editor_buffer_data.completion.clone();
         self.hover = editor_buffer_data.hover.clone();
+        self.signature = editor_buffer_data.signature.clone();
         self.main_split = editor_buffer_data.main_split.clone();
         self.find = editor_buffer_data.find.clone();
         if !editor_buffer_data.editor.same(editor) {
@@ -806,6 +860,11 @@ impl LapceTabData {
                         .value_docs
                         .insert(name.clone(), editor_buffer_data.doc);
                 }
+                BufferContent::LspLog(name) => {
+                    self.main_split
+                        .log_docs
+                        .insert(name.clone(), editor_buffer_data.doc);
+                }
             }
         }
     }
@@ -833,10 +892,13 @@ impl LapceTabData {
                 *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
             }
-            BufferContent::File(_) | BufferContent::Scratch(..) => {
+            BufferContent::File(_)
+            | BufferContent::Scratch(..)
+            | BufferContent::LspLog(_) => {
                 let doc = self.main_split.editor_doc(editor.view_id);
                 let offset = self.completion.offset;
                 let (line, col) = doc.buffer().offset_to_line_col(offset);
+                let line = doc.folding().row_of_line(line);
                 let width = config.editor_char_width(text);
                 let x = col as f64 * width - line_height - 5.0;
                 let y = (line + 1) as f64 * line_height;
@@ -889,7 +951,9 @@ impl LapceTabData {
                 *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
             }
-            BufferContent::File(_) | BufferContent::Scratch(..) => {
+            BufferContent::File(_)
+            | BufferContent::Scratch(..)
+            | BufferContent::LspLog(_) => {
                 let doc = self.main_split.editor_doc(editor.view_id);
                 let offset = self.hover.offset;
                 let (line, col) = doc.buffer().offset_to_line_col(offset);
@@ -901,7 +965,7 @@ impl LapceTabData {
                     config,
                 );
                 let x = point.x;
-                let y = line as f64 * line_height;
+                let y = doc.folding().row_of_line(line) as f64 * line_height;
                 let mut origin = *editor.window_origin.borrow()
                     - self.window_origin.borrow().to_vec2()
                     + Vec2::new(x, y - self.hover.content_size.borrow().height);
@@ -921,6 +985,395 @@ impl LapceTabData {
         }
     }
 
+    /// Above the cursor, or below it when there's no room for the popup
+    /// there.
+    pub fn signature_origin(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        signature_size: Size,
+        config: &Config,
+    ) -> Point {
+        let line_height = self.config.editor.line_height as f64;
+
+        let editor =
+            match self.main_split.editors.get(&self.signature.editor_view_id) {
+                Some(editor) => editor,
+                None => return Point::ZERO,
+            };
+
+        let doc = self.main_split.editor_doc(editor.view_id);
+        let offset = self.signature.offset;
+        let (line, col) = doc.buffer().offset_to_line_col(offset);
+        let point =
+            doc.point_of_line_col(text, line, col, config.editor.font_size, config);
+        let y = doc.folding().row_of_line(line) as f64 * line_height;
+        let mut origin = *editor.window_origin.borrow()
+            - self.window_origin.borrow().to_vec2()
+            + Vec2::new(point.x, y - signature_size.height);
+        if origin.y < 0.0 {
+            origin.y += signature_size.height + line_height;
+        }
+        if origin.x + signature_size.width + 1.0 > tab_size.width {
+            origin.x = tab_size.width - signature_size.width - 1.0;
+        }
+        if origin.x <= 0.0 {
+            origin.x = 0.0;
+        }
+
+        origin
+    }
+
+    pub fn rename_origin(
+        &self,
+        text: &mut PietText,
+        tab_size: Size,
+        config: &Config,
+    ) -> Point {
+        let line_height = self.config.editor.line_height as f64;
+
+        let editor = match self.main_split.editors.get(&self.rename.from_editor) {
+            Some(editor) => editor,
+            None => return Point::ZERO,
+        };
+        let doc = match self.main_split.open_docs.get(&self.rename.path) {
+            Some(doc) => doc,
+            None => return Point::ZERO,
+        };
+
+        let (line, col) = doc.buffer().offset_to_line_col(self.rename.start);
+        let point =
+            doc.point_of_line_col(text, line, col, config.editor.font_size, config);
+        let line = doc.folding().row_of_line(line);
+        let mut origin = *editor.window_origin.borrow()
+            - self.window_origin.borrow().to_vec2()
+            + Vec2::new(point.x, (line + 1) as f64 * line_height);
+        if origin.y + self.rename.size.height + 1.0 > tab_size.height {
+            origin.y = editor.window_origin.borrow().y
+                - self.window_origin.borrow().y
+                + line as f64 * line_height
+                - self.rename.size.height;
+        }
+        if origin.x + self.rename.size.width + 1.0 > tab_size.width {
+            origin.x = tab_size.width - self.rename.size.width - 1.0;
+        }
+        if origin.x <= 0.0 {
+            origin.x = 0.0;
+        }
+
+        origin
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare_rename(
+        &mut self,
+        ctx: &mut EventCtx,
+        editor_view_id: WidgetId,
+        path: &Path,
+        rev: u64,
+        offset: usize,
+        position: Position,
+        response: Option<&PrepareRenameResponse>,
+    ) {
+        match self.main_split.active_editor() {
+            Some(editor)
+                if editor.view_id == editor_view_id
+                    && editor.cursor.offset() == offset => {}
+            _ => return,
+        }
+        let doc = match self.main_split.open_docs.get(path) {
+            Some(doc) if doc.rev() == rev => doc.clone(),
+            _ => return,
+        };
+
+        let (start, end, placeholder) = match response {
+            Some(PrepareRenameResponse::Range(range)) => (
+                doc.buffer().offset_of_position(&range.start),
+                doc.buffer().offset_of_position(&range.end),
+                None,
+            ),
+            Some(PrepareRenameResponse::RangeWithPlaceholder {
+                range,
+                placeholder,
+            }) => (
+                doc.buffer().offset_of_position(&range.start),
+                doc.buffer().offset_of_position(&range.end),
+                Some(placeholder.to_string()),
+            ),
+            Some(PrepareRenameResponse::DefaultBehavior { .. }) | None => {
+                let (start, end) = doc.buffer().select_word(offset);
+                (start, end, None)
+            }
+        };
+        let placeholder = placeholder
+            .unwrap_or_else(|| doc.buffer().slice_to_cow(start..end).to_string());
+        if placeholder.is_empty() {
+            return;
+        }
+
+        let rename = Arc::make_mut(&mut self.rename);
+        rename.update(
+            editor_view_id,
+            path.to_path_buf(),
+            rev,
+            offset,
+            position,
+            start,
+        );
+        rename.size.height = self.config.editor.line_height as f64 + 10.0;
+
+        let doc = self
+            .main_split
+            .local_docs
+            .get_mut(&LocalBufferKind::Rename)
+            .unwrap();
+        let doc = Arc::make_mut(doc);
+        doc.reload(Rope::from(&placeholder), true);
+        let len = doc.buffer().len();
+        let editor = self.main_split.editors.get_mut(&rename.view_id).unwrap();
+        Arc::make_mut(editor).cursor =
+            Cursor::new(CursorMode::Insert(Selection::region(0, len)), None, None);
+
+        self.focus = rename.view_id;
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(rename.view_id),
+        ));
+    }
+
+    pub fn confirm_rename(&mut self, ctx: &mut EventCtx) {
+        if !self.rename.active {
+            return;
+        }
+        let new_name = self
+            .main_split
+            .local_docs
+            .get(&LocalBufferKind::Rename)
+            .unwrap()
+            .buffer()
+            .text()
+            .to_string();
+        let new_name = new_name.trim();
+        self.cancel_rename(ctx);
+
+        if new_name.is_empty() {
+            return;
+        }
+        let doc = match self.main_split.open_docs.get(&self.rename.path) {
+            Some(doc) if doc.rev() == self.rename.rev => doc,
+            _ => return,
+        };
+
+        let tab_id = self.id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.rename(
+            doc.id(),
+            self.rename.position,
+            new_name.to_string(),
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    if let Ok(edit) = serde_json::from_value::<WorkspaceEdit>(res) {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::ApplyWorkspaceEdit(edit),
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
+    pub fn cancel_rename(&mut self, ctx: &mut EventCtx) {
+        if !self.rename.active {
+            return;
+        }
+        Arc::make_mut(&mut self.rename).cancel();
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::Focus,
+            Target::Widget(self.rename.from_editor),
+        ));
+    }
+
+    /// Show a `window/showMessageRequest` from a language server as an
+    /// alert, with one button per action the server offered. Dismissing the
+    /// alert answers with no action.
+    pub fn show_message_request(
+        &mut self,
+        ctx: &mut EventCtx,
+        id: RequestId,
+        params: &ShowMessageRequestParams,
+    ) {
+        let actions = params.actions.clone().unwrap_or_default();
+        let title = match params.typ {
+            MessageType::Error => "Language Server Error",
+            MessageType::Warning => "Language Server Warning",
+            MessageType::Info | MessageType::Log => "Language Server",
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::ShowAlert(AlertContentData {
+                title: title.to_string(),
+                msg: params.message.clone(),
+                buttons: actions
+                    .into_iter()
+                    .map(|action| {
+                        (
+                            action.title.clone(),
+                            self.id,
+                            LapceCommand {
+                                kind: CommandKind::Workbench(
+                                    LapceWorkbenchCommand::RespondMessageRequest,
+                                ),
+                                data: Some(json!({
+                                    "id": id,
+                                    "action": action,
+                                })),
+                            },
+                        )
+                    })
+                    .collect(),
+                cancel: Some((
+                    self.id,
+                    LapceCommand {
+                        kind: CommandKind::Workbench(
+                            LapceWorkbenchCommand::RespondMessageRequest,
+                        ),
+                        data: Some(json!({
+                            "id": id,
+                            "action": null,
+                        })),
+                    },
+                )),
+            }),
+            Target::Widget(self.id),
+        ));
+    }
+
+    /// The file of a source control command, which is the one it comes with
+    /// or else the selected one of the staged or unstaged list.
+    fn source_control_diff(
+        &self,
+        data: Option<Value>,
+        staged: bool,
+    ) -> Option<FileDiff> {
+        if let Some(diff) =
+            data.and_then(|value| serde_json::from_value::<FileDiff>(value).ok())
+        {
+            return Some(diff);
+        }
+        self.source_control.selected_diff(staged).cloned()
+    }
+
+    /// The file of the active editor and the line of its cursor. The hunk
+    /// commands work on the file as it's saved, so when it has unsaved
+    /// changes an alert says why the operation won't run instead.
+    fn active_editor_line(
+        &self,
+        ctx: &mut EventCtx,
+        operation: &str,
+    ) -> Option<(PathBuf, usize)> {
+        let editor = self.main_split.active_editor()?;
+        let path = match &editor.content {
+            BufferContent::File(path) => path.clone(),
+            _ => return None,
+        };
+        let doc = self.main_split.open_docs.get(&path)?;
+        if !doc.buffer().is_pristine() {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::ShowAlert(AlertContentData {
+                    title: format!("Failed to {operation}"),
+                    msg: "The file has unsaved changes. Save it first.".to_string(),
+                    buttons: Vec::new(),
+                    cancel: None,
+                }),
+                Target::Widget(self.id),
+            ));
+            return None;
+        }
+        let line = doc.buffer().line_of_offset(editor.cursor.offset());
+        Some((path, line))
+    }
+
+    /// Shows the error of a git operation that failed in an alert.
+    fn git_callback(
+        &self,
+        ctx: &mut EventCtx,
+        operation: &str,
+    ) -> Box<dyn Callback> {
+        let event_sink = ctx.get_external_handle();
+        let tab_id = self.id;
+        let workspace_path = self.workspace.path.clone();
+        let title = format!("Failed to {operation}");
+        Box::new(move |result: Result<Value, Value>| {
+            if let Err(error) = result {
+                let mut msg = error["message"]
+                    .as_str()
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| error.to_string());
+                let mut buttons = Vec::new();
+                // The branch operations say why they failed.
+                match serde_json::from_value::<GitBranchError>(error["data"].clone())
+                {
+                    Ok(GitBranchError::DirtyWorktree(paths)) => {
+                        for path in paths {
+                            let path = workspace_path
+                                .as_ref()
+                                .and_then(|w| path.strip_prefix(w).ok())
+                                .unwrap_or_else(|| path.as_path());
+                            msg.push_str(&format!("\n{}", path.display()));
+                        }
+                    }
+                    Ok(GitBranchError::BranchNotMerged(name)) => {
+                        buttons.push((
+                            "Delete Anyway".to_string(),
+                            tab_id,
+                            LapceCommand {
+                                kind: CommandKind::Workbench(
+                                    LapceWorkbenchCommand::DeleteBranch,
+                                ),
+                                data: Some(json!({
+                                    "name": name,
+                                    "force": true,
+                                })),
+                            },
+                        ));
+                    }
+                    _ => {}
+                }
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title,
+                        msg,
+                        buttons,
+                        cancel: None,
+                    }),
+                    Target::Widget(tab_id),
+                );
+            }
+        })
+    }
+
+    fn run_git_branch_palette(&self, ctx: &mut EventCtx, action: GitBranchAction) {
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::RunPalette(Some(PaletteType::GitBranch(action))),
+            Target::Widget(self.palette.widget_id),
+        ));
+    }
+
+    fn run_git_stash_palette(&self, ctx: &mut EventCtx, action: GitStashAction) {
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::RunPalette(Some(PaletteType::GitStash(action))),
+            Target::Widget(self.palette.widget_id),
+        ));
+    }
+
     pub fn palette_view_data(&self) -> PaletteViewData {
         PaletteViewData {
             palette: self.palette.clone(),
@@ -931,6 +1384,7 @@ impl LapceTabData {
             find: self.find.clone(),
             focus_area: self.focus_area.clone(),
             terminal: self.terminal.clone(),
+            source_control: self.source_control.clone(),
         }
     }
 
@@ -1095,6 +1549,13 @@ impl LapceTabData {
                     Target::Widget(self.palette.widget_id),
                 ));
             }
+            LapceWorkbenchCommand::PaletteWorkspaceSymbol => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::WorkspaceSymbol)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
             LapceWorkbenchCommand::PaletteCommand => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1202,6 +1663,9 @@ impl LapceTabData {
             LapceWorkbenchCommand::ToggleProblemVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleHierarchyVisual => {
+                self.toggle_panel_visual(ctx, PanelKind::Hierarchy);
+            }
             LapceWorkbenchCommand::ToggleTerminalVisual => {
                 self.toggle_panel_visual(ctx, PanelKind::Terminal);
             }
@@ -1228,6 +1692,9 @@ impl LapceTabData {
             LapceWorkbenchCommand::ToggleProblemFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Problem);
             }
+            LapceWorkbenchCommand::ToggleHierarchyFocus => {
+                self.toggle_panel_focus(ctx, PanelKind::Hierarchy);
+            }
             LapceWorkbenchCommand::ToggleTerminalFocus => {
                 self.toggle_panel_focus(ctx, PanelKind::Terminal);
             }
@@ -1254,23 +1721,17 @@ impl LapceTabData {
                 }
             }
             LapceWorkbenchCommand::SourceControlCommit => {
-                let diffs: Vec<FileDiff> = self
-                    .source_control
-                    .file_diffs
-                    .iter()
-                    .filter_map(
-                        |(diff, checked)| {
-                            if *checked {
-                                Some(diff.clone())
-                            } else {
-                                None
-                            }
-                        },
-                    )
-                    .collect();
-                if diffs.is_empty() {
+                if self.source_control.file_diffs.is_empty() {
                     return;
                 }
+                // What's staged is committed, or every change when nothing
+                // is.
+                let diffs: Vec<FileDiff> =
+                    if self.source_control.staged_diffs.is_empty() {
+                        self.source_control.file_diffs.clone()
+                    } else {
+                        Vec::new()
+                    };
                 let doc = self
                     .main_split
                     .local_docs
@@ -1300,7 +1761,286 @@ impl LapceTabData {
                     )
                 };
             }
-            LapceWorkbenchCommand::CheckoutBranch => {}
+            LapceWorkbenchCommand::SourceControlStage => {
+                if let Some(diff) = self.source_control_diff(data, false) {
+                    self.proxy
+                        .git_stage(vec![diff], self.git_callback(ctx, "stage"));
+                }
+            }
+            LapceWorkbenchCommand::SourceControlUnstage => {
+                if let Some(diff) = self.source_control_diff(data, true) {
+                    self.proxy
+                        .git_unstage(vec![diff], self.git_callback(ctx, "unstage"));
+                }
+            }
+            LapceWorkbenchCommand::SourceControlDiscard => {
+                // The file comes with the command once the discard is
+                // confirmed.
+                if let Some(value) = data {
+                    if let Ok(diff) = serde_json::from_value::<FileDiff>(value) {
+                        self.proxy.git_discard(
+                            vec![diff],
+                            self.git_callback(ctx, "discard"),
+                        );
+                    }
+                    return;
+                }
+                if let Some(diff) = self.source_control_diff(None, false) {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ShowAlert(AlertContentData {
+                            title: format!(
+                                "Do you want to discard the changes to {}?",
+                                diff.path()
+                                    .file_name()
+                                    .and_then(|s| s.to_str())
+                                    .unwrap_or("")
+                            ),
+                            msg: "The changes that aren't staged will be lost."
+                                .to_string(),
+                            buttons: vec![(
+                                "Discard".to_string(),
+                                self.id,
+                                LapceCommand {
+                                    kind: CommandKind::Workbench(
+                                        LapceWorkbenchCommand::SourceControlDiscard,
+                                    ),
+                                    data: Some(json!(diff)),
+                                },
+                            )],
+                            cancel: None,
+                        }),
+                        Target::Widget(self.id),
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::SourceControlStageHunk => {
+                if let Some((path, line)) =
+                    self.active_editor_line(ctx, "stage the hunk")
+                {
+                    self.proxy.git_stage_hunk(
+                        &path,
+                        line,
+                        self.git_callback(ctx, "stage the hunk"),
+                    );
+                }
+            }
+            LapceWorkbenchCommand::SourceControlUnstageHunk => {
+                if let Some((path, line)) =
+                    self.active_editor_line(ctx, "unstage the hunk")
+                {
+                    self.proxy.git_unstage_hunk(
+                        &path,
+                        line,
+                        self.git_callback(ctx, "unstage the hunk"),
+                    );
+                }
+            }
+            LapceWorkbenchCommand::SourceControlDiscardHunk => {
+                // The file and the line come with the command once the
+                // discard is confirmed.
+                if let Some(value) = data {
+                    if let Ok((path, line)) =
+                        serde_json::from_value::<(PathBuf, usize)>(value)
+                    {
+                        self.proxy.git_discard_hunk(
+                            &path,
+                            line,
+                            self.git_callback(ctx, "discard the hunk"),
+                        );
+                    }
+                    return;
+                }
+                if let Some((path, line)) =
+                    self.active_editor_line(ctx, "discard the hunk")
+                {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ShowAlert(AlertContentData {
+                            title: format!(
+                                "Do you want to discard the change at line {} of {}?",
+                                line + 1,
+                                path.file_name()
+                                    .and_then(|s| s.to_str())
+                                    .unwrap_or("")
+                            ),
+                            msg: "The change will be lost.".to_string(),
+                            buttons: vec![(
+                                "Discard".to_string(),
+                                self.id,
+                                LapceCommand {
+                                    kind: CommandKind::Workbench(
+                                        LapceWorkbenchCommand::SourceControlDiscardHunk,
+                                    ),
+                                    data: Some(json!((path, line))),
+                                },
+                            )],
+                            cancel: None,
+                        }),
+                        Target::Widget(self.id),
+                    ));
+                }
+            }
+            LapceWorkbenchCommand::RestartLanguageServer => {
+                let buffer_id =
+                    self.main_split
+                        .active_editor()
+                        .and_then(|editor| match &editor.content {
+                            BufferContent::File(path) => self
+                                .main_split
+                                .open_docs
+                                .get(path)
+                                .map(|doc| doc.id()),
+                            _ => None,
+                        });
+                self.proxy.restart_lsp_servers(buffer_id);
+            }
+            LapceWorkbenchCommand::OpenLanguageServerLog => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::LspLog)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
+            LapceWorkbenchCommand::ToggleLanguageServerTrace => {
+                self.proxy.toggle_lsp_trace();
+            }
+            LapceWorkbenchCommand::ShowFileHistory => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::FileHistory)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
+            LapceWorkbenchCommand::CompareWithRevision => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::RunPalette(Some(PaletteType::DiffRevision)),
+                    Target::Widget(self.palette.widget_id),
+                ));
+            }
+            LapceWorkbenchCommand::CheckoutBranch => match data {
+                Some(Value::String(reference)) => {
+                    self.proxy.git_checkout(
+                        &reference,
+                        self.git_callback(ctx, &format!("check out {reference}")),
+                    );
+                }
+                _ => self.run_git_branch_palette(ctx, GitBranchAction::Checkout),
+            },
+            LapceWorkbenchCommand::CreateBranch => match data {
+                Some(data) => {
+                    if let Some(name) = data["name"].as_str() {
+                        self.proxy.git_create_branch(
+                            name,
+                            data["start_point"].as_str(),
+                            true,
+                            self.git_callback(ctx, &format!("create {name}")),
+                        );
+                    }
+                }
+                None => self.run_git_branch_palette(ctx, GitBranchAction::Create),
+            },
+            LapceWorkbenchCommand::DeleteBranch => match data {
+                Some(data) => {
+                    if let Some(name) = data["name"].as_str() {
+                        self.proxy.git_delete_branch(
+                            name,
+                            data["force"].as_bool().unwrap_or(false),
+                            self.git_callback(ctx, &format!("delete {name}")),
+                        );
+                    }
+                }
+                None => self.run_git_branch_palette(ctx, GitBranchAction::Delete),
+            },
+            LapceWorkbenchCommand::RenameBranch => match data {
+                Some(data) => {
+                    if let (Some(name), Some(new_name)) =
+                        (data["name"].as_str(), data["new_name"].as_str())
+                    {
+                        self.proxy.git_rename_branch(
+                            name,
+                            new_name,
+                            self.git_callback(ctx, &format!("rename {name}")),
+                        );
+                    }
+                }
+                None => self.run_git_branch_palette(ctx, GitBranchAction::Rename),
+            },
+            LapceWorkbenchCommand::Fetch => {
+                self.proxy.git_fetch(self.git_callback(ctx, "fetch"));
+            }
+            LapceWorkbenchCommand::Pull => {
+                self.proxy.git_pull(false, self.git_callback(ctx, "pull"));
+            }
+            LapceWorkbenchCommand::PullRebase => {
+                self.proxy.git_pull(true, self.git_callback(ctx, "pull"));
+            }
+            LapceWorkbenchCommand::Push => {
+                self.proxy.git_push(self.git_callback(ctx, "push"));
+            }
+            LapceWorkbenchCommand::StashChanges => match data {
+                Some(data) => {
+                    self.proxy.git_stash_save(
+                        data["message"].as_str(),
+                        data["include_untracked"].as_bool().unwrap_or(false),
+                        self.git_callback(ctx, "stash the changes"),
+                    );
+                }
+                None => self.run_git_stash_palette(
+                    ctx,
+                    GitStashAction::Save {
+                        include_untracked: false,
+                    },
+                ),
+            },
+            LapceWorkbenchCommand::StashAllChanges => self.run_git_stash_palette(
+                ctx,
+                GitStashAction::Save {
+                    include_untracked: true,
+                },
+            ),
+            LapceWorkbenchCommand::ApplyStash => {
+                match data.and_then(|data| data.as_u64()) {
+                    Some(index) => self.proxy.git_stash_apply(
+                        index as usize,
+                        false,
+                        self.git_callback(ctx, "apply the stash"),
+                    ),
+                    None => self.run_git_stash_palette(ctx, GitStashAction::Apply),
+                }
+            }
+            LapceWorkbenchCommand::PopStash => {
+                match data.and_then(|data| data.as_u64()) {
+                    Some(index) => self.proxy.git_stash_apply(
+                        index as usize,
+                        true,
+                        self.git_callback(ctx, "pop the stash"),
+                    ),
+                    None => self.run_git_stash_palette(ctx, GitStashAction::Pop),
+                }
+            }
+            LapceWorkbenchCommand::DropStash => {
+                match data.and_then(|data| data.as_u64()) {
+                    Some(index) => self.proxy.git_stash_drop(
+                        index as usize,
+                        self.git_callback(ctx, "drop the stash"),
+                    ),
+                    None => self.run_git_stash_palette(ctx, GitStashAction::Drop),
+                }
+            }
+            LapceWorkbenchCommand::RespondMessageRequest => {
+                if let Some(data) = data {
+                    if let (Some(id), Ok(action)) = (
+                        data["id"].as_u64(),
+                        serde_json::from_value::<Option<MessageActionItem>>(
+                            data["action"].clone(),
+                        ),
+                    ) {
+                        self.proxy.show_message_response(id, action);
+                    }
+                }
+            }
             LapceWorkbenchCommand::ConnectSshHost => {
                 ctx.submit_command(Command::new(
                     LAPCE_UI_COMMAND,
@@ -1473,6 +2213,7 @@ impl LapceTabData {
                     PanelKind::Terminal => self.terminal.widget_id,
                     PanelKind::Search => self.search.active,
                     PanelKind::Problem => self.problem.widget_id,
+                    PanelKind::Hierarchy => self.hierarchy.widget_id,
                 };
                 if let PanelKind::Search = kind {
                     ctx.submit_command(Command::new(
@@ -1496,6 +2237,42 @@ impl LapceTabData {
         }
     }
 
+    /// Runs the command line in a new terminal of the terminal panel.
+    pub fn run_in_terminal(
+        &mut self,
+        ctx: &mut EventCtx,
+        title: String,
+        cwd: Option<PathBuf>,
+        command: String,
+    ) {
+        self.show_panel(ctx, PanelKind::Terminal);
+        ctx.submit_command(Command::new(
+            LAPCE_UI_COMMAND,
+            LapceUICommand::NewTerminal {
+                title,
+                cwd,
+                command,
+            },
+            Target::Widget(self.terminal.split_id),
+        ));
+    }
+
+    /// Shows the hierarchy of the items in the hierarchy panel.
+    pub fn show_hierarchy(
+        &mut self,
+        ctx: &mut EventCtx,
+        is_call: bool,
+        items: Vec<CallHierarchyItem>,
+    ) {
+        Arc::make_mut(&mut self.hierarchy).set_items(
+            is_call,
+            items,
+            &self.proxy,
+            ctx.get_external_handle(),
+        );
+        self.show_panel(ctx, PanelKind::Hierarchy);
+    }
+
     fn toggle_panel_visual(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         if self.is_panel_visible(kind) {
             self.hide_panel(ctx, kind);
@@ -1506,7 +2283,10 @@ impl LapceTabData {
 
     fn toggle_panel_focus(&mut self, ctx: &mut EventCtx, kind: PanelKind) {
         let should_hide = match kind {
-            PanelKind::FileExplorer | PanelKind::Plugin | PanelKind::Problem => {
+            PanelKind::FileExplorer
+            | PanelKind::Plugin
+            | PanelKind::Problem
+            | PanelKind::Hierarchy => {
                 // Some panels don't accept focus (yet). Fall back to visibility check
                 // in those cases.
                 self.is_panel_visible(kind)
@@ -1765,11 +2545,16 @@ pub struct LapceMainSplitData {
     pub local_docs: im::HashMap<LocalBufferKind, Arc<Document>>,
     pub value_docs: im::HashMap<String, Arc<Document>>,
     pub scratch_docs: im::HashMap<BufferId, Arc<Document>>,
+    /// The logs of the language servers that were opened, by name
+    pub log_docs: im::HashMap<String, Arc<Document>>,
     pub current_save_as: Option<Arc<(BufferContent, WidgetId, bool)>>,
     pub register: Arc<Register>,
     pub proxy: Arc<LapceProxy>,
     pub palette_preview_editor: Arc<WidgetId>,
     pub diagnostics: im::HashMap<PathBuf, Arc<Vec<EditorDiagnostic>>>,
+    /// Edits for documents that were not loaded yet when a workspace edit
+    /// was applied, they are applied once the content arrives
+    pub pending_edits: im::HashMap<PathBuf, Arc<Vec<TextEdit>>>,
     pub error_count: usize,
     pub warning_count: usize,
     pub workspace: Arc<LapceWorkspace>,
@@ -1792,6 +2577,7 @@ impl LapceMainSplitData {
             BufferContent::Scratch(id, _) => {
                 self.scratch_docs.get(id).unwrap().clone()
             }
+            BufferContent::LspLog(name) => self.log_docs.get(name).unwrap().clone(),
         }
     }
 
@@ -1816,23 +2602,112 @@ impl LapceMainSplitData {
                 serde_json::from_value(res.clone());
             if let Ok(edits) = edits {
                 if !edits.is_empty() {
-                    let doc = self.open_docs.get_mut(path).unwrap();
+                    self.apply_text_edits(path, &edits);
+                }
+            }
+        }
+    }
 
-                    let edits: Vec<(lapce_core::selection::Selection, &str)> = edits
-                        .iter()
-                        .map(|edit| {
-                            let selection = lapce_core::selection::Selection::region(
-                                doc.buffer().offset_of_position(&edit.range.start),
-                                doc.buffer().offset_of_position(&edit.range.end),
-                            );
-                            (selection, edit.new_text.as_str())
-                        })
-                        .collect();
+    fn apply_text_edits(&mut self, path: &Path, edits: &[TextEdit]) {
+        let doc = match self.open_docs.get(path) {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let edits: Vec<(lapce_core::selection::Selection, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let selection = lapce_core::selection::Selection::region(
+                    doc.buffer().offset_of_position(&edit.range.start),
+                    doc.buffer().offset_of_position(&edit.range.end),
+                );
+                (selection, edit.new_text.as_str())
+            })
+            .collect();
 
-                    self.edit(path, &edits, lapce_core::editor::EditType::Other);
+        self.edit(path, &edits, lapce_core::editor::EditType::Other);
+    }
+
+    /// Apply a workspace edit from the language server. Documents that are
+    /// not opened yet get loaded in the background, and are saved after the
+    /// edits are applied so that no unsaved changes are left behind
+    /// without an editor showing them. An edit that can't be applied is
+    /// shown in an alert, and nothing of it is applied.
+    pub fn apply_workspace_edit(
+        &mut self,
+        ctx: &mut EventCtx,
+        edit: &WorkspaceEdit,
+    ) -> Result<(), String> {
+        let edits = match workspce_edits(edit) {
+            Ok(edits) => edits,
+            Err(reason) => {
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowAlert(AlertContentData {
+                        title: "Failed to apply the edit".to_string(),
+                        msg: reason.clone(),
+                        buttons: Vec::new(),
+                        cancel: None,
+                    }),
+                    Target::Widget(*self.tab_id),
+                ));
+                return Err(reason);
+            }
+        };
+
+        for (url, edits) in edits.iter() {
+            let path = path_from_url(url);
+            match self.open_docs.get(&path).map(|doc| doc.loaded()) {
+                Some(true) => {
+                    self.apply_text_edits(&path, edits);
+                }
+                Some(false) => {
+                    let pending = self
+                        .pending_edits
+                        .entry(path)
+                        .or_insert_with(|| Arc::new(Vec::new()));
+                    Arc::make_mut(pending).extend_from_slice(edits);
+                }
+                None => {
+                    let mut doc = Document::new(
+                        BufferContent::File(path.clone()),
+                        *self.tab_id,
+                        ctx.get_external_handle(),
+                        self.proxy.clone(),
+                    );
+                    doc.retrieve_file(vec![]);
+                    self.open_docs.insert(path.clone(), Arc::new(doc));
+                    self.pending_edits.insert(path, Arc::new(edits.clone()));
                 }
             }
         }
+        Ok(())
+    }
+
+    pub fn apply_pending_edits(&mut self, ctx: &mut EventCtx, path: &Path) {
+        let edits = match self.pending_edits.remove(path) {
+            Some(edits) => edits,
+            None => return,
+        };
+        self.apply_text_edits(path, &edits);
+
+        let doc = self.open_docs.get(path).unwrap();
+        let rev = doc.rev();
+        let event_sink = ctx.get_external_handle();
+        let path = path.to_path_buf();
+        self.proxy.save(
+            rev,
+            doc.id(),
+            Box::new(move |result| {
+                if let Ok(_r) = result {
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::BufferSave(path, rev, None),
+                        Target::Auto,
+                    );
+                }
+            }),
+        );
     }
 
     pub fn document_format_and_save(
@@ -2346,6 +3221,111 @@ impl LapceMainSplitData {
         buffer_id
     }
 
+    /// Shows the log of a language server, in the editor already showing
+    /// it if there's one, and asks the proxy for what it has so far.
+    pub fn open_lsp_log(&mut self, ctx: &mut EventCtx, name: &str, config: &Config) {
+        let content = BufferContent::LspLog(name.to_string());
+        if !self.log_docs.contains_key(name) {
+            let doc = Document::new(
+                content.clone(),
+                *self.tab_id,
+                ctx.get_external_handle(),
+                self.proxy.clone(),
+            );
+            self.log_docs.insert(name.to_string(), Arc::new(doc));
+        }
+
+        let existing = self
+            .editors
+            .values()
+            .find(|editor| {
+                editor.content == content
+                    && editor.view_id != *self.palette_preview_editor
+            })
+            .map(|editor| (editor.view_id, editor.tab_id));
+        match existing {
+            Some((view_id, tab_id)) => {
+                if let Some(editor_tab) =
+                    tab_id.and_then(|tab_id| self.editor_tabs.get_mut(&tab_id))
+                {
+                    let editor_tab = Arc::make_mut(editor_tab);
+                    if let Some(index) = editor_tab
+                        .children
+                        .iter()
+                        .position(|child| child.widget_id() == view_id)
+                    {
+                        editor_tab.active = index;
+                    }
+                }
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::Focus,
+                    Target::Widget(view_id),
+                ));
+            }
+            None => {
+                let editor = self.get_editor_or_new(ctx, None, None, true, config);
+                editor.content = content;
+                editor.cursor = if config.lapce.modal {
+                    Cursor::new(CursorMode::Normal(0), None, None)
+                } else {
+                    Cursor::new(CursorMode::Insert(Selection::caret(0)), None, None)
+                };
+            }
+        }
+
+        if let Some(doc) = self.log_docs.get(name) {
+            doc.retrieve_lsp_log();
+        }
+    }
+
+    /// Replaces the content of the log of a language server, and moves the
+    /// editors showing it to its last line, where the latest entries are.
+    pub fn update_lsp_log(&mut self, ctx: &mut EventCtx, name: &str, content: Rope) {
+        let doc = match self.log_docs.get_mut(name) {
+            Some(doc) => Arc::make_mut(doc),
+            None => return,
+        };
+        doc.reload(content, true);
+        let offset = doc.buffer().offset_of_line(doc.buffer().last_line());
+
+        let content = BufferContent::LspLog(name.to_string());
+        for (_, editor) in self.editors.iter_mut() {
+            if editor.content != content {
+                continue;
+            }
+            let editor = Arc::make_mut(editor);
+            editor.cursor.set_offset(offset, false, false);
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::EnsureCursorVisible(None),
+                Target::Widget(editor.view_id),
+            ));
+        }
+    }
+
+    /// Adds an entry to the log of a language server if it's open, dropping
+    /// its oldest lines once it's full like the proxy does.
+    pub fn append_lsp_log(&mut self, ctx: &mut EventCtx, name: &str, entry: &str) {
+        let doc = match self.log_docs.get(name) {
+            Some(doc) => doc,
+            None => return,
+        };
+        let mut content = doc.buffer().text().clone();
+        let entry = if content.is_empty() {
+            entry.to_string()
+        } else {
+            format!("\n{entry}")
+        };
+        content.edit(content.len()..content.len(), entry);
+        let lines = content.measure::<LinesMetric>() + 1;
+        if lines > LSP_LOG_CAPACITY {
+            let start = content.offset_of_line(lines - LSP_LOG_CAPACITY);
+            content.edit(0..start, "");
+        }
+        self.update_lsp_log(ctx, name, content);
+    }
+
     pub fn go_to_location(
         &mut self,
         ctx: &mut EventCtx,
@@ -2368,6 +3348,7 @@ impl LapceMainSplitData {
             BufferContent::Local(_) => true,
             BufferContent::SettingsValue(..) => true,
             BufferContent::Scratch(..) => true,
+            BufferContent::LspLog(_) => true,
         };
         if new_buffer {
             self.db.save_doc_position(&self.workspace, &doc);
@@ -2424,7 +3405,11 @@ impl LapceMainSplitData {
                 config,
             );
             if let Some(version) = location.history.as_ref() {
-                editor.view = EditorView::Diff(version.to_string());
+                editor.view = if config.editor.side_by_side_diff {
+                    EditorView::SideBySide(version.to_string())
+                } else {
+                    EditorView::Diff(version.to_string())
+                };
             } else {
                 editor.view = EditorView::Normal;
             }
@@ -2534,6 +3519,7 @@ impl LapceMainSplitData {
             local_docs,
             value_docs,
             scratch_docs,
+            log_docs: im::HashMap::new(),
             active: Arc::new(None),
             active_tab: Arc::new(None),
             register: Arc::new(Register::default()),
@@ -2541,6 +3527,7 @@ impl LapceMainSplitData {
             proxy,
             palette_preview_editor: Arc::new(palette_preview_editor),
             diagnostics: im::HashMap::new(),
+            pending_edits: im::HashMap::new(),
             error_count: 0,
             warning_count: 0,
             workspace,
@@ -2619,6 +3606,44 @@ impl LapceMainSplitData {
         self.editors.insert(editor.view_id, Arc::new(editor));
     }
 
+    /// Removes the editor, and asks for its document to be closed, which
+    /// only happens once no other editor shows it.
+    pub fn remove_editor(&mut self, ctx: &mut EventCtx, view_id: &WidgetId) {
+        if let Some(editor) = self.editors.remove(view_id) {
+            if let BufferContent::File(path) = &editor.content {
+                if let Some(doc) = self.open_docs.get(path) {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::CloseBuffers(vec![doc.id()]),
+                        Target::Widget(*self.tab_id),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Drops the documents of the buffers that no editor shows anymore, and
+    /// tells the proxy it can forget about them.
+    pub fn close_buffers(&mut self, buffer_ids: &[BufferId]) {
+        let paths: Vec<PathBuf> = self
+            .open_docs
+            .iter()
+            .filter(|(path, doc)| {
+                buffer_ids.contains(&doc.id())
+                    && !self.editors.values().any(|editor| {
+                        matches!(&editor.content, BufferContent::File(p) if p == *path)
+                    })
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in paths {
+            if let Some(doc) = self.open_docs.remove(&path) {
+                self.pending_edits.remove(&path);
+                self.proxy.close_buffer(doc.id());
+            }
+        }
+    }
+
     pub fn split_close(
         &mut self,
         _ctx: &mut EventCtx,
@@ -2814,6 +3839,7 @@ impl LapceMainSplitData {
                                     },
                                 ),
                             ],
+                            cancel: None,
                         }),
                         Target::Widget(*self.tab_id),
                     ));
@@ -3191,10 +4217,12 @@ pub struct SelectionHistory {
     pub selections: im::Vector<Selection>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum EditorView {
     Normal,
     Diff(String),
+    /// The version on the left and the working tree on the right
+    SideBySide(String),
     Lens,
 }
 
@@ -3209,7 +4237,13 @@ pub struct LapceEditorData {
     pub view: EditorView,
     pub compare: Option<String>,
     pub code_lens: bool,
+    /// If the gutter shows the commit that last changed each line
+    pub blame: bool,
+    pub document_highlights: Option<Arc<DocumentHighlights>>,
     pub scroll_offset: Vec2,
+    /// The cursor before and after each expansion of the selection, the
+    /// latest last, for shrinking it back
+    pub expanded_selections: Vec<(CursorMode, CursorMode)>,
     pub cursor: Cursor,
     pub last_cursor_instant: Rc<RefCell<Instant>>,
     pub size: Rc<RefCell<Size>>,
@@ -3255,6 +4289,9 @@ impl LapceEditorData {
             size: Rc::new(RefCell::new(Size::ZERO)),
             compare: None,
             code_lens: false,
+            blame: false,
+            document_highlights: None,
+            expanded_selections: Vec::new(),
             window_origin: Rc::new(RefCell::new(Point::ZERO)),
             snippet: None,
             locations: vec![],
@@ -3266,6 +4303,11 @@ impl LapceEditorData {
         }
     }
 
+    /// Where the right side of a side-by-side diff starts
+    pub fn side_by_side_offset(&self) -> f64 {
+        (self.size.borrow().width / 2.0).round()
+    }
+
     pub fn copy(&self) -> LapceEditorData {
         let mut new_editor = self.clone();
         new_editor.view_id = WidgetId::next();
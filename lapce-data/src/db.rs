@@ -309,6 +309,17 @@ impl EditorInfo {
                 }
                 data.scratch_docs.insert(*id, Arc::new(doc));
             }
+        } else if let BufferContent::LspLog(name) = &self.content {
+            if !data.log_docs.contains_key(name) {
+                let doc = Document::new(
+                    self.content.clone(),
+                    tab_id,
+                    event_sink,
+                    data.proxy.clone(),
+                );
+                doc.retrieve_lsp_log();
+                data.log_docs.insert(name.clone(), Arc::new(doc));
+            }
         }
         data.insert_editor(Arc::new(editor_data.clone()), config);
         editor_data
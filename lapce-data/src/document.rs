@@ -16,10 +16,11 @@ use druid::{
     ExtEventSink, Point, SingleUse, Size, Target, Vec2, WidgetId,
 };
 use lapce_core::{
-    buffer::{Buffer, DiffLines, InvalLines},
+    buffer::{Buffer, DiffBlock, DiffLines, InvalLines},
     command::{EditCommand, MultiSelectionCommand},
     cursor::{ColPosition, Cursor, CursorMode},
     editor::{EditType, Editor},
+    folding::Folding,
     language::LapceLanguage,
     mode::{Mode, MotionMode},
     movement::{LinePosition, Movement},
@@ -31,11 +32,17 @@ use lapce_core::{
 };
 use lapce_rpc::{
     buffer::{BufferId, NewBufferResponse},
+    proxy::InlayHint,
+    source_control::BlameHunk,
     style::{LineStyle, LineStyles, Style},
 };
-use lsp_types::{CodeActionOrCommand, CodeActionResponse};
+use lsp_types::{CodeActionOrCommand, CodeActionResponse, CodeLens, SelectionRange};
 use serde::{Deserialize, Serialize};
-use xi_rope::{spans::Spans, Rope, RopeDelta};
+use serde_json::Value;
+use xi_rope::{
+    spans::{Spans, SpansBuilder},
+    Interval, Rope, RopeDelta, Transformer,
+};
 
 use crate::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
@@ -45,6 +52,7 @@ use crate::{
     history::DocumentHisotry,
     proxy::LapceProxy,
     settings::SettingsValueKind,
+    signature::SignatureTriggers,
 };
 
 pub struct SystemClipboard {}
@@ -94,6 +102,7 @@ pub enum LocalBufferKind {
     FilePicker,
     Keymap,
     Settings,
+    Rename,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -102,6 +111,8 @@ pub enum BufferContent {
     Local(LocalBufferKind),
     SettingsValue(String, SettingsValueKind, String, String),
     Scratch(BufferId, String),
+    /// The log of a language server, by the name the proxy gave it
+    LspLog(String),
 }
 
 impl BufferContent {
@@ -118,11 +129,13 @@ impl BufferContent {
                 | LocalBufferKind::SourceControl
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
-                | LocalBufferKind::Keymap => true,
+                | LocalBufferKind::Keymap
+                | LocalBufferKind::Rename => true,
                 LocalBufferKind::Empty => false,
             },
             BufferContent::SettingsValue(..) => true,
             BufferContent::Scratch(..) => false,
+            BufferContent::LspLog(_) => false,
         }
     }
 
@@ -134,11 +147,13 @@ impl BufferContent {
                 | LocalBufferKind::Palette
                 | LocalBufferKind::FilePicker
                 | LocalBufferKind::Settings
-                | LocalBufferKind::Keymap => true,
+                | LocalBufferKind::Keymap
+                | LocalBufferKind::Rename => true,
                 LocalBufferKind::Empty | LocalBufferKind::SourceControl => false,
             },
             BufferContent::SettingsValue(..) => true,
             BufferContent::Scratch(..) => false,
+            BufferContent::LspLog(_) => false,
         }
     }
 
@@ -147,6 +162,7 @@ impl BufferContent {
             BufferContent::File(_) => false,
             BufferContent::SettingsValue(..) => false,
             BufferContent::Scratch(..) => false,
+            BufferContent::LspLog(_) => false,
             BufferContent::Local(local) => matches!(local, LocalBufferKind::Search),
         }
     }
@@ -157,15 +173,23 @@ impl BufferContent {
             BufferContent::SettingsValue(..) => true,
             BufferContent::Local(_) => false,
             BufferContent::Scratch(..) => false,
+            BufferContent::LspLog(_) => false,
         }
     }
 
+    /// If the content is only there to be looked at, so that the edit
+    /// commands leave it alone.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, BufferContent::LspLog(_))
+    }
+
     pub fn file_name(&self) -> &str {
         match self {
             BufferContent::File(p) => {
                 p.file_name().and_then(|f| f.to_str()).unwrap_or("")
             }
             BufferContent::Scratch(_, scratch_doc_name) => scratch_doc_name,
+            BufferContent::LspLog(name) => name,
             _ => "",
         }
     }
@@ -180,6 +204,10 @@ pub struct Document {
     syntax: Option<Syntax>,
     line_styles: Rc<RefCell<LineStyles>>,
     semantic_styles: Option<Arc<Spans<Style>>>,
+    /// The rev of the semantic styles last received from the proxy, which
+    /// the ones that changed since are sent against, None when only the
+    /// ones of some ranges arrived
+    semantic_styles_rev: Option<u64>,
     text_layouts: Rc<RefCell<TextLayoutCache>>,
     load_started: Rc<RefCell<bool>>,
     loaded: bool,
@@ -187,6 +215,24 @@ pub struct Document {
     pub cursor_offset: usize,
     pub scroll_offset: Vec2,
     pub code_actions: im::HashMap<usize, CodeActionResponse>,
+    /// The inlay hints of the lines last in view, as the offset they show
+    /// up before and their text, sorted by offset
+    inlay_hints: Arc<Vec<(usize, String)>>,
+    show_inlay_hints: bool,
+    /// The code lenses of the document, as the offset of the start of the
+    /// item they're about and the code lens, sorted by offset
+    code_lenses: Arc<Vec<(usize, CodeLens)>>,
+    /// Counts the times the code lenses were set, not counting the ones
+    /// resolved since
+    code_lenses_rev: u64,
+    /// The indices of the code lenses being resolved
+    code_lenses_resolving: Rc<RefCell<HashSet<usize>>>,
+    /// The commits that last changed the lines, sorted by line, and the rev
+    /// they were blamed at
+    blame: Arc<Vec<BlameHunk>>,
+    blame_rev: u64,
+    signature_triggers: Arc<SignatureTriggers>,
+    folding: Arc<Folding>,
     pub find: Rc<RefCell<Find>>,
     find_progress: Rc<RefCell<FindProgress>>,
     pub event_sink: ExtEventSink,
@@ -205,6 +251,7 @@ impl Document {
             BufferContent::Local(_) => None,
             BufferContent::SettingsValue(..) => None,
             BufferContent::Scratch(..) => None,
+            BufferContent::LspLog(_) => None,
         };
         let id = match &content {
             BufferContent::Scratch(id, _) => *id,
@@ -220,12 +267,22 @@ impl Document {
             line_styles: Rc::new(RefCell::new(HashMap::new())),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             semantic_styles: None,
+            semantic_styles_rev: None,
             load_started: Rc::new(RefCell::new(false)),
             histories: im::HashMap::new(),
             loaded: false,
             cursor_offset: 0,
             scroll_offset: Vec2::ZERO,
             code_actions: im::HashMap::new(),
+            inlay_hints: Arc::new(Vec::new()),
+            show_inlay_hints: true,
+            code_lenses: Arc::new(Vec::new()),
+            code_lenses_rev: 0,
+            code_lenses_resolving: Rc::new(RefCell::new(HashSet::new())),
+            blame: Arc::new(Vec::new()),
+            blame_rev: 0,
+            signature_triggers: Arc::new(SignatureTriggers::default()),
+            folding: Arc::new(Folding::new(1)),
             find: Rc::new(RefCell::new(Find::new(0))),
             find_progress: Rc::new(RefCell::new(FindProgress::Ready)),
             event_sink,
@@ -248,6 +305,7 @@ impl Document {
             BufferContent::Local(_) => None,
             BufferContent::SettingsValue(..) => None,
             BufferContent::Scratch(..) => None,
+            BufferContent::LspLog(_) => None,
         };
         self.on_update(None);
     }
@@ -263,6 +321,9 @@ impl Document {
     pub fn init_content(&mut self, content: Rope) {
         self.buffer.init_content(content);
         self.buffer.detect_indent(self.syntax.as_ref());
+        self.folding = Arc::new(Folding::new(self.buffer.num_lines()));
+        self.code_lenses = Arc::new(Vec::new());
+        self.code_lenses_resolving.borrow_mut().clear();
         self.loaded = true;
         self.on_update(None);
     }
@@ -323,6 +384,31 @@ impl Document {
         self.retrieve_history("head");
     }
 
+    /// Asks the proxy for the log of the language server the content is the
+    /// log of, the entries added since are appended as they come.
+    pub fn retrieve_lsp_log(&self) {
+        if let BufferContent::LspLog(name) = &self.content {
+            let log_name = name.clone();
+            let tab_id = self.tab_id;
+            let event_sink = self.event_sink.clone();
+            self.proxy.get_lsp_log(
+                name,
+                Box::new(move |result| {
+                    if let Ok(Value::String(log)) = result {
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::UpdateLspLog {
+                                name: log_name,
+                                content: Rope::from(log),
+                            },
+                            Target::Widget(tab_id),
+                        );
+                    }
+                }),
+            );
+        }
+    }
+
     pub fn retrieve_history(&mut self, version: &str) {
         if self.histories.contains_key(version) {
             return;
@@ -339,6 +425,14 @@ impl Document {
         }
     }
 
+    /// Gets all the versions again, since the head, the index and branches
+    /// move with the repository
+    pub fn reload_histories(&self) {
+        for history in self.histories.values() {
+            history.retrieve(self);
+        }
+    }
+
     pub fn load_history(&mut self, version: &str, content: Rope) {
         let mut history = DocumentHisotry::new(version.to_string());
         history.load_content(content, self);
@@ -437,9 +531,54 @@ impl Document {
         }
     }
 
-    fn trigger_head_change(&self) {
-        if let Some(head) = self.histories.get("head") {
-            head.trigger_update_change(self);
+    /// The row of the line in the side-by-side diff against the version
+    pub fn side_by_side_row(&self, version: &str, line: usize) -> usize {
+        let mut row = 0;
+        if let Some(history) = self.histories.get(version) {
+            for block in history.diff_blocks() {
+                match &block {
+                    DiffBlock::Both(_, r) | DiffBlock::Changed(_, r)
+                        if r.contains(&line) =>
+                    {
+                        return row + line - r.start;
+                    }
+                    DiffBlock::Skip(_, r) if r.contains(&line) => {
+                        return row;
+                    }
+                    _ => row += block.rows(),
+                }
+            }
+        }
+        row
+    }
+
+    /// The line of the right side at the row of the side-by-side diff
+    /// against the version, or the closest one when the row only has a line
+    /// on the left
+    pub fn side_by_side_line_from_row(&self, version: &str, row: usize) -> usize {
+        let mut current_row = 0;
+        if let Some(history) = self.histories.get(version) {
+            for block in history.diff_blocks() {
+                let rows = block.rows();
+                if current_row + rows > row {
+                    let line = match block {
+                        DiffBlock::Skip(_, r) => r.end,
+                        DiffBlock::Both(_, r) | DiffBlock::Changed(_, r) => {
+                            r.start
+                                + (row - current_row).min(r.len().saturating_sub(1))
+                        }
+                    };
+                    return line.min(self.buffer.last_line());
+                }
+                current_row += rows;
+            }
+        }
+        self.buffer.last_line()
+    }
+
+    fn trigger_history_change(&self) {
+        for history in self.histories.values() {
+            history.trigger_update_change(self);
         }
     }
 
@@ -472,7 +611,7 @@ impl Document {
         *self.find_progress.borrow_mut() = FindProgress::Started;
         self.clear_style_cache();
         self.trigger_syntax_change(delta);
-        self.trigger_head_change();
+        self.trigger_history_change();
         self.notify_special();
     }
 
@@ -480,6 +619,7 @@ impl Document {
         match &self.content {
             BufferContent::File(_) => {}
             BufferContent::Scratch(..) => {}
+            BufferContent::LspLog(_) => {}
             BufferContent::Local(local) => {
                 let s = self.buffer.text().to_string();
                 match local {
@@ -492,6 +632,7 @@ impl Document {
                     }
                     LocalBufferKind::SourceControl => {}
                     LocalBufferKind::Empty => {}
+                    LocalBufferKind::Rename => {}
                     LocalBufferKind::Palette => {
                         let _ = self.event_sink.submit_command(
                             LAPCE_UI_COMMAND,
@@ -535,10 +676,243 @@ impl Document {
     }
 
     pub fn set_semantic_styles(&mut self, styles: Option<Arc<Spans<Style>>>) {
+        self.semantic_styles_rev = styles.as_ref().map(|_| self.rev());
         self.semantic_styles = styles;
         self.clear_style_cache();
     }
 
+    pub fn has_semantic_styles(&self) -> bool {
+        self.semantic_styles.is_some()
+    }
+
+    /// Replaces the semantic styles in `start..end` with the ones that
+    /// changed since `base_rev`. It returns false when the styles aren't
+    /// the ones of `base_rev` anymore, in which case all of them need to be
+    /// asked for again. Without a base, the styles are the ones of a range
+    /// only, and are put over the syntax styles until all of them arrive.
+    pub fn edit_semantic_styles(
+        &mut self,
+        base_rev: Option<u64>,
+        start: usize,
+        end: usize,
+        styles: Spans<Style>,
+    ) -> bool {
+        let len = self.buffer.len();
+        if start > end || end > len || styles.len() != end - start {
+            return base_rev.is_none();
+        }
+        if let Some(base_rev) = base_rev {
+            if self.semantic_styles_rev != Some(base_rev) {
+                return false;
+            }
+            self.semantic_styles_rev = Some(self.rev());
+        }
+
+        let semantic_styles = self.semantic_styles.get_or_insert_with(|| {
+            self.syntax
+                .as_ref()
+                .and_then(|syntax| syntax.styles.clone())
+                .unwrap_or_else(|| Arc::new(SpansBuilder::new(len).build()))
+        });
+        Arc::make_mut(semantic_styles).edit(Interval::new(start, end), styles);
+        self.clear_style_cache();
+        true
+    }
+
+    pub fn set_inlay_hints(&mut self, hints: &[InlayHint]) {
+        let mut inlay_hints: Vec<(usize, String)> = hints
+            .iter()
+            .map(|hint| {
+                let offset = self.buffer.offset_of_position(&hint.position);
+                let label = format!(
+                    "{}{}{}",
+                    if hint.padding_left { " " } else { "" },
+                    hint.label,
+                    if hint.padding_right { " " } else { "" },
+                );
+                (offset, label)
+            })
+            .collect();
+        inlay_hints.sort_by_key(|(offset, _)| *offset);
+        self.inlay_hints = Arc::new(inlay_hints);
+        self.clear_text_layout_cache();
+    }
+
+    pub fn inlay_hints(&self) -> &Arc<Vec<(usize, String)>> {
+        &self.inlay_hints
+    }
+
+    pub fn show_inlay_hints(&self) -> bool {
+        self.show_inlay_hints
+    }
+
+    pub fn toggle_inlay_hints(&mut self) {
+        self.show_inlay_hints = !self.show_inlay_hints;
+        self.clear_text_layout_cache();
+    }
+
+    pub fn set_code_lenses(&mut self, code_lenses: Vec<CodeLens>) {
+        let mut code_lenses: Vec<(usize, CodeLens)> = code_lenses
+            .into_iter()
+            .map(|code_lens| {
+                let offset = self.buffer.offset_of_position(&code_lens.range.start);
+                (offset, code_lens)
+            })
+            .collect();
+        code_lenses.sort_by_key(|(offset, _)| *offset);
+        self.code_lenses = Arc::new(code_lenses);
+        self.code_lenses_rev += 1;
+        self.code_lenses_resolving.borrow_mut().clear();
+        self.update_code_lens_lines();
+    }
+
+    /// Puts the command of a code lens in place of the unresolved one it was
+    /// resolved from.
+    pub fn set_resolved_code_lens(&mut self, resolved: CodeLens) {
+        if let Some(index) = self.code_lenses.iter().position(|(_, code_lens)| {
+            code_lens.command.is_none()
+                && code_lens.range == resolved.range
+                && code_lens.data == resolved.data
+        }) {
+            self.code_lenses_resolving.borrow_mut().remove(&index);
+            Arc::make_mut(&mut self.code_lenses)[index].1 = resolved;
+        }
+    }
+
+    /// Marks the code lens at the index as being resolved, false when it
+    /// already was.
+    pub fn start_resolving_code_lens(&self, index: usize) -> bool {
+        self.code_lenses_resolving.borrow_mut().insert(index)
+    }
+
+    pub fn code_lenses(&self) -> &Arc<Vec<(usize, CodeLens)>> {
+        &self.code_lenses
+    }
+
+    pub fn code_lenses_rev(&self) -> u64 {
+        self.code_lenses_rev
+    }
+
+    pub fn signature_triggers(&self) -> &SignatureTriggers {
+        &self.signature_triggers
+    }
+
+    pub fn set_signature_triggers(&mut self, triggers: SignatureTriggers) {
+        self.signature_triggers = Arc::new(triggers);
+    }
+
+    /// The code lenses drawn above the line.
+    pub fn line_code_lenses(&self, line: usize) -> &[(usize, CodeLens)] {
+        let start_offset = self.buffer.offset_of_line(line);
+        let end_offset = self.buffer.offset_of_line(line + 1);
+        let start = self
+            .code_lenses
+            .partition_point(|(offset, _)| *offset < start_offset);
+        let end = self
+            .code_lenses
+            .partition_point(|(offset, _)| *offset < end_offset);
+        &self.code_lenses[start..end.max(start)]
+    }
+
+    pub fn set_blame(&mut self, rev: u64, blame: Vec<BlameHunk>) {
+        self.blame = Arc::new(blame);
+        self.blame_rev = rev;
+    }
+
+    pub fn blame(&self) -> &Arc<Vec<BlameHunk>> {
+        &self.blame
+    }
+
+    /// The blame of the line, unless the document changed since it was
+    /// blamed.
+    pub fn line_blame(&self, line: usize) -> Option<&BlameHunk> {
+        if self.blame_rev != self.rev() {
+            return None;
+        }
+        let index = self.blame.partition_point(|hunk| hunk.line <= line);
+        self.blame
+            .get(index.checked_sub(1)?)
+            .filter(|hunk| line < hunk.line + hunk.lines)
+    }
+
+    fn update_code_lens_lines(&mut self) {
+        let lines = self
+            .code_lenses
+            .iter()
+            .map(|(offset, _)| self.buffer.line_of_offset(*offset))
+            .collect();
+        self.folding_mut().set_code_lens_lines(lines);
+    }
+
+    pub fn folding(&self) -> &Arc<Folding> {
+        &self.folding
+    }
+
+    pub fn folding_mut(&mut self) -> &mut Folding {
+        Arc::make_mut(&mut self.folding)
+    }
+
+    pub fn set_folding_ranges(&mut self, ranges: Vec<(usize, usize)>) {
+        self.folding_mut().set_ranges(ranges);
+    }
+
+    /// The inlay hints shown on the line, as the column they show up
+    /// before and their text.
+    fn line_inlay_hints(&self, line: usize) -> Vec<(usize, &str)> {
+        if !self.show_inlay_hints
+            || self.inlay_hints.is_empty()
+            || self.content.is_input()
+        {
+            return Vec::new();
+        }
+        let line_start = self.buffer.offset_of_line(line);
+        let line_end = self.buffer.line_end_offset(line, true);
+        let first = self
+            .inlay_hints
+            .partition_point(|(offset, _)| *offset < line_start);
+        self.inlay_hints[first..]
+            .iter()
+            .take_while(|(offset, _)| *offset <= line_end)
+            .map(|(offset, label)| (offset - line_start, label.as_str()))
+            .collect()
+    }
+
+    /// Where a column of the line is in its text layout, which has the
+    /// inlay hints in it. A column with hints in front of it lands before
+    /// them unless `after_hints` is set.
+    fn layout_index_of_col(
+        &self,
+        line: usize,
+        col: usize,
+        after_hints: bool,
+    ) -> usize {
+        let shift: usize = self
+            .line_inlay_hints(line)
+            .iter()
+            .take_while(|(hint_col, _)| {
+                *hint_col < col || (after_hints && *hint_col == col)
+            })
+            .map(|(_, label)| label.len())
+            .sum();
+        col + shift
+    }
+
+    /// The column of the line at an index of its text layout. An index
+    /// inside an inlay hint lands on the column the hint is at.
+    fn col_of_layout_index(&self, line: usize, index: usize) -> usize {
+        let mut shift = 0;
+        for (hint_col, label) in self.line_inlay_hints(line) {
+            if index < hint_col + shift {
+                break;
+            }
+            if index < hint_col + shift + label.len() {
+                return hint_col;
+            }
+            shift += label.len();
+        }
+        index - shift
+    }
+
     fn clear_style_cache(&self) {
         self.line_styles.borrow_mut().clear();
         self.clear_text_layout_cache();
@@ -602,16 +976,34 @@ impl Document {
         if let Some(syntax) = self.syntax.as_mut() {
             syntax.lens.apply_delta(delta);
         }
+
+        if !self.inlay_hints.is_empty() {
+            let mut transformer = Transformer::new(delta);
+            for (offset, _) in Arc::make_mut(&mut self.inlay_hints).iter_mut() {
+                *offset = transformer.transform(*offset, true);
+            }
+        }
+
+        if !self.code_lenses.is_empty() {
+            let mut transformer = Transformer::new(delta);
+            for (offset, _) in Arc::make_mut(&mut self.code_lenses).iter_mut() {
+                *offset = transformer.transform(*offset, true);
+            }
+        }
     }
 
     fn apply_deltas(&mut self, deltas: &[(RopeDelta, InvalLines)]) {
         let rev = self.rev() - deltas.len() as u64;
-        for (i, (delta, _)) in deltas.iter().enumerate() {
+        for (i, (delta, inval_lines)) in deltas.iter().enumerate() {
             self.update_styles(delta);
+            Arc::make_mut(&mut self.folding).update_lines(inval_lines);
             if self.content.is_file() {
                 self.proxy.update(self.id, delta, rev + i as u64 + 1);
             }
         }
+        if !self.code_lenses.is_empty() {
+            self.update_code_lens_lines();
+        }
 
         let delta = if deltas.len() == 1 {
             Some(&deltas[0].0)
@@ -669,6 +1061,45 @@ impl Document {
         deltas
     }
 
+    /// Grows each region of the selection to the smallest range around it,
+    /// from the selection ranges the language server gave for both ends of
+    /// the region when one of them encloses it, or else from the syntax
+    /// tree.
+    pub fn expand_selection(
+        &self,
+        selection: &Selection,
+        ranges: Option<&[SelectionRange]>,
+    ) -> Selection {
+        let mut new_selection = Selection::new();
+        for (i, region) in selection.regions().iter().enumerate() {
+            let (start, end) = (region.min(), region.max());
+            let range = ranges
+                .into_iter()
+                .flat_map(|ranges| ranges.iter().skip(i * 2).take(2))
+                .flat_map(|range| {
+                    std::iter::successors(Some(range), |r| r.parent.as_deref())
+                })
+                .map(|r| {
+                    (
+                        self.buffer.offset_of_position(&r.range.start),
+                        self.buffer.offset_of_position(&r.range.end),
+                    )
+                })
+                .filter(|range| {
+                    range.0 <= start && range.1 >= end && *range != (start, end)
+                })
+                .min_by_key(|range| range.1 - range.0)
+                .or_else(|| {
+                    self.syntax
+                        .as_ref()
+                        .and_then(|syntax| syntax.expand_range(start, end))
+                });
+            let (start, end) = range.unwrap_or((start, end));
+            new_selection.add_region(SelRegion::new(start, end, None));
+        }
+        new_selection
+    }
+
     pub fn do_multi_selection(
         &self,
         text: &mut PietText,
@@ -678,6 +1109,9 @@ impl Document {
     ) {
         use MultiSelectionCommand::*;
         match cmd {
+            // The editor expands the selection once the language server
+            // answers, and keeps what it shrinks back to.
+            ExpandSelection | ShrinkSelection => {}
             SelectUndo => {
                 if let CursorMode::Insert(_) = cursor.mode.clone() {
                     if let Some(selection) =
@@ -943,7 +1377,8 @@ impl Document {
         config: &Config,
     ) -> Point {
         let text_layout = self.get_text_layout(text, line, font_size, config);
-        text_layout.hit_test_text_position(col).point
+        let index = self.layout_index_of_col(line, col, false);
+        text_layout.hit_test_text_position(index).point
     }
 
     pub fn offset_of_point(
@@ -955,11 +1390,13 @@ impl Document {
         config: &Config,
     ) -> (usize, bool) {
         let last_line = self.buffer.last_line();
-        let line = ((point.y / config.editor.line_height as f64).floor() as usize)
-            .min(last_line);
+        let row = (point.y / config.editor.line_height as f64).floor() as usize;
+        let (line, _) = self.folding.line_of_row(row);
+        let line = line.min(last_line);
+        let line = self.folding.prev_visible_line(line);
         let text_layout = self.get_text_layout(text, line, font_size, config);
         let hit_point = text_layout.hit_test_point(Point::new(point.x, 0.0));
-        let col = hit_point.idx;
+        let col = self.col_of_layout_index(line, hit_point.idx);
         let max_col = self.buffer.line_end_col(line, mode != Mode::Normal);
         (
             self.buffer.offset_of_line_col(line, col.min(max_col)),
@@ -975,8 +1412,7 @@ impl Document {
         config: &Config,
     ) -> Point {
         let (line, col) = self.buffer.offset_to_line_col(offset);
-        let text_layout = self.get_text_layout(text, line, font_size, config);
-        text_layout.hit_test_text_position(col).point
+        self.point_of_line_col(text, line, col, font_size, config)
     }
 
     pub fn get_text_layout(
@@ -1009,6 +1445,20 @@ impl Document {
         config: &Config,
     ) -> PietTextLayout {
         let line_content = self.buffer.line_content(line);
+        let inlay_hints = self.line_inlay_hints(line);
+        let line_content = if inlay_hints.is_empty() {
+            line_content.to_string()
+        } else {
+            let mut content = String::new();
+            let mut col = 0;
+            for (hint_col, label) in inlay_hints.iter() {
+                content.push_str(&line_content[col..*hint_col]);
+                content.push_str(label);
+                col = *hint_col;
+            }
+            content.push_str(&line_content[col..]);
+            content
+        };
         let tab_width =
             config.tab_width(text, config.editor.font_family(), font_size);
 
@@ -1023,7 +1473,7 @@ impl Document {
             font_size
         };
         let mut layout_builder = text
-            .new_text_layout(line_content.to_string())
+            .new_text_layout(line_content)
             .font(font_family, font_size as f64)
             .text_color(
                 config
@@ -1036,14 +1486,29 @@ impl Document {
         for line_style in styles.iter() {
             if let Some(fg_color) = line_style.style.fg_color.as_ref() {
                 if let Some(fg_color) = config.get_style_color(fg_color) {
+                    let start =
+                        self.layout_index_of_col(line, line_style.start, true);
+                    let end = self.layout_index_of_col(line, line_style.end, false);
                     layout_builder = layout_builder.range_attribute(
-                        line_style.start..line_style.end,
+                        start..end,
                         TextAttribute::TextColor(fg_color.clone()),
                     );
                 }
             }
         }
 
+        let mut shift = 0;
+        for (hint_col, label) in inlay_hints.iter() {
+            let start = hint_col + shift;
+            layout_builder = layout_builder.range_attribute(
+                start..start + label.len(),
+                TextAttribute::TextColor(
+                    config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone(),
+                ),
+            );
+            shift += label.len();
+        }
+
         layout_builder.build().unwrap()
     }
 
@@ -1060,7 +1525,8 @@ impl Document {
             ColPosition::Col(x) => {
                 let text_layout =
                     self.get_text_layout(text, line, font_size, config);
-                let n = text_layout.hit_test_point(Point::new(x, 0.0)).idx;
+                let index = text_layout.hit_test_point(Point::new(x, 0.0)).idx;
+                let n = self.col_of_layout_index(line, index);
                 n.min(self.buffer.line_end_col(line, caret))
             }
             ColPosition::End => self.buffer.line_end_col(line, caret),
@@ -1221,6 +1687,19 @@ impl Document {
         new_selection
     }
 
+    /// The offset itself if it's on a shown line, or else the end of the
+    /// line of the fold hiding it.
+    fn visible_offset(&self, offset: usize, mode: Mode) -> usize {
+        let line = self.buffer.line_of_offset(offset);
+        if !self.folding.is_hidden(line) {
+            return offset;
+        }
+        self.buffer.line_end_offset(
+            self.folding.prev_visible_line(line),
+            mode != Mode::Normal,
+        )
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn move_offset(
         &self,
@@ -1246,7 +1725,7 @@ impl Document {
 
                 let new_offset =
                     self.buffer.prev_grapheme_offset(offset, count, min_offset);
-                (new_offset, None)
+                (self.visible_offset(new_offset, mode), None)
             }
             Movement::Right => {
                 let line_end =
@@ -1260,16 +1739,21 @@ impl Document {
 
                 let new_offset =
                     self.buffer.next_grapheme_offset(offset, count, max_offset);
+                let line = self.buffer.line_of_offset(new_offset);
+                let next_line = self.folding.next_visible_line(line);
+                let new_offset = if next_line > line {
+                    self.buffer.offset_of_line(next_line)
+                } else {
+                    self.visible_offset(new_offset, mode)
+                };
 
                 (new_offset, None)
             }
             Movement::Up => {
                 let line = self.buffer.line_of_offset(offset);
-                let line = if line == 0 {
-                    0
-                } else {
-                    line.saturating_sub(count)
-                };
+                let line = self.folding.actual_line(
+                    self.folding.visual_line(line).saturating_sub(count),
+                );
 
                 let horiz = horiz.cloned().unwrap_or_else(|| {
                     ColPosition::Col(
@@ -1291,7 +1775,11 @@ impl Document {
                 let last_line = self.buffer.last_line();
                 let line = self.buffer.line_of_offset(offset);
 
-                let line = (line + count).min(last_line);
+                let line = self
+                    .folding
+                    .actual_line(self.folding.visual_line(line) + count)
+                    .min(last_line);
+                let line = self.folding.prev_visible_line(line);
 
                 let horiz = horiz.cloned().unwrap_or_else(|| {
                     ColPosition::Col(
@@ -1314,7 +1802,10 @@ impl Document {
                 let last_offset = self
                     .buffer
                     .offset_line_end(self.buffer.len(), mode != Mode::Normal);
-                (last_offset, Some(ColPosition::End))
+                (
+                    self.visible_offset(last_offset, mode),
+                    Some(ColPosition::End),
+                )
             }
             Movement::FirstNonBlank => {
                 let line = self.buffer.line_of_offset(offset);
@@ -1339,6 +1830,7 @@ impl Document {
                     LinePosition::First => 0,
                     LinePosition::Last => self.buffer.last_line(),
                 };
+                let line = self.folding.prev_visible_line(line);
                 let horiz = horiz.cloned().unwrap_or_else(|| {
                     ColPosition::Col(
                         self.point_of_offset(text, offset, font_size, config).x,
@@ -1362,7 +1854,7 @@ impl Document {
                     .text()
                     .prev_grapheme_offset(new_offset + 1)
                     .unwrap();
-                (new_offset, None)
+                (self.visible_offset(new_offset, mode), None)
             }
             Movement::WordEndForward => {
                 let new_offset = self.buffer.move_n_wordends_forward(
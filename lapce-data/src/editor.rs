@@ -5,8 +5,8 @@ use crate::command::{CommandExecuted, CommandKind};
 use crate::completion::{CompletionData, CompletionStatus, Snippet};
 use crate::config::Config;
 use crate::data::{
-    EditorDiagnostic, InlineFindDirection, LapceEditorData, LapceMainSplitData,
-    SplitContent,
+    EditorDiagnostic, EditorView, InlineFindDirection, LapceEditorData,
+    LapceMainSplitData, SplitContent,
 };
 use crate::document::BufferContent;
 use crate::document::Document;
@@ -16,7 +16,8 @@ use crate::hover::HoverStatus;
 use crate::keypress::KeyMap;
 use crate::keypress::KeyPressFocus;
 use crate::palette::PaletteData;
-use crate::proxy::path_from_url;
+use crate::proxy::{path_from_url, styles_spans};
+use crate::signature::{SignatureData, SignatureStatus};
 use crate::{
     command::{EnsureVisiblePosition, LapceUICommand, LAPCE_UI_COMMAND},
     split::SplitMoveDirection,
@@ -38,21 +39,31 @@ use lapce_core::buffer::{DiffLines, InvalLines};
 use lapce_core::command::{
     EditCommand, FocusCommand, MotionModeCommand, MultiSelectionCommand,
 };
-use lapce_core::mode::{Mode, MotionMode};
+use lapce_core::cursor::CursorMode;
+use lapce_core::mode::{Mode, MotionMode, VisualMode};
 pub use lapce_core::syntax::Syntax;
+use lapce_rpc::proxy::{HierarchyKind, InlayHint, TaggedData};
+use lapce_rpc::source_control::BlameHunk;
+use lapce_rpc::style::LineStyle;
+use lsp_types::CodeAction;
 use lsp_types::CodeActionOrCommand;
 use lsp_types::CompletionTextEdit;
 use lsp_types::DocumentChangeOperation;
 use lsp_types::DocumentChanges;
 use lsp_types::OneOf;
+use lsp_types::ResourceOp;
+use lsp_types::TextDocumentEdit;
 use lsp_types::TextEdit;
 use lsp_types::Url;
 use lsp_types::WorkspaceEdit;
 use lsp_types::{
-    CodeActionResponse, CompletionItem, DiagnosticSeverity, GotoDefinitionResponse,
-    Location, Position,
+    CallHierarchyItem, CodeActionResponse, CodeLens, CompletionItem,
+    DiagnosticSeverity, DocumentHighlight, DocumentHighlightKind, FoldingRange,
+    GotoDefinitionResponse, Location, Position, PrepareRenameResponse, Range,
+    SelectionRange,
 };
 use serde_json::Value;
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::path::Path;
 use std::thread;
@@ -80,12 +91,69 @@ pub struct EditorLocation {
     pub history: Option<String>,
 }
 
+/// The occurrences of the symbol under the cursor, as byte ranges with their
+/// kind, for the document revision and cursor offset they were asked for.
+#[derive(Clone, Debug)]
+pub struct DocumentHighlights {
+    pub rev: u64,
+    pub offset: usize,
+    pub highlights: Vec<(usize, usize, DocumentHighlightKind)>,
+}
+
+impl DocumentHighlights {
+    /// Without an answer from a language server, the highlights are the
+    /// tree-sitter identifiers named like the one at the offset.
+    pub fn new(
+        doc: &Document,
+        offset: usize,
+        highlights: Option<&[DocumentHighlight]>,
+    ) -> Self {
+        let highlights = match highlights {
+            Some(highlights) => highlights
+                .iter()
+                .map(|highlight| {
+                    (
+                        doc.buffer().offset_of_position(&highlight.range.start),
+                        doc.buffer().offset_of_position(&highlight.range.end),
+                        highlight.kind.unwrap_or(DocumentHighlightKind::Text),
+                    )
+                })
+                .collect(),
+            None => doc
+                .syntax()
+                .filter(|syntax| syntax.rev() == doc.rev())
+                .and_then(|syntax| syntax.find_identifier_occurrences(offset))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(start, end)| (start, end, DocumentHighlightKind::Text))
+                .collect(),
+        };
+        Self {
+            rev: doc.rev(),
+            offset,
+            highlights,
+        }
+    }
+
+    /// If these are still the highlights to show with the cursor at the
+    /// offset, which holds as long as it stays on one of the occurrences.
+    pub fn is_for(&self, rev: u64, offset: usize) -> bool {
+        self.rev == rev
+            && (self.offset == offset
+                || self
+                    .highlights
+                    .iter()
+                    .any(|(start, end, _)| *start <= offset && offset <= *end))
+    }
+}
+
 pub struct LapceEditorBufferData {
     pub view_id: WidgetId,
     pub editor: Arc<LapceEditorData>,
     pub doc: Arc<Document>,
     pub completion: Arc<CompletionData>,
     pub hover: Arc<HoverData>,
+    pub signature: Arc<SignatureData>,
     pub main_split: LapceMainSplitData,
     pub source_control: Arc<SourceControlData>,
     pub palette: Arc<PaletteData>,
@@ -192,6 +260,363 @@ impl LapceEditorBufferData {
         }
     }
 
+    /// Ask for the occurrences of the symbol under the cursor, unless the
+    /// ones the editor has still apply.
+    pub fn get_document_highlights(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded() {
+            return;
+        }
+        let offset = self.editor.cursor.offset();
+        let rev = self.doc.rev();
+        if let Some(highlights) = self.editor.document_highlights.as_ref() {
+            if highlights.is_for(rev, offset) {
+                return;
+            }
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let view_id = self.editor.view_id;
+            let position = self.doc.buffer().offset_to_position(offset);
+            let event_sink = ctx.get_external_handle();
+            self.proxy.get_document_highlights(
+                self.doc.id(),
+                position,
+                Box::new(move |result| {
+                    let highlights = result.ok().and_then(|res| {
+                        serde_json::from_value::<Option<Vec<DocumentHighlight>>>(res)
+                            .ok()
+                            .flatten()
+                    });
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateDocumentHighlights {
+                            view_id,
+                            path,
+                            rev,
+                            offset,
+                            highlights,
+                        },
+                        Target::Auto,
+                    );
+                }),
+            );
+        }
+    }
+
+    /// Ask for the inlay hints of the lines in view.
+    pub fn get_inlay_hints(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded() || !self.doc.show_inlay_hints() {
+            return;
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let buffer = self.doc.buffer();
+            let (start, end) = self.visible_offsets();
+            let range = Range {
+                start: buffer.offset_to_position(start),
+                end: buffer.offset_to_position(end),
+            };
+            let rev = self.doc.rev();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.get_inlay_hints(
+                self.doc.id(),
+                range,
+                Box::new(move |result| {
+                    if let Ok(res) = result {
+                        if let Ok(hints) =
+                            serde_json::from_value::<Vec<InlayHint>>(res)
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::UpdateInlayHints(path, rev, hints),
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Ask for the semantic styles of the lines in view, so that they show
+    /// up before the ones of the whole file, which can take a while for a
+    /// big one.
+    pub fn get_semantic_styles_range(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded() || self.doc.has_semantic_styles() {
+            return;
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let buffer = self.doc.buffer();
+            let (start, end) = self.visible_offsets();
+            let range = Range {
+                start: buffer.offset_to_position(start),
+                end: buffer.offset_to_position(end),
+            };
+            let buffer_id = self.doc.id();
+            let rev = self.doc.rev();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.get_semantic_styles_range(
+                buffer_id,
+                range,
+                Box::new(move |result| {
+                    if let Ok(res) = result {
+                        if let Ok(styles) =
+                            serde_json::from_value::<Vec<LineStyle>>(res)
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::EditSemanticStyles {
+                                    buffer_id,
+                                    path,
+                                    rev,
+                                    base_rev: None,
+                                    start,
+                                    end,
+                                    styles: styles_spans(&styles, start, end),
+                                },
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// The offsets of the start of the first line in view and the end of
+    /// the last one.
+    fn visible_offsets(&self) -> (usize, usize) {
+        let buffer = self.doc.buffer();
+        let line_height = self.config.editor.line_height as f64;
+        let folding = self.doc.folding();
+        let (start_line, _) = folding.line_of_row(
+            (self.editor.scroll_offset.y / line_height).floor() as usize,
+        );
+        let (end_line, _) = folding.line_of_row(
+            ((self.editor.scroll_offset.y + self.editor.size.borrow().height)
+                / line_height)
+                .ceil() as usize,
+        );
+        let start_line = start_line.min(buffer.last_line());
+        let end_line = end_line.min(buffer.last_line());
+        (
+            buffer.offset_of_line(start_line),
+            buffer.line_end_offset(end_line, true),
+        )
+    }
+
+    /// Ask for the ranges that can be folded, which come from the syntax
+    /// tree when no language server provides them.
+    pub fn get_folding_ranges(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded() {
+            return;
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let rev = self.doc.rev();
+            let syntax_ranges = self
+                .doc
+                .syntax()
+                .map(|syntax| syntax.folding_ranges())
+                .unwrap_or_default();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.get_folding_ranges(
+                self.doc.id(),
+                Box::new(move |result| {
+                    let ranges = result
+                        .ok()
+                        .and_then(|res| {
+                            serde_json::from_value::<Vec<FoldingRange>>(res).ok()
+                        })
+                        .map(|ranges| {
+                            ranges
+                                .into_iter()
+                                .map(|r| {
+                                    (r.start_line as usize, r.end_line as usize)
+                                })
+                                .collect()
+                        })
+                        .unwrap_or(syntax_ranges);
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdateFoldingRanges(path, rev, ranges),
+                        Target::Auto,
+                    );
+                }),
+            );
+        }
+    }
+
+    pub fn get_code_lens(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded() {
+            return;
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let rev = self.doc.rev();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.get_code_lens(
+                self.doc.id(),
+                Box::new(move |result| {
+                    if let Ok(res) = result {
+                        if let Ok(code_lenses) =
+                            serde_json::from_value::<Vec<CodeLens>>(res)
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::UpdateCodeLens(
+                                    path,
+                                    rev,
+                                    code_lenses,
+                                ),
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Blames the lines, when they're shown inline or in the gutter.
+    pub fn get_blame(&self, ctx: &mut EventCtx) {
+        if !self.doc.loaded()
+            || !(self.config.editor.inline_blame || self.editor.blame)
+        {
+            return;
+        }
+        if let BufferContent::File(path) = self.doc.content() {
+            let path = path.clone();
+            let rev = self.doc.rev();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.git_blame(
+                self.doc.id(),
+                Box::new(move |result| {
+                    if let Ok(res) = result {
+                        if let Ok(blame) =
+                            serde_json::from_value::<Vec<BlameHunk>>(res)
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::UpdateBlame(path, rev, blame),
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Ask for the commands of the code lenses in view that came without
+    /// one, unless they were already asked for.
+    pub fn resolve_code_lenses(&self, ctx: &mut EventCtx) {
+        let path = match self.doc.content() {
+            BufferContent::File(path) => path,
+            _ => return,
+        };
+        let (start, end) = self.visible_offsets();
+        for (i, (offset, code_lens)) in self.doc.code_lenses().iter().enumerate() {
+            if *offset < start
+                || *offset > end
+                || code_lens.command.is_some()
+                || !self.doc.start_resolving_code_lens(i)
+            {
+                continue;
+            }
+            let path = path.clone();
+            let event_sink = ctx.get_external_handle();
+            self.proxy.resolve_code_lens(
+                code_lens,
+                Box::new(move |result| {
+                    if let Ok(res) = result {
+                        if let Ok(code_lens) =
+                            serde_json::from_value::<CodeLens>(res)
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ResolveCodeLens(path, code_lens),
+                                Target::Auto,
+                            );
+                        }
+                    }
+                }),
+            );
+        }
+    }
+
+    /// Runs a command from a code lens, a code action or a completion item.
+    /// The commands servers leave to the editor are run here, rust-analyzer
+    /// runnables in a new terminal, and the other commands go back to the
+    /// server with `workspace/executeCommand`, the one the data of the item
+    /// is tagged with.
+    pub fn run_lsp_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        command: &lsp_types::Command,
+        data: Option<&Value>,
+    ) {
+        let arguments = command.arguments.as_deref().unwrap_or(&[]);
+        match command.command.as_str() {
+            "editor.action.triggerSuggest" => {
+                self.update_completion(ctx);
+            }
+            // There's no debugger, so debugging a runnable runs it.
+            "rust-analyzer.runSingle" | "rust-analyzer.debugSingle" => {
+                if let Some((title, cwd, command)) =
+                    arguments.first().and_then(rust_analyzer_runnable)
+                {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RunInTerminal {
+                            title,
+                            cwd,
+                            command,
+                        },
+                        Target::Auto,
+                    ));
+                }
+            }
+            "rust-analyzer.showReferences" | "editor.action.showReferences" => {
+                let locations: Vec<EditorLocation> = arguments
+                    .get(2)
+                    .and_then(|locations| {
+                        serde_json::from_value::<Vec<Location>>(locations.clone())
+                            .ok()
+                    })
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|location| EditorLocation {
+                        path: path_from_url(&location.uri),
+                        position: Some(location.range.start),
+                        scroll_offset: None,
+                        history: None,
+                    })
+                    .collect();
+                if !locations.is_empty() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::RunPaletteReferences(locations),
+                        Target::Widget(self.palette.widget_id),
+                    ));
+                }
+            }
+            _ => {
+                self.proxy.execute_command(
+                    self.doc.id(),
+                    command,
+                    data,
+                    Box::new(|result| {
+                        if let Err(e) = result {
+                            log::error!("failed to execute command: {e:?}");
+                        }
+                    }),
+                );
+            }
+        }
+    }
+
     fn inactive_apply_delta(&mut self, delta: &RopeDelta) {
         for (view_id, editor) in self.main_split.editors.iter_mut() {
             if view_id != &self.editor.view_id
@@ -206,6 +631,10 @@ impl LapceEditorBufferData {
         self.editor.content == BufferContent::Local(LocalBufferKind::Palette)
     }
 
+    fn is_rename(&self) -> bool {
+        self.editor.content == BufferContent::Local(LocalBufferKind::Rename)
+    }
+
     /// Check if there are completions that are being rendered
     fn has_completions(&self) -> bool {
         self.completion.status != CompletionStatus::Inactive
@@ -216,54 +645,80 @@ impl LapceEditorBufferData {
         self.hover.status != HoverStatus::Inactive && !self.hover.is_empty()
     }
 
-    pub fn run_code_action(&mut self, action: &CodeActionOrCommand) {
-        if let BufferContent::File(path) = &self.editor.content {
-            match action {
-                CodeActionOrCommand::Command(_cmd) => {}
-                CodeActionOrCommand::CodeAction(action) => {
-                    if let Some(edit) = action.edit.as_ref() {
-                        if let Some(edits) = workspce_edits(edit) {
-                            if let Some(edits) =
-                                edits.get(&Url::from_file_path(&path).unwrap())
-                            {
-                                let path = path.clone();
-                                let doc = self
-                                    .main_split
-                                    .open_docs
-                                    .get_mut(&path)
-                                    .unwrap();
-                                let edits: Vec<(
-                                    lapce_core::selection::Selection,
-                                    &str,
-                                )> = edits
-                                    .iter()
-                                    .map(|edit| {
-                                        let selection =
-                                            lapce_core::selection::Selection::region(
-                                                doc.buffer().offset_of_position(
-                                                    &edit.range.start,
-                                                ),
-                                                doc.buffer().offset_of_position(
-                                                    &edit.range.end,
-                                                ),
-                                            );
-                                        (selection, edit.new_text.as_str())
-                                    })
-                                    .collect();
-                                self.main_split.edit(
-                                    &path,
-                                    &edits,
-                                    lapce_core::editor::EditType::Other,
-                                );
-                            }
-                        }
-                    }
+    fn has_signature(&self) -> bool {
+        self.signature.is_shown()
+    }
+
+    /// Runs a code action. An action that comes without its edit is
+    /// resolved first, since servers can leave computing the edit until the
+    /// action is picked.
+    pub fn run_code_action(
+        &mut self,
+        ctx: &mut EventCtx,
+        action: &CodeActionOrCommand,
+    ) {
+        match action {
+            CodeActionOrCommand::Command(command) => {
+                self.run_lsp_command(ctx, command, None);
+            }
+            CodeActionOrCommand::CodeAction(action) => {
+                // A bare command the proxy turned into an action has nothing
+                // the server could fill in, so it's run without resolving.
+                let bare_command = action.command.is_some()
+                    && !TaggedData::has_server_data(action.data.as_ref());
+                if action.edit.is_none() && !bare_command {
+                    let view_id = self.editor.view_id;
+                    let event_sink = ctx.get_external_handle();
+                    let unresolved = action.clone();
+                    self.proxy.resolve_code_action(
+                        action,
+                        Box::new(move |result| {
+                            let action = result
+                                .ok()
+                                .and_then(|value| {
+                                    serde_json::from_value::<CodeAction>(value).ok()
+                                })
+                                .unwrap_or(unresolved);
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ApplyCodeAction(action),
+                                Target::Widget(view_id),
+                            );
+                        }),
+                    );
+                } else {
+                    self.apply_code_action(ctx, action);
                 }
             }
         }
     }
 
-    pub fn apply_completion_item(&mut self, item: &CompletionItem) -> Result<()> {
+    /// Applies the edit of a code action, and then runs its command.
+    pub fn apply_code_action(&mut self, ctx: &mut EventCtx, action: &CodeAction) {
+        if let Some(edit) = action.edit.as_ref() {
+            if self.main_split.apply_workspace_edit(ctx, edit).is_err() {
+                return;
+            }
+        }
+        if let Some(command) = action.command.as_ref() {
+            self.run_lsp_command(ctx, command, action.data.as_ref());
+        }
+    }
+
+    /// Applies a completion item, and then runs its command.
+    pub fn apply_completion_item(
+        &mut self,
+        ctx: &mut EventCtx,
+        item: &CompletionItem,
+    ) -> Result<()> {
+        self.apply_completion_edits(item)?;
+        if let Some(command) = item.command.as_ref() {
+            self.run_lsp_command(ctx, command, item.data.as_ref());
+        }
+        Ok(())
+    }
+
+    fn apply_completion_edits(&mut self, item: &CompletionItem) -> Result<()> {
         let additional_edit: Option<Vec<_>> =
             item.additional_text_edits.as_ref().map(|edits| {
                 edits
@@ -411,6 +866,39 @@ impl LapceEditorBufferData {
         hover.cancel();
     }
 
+    pub fn cancel_signature(&mut self) {
+        let signature = Arc::make_mut(&mut self.signature);
+        signature.cancel();
+    }
+
+    /// Asks for the signature help after `ch` was typed, or again after an
+    /// edit or a cursor move while it's shown.
+    fn update_signature(&mut self, ctx: &mut EventCtx, ch: Option<&str>) {
+        if self.signature.status == SignatureStatus::Inactive && ch.is_none() {
+            return;
+        }
+        if self.get_mode() != Mode::Insert || !self.doc.content().is_file() {
+            self.cancel_signature();
+            return;
+        }
+        if !self.doc.loaded() {
+            return;
+        }
+        let offset = self.editor.cursor.offset();
+        let position = self.doc.buffer().offset_to_position(offset);
+        let signature = Arc::make_mut(&mut self.signature);
+        signature.request(
+            &self.proxy,
+            self.doc.id(),
+            self.editor.view_id,
+            offset,
+            position,
+            ch,
+            self.doc.signature_triggers(),
+            ctx.get_external_handle(),
+        );
+    }
+
     fn update_completion(&mut self, ctx: &mut EventCtx) {
         if self.get_mode() != Mode::Insert {
             self.cancel_completion();
@@ -685,6 +1173,28 @@ impl LapceEditorBufferData {
         }
     }
 
+    /// Moves the cursor to the line of the fold hiding it, if any.
+    fn cursor_out_of_folds(&mut self, ctx: &mut EventCtx) {
+        let offset = self.editor.cursor.offset();
+        let line = self.doc.buffer().line_of_offset(offset);
+        if self.doc.folding().is_hidden(line) {
+            self.run_move_command(
+                ctx,
+                &lapce_core::movement::Movement::Offset(offset),
+                None,
+                Modifiers::empty(),
+            );
+        }
+    }
+
+    /// Folds or unfolds the range starting on the line, from a click on its
+    /// marker in the gutter.
+    pub fn toggle_fold(&mut self, ctx: &mut EventCtx, line: usize) {
+        if self.doc_mut().folding_mut().toggle(line) {
+            self.cursor_out_of_folds(ctx);
+        }
+    }
+
     fn next_diff(&mut self, ctx: &mut EventCtx) {
         if let BufferContent::File(buffer_path) = self.doc.content() {
             if self.source_control.file_diffs.is_empty() {
@@ -694,7 +1204,7 @@ impl LapceEditorBufferData {
                 .source_control
                 .file_diffs
                 .iter()
-                .map(|(diff, _)| {
+                .map(|diff| {
                     let path = diff.path();
                     let mut positions = Vec::new();
                     if let Some(doc) = self.main_split.open_docs.get(path) {
@@ -895,6 +1405,7 @@ impl LapceEditorBufferData {
 
         let offset = self.editor.cursor.offset();
         let (line, _col) = self.doc.buffer().offset_to_line_col(offset);
+        let line = self.doc.folding().visual_line(line);
         let top = self.editor.scroll_offset.y + diff;
         let bottom = top + self.editor.size.borrow().height;
 
@@ -994,7 +1505,9 @@ impl LapceEditorBufferData {
             let line = self.doc.history_actual_line_from_visual(compare, line);
             (line, config.editor_char_width(text))
         } else {
-            let line = (pos.y / config.editor.line_height as f64).floor() as usize;
+            let row = (pos.y / config.editor.line_height as f64).floor() as usize;
+            let folding = self.doc.folding();
+            let line = folding.prev_visible_line(folding.line_of_row(row).0);
             (line, config.editor_char_width(text))
         };
 
@@ -1018,17 +1531,42 @@ impl LapceEditorBufferData {
         self.doc.buffer().offset_of_line_col(line, col)
     }
 
+    /// The offset under the point, which in a side-by-side diff is on the
+    /// line of the right side at the row of the point
+    pub fn offset_of_point(
+        &self,
+        text: &mut PietText,
+        mode: Mode,
+        point: Point,
+        config: &Config,
+    ) -> (usize, bool) {
+        let point = match &self.editor.view {
+            EditorView::SideBySide(version) => {
+                let line_height = config.editor.line_height as f64;
+                let row = (point.y / line_height).floor() as usize;
+                let line = self.doc.side_by_side_line_from_row(version, row);
+                let row = self.doc.folding().row_of_line(line);
+                Point::new(
+                    point.x - self.editor.side_by_side_offset(),
+                    (row as f64 + 0.5) * line_height,
+                )
+            }
+            _ => point,
+        };
+        self.doc
+            .offset_of_point(text, mode, point, config.editor.font_size, config)
+    }
+
     pub fn single_click(
         &mut self,
         ctx: &mut EventCtx,
         mouse_event: &MouseEvent,
         config: &Config,
     ) {
-        let (new_offset, _) = self.doc.offset_of_point(
+        let (new_offset, _) = self.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
-            config.editor.font_size,
             config,
         );
         let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
@@ -1069,11 +1607,10 @@ impl LapceEditorBufferData {
         config: &Config,
     ) {
         ctx.set_active(true);
-        let (mouse_offset, _) = self.doc.offset_of_point(
+        let (mouse_offset, _) = self.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
-            config.editor.font_size,
             config,
         );
         let (start, end) = self.doc.buffer().select_word(mouse_offset);
@@ -1093,11 +1630,10 @@ impl LapceEditorBufferData {
         config: &Config,
     ) {
         ctx.set_active(true);
-        let (mouse_offset, _) = self.doc.offset_of_point(
+        let (mouse_offset, _) = self.offset_of_point(
             ctx.text(),
             self.get_mode(),
             mouse_event.pos,
-            config.editor.font_size,
             config,
         );
         let line = self.doc.buffer().line_of_offset(mouse_offset);
@@ -1218,6 +1754,7 @@ impl LapceEditorBufferData {
         }
         self.cancel_completion();
         self.cancel_hover();
+        self.update_signature(ctx, None);
         CommandExecuted::Yes
     }
 
@@ -1226,6 +1763,20 @@ impl LapceEditorBufferData {
         ctx: &mut EventCtx,
         cmd: &EditCommand,
     ) -> CommandExecuted {
+        if self.editor.content.is_read_only()
+            && !matches!(
+                cmd,
+                EditCommand::ClipboardCopy
+                    | EditCommand::Yank
+                    | EditCommand::NormalMode
+                    | EditCommand::ToggleVisualMode
+                    | EditCommand::ToggleLinewiseVisualMode
+                    | EditCommand::ToggleBlockwiseVisualMode
+            )
+        {
+            return CommandExecuted::Yes;
+        }
+
         let modal = self.config.lapce.modal && !self.editor.content.is_input();
         let doc = Arc::make_mut(&mut self.doc);
         let register = Arc::make_mut(&mut self.main_split.register);
@@ -1246,11 +1797,69 @@ impl LapceEditorBufferData {
         }
 
         self.update_completion(ctx);
+        self.update_signature(ctx, None);
         self.apply_deltas(&deltas);
+        if *cmd == EditCommand::InsertNewLine && !deltas.is_empty() {
+            self.format_on_type(ctx, "\n");
+        }
 
         CommandExecuted::Yes
     }
 
+    /// The selected ranges to format, empty when nothing is selected and
+    /// the whole document should be formatted instead.
+    fn format_ranges(&self) -> Vec<Range> {
+        if let lapce_core::cursor::CursorMode::Normal(_) = &self.editor.cursor.mode {
+            return Vec::new();
+        }
+        let buffer = self.doc.buffer();
+        self.editor
+            .cursor
+            .edit_selection(buffer)
+            .regions()
+            .iter()
+            .filter(|region| !region.is_caret())
+            .map(|region| Range {
+                start: buffer.offset_to_position(region.min()),
+                end: buffer.offset_to_position(region.max()),
+            })
+            .collect()
+    }
+
+    /// Asks the language server to format the code around the cursor after
+    /// `ch` was typed, if it's one of the server's trigger characters.
+    fn format_on_type(&self, ctx: &mut EventCtx, ch: &str) {
+        if !self.config.editor.format_on_type {
+            return;
+        }
+        let path = match self.doc.content() {
+            BufferContent::File(path) => path.clone(),
+            _ => return,
+        };
+        let rev = self.doc.rev();
+        let position = self
+            .doc
+            .buffer()
+            .offset_to_position(self.editor.cursor.offset());
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_on_type_formatting(
+            self.doc.id(),
+            position,
+            ch,
+            Box::new(move |result| {
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::DocumentFormat(
+                        path,
+                        rev,
+                        result.map_err(|e| anyhow!("{:?}", e)),
+                    ),
+                    Target::Auto,
+                );
+            }),
+        );
+    }
+
     fn run_focus_command(
         &mut self,
         ctx: &mut EventCtx,
@@ -1277,6 +1886,16 @@ impl LapceEditorBufferData {
                 if self.has_hover() {
                     self.cancel_hover();
                 }
+                if self.has_signature() {
+                    self.cancel_signature();
+                }
+                if self.is_rename() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::CancelRename,
+                        Target::Widget(*self.main_split.tab_id),
+                    ));
+                }
             }
             SplitVertical => {
                 self.main_split.split_editor(
@@ -1508,10 +2127,16 @@ impl LapceEditorBufferData {
                         },
                         Target::Widget(self.palette.widget_id),
                     ));
+                } else if self.is_rename() {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ConfirmRename,
+                        Target::Widget(*self.main_split.tab_id),
+                    ));
                 } else {
                     let item = self.completion.current_item().to_owned();
                     self.cancel_completion();
-                    if item.data.is_some() {
+                    if TaggedData::has_server_data(item.data.as_ref()) {
                         let view_id = self.editor.view_id;
                         let buffer_id = self.doc.id();
                         let rev = self.doc.rev();
@@ -1542,7 +2167,7 @@ impl LapceEditorBufferData {
                             }),
                         );
                     } else {
-                        let _ = self.apply_completion_item(&item);
+                        let _ = self.apply_completion_item(ctx, &item);
                     }
                 }
             }
@@ -1556,6 +2181,9 @@ impl LapceEditorBufferData {
                         },
                         Target::Widget(self.palette.widget_id),
                     ));
+                } else if !self.has_completions() && self.has_signature() {
+                    let signature = Arc::make_mut(&mut self.signature);
+                    signature.next();
                 } else {
                     let completion = Arc::make_mut(&mut self.completion);
                     completion.next();
@@ -1571,6 +2199,9 @@ impl LapceEditorBufferData {
                         },
                         Target::Widget(self.palette.widget_id),
                     ));
+                } else if !self.has_completions() && self.has_signature() {
+                    let signature = Arc::make_mut(&mut self.signature);
+                    signature.previous();
                 } else {
                     let completion = Arc::make_mut(&mut self.completion);
                     completion.previous();
@@ -1750,6 +2381,102 @@ impl LapceEditorBufferData {
                     }),
                 );
             }
+            GotoTypeDefinition | GotoImplementation | GotoDeclaration => {
+                let offset = self.editor.cursor.offset();
+                let buffer_id = self.doc.id();
+                let position = self.doc.buffer().offset_to_position(offset);
+                let editor_view_id = self.editor.view_id;
+                let event_sink = ctx.get_external_handle();
+                let f = Box::new(move |result| {
+                    let _ = process_goto_locations(
+                        editor_view_id,
+                        offset,
+                        result,
+                        event_sink,
+                    );
+                });
+                match cmd {
+                    GotoTypeDefinition => {
+                        self.proxy.get_type_definition(buffer_id, position, f)
+                    }
+                    GotoImplementation => {
+                        self.proxy.get_implementation(buffer_id, position, f)
+                    }
+                    _ => self.proxy.get_declaration(buffer_id, position, f),
+                }
+            }
+            ShowCallHierarchy | ShowTypeHierarchy => {
+                let offset = self.editor.cursor.offset();
+                let buffer_id = self.doc.id();
+                let position = self.doc.buffer().offset_to_position(offset);
+                let is_call = matches!(cmd, ShowCallHierarchy);
+                let kind = if is_call {
+                    HierarchyKind::IncomingCalls
+                } else {
+                    HierarchyKind::Supertypes
+                };
+                let tab_id = *self.main_split.tab_id;
+                let event_sink = ctx.get_external_handle();
+                self.proxy.prepare_hierarchy(
+                    buffer_id,
+                    position,
+                    kind,
+                    Box::new(move |result| {
+                        let items = result.ok().and_then(|res| {
+                            serde_json::from_value::<Option<Vec<CallHierarchyItem>>>(
+                                res,
+                            )
+                            .ok()
+                            .flatten()
+                        });
+                        if let Some(items) = items.filter(|items| !items.is_empty())
+                        {
+                            let _ = event_sink.submit_command(
+                                LAPCE_UI_COMMAND,
+                                LapceUICommand::ShowHierarchy { is_call, items },
+                                Target::Widget(tab_id),
+                            );
+                        }
+                    }),
+                );
+            }
+            RenameSymbol => {
+                if let BufferContent::File(path) = self.doc.content() {
+                    let path = path.clone();
+                    let offset = self.editor.cursor.offset();
+                    let rev = self.doc.rev();
+                    let buffer_id = self.doc.id();
+                    let position = self.doc.buffer().offset_to_position(offset);
+                    let editor_view_id = self.editor.view_id;
+                    let tab_id = *self.main_split.tab_id;
+                    let event_sink = ctx.get_external_handle();
+                    self.proxy.prepare_rename(
+                        buffer_id,
+                        position,
+                        Box::new(move |result| {
+                            if let Ok(res) = result {
+                                let response = serde_json::from_value::<
+                                    Option<PrepareRenameResponse>,
+                                >(res)
+                                .ok()
+                                .flatten();
+                                let _ = event_sink.submit_command(
+                                    LAPCE_UI_COMMAND,
+                                    LapceUICommand::PrepareRename {
+                                        editor_view_id,
+                                        path,
+                                        rev,
+                                        offset,
+                                        position,
+                                        response,
+                                    },
+                                    Target::Widget(tab_id),
+                                );
+                            }
+                        }),
+                    );
+                }
+            }
             JumpLocationBackward => {
                 self.jump_location_backward(ctx);
             }
@@ -1766,21 +2493,66 @@ impl LapceEditorBufferData {
                 let editor = Arc::make_mut(&mut self.editor);
                 editor.code_lens = !editor.code_lens;
             }
+            ToggleBlame => {
+                let editor = Arc::make_mut(&mut self.editor);
+                editor.blame = !editor.blame;
+            }
+            ToggleSideBySideDiff => {
+                let editor = Arc::make_mut(&mut self.editor);
+                editor.view = match &editor.view {
+                    EditorView::Diff(version) => {
+                        EditorView::SideBySide(version.clone())
+                    }
+                    EditorView::SideBySide(version) => {
+                        EditorView::Diff(version.clone())
+                    }
+                    _ => return CommandExecuted::Yes,
+                };
+            }
+            ToggleInlayHints => {
+                self.doc_mut().toggle_inlay_hints();
+            }
+            FoldCode => {
+                let line = self
+                    .doc
+                    .buffer()
+                    .line_of_offset(self.editor.cursor.offset());
+                if self.doc_mut().folding_mut().fold(line) {
+                    self.cursor_out_of_folds(ctx);
+                }
+            }
+            UnfoldCode => {
+                let line = self
+                    .doc
+                    .buffer()
+                    .line_of_offset(self.editor.cursor.offset());
+                self.doc_mut().folding_mut().unfold(line);
+            }
+            FoldAll => {
+                self.doc_mut().folding_mut().fold_all();
+                self.cursor_out_of_folds(ctx);
+            }
+            UnfoldAll => {
+                self.doc_mut().folding_mut().unfold_all();
+            }
             FormatDocument => {
                 if let BufferContent::File(path) = self.doc.content() {
                     let path = path.clone();
                     let proxy = self.proxy.clone();
                     let buffer_id = self.doc.id();
                     let rev = self.doc.rev();
+                    let ranges = self.format_ranges();
                     let event_sink = ctx.get_external_handle();
                     let (sender, receiver) = bounded(1);
                     thread::spawn(move || {
-                        proxy.get_document_formatting(
-                            buffer_id,
-                            Box::new(move |result| {
-                                let _ = sender.send(result);
-                            }),
-                        );
+                        let f = Box::new(move |result: Result<Value, Value>| {
+                            let _ = sender.send(result);
+                        });
+                        if ranges.is_empty() {
+                            proxy.get_document_formatting(buffer_id, f);
+                        } else {
+                            proxy.get_range_formatting(buffer_id, ranges, f);
+                        }
 
                         let result = receiver
                             .recv_timeout(Duration::from_secs(1))
@@ -1902,12 +2674,109 @@ impl LapceEditorBufferData {
         ctx: &mut EventCtx,
         cmd: &MultiSelectionCommand,
     ) -> CommandExecuted {
-        let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
-        self.doc
-            .do_multi_selection(ctx.text(), cursor, cmd, &self.config);
+        match cmd {
+            MultiSelectionCommand::ExpandSelection => {
+                self.expand_selection(ctx);
+            }
+            MultiSelectionCommand::ShrinkSelection => {
+                self.shrink_selection();
+            }
+            _ => {
+                let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
+                self.doc
+                    .do_multi_selection(ctx.text(), cursor, cmd, &self.config);
+            }
+        }
         self.cancel_completion();
         CommandExecuted::Yes
     }
+
+    /// Asks the language server for the selection ranges around both ends
+    /// of each region, and falls back to the syntax tree when it has none.
+    fn expand_selection(&mut self, ctx: &mut EventCtx) {
+        if !self.doc.content().is_file() {
+            self.apply_expand_selection(None);
+            return;
+        }
+
+        let buffer = self.doc.buffer();
+        let positions: Vec<Position> = self
+            .editor
+            .cursor
+            .edit_selection(buffer)
+            .regions()
+            .iter()
+            .flat_map(|region| [region.min(), region.max()])
+            .map(|offset| buffer.offset_to_position(offset))
+            .collect();
+        let rev = self.doc.rev();
+        let offset = self.editor.cursor.offset();
+        let view_id = self.view_id;
+        let event_sink = ctx.get_external_handle();
+        self.proxy.get_selection_ranges(
+            self.doc.id(),
+            &positions,
+            Box::new(move |result| {
+                let ranges = result.ok().and_then(|res| {
+                    serde_json::from_value::<Vec<SelectionRange>>(res).ok()
+                });
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ExpandSelection {
+                        rev,
+                        offset,
+                        ranges,
+                    },
+                    Target::Widget(view_id),
+                );
+            }),
+        );
+    }
+
+    pub fn apply_expand_selection(&mut self, ranges: Option<&[SelectionRange]>) {
+        let selection = self.editor.cursor.edit_selection(self.doc.buffer());
+        let new_selection = self.doc.expand_selection(&selection, ranges);
+        if new_selection == selection {
+            return;
+        }
+
+        let before = self.editor.cursor.mode.clone();
+        let mode = match &before {
+            CursorMode::Insert(_) => CursorMode::Insert(new_selection),
+            CursorMode::Normal(_) | CursorMode::Visual { .. } => {
+                let start = new_selection.min_offset();
+                let end = new_selection.max_offset();
+                CursorMode::Visual {
+                    start,
+                    end: self.doc.buffer().prev_grapheme_offset(end, 1, start),
+                    mode: VisualMode::Normal,
+                }
+            }
+        };
+        let editor = Arc::make_mut(&mut self.editor);
+        if editor
+            .expanded_selections
+            .last()
+            .map(|(_, after)| after != &before)
+            .unwrap_or(false)
+        {
+            editor.expanded_selections.clear();
+        }
+        editor.cursor.set_mode(mode.clone());
+        editor.expanded_selections.push((before, mode));
+    }
+
+    /// Goes back to the selection before the last expansion, as long as
+    /// the selection hasn't changed since.
+    fn shrink_selection(&mut self) {
+        let editor = Arc::make_mut(&mut self.editor);
+        match editor.expanded_selections.pop() {
+            Some((before, after)) if after == editor.cursor.mode => {
+                editor.cursor.set_mode(before);
+            }
+            _ => editor.expanded_selections.clear(),
+        }
+    }
 }
 
 impl KeyPressFocus for LapceEditorBufferData {
@@ -1937,6 +2806,7 @@ impl KeyPressFocus for LapceEditorBufferData {
             "editor_focus" => match self.editor.content {
                 BufferContent::File(_) => true,
                 BufferContent::Scratch(..) => true,
+                BufferContent::LspLog(_) => true,
                 BufferContent::Local(_) => false,
                 BufferContent::SettingsValue(..) => false,
             },
@@ -1948,26 +2818,33 @@ impl KeyPressFocus for LapceEditorBufferData {
             "in_snippet" => self.editor.snippet.is_some(),
             "completion_focus" => self.has_completions(),
             "hover_focus" => self.has_hover(),
-            "list_focus" => self.has_completions() || self.is_palette(),
+            "signature_focus" => self.has_signature() && self.signature.len() > 1,
+            "list_focus" => {
+                self.has_completions() || self.is_palette() || self.is_rename()
+            }
             "modal_focus" => {
                 (self.has_completions() && !self.config.lapce.modal)
+                    || (self.has_signature() && !self.config.lapce.modal)
                     || self.has_hover()
                     || self.is_palette()
+                    || self.is_rename()
             }
             _ => false,
         }
     }
 
     fn receive_char(&mut self, ctx: &mut EventCtx, c: &str) {
-        if self.get_mode() == Mode::Insert {
+        if self.get_mode() == Mode::Insert && !self.editor.content.is_read_only() {
             self.initiate_diagnostics_offset();
             let doc = Arc::make_mut(&mut self.doc);
             let cursor = &mut Arc::make_mut(&mut self.editor).cursor;
             let deltas = doc.do_insert(cursor, c);
 
             self.update_completion(ctx);
+            self.update_signature(ctx, Some(c));
             self.cancel_hover();
             self.apply_deltas(&deltas);
+            self.format_on_type(ctx, c);
         } else if let Some(direction) = self.editor.inline_find.clone() {
             self.inline_find(ctx, direction.clone(), c);
             let editor = Arc::make_mut(&mut self.editor);
@@ -2072,6 +2949,70 @@ fn next_in_file_errors_offset(
     ((*file_diagnostics[0].0).clone(), file_diagnostics[0].1[0])
 }
 
+/// The title, the working directory and the command line of one of
+/// rust-analyzer's runnables, the argument of its run and debug commands.
+fn rust_analyzer_runnable(
+    runnable: &Value,
+) -> Option<(String, Option<PathBuf>, String)> {
+    if runnable.get("kind")?.as_str()? != "cargo" {
+        return None;
+    }
+    let args = runnable.get("args")?;
+    let strings = |key: &str| -> Vec<&str> {
+        args.get(key)
+            .and_then(|value| value.as_array())
+            .map(|values| values.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default()
+    };
+
+    let cargo = args
+        .get("overrideCargo")
+        .and_then(|cargo| cargo.as_str())
+        .unwrap_or("cargo");
+    let mut command = vec![cargo];
+    command.extend(strings("cargoArgs"));
+    command.extend(strings("cargoExtraArgs"));
+    let executable_args = strings("executableArgs");
+    if !executable_args.is_empty() {
+        command.push("--");
+        command.extend(executable_args);
+    }
+
+    let title = runnable
+        .get("label")
+        .and_then(|label| label.as_str())
+        .unwrap_or(cargo)
+        .to_string();
+    let cwd = args
+        .get("workspaceRoot")
+        .and_then(|root| root.as_str())
+        .map(PathBuf::from);
+    let command = command
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some((title, cwd, command))
+}
+
+/// Quotes the argument for the shell of the terminal, unless it's made of
+/// characters no shell would read anything into.
+fn shell_quote(arg: &str) -> Cow<str> {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=+./:".contains(c))
+    {
+        return Cow::Borrowed(arg);
+    }
+    // PowerShell, the usual shell there, escapes a quote by doubling it.
+    #[cfg(target_os = "windows")]
+    let arg = arg.replace('\'', "''");
+    #[cfg(not(target_os = "windows"))]
+    let arg = arg.replace('\'', r"'\''");
+    Cow::Owned(format!("'{arg}'"))
+}
+
 fn process_get_references(
     editor_view_id: WidgetId,
     offset: usize,
@@ -2108,44 +3049,225 @@ fn process_get_references(
     Ok(())
 }
 
-fn workspce_edits(edit: &WorkspaceEdit) -> Option<HashMap<Url, Vec<TextEdit>>> {
+/// Jumps to the location when there's a single one, and lists them in the
+/// reference palette otherwise.
+fn process_goto_locations(
+    editor_view_id: WidgetId,
+    offset: usize,
+    result: Result<Value, Value>,
+    event_sink: ExtEventSink,
+) -> Result<()> {
+    let res = result.map_err(|e| anyhow!("{:?}", e))?;
+    let locations =
+        match serde_json::from_value::<Option<GotoDefinitionResponse>>(res)? {
+            Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+            Some(GotoDefinitionResponse::Array(locations)) => locations,
+            Some(GotoDefinitionResponse::Link(links)) => links
+                .into_iter()
+                .map(|link| Location {
+                    uri: link.target_uri,
+                    range: link.target_selection_range,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+    match locations.len() {
+        0 => {}
+        1 => {
+            let location = &locations[0];
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::GotoDefinition(
+                    editor_view_id,
+                    offset,
+                    EditorLocation {
+                        path: path_from_url(&location.uri),
+                        position: Some(location.range.start),
+                        scroll_offset: None,
+                        history: None,
+                    },
+                ),
+                Target::Auto,
+            );
+        }
+        _ => {
+            let _ = event_sink.submit_command(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::PaletteReferences(offset, locations),
+                Target::Auto,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The text edits of a workspace edit by document. Creating, renaming and
+/// deleting files isn't supported, so an edit that does any of that is
+/// refused as a whole rather than applied in part.
+pub fn workspce_edits(
+    edit: &WorkspaceEdit,
+) -> Result<HashMap<Url, Vec<TextEdit>>, String> {
     if let Some(changes) = edit.changes.as_ref() {
-        return Some(changes.clone());
+        return Ok(changes.clone());
     }
 
-    let changes = edit.document_changes.as_ref()?;
-    let edits = match changes {
-        DocumentChanges::Edits(edits) => edits
-            .iter()
-            .map(|e| {
-                (
-                    e.text_document.uri.clone(),
-                    e.edits
-                        .iter()
-                        .map(|e| match e {
-                            OneOf::Left(e) => e.clone(),
-                            OneOf::Right(e) => e.text_edit.clone(),
-                        })
-                        .collect(),
-                )
-            })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
-        DocumentChanges::Operations(ops) => ops
-            .iter()
-            .filter_map(|o| match o {
-                DocumentChangeOperation::Op(_op) => None,
-                DocumentChangeOperation::Edit(e) => Some((
-                    e.text_document.uri.clone(),
-                    e.edits
-                        .iter()
-                        .map(|e| match e {
-                            OneOf::Left(e) => e.clone(),
-                            OneOf::Right(e) => e.text_edit.clone(),
-                        })
-                        .collect(),
-                )),
-            })
-            .collect::<HashMap<Url, Vec<TextEdit>>>(),
+    let changes = match edit.document_changes.as_ref() {
+        Some(changes) => changes,
+        None => return Ok(HashMap::new()),
     };
-    Some(edits)
+    // A document can show up in more than one TextDocumentEdit, so the edits
+    // are gathered per uri instead of keeping only the last ones
+    let mut edits: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    let mut add_edits = |e: &TextDocumentEdit| {
+        edits
+            .entry(e.text_document.uri.clone())
+            .or_default()
+            .extend(e.edits.iter().map(|e| match e {
+                OneOf::Left(e) => e.clone(),
+                OneOf::Right(e) => e.text_edit.clone(),
+            }));
+    };
+    match changes {
+        DocumentChanges::Edits(changes) => {
+            for e in changes {
+                add_edits(e);
+            }
+        }
+        DocumentChanges::Operations(ops) => {
+            for o in ops {
+                match o {
+                    DocumentChangeOperation::Op(op) => {
+                        let (action, uri) = match op {
+                            ResourceOp::Create(op) => ("create", &op.uri),
+                            ResourceOp::Rename(op) => ("rename", &op.old_uri),
+                            ResourceOp::Delete(op) => ("delete", &op.uri),
+                        };
+                        return Err(format!(
+                            "The edit would {} {}, which isn't supported",
+                            action,
+                            path_from_url(uri).display()
+                        ));
+                    }
+                    DocumentChangeOperation::Edit(e) => add_edits(e),
+                }
+            }
+        }
+    }
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::WorkspaceEdit;
+    use serde_json::json;
+
+    use super::{rust_analyzer_runnable, workspce_edits};
+
+    fn edit(value: serde_json::Value) -> WorkspaceEdit {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn text_edit(text: &str) -> serde_json::Value {
+        json!({
+            "range": {
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 3 },
+            },
+            "newText": text,
+        })
+    }
+
+    #[test]
+    fn test_document_changes() {
+        let edits = workspce_edits(&edit(json!({
+            "documentChanges": [
+                {
+                    "textDocument": { "uri": "file:///a.rs", "version": 1 },
+                    "edits": [text_edit("foo")],
+                },
+                {
+                    "textDocument": { "uri": "file:///b.rs", "version": 1 },
+                    "edits": [text_edit("bar"), text_edit("baz")],
+                },
+            ],
+        })))
+        .unwrap();
+        assert_eq!(edits.len(), 2);
+        let url = "file:///b.rs".parse().unwrap();
+        assert_eq!(edits[&url].len(), 2);
+        assert_eq!(edits[&url][1].new_text, "baz");
+    }
+
+    #[test]
+    fn test_document_changes_to_the_same_uri() {
+        let edits = workspce_edits(&edit(json!({
+            "documentChanges": [
+                {
+                    "textDocument": { "uri": "file:///a.rs", "version": 1 },
+                    "edits": [text_edit("foo")],
+                },
+                {
+                    "textDocument": { "uri": "file:///a.rs", "version": 1 },
+                    "edits": [text_edit("bar")],
+                },
+            ],
+        })))
+        .unwrap();
+        let url = "file:///a.rs".parse().unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[&url].len(), 2);
+        assert_eq!(edits[&url][0].new_text, "foo");
+        assert_eq!(edits[&url][1].new_text, "bar");
+    }
+
+    #[test]
+    fn test_no_changes() {
+        assert!(workspce_edits(&edit(json!({}))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_file_operations_are_refused() {
+        for op in [
+            json!({ "kind": "create", "uri": "file:///c.rs" }),
+            json!({
+                "kind": "rename",
+                "oldUri": "file:///a.rs",
+                "newUri": "file:///c.rs",
+            }),
+            json!({ "kind": "delete", "uri": "file:///a.rs" }),
+        ] {
+            let result = workspce_edits(&edit(json!({
+                "documentChanges": [
+                    {
+                        "textDocument": { "uri": "file:///a.rs", "version": 1 },
+                        "edits": [text_edit("foo")],
+                    },
+                    op,
+                ],
+            })));
+            assert!(result.is_err());
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn test_runnable_arguments_are_quoted() {
+        let (title, cwd, command) = rust_analyzer_runnable(&json!({
+            "kind": "cargo",
+            "label": "test it's quoted",
+            "args": {
+                "workspaceRoot": "/ws",
+                "cargoArgs": ["test", "--package", "foo"],
+                "cargoExtraArgs": [],
+                "executableArgs": ["it's quoted", "--exact", "a b", ""],
+            },
+        }))
+        .unwrap();
+        assert_eq!(title, "test it's quoted");
+        assert_eq!(cwd, Some("/ws".into()));
+        assert_eq!(
+            command,
+            r"cargo test --package foo -- 'it'\''s quoted' --exact 'a b' ''"
+        );
+    }
 }
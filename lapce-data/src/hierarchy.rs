@@ -0,0 +1,258 @@
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::proxy::HierarchyKind;
+use lsp_types::CallHierarchyItem;
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    proxy::LapceProxy,
+};
+
+#[derive(Clone)]
+pub struct HierarchyNode {
+    pub item: CallHierarchyItem,
+    pub open: bool,
+    /// None until the children are asked for the first time
+    pub children: Option<Vec<HierarchyNode>>,
+}
+
+impl HierarchyNode {
+    fn new(item: CallHierarchyItem) -> Self {
+        Self {
+            item,
+            open: false,
+            children: None,
+        }
+    }
+
+    /// The number of rows the node takes in the panel, its own included.
+    fn rows(&self) -> usize {
+        1 + if self.open {
+            self.children
+                .as_ref()
+                .map(|children| children.iter().map(|c| c.rows()).sum())
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    }
+}
+
+/// One direction of the hierarchy, like the incoming calls, starting from
+/// the items the hierarchy was prepared with.
+#[derive(Clone)]
+pub struct HierarchyTree {
+    pub kind: HierarchyKind,
+    pub roots: Vec<HierarchyNode>,
+}
+
+/// A row of the hierarchy panel.
+pub enum HierarchyRow<'a> {
+    Header(HierarchyKind),
+    Node {
+        tree: usize,
+        /// The indices of the node and of its parents, from the root
+        path: Vec<usize>,
+        node: &'a HierarchyNode,
+    },
+}
+
+#[derive(Clone)]
+pub struct HierarchyData {
+    pub widget_id: WidgetId,
+    pub split_id: WidgetId,
+    /// Bumped every time the hierarchy is shown, so that the children
+    /// asked for an earlier one are dropped
+    pub id: u64,
+    pub trees: Vec<HierarchyTree>,
+}
+
+impl HierarchyData {
+    pub fn new() -> Self {
+        Self {
+            widget_id: WidgetId::next(),
+            split_id: WidgetId::next(),
+            id: 0,
+            trees: Vec::new(),
+        }
+    }
+
+    /// Shows the callers and callees of the items, or their supertypes and
+    /// subtypes, with the first level of each opened.
+    pub fn set_items(
+        &mut self,
+        is_call: bool,
+        items: Vec<CallHierarchyItem>,
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        self.id += 1;
+        let kinds = if is_call {
+            [HierarchyKind::IncomingCalls, HierarchyKind::OutgoingCalls]
+        } else {
+            [HierarchyKind::Supertypes, HierarchyKind::Subtypes]
+        };
+        self.trees = kinds
+            .into_iter()
+            .map(|kind| HierarchyTree {
+                kind,
+                roots: items.iter().cloned().map(HierarchyNode::new).collect(),
+            })
+            .collect();
+        for tree in 0..self.trees.len() {
+            if !self.trees[tree].roots.is_empty() {
+                self.toggle(tree, &[0], proxy, event_sink.clone());
+            }
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.trees
+            .iter()
+            .map(|tree| 1 + tree.roots.iter().map(|n| n.rows()).sum::<usize>())
+            .sum()
+    }
+
+    /// The rows from the `min`th one to before the `max`th one.
+    pub fn rows_between(&self, min: usize, max: usize) -> Vec<HierarchyRow> {
+        fn add_rows<'a>(
+            rows: &mut Vec<HierarchyRow<'a>>,
+            i: &mut usize,
+            min: usize,
+            max: usize,
+            tree: usize,
+            path: &mut Vec<usize>,
+            nodes: &'a [HierarchyNode],
+        ) {
+            for (index, node) in nodes.iter().enumerate() {
+                if *i >= max {
+                    return;
+                }
+                let node_rows = node.rows();
+                if *i + node_rows <= min {
+                    *i += node_rows;
+                    continue;
+                }
+                path.push(index);
+                if *i >= min {
+                    rows.push(HierarchyRow::Node {
+                        tree,
+                        path: path.clone(),
+                        node,
+                    });
+                }
+                *i += 1;
+                if node.open {
+                    if let Some(children) = node.children.as_ref() {
+                        add_rows(rows, i, min, max, tree, path, children);
+                    }
+                }
+                path.pop();
+            }
+        }
+
+        let mut rows = Vec::new();
+        let mut i = 0;
+        for (tree_index, tree) in self.trees.iter().enumerate() {
+            if i >= max {
+                break;
+            }
+            if i >= min {
+                rows.push(HierarchyRow::Header(tree.kind));
+            }
+            i += 1;
+            add_rows(
+                &mut rows,
+                &mut i,
+                min,
+                max,
+                tree_index,
+                &mut Vec::new(),
+                &tree.roots,
+            );
+        }
+        rows
+    }
+
+    fn node_mut(
+        &mut self,
+        tree: usize,
+        path: &[usize],
+    ) -> Option<&mut HierarchyNode> {
+        let (first, rest) = path.split_first()?;
+        let mut node = self.trees.get_mut(tree)?.roots.get_mut(*first)?;
+        for index in rest {
+            node = node.children.as_mut()?.get_mut(*index)?;
+        }
+        Some(node)
+    }
+
+    /// Opens or closes the node, asking for its children the first time it
+    /// opens.
+    pub fn toggle(
+        &mut self,
+        tree: usize,
+        path: &[usize],
+        proxy: &LapceProxy,
+        event_sink: ExtEventSink,
+    ) {
+        let id = self.id;
+        let kind = match self.trees.get(tree) {
+            Some(tree) => tree.kind,
+            None => return,
+        };
+        let node = match self.node_mut(tree, path) {
+            Some(node) => node,
+            None => return,
+        };
+        node.open = !node.open;
+        if !node.open || node.children.is_some() {
+            return;
+        }
+
+        let path = path.to_vec();
+        proxy.get_hierarchy_children(
+            kind,
+            &node.item,
+            Box::new(move |result| {
+                let items = result
+                    .ok()
+                    .and_then(|value| {
+                        serde_json::from_value::<Vec<CallHierarchyItem>>(value).ok()
+                    })
+                    .unwrap_or_default();
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateHierarchyChildren {
+                        id,
+                        tree,
+                        path,
+                        items,
+                    },
+                    Target::Auto,
+                );
+            }),
+        );
+    }
+
+    pub fn set_children(
+        &mut self,
+        id: u64,
+        tree: usize,
+        path: &[usize],
+        items: Vec<CallHierarchyItem>,
+    ) {
+        if id != self.id {
+            return;
+        }
+        if let Some(node) = self.node_mut(tree, path) {
+            node.children =
+                Some(items.into_iter().map(HierarchyNode::new).collect());
+        }
+    }
+}
+
+impl Default for HierarchyData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -9,7 +9,7 @@ use druid::{
     Target,
 };
 use lapce_core::{
-    buffer::{rope_diff, Buffer, DiffLines},
+    buffer::{diff_blocks, rope_diff, Buffer, DiffBlock, DiffLines},
     style::line_styles,
     syntax::Syntax,
 };
@@ -32,6 +32,8 @@ pub struct DocumentHisotry {
     styles: Arc<Spans<Style>>,
     line_styles: Rc<RefCell<LineStyles>>,
     changes: Arc<Vec<DiffLines>>,
+    /// The changes lined up for the side-by-side diff, kept along with them
+    diff_blocks: Arc<Vec<DiffBlock>>,
     text_layouts: Rc<RefCell<TextLayoutCache>>,
 }
 
@@ -64,6 +66,7 @@ impl DocumentHisotry {
             line_styles: Rc::new(RefCell::new(LineStyles::new())),
             text_layouts: Rc::new(RefCell::new(TextLayoutCache::new())),
             changes: Arc::new(Vec::new()),
+            diff_blocks: Arc::new(Vec::new()),
         }
     }
 
@@ -151,12 +154,14 @@ impl DocumentHisotry {
             let id = doc.id();
             let tab_id = doc.tab_id;
             let path = path.clone();
+            let version = self.version.clone();
             let proxy = doc.proxy.clone();
             let event_sink = doc.event_sink.clone();
             std::thread::spawn(move || {
                 proxy.get_buffer_head(
                     id,
                     path.clone(),
+                    &version,
                     Box::new(move |result| {
                         if let Ok(res) = result {
                             if let Ok(resp) =
@@ -188,6 +193,7 @@ impl DocumentHisotry {
             let rev = doc.rev();
             let atomic_rev = doc.buffer().atomic_rev();
             let path = path.clone();
+            let version = self.version.clone();
             let left_rope = self.buffer.as_ref().unwrap().text().clone();
             let right_rope = doc.buffer().text().clone();
             let event_sink = doc.event_sink.clone();
@@ -212,7 +218,7 @@ impl DocumentHisotry {
                         id,
                         path,
                         rev,
-                        history: "head".to_string(),
+                        history: version,
                         changes: Arc::new(changes),
                     },
                     Target::Widget(tab_id),
@@ -225,7 +231,18 @@ impl DocumentHisotry {
         &self.changes
     }
 
+    /// The length of the longest line of the version
+    pub fn max_len(&self) -> usize {
+        self.buffer.as_ref().map(|b| b.max_len()).unwrap_or(0)
+    }
+
+    /// The changes lined up in rows for a side-by-side diff
+    pub fn diff_blocks(&self) -> &[DiffBlock] {
+        &self.diff_blocks
+    }
+
     pub fn update_changes(&mut self, changes: Arc<Vec<DiffLines>>) {
+        self.diff_blocks = Arc::new(diff_blocks(&changes));
         self.changes = changes;
     }
 
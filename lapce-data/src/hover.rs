@@ -1,6 +1,6 @@
 use std::{cell::RefCell, rc::Rc, sync::Arc};
 
-use druid::{ExtEventSink, FontStyle, FontWeight, Size, Target, WidgetId};
+use druid::{Command, ExtEventSink, FontStyle, FontWeight, Size, Target, WidgetId};
 use lapce_core::syntax::Syntax;
 use lapce_rpc::buffer::BufferId;
 use lsp_types::{Hover, HoverContents, MarkedString, MarkupKind, Position};
@@ -12,7 +12,8 @@ use crate::{
     config::{Config, LapceTheme},
     data::EditorDiagnostic,
     document::Document,
-    proxy::LapceProxy,
+    editor::EditorLocation,
+    proxy::{path_from_url, LapceProxy},
     rich_text::{AttributesAdder, RichText, RichTextBuilder},
 };
 
@@ -184,6 +185,8 @@ impl HoverData {
             // not significant
             let dim_color =
                 config.get_color_unchecked(LapceTheme::EDITOR_DIM).clone();
+            let link_color =
+                config.get_color_unchecked(LapceTheme::EDITOR_LINK).clone();
 
             // Build up the text for all the diagnostics
             let mut content = RichTextBuilder::new();
@@ -198,31 +201,73 @@ impl HoverData {
 
                     // If there's an available error code then include that
                     if let Some(code) = &diagnostic.code {
-                        // TODO: code description field has information like documentation on the
-                        // error code which could be useful to provide as a link
+                        let code = match code {
+                            lsp_types::NumberOrString::Number(v) => v.to_string(),
+                            lsp_types::NumberOrString::String(v) => v.clone(),
+                        };
 
                         // formatted as  diagsource(code)
                         content.push("(").text_color(dim_color.clone());
-                        match code {
-                            lsp_types::NumberOrString::Number(v) => {
+                        match &diagnostic.code_description {
+                            // The code links to its documentation when the
+                            // server gives one
+                            Some(description) => {
                                 content
-                                    .push(&v.to_string())
-                                    .text_color(dim_color.clone());
+                                    .push(&code)
+                                    .text_color(link_color.clone())
+                                    .underline(true)
+                                    .link(Command::new(
+                                        LAPCE_UI_COMMAND,
+                                        LapceUICommand::OpenURI(
+                                            description.href.to_string(),
+                                        ),
+                                        Target::Auto,
+                                    ));
                             }
-                            lsp_types::NumberOrString::String(v) => {
-                                content
-                                    .push(v.as_str())
-                                    .text_color(dim_color.clone());
+                            None => {
+                                content.push(&code).text_color(dim_color.clone());
                             }
                         }
                         content.push(")").text_color(dim_color.clone());
                     }
                 }
 
-                // TODO: The Related information field has data that can give better insight into
-                // the causes of the error
-                // (ex: The place where a variable was moved into when the 'main' error is at where
-                // you tried using it. This would work the best with some way to link to files)
+                // The related information points at other places involved in
+                // the error, like where a variable was moved to, each of them
+                // a link to jump there.
+                for related in diagnostic.related_information.iter().flatten() {
+                    let path = path_from_url(&related.location.uri);
+                    let position = related.location.range.start;
+                    let file_name = path
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("")
+                        .to_string();
+                    content.push("\n");
+                    content
+                        .push(&format!(
+                            "{file_name}[{}, {}]:",
+                            position.line + 1,
+                            position.character + 1
+                        ))
+                        .text_color(link_color.clone())
+                        .underline(true)
+                        .link(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::JumpToLocation(
+                                None,
+                                EditorLocation {
+                                    path,
+                                    position: Some(position),
+                                    scroll_offset: None,
+                                    history: None,
+                                },
+                            ),
+                            Target::Auto,
+                        ));
+                    content.push(" ");
+                    content.push(&related.message).text_color(dim_color.clone());
+                }
 
                 content.push("\n");
             }
@@ -9,6 +9,7 @@ pub mod document;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod hierarchy;
 pub mod history;
 pub mod hover;
 pub mod keypress;
@@ -19,6 +20,7 @@ pub mod picker;
 pub mod plugin;
 pub mod problem;
 pub mod proxy;
+pub mod rename;
 pub mod rich_text;
 pub mod search;
 pub mod settings;
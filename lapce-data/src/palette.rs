@@ -9,15 +9,17 @@ use itertools::Itertools;
 use lapce_core::command::{EditCommand, FocusCommand};
 use lapce_core::mode::Mode;
 use lapce_core::movement::Movement;
-use lsp_types::{DocumentSymbolResponse, Range, SymbolKind};
-use serde_json;
+use lapce_rpc::source_control::{CommitInfo, GitCredentials, StashEntry};
+use lapce_rpc::RequestId;
+use lsp_types::{DocumentSymbolResponse, Range, SymbolInformation, SymbolKind};
+use serde_json::{self, json};
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::command::CommandKind;
+use crate::command::{CommandKind, LapceWorkbenchCommand};
 use crate::data::{LapceWorkspace, LapceWorkspaceType};
 use crate::document::BufferContent;
 use crate::editor::EditorLocation;
@@ -30,6 +32,7 @@ use crate::{
     find::Find,
     keypress::{KeyPressData, KeyPressFocus},
     proxy::LapceProxy,
+    source_control::SourceControlData,
     terminal::TerminalSplitData,
 };
 
@@ -39,11 +42,50 @@ pub enum PaletteType {
     Line,
     GlobalSearch,
     DocumentSymbol,
+    WorkspaceSymbol,
     Workspace,
     Command,
     Reference,
     Theme,
     SshHost,
+    LspLog,
+    GitBranch(GitBranchAction),
+    FileHistory,
+    /// Picks what to compare the file of the active editor with
+    DiffRevision,
+    GitStash(GitStashAction),
+    /// Asks for the credentials of a git remote, or the passphrase of the SSH
+    /// key, on behalf of the proxy
+    GitCredentials {
+        id: RequestId,
+        url: String,
+        username: Option<String>,
+        key: Option<PathBuf>,
+    },
+}
+
+/// What the branch palette does with the branch it's given
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GitBranchAction {
+    Checkout,
+    /// Takes a name, and the revision to start from after it
+    Create,
+    Delete,
+    /// Takes the new name of the current branch, or a branch and its new
+    /// name
+    Rename,
+}
+
+/// What the stash palette does with the stash entry it's given
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GitStashAction {
+    /// Takes the message of the new entry
+    Save {
+        include_untracked: bool,
+    },
+    Apply,
+    Pop,
+    Drop,
 }
 
 impl PaletteType {
@@ -52,20 +94,70 @@ impl PaletteType {
             PaletteType::File => "".to_string(),
             PaletteType::Line => "/".to_string(),
             PaletteType::DocumentSymbol => "@".to_string(),
+            PaletteType::WorkspaceSymbol => "#".to_string(),
             PaletteType::GlobalSearch => "?".to_string(),
             PaletteType::Workspace => ">".to_string(),
             PaletteType::Command => ":".to_string(),
             PaletteType::Reference => "".to_string(),
             PaletteType::Theme => "".to_string(),
             PaletteType::SshHost => "".to_string(),
+            PaletteType::LspLog => "".to_string(),
+            PaletteType::GitBranch(_) => "".to_string(),
+            PaletteType::FileHistory => "".to_string(),
+            PaletteType::DiffRevision => "".to_string(),
+            PaletteType::GitStash(_) => "".to_string(),
+            PaletteType::GitCredentials { .. } => "".to_string(),
         }
     }
 
+    /// The text shown while the input is empty, for the palettes that take
+    /// what's typed in
+    pub fn placeholder(&self) -> Option<String> {
+        if let PaletteType::GitCredentials {
+            url, username, key, ..
+        } = self
+        {
+            if let Some(key) = key {
+                return Some(format!(
+                    "Enter the passphrase of {} for {url}",
+                    key.display()
+                ));
+            }
+            return Some(match username {
+                Some(username) => {
+                    format!("Enter the password of {username} for {url}")
+                }
+                None => format!("Enter your user name and password for {url}"),
+            });
+        }
+        match self {
+            PaletteType::SshHost => Some("Enter your SSH details, like user@host"),
+            PaletteType::GitBranch(GitBranchAction::Checkout) => {
+                Some("Pick a branch, or enter a revision")
+            }
+            PaletteType::GitBranch(GitBranchAction::Create) => {
+                Some("Enter a branch name, then where it starts if not at the head")
+            }
+            PaletteType::GitBranch(GitBranchAction::Rename) => Some(
+                "Enter a new name for the current branch, or a branch and its new name",
+            ),
+            PaletteType::DiffRevision => {
+                Some("Pick what to compare with, or enter a revision")
+            }
+            PaletteType::GitStash(GitStashAction::Save { .. }) => {
+                Some("Enter a message for the stash, or leave it empty")
+            }
+            _ => None,
+        }
+        .map(|placeholder| placeholder.to_string())
+    }
+
     pub fn has_preview(&self) -> bool {
         matches!(
             self,
             PaletteType::Line
                 | PaletteType::DocumentSymbol
+                | PaletteType::WorkspaceSymbol
                 | PaletteType::GlobalSearch
                 | PaletteType::Reference
         )
@@ -97,11 +189,37 @@ pub enum PaletteItemContent {
         range: Range,
         container_name: Option<String>,
     },
+    WorkspaceSymbol {
+        kind: SymbolKind,
+        name: String,
+        container_name: Option<String>,
+        /// The path relative to the workspace
+        path: PathBuf,
+        location: EditorLocation,
+    },
     ReferenceLocation(PathBuf, EditorLocation),
     Workspace(LapceWorkspace),
     SshHost(String, String),
     Command(LapceCommand),
     Theme(String),
+    /// The name of the log of a language server
+    LspLog(String),
+    GitBranch(GitBranchAction, String),
+    /// A commit that changed the file
+    FileCommit(PathBuf, CommitInfo),
+    /// A version to compare the file with, which is "index", "head" or a
+    /// branch by its full name
+    DiffRevision(PathBuf, String),
+    GitStash(GitStashAction, StashEntry),
+}
+
+/// The name a version to compare the file with is shown by, which is the
+/// short name for a branch.
+pub fn revision_name(revision: &str) -> &str {
+    revision
+        .strip_prefix("refs/heads/")
+        .or_else(|| revision.strip_prefix("refs/remotes/"))
+        .unwrap_or(revision)
 }
 
 impl PaletteItemContent {
@@ -145,6 +263,18 @@ impl PaletteItemContent {
                     Target::Auto,
                 ));
             }
+            PaletteItemContent::WorkspaceSymbol { location, .. } => {
+                let editor_id = if preview {
+                    Some(preview_editor_id)
+                } else {
+                    None
+                };
+                ctx.submit_command(Command::new(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::JumpToLocation(editor_id, location.clone()),
+                    Target::Auto,
+                ));
+            }
             PaletteItemContent::ReferenceLocation(_rel_path, location) => {
                 let editor_id = if preview {
                     Some(preview_editor_id)
@@ -173,6 +303,75 @@ impl PaletteItemContent {
                     Target::Auto,
                 ));
             }
+            PaletteItemContent::LspLog(name) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::OpenLspLog(name.to_string()),
+                        Target::Auto,
+                    ));
+                }
+            }
+            PaletteItemContent::GitBranch(action, name) => {
+                if !preview {
+                    let (command, data) = match action {
+                        GitBranchAction::Delete => (
+                            LapceWorkbenchCommand::DeleteBranch,
+                            json!({
+                                "name": name,
+                                "force": false,
+                            }),
+                        ),
+                        _ => (LapceWorkbenchCommand::CheckoutBranch, json!(name)),
+                    };
+                    ctx.submit_command(Command::new(
+                        LAPCE_COMMAND,
+                        LapceCommand {
+                            kind: CommandKind::Workbench(command),
+                            data: Some(data),
+                        },
+                        Target::Auto,
+                    ));
+                }
+            }
+            PaletteItemContent::FileCommit(path, commit) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::OpenFileDiff(
+                            path.clone(),
+                            commit.id.clone(),
+                        ),
+                        Target::Auto,
+                    ));
+                }
+            }
+            PaletteItemContent::DiffRevision(path, revision) => {
+                if !preview {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::OpenFileDiff(path.clone(), revision.clone()),
+                        Target::Auto,
+                    ));
+                }
+            }
+            PaletteItemContent::GitStash(action, entry) => {
+                if !preview {
+                    let command = match action {
+                        GitStashAction::Pop => LapceWorkbenchCommand::PopStash,
+                        GitStashAction::Drop => LapceWorkbenchCommand::DropStash,
+                        _ => LapceWorkbenchCommand::ApplyStash,
+                    };
+                    ctx.submit_command(Command::new(
+                        LAPCE_COMMAND,
+                        LapceCommand {
+                            kind: CommandKind::Workbench(command),
+                            data: Some(json!(entry.index)),
+                        },
+                        Target::Auto,
+                    ));
+                }
+            }
             PaletteItemContent::Command(command) => {
                 if !preview {
                     ctx.submit_command(Command::new(
@@ -233,6 +432,7 @@ pub struct PaletteViewData {
     pub config: Arc<Config>,
     pub focus_area: FocusArea,
     pub terminal: Arc<TerminalSplitData>,
+    pub source_control: Arc<SourceControlData>,
 }
 
 impl Lens<LapceTabData, PaletteViewData> for PaletteViewLens {
@@ -428,17 +628,66 @@ impl PaletteData {
             PaletteType::Reference => &self.input,
             PaletteType::Theme => &self.input,
             PaletteType::SshHost => &self.input,
+            PaletteType::LspLog => &self.input,
+            PaletteType::GitBranch(_) => &self.input,
+            PaletteType::FileHistory => &self.input,
+            PaletteType::DiffRevision => &self.input,
+            PaletteType::GitStash(_) => &self.input,
+            PaletteType::GitCredentials { .. } => &self.input,
             PaletteType::Line => &self.input[1..],
             PaletteType::DocumentSymbol => &self.input[1..],
+            PaletteType::WorkspaceSymbol => &self.input[1..],
             PaletteType::Workspace => &self.input[1..],
             PaletteType::Command => &self.input[1..],
             PaletteType::GlobalSearch => &self.input[1..],
         }
     }
+
+    /// The input as it's painted, with the password of the credentials
+    /// palette hidden. Each byte is masked so that the cursor stays in
+    /// place.
+    pub fn display_input(&self) -> String {
+        let hidden_from = match &self.palette_type {
+            PaletteType::GitCredentials { username: None, .. } => {
+                match self.input.find(' ') {
+                    Some(i) => i + 1,
+                    None => return self.input.clone(),
+                }
+            }
+            PaletteType::GitCredentials { .. } => 0,
+            _ => return self.input.clone(),
+        };
+        let mut input = self.input[..hidden_from].to_string();
+        input.push_str(&"*".repeat(self.input.len() - hidden_from));
+        input
+    }
+
+    /// The credentials typed in the credentials palette, which are the
+    /// password when the user name is known, or else the user name and
+    /// the password.
+    fn git_credentials(&self) -> Option<GitCredentials> {
+        let username = match &self.palette_type {
+            PaletteType::GitCredentials { username, .. } => username.clone(),
+            _ => return None,
+        };
+        let input = self.get_input();
+        let (username, password) = match username {
+            Some(username) => (username, input),
+            None => {
+                let (username, password) = input.split_once(' ')?;
+                (username.to_string(), password)
+            }
+        };
+        Some(GitCredentials {
+            username,
+            password: password.to_string(),
+        })
+    }
 }
 
 impl PaletteViewData {
     pub fn cancel(&mut self, ctx: &mut EventCtx) {
+        self.give_up_git_credentials();
         let palette = Arc::make_mut(&mut self.palette);
         palette.status = PaletteStatus::Inactive;
         palette.input = "".to_string();
@@ -487,7 +736,17 @@ impl PaletteViewData {
         palette.preview(ctx);
     }
 
+    /// Tells the proxy that no credentials are coming, when the
+    /// credentials palette is closed or replaced without an answer.
+    fn give_up_git_credentials(&mut self) {
+        if let PaletteType::GitCredentials { id, .. } = self.palette.palette_type {
+            self.palette.proxy.git_credentials_response(id, None);
+            Arc::make_mut(&mut self.palette).palette_type = PaletteType::File;
+        }
+    }
+
     pub fn run(&mut self, ctx: &mut EventCtx, palette_type: Option<PaletteType>) {
+        self.give_up_git_credentials();
         let palette = Arc::make_mut(&mut self.palette);
         palette.status = PaletteStatus::Started;
         palette.palette_type = palette_type.unwrap_or(PaletteType::File);
@@ -526,6 +785,7 @@ impl PaletteViewData {
             PaletteType::DocumentSymbol => {
                 self.get_document_symbols(ctx);
             }
+            PaletteType::WorkspaceSymbol => {}
             PaletteType::Workspace => {
                 self.get_workspaces(ctx);
             }
@@ -543,6 +803,22 @@ impl PaletteViewData {
                 let config = self.config.clone();
                 self.get_themes(ctx, &config);
             }
+            PaletteType::LspLog => {
+                self.get_lsp_logs(ctx);
+            }
+            PaletteType::GitBranch(action) => {
+                self.get_git_branches(action);
+            }
+            PaletteType::FileHistory => {
+                self.get_file_history(ctx);
+            }
+            PaletteType::DiffRevision => {
+                self.get_diff_revisions();
+            }
+            PaletteType::GitStash(action) => {
+                self.get_git_stashes(ctx, action);
+            }
+            PaletteType::GitCredentials { .. } => {}
         }
     }
 
@@ -568,8 +844,15 @@ impl PaletteViewData {
             PaletteType::Reference => 0,
             PaletteType::Theme => 0,
             PaletteType::SshHost => 0,
+            PaletteType::LspLog => 0,
+            PaletteType::GitBranch(_) => 0,
+            PaletteType::FileHistory => 0,
+            PaletteType::DiffRevision => 0,
+            PaletteType::GitStash(_) => 0,
+            PaletteType::GitCredentials { .. } => 0,
             PaletteType::Line => 1,
             PaletteType::DocumentSymbol => 1,
+            PaletteType::WorkspaceSymbol => 1,
             PaletteType::Workspace => 1,
             PaletteType::Command => 1,
             PaletteType::GlobalSearch => 1,
@@ -637,10 +920,103 @@ impl PaletteViewData {
                 ));
                 return;
             }
+            if let PaletteType::GitBranch(action) = self.palette.palette_type {
+                self.select_git_branch_input(ctx, action);
+            }
+            if self.palette.palette_type == PaletteType::DiffRevision {
+                if let Some(BufferContent::File(path)) =
+                    self.main_split.active_editor().map(|e| &e.content)
+                {
+                    let revision = self.palette.get_input().trim();
+                    if !revision.is_empty() {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::OpenFileDiff(
+                                path.clone(),
+                                revision.to_string(),
+                            ),
+                            Target::Auto,
+                        ));
+                    }
+                }
+            }
+            if let PaletteType::GitStash(GitStashAction::Save {
+                include_untracked,
+            }) = self.palette.palette_type
+            {
+                let message = self.palette.get_input().trim();
+                ctx.submit_command(Command::new(
+                    LAPCE_COMMAND,
+                    LapceCommand {
+                        kind: CommandKind::Workbench(
+                            LapceWorkbenchCommand::StashChanges,
+                        ),
+                        data: Some(json!({
+                            "message": Some(message).filter(|m| !m.is_empty()),
+                            "include_untracked": include_untracked,
+                        })),
+                    },
+                    Target::Auto,
+                ));
+            }
+            if let PaletteType::GitCredentials { id, .. } = self.palette.palette_type
+            {
+                let credentials = match self.palette.git_credentials() {
+                    Some(credentials) => credentials,
+                    // Keep the palette open until there is a password.
+                    None => return,
+                };
+                self.palette
+                    .proxy
+                    .git_credentials_response(id, Some(credentials));
+                Arc::make_mut(&mut self.palette).palette_type = PaletteType::File;
+            }
             self.cancel(ctx);
         }
     }
 
+    /// Runs the branch command with what's typed in, when no branch of the
+    /// list is picked.
+    fn select_git_branch_input(&self, ctx: &mut EventCtx, action: GitBranchAction) {
+        let input = self.palette.get_input();
+        let mut words = input.split_whitespace();
+        let (command, data) = match (action, words.next(), words.next()) {
+            (GitBranchAction::Checkout, Some(reference), None) => {
+                (LapceWorkbenchCommand::CheckoutBranch, json!(reference))
+            }
+            (GitBranchAction::Create, Some(name), start_point) => (
+                LapceWorkbenchCommand::CreateBranch,
+                json!({
+                    "name": name,
+                    "start_point": start_point,
+                }),
+            ),
+            (GitBranchAction::Rename, Some(new_name), None) => (
+                LapceWorkbenchCommand::RenameBranch,
+                json!({
+                    "name": self.source_control.branch,
+                    "new_name": new_name,
+                }),
+            ),
+            (GitBranchAction::Rename, Some(name), Some(new_name)) => (
+                LapceWorkbenchCommand::RenameBranch,
+                json!({
+                    "name": name,
+                    "new_name": new_name,
+                }),
+            ),
+            _ => return,
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_COMMAND,
+            LapceCommand {
+                kind: CommandKind::Workbench(command),
+                data: Some(data),
+            },
+            Target::Auto,
+        ));
+    }
+
     pub fn update_input(&mut self, ctx: &mut EventCtx, input: String) {
         let palette = Arc::make_mut(&mut self.palette);
         palette.input = input;
@@ -655,7 +1031,11 @@ impl PaletteViewData {
             self.run(ctx, Some(palette_type));
             return;
         }
-        if self.palette.get_input() != "" {
+        if self.palette.palette_type == PaletteType::WorkspaceSymbol {
+            // The servers do the filtering, so that the query can reach the
+            // symbols they haven't sent yet.
+            self.get_workspace_symbols(ctx);
+        } else if self.palette.get_input() != "" {
             let _ = self.palette.sender.send((
                 self.palette.run_id.clone(),
                 self.palette.get_input().to_string(),
@@ -668,7 +1048,15 @@ impl PaletteViewData {
 
     fn get_palette_type(&self) -> PaletteType {
         match self.palette.palette_type {
-            PaletteType::Reference | PaletteType::SshHost | PaletteType::Theme => {
+            PaletteType::Reference
+            | PaletteType::SshHost
+            | PaletteType::Theme
+            | PaletteType::LspLog
+            | PaletteType::GitBranch(_)
+            | PaletteType::FileHistory
+            | PaletteType::DiffRevision
+            | PaletteType::GitStash(_)
+            | PaletteType::GitCredentials { .. } => {
                 return self.palette.palette_type.clone();
             }
             _ => (),
@@ -679,6 +1067,7 @@ impl PaletteViewData {
         match self.palette.input {
             _ if self.palette.input.starts_with('/') => PaletteType::Line,
             _ if self.palette.input.starts_with('@') => PaletteType::DocumentSymbol,
+            _ if self.palette.input.starts_with('#') => PaletteType::WorkspaceSymbol,
             _ if self.palette.input.starts_with('>') => PaletteType::Workspace,
             _ if self.palette.input.starts_with(':') => PaletteType::Command,
             _ => PaletteType::File,
@@ -799,6 +1188,166 @@ impl PaletteViewData {
             .collect();
     }
 
+    /// Lists the branches other than the current one, for the actions that
+    /// pick a branch. Only the local branches can be deleted.
+    fn get_git_branches(&mut self, action: GitBranchAction) {
+        if !matches!(action, GitBranchAction::Checkout | GitBranchAction::Delete) {
+            return;
+        }
+        let current = self.source_control.branch.clone();
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.items = self
+            .source_control
+            .branches
+            .iter()
+            .filter(|branch| **branch != current)
+            .filter(|branch| {
+                action != GitBranchAction::Delete
+                    || !self.source_control.remote_branches.contains(branch)
+            })
+            .map(|branch| PaletteItem {
+                content: PaletteItemContent::GitBranch(action, branch.to_string()),
+                filter_text: branch.to_string(),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+    }
+
+    /// Lists the index, the head and the other branches, to compare the file
+    /// of the active editor with.
+    fn get_diff_revisions(&mut self) {
+        let path = match self.main_split.active_editor().map(|e| &e.content) {
+            Some(BufferContent::File(path)) => path.clone(),
+            _ => return,
+        };
+        let current = self.source_control.branch.clone();
+        // The branches go by their full name, for one called "index" or
+        // "head" not to be taken for the index or the head.
+        let remote_branches = &self.source_control.remote_branches;
+        let revisions = ["index".to_string(), "head".to_string()].into_iter().chain(
+            self.source_control
+                .branches
+                .iter()
+                .filter(|branch| **branch != current)
+                .map(|branch| {
+                    if remote_branches.contains(branch) {
+                        format!("refs/remotes/{branch}")
+                    } else {
+                        format!("refs/heads/{branch}")
+                    }
+                }),
+        );
+        let palette = Arc::make_mut(&mut self.palette);
+        palette.items = revisions
+            .map(|revision| PaletteItem {
+                filter_text: revision_name(&revision).to_string(),
+                content: PaletteItemContent::DiffRevision(path.clone(), revision),
+                score: 0,
+                indices: vec![],
+            })
+            .collect();
+    }
+
+    /// Lists the stash entries, for the actions that pick one.
+    fn get_git_stashes(&self, ctx: &mut EventCtx, action: GitStashAction) {
+        if let GitStashAction::Save { .. } = action {
+            return;
+        }
+        let run_id = self.palette.run_id.clone();
+        let widget_id = self.palette.widget_id;
+        let event_sink = ctx.get_external_handle();
+        self.palette.proxy.git_stash_list(Box::new(move |result| {
+            if let Ok(Ok(entries)) =
+                result.map(serde_json::from_value::<Vec<StashEntry>>)
+            {
+                let items = entries
+                    .into_iter()
+                    .map(|entry| PaletteItem {
+                        filter_text: entry.message.clone(),
+                        content: PaletteItemContent::GitStash(action, entry),
+                        score: 0,
+                        indices: vec![],
+                    })
+                    .collect();
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdatePaletteItems(run_id, items),
+                    Target::Widget(widget_id),
+                );
+            }
+        }));
+    }
+
+    /// Lists the commits that changed the file of the active editor.
+    fn get_file_history(&self, ctx: &mut EventCtx) {
+        let path = match self.main_split.active_editor().map(|e| &e.content) {
+            Some(BufferContent::File(path)) => path.clone(),
+            _ => return,
+        };
+        let run_id = self.palette.run_id.clone();
+        let widget_id = self.palette.widget_id;
+        let event_sink = ctx.get_external_handle();
+        let file = path.clone();
+        self.palette.proxy.git_file_history(
+            &path,
+            Box::new(move |result| {
+                if let Ok(Ok(commits)) =
+                    result.map(serde_json::from_value::<Vec<CommitInfo>>)
+                {
+                    let items = commits
+                        .into_iter()
+                        .map(|commit| PaletteItem {
+                            filter_text: format!(
+                                "{} {} {}",
+                                commit.summary, commit.author, commit.id
+                            ),
+                            content: PaletteItemContent::FileCommit(
+                                file.clone(),
+                                commit,
+                            ),
+                            score: 0,
+                            indices: vec![],
+                        })
+                        .collect();
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdatePaletteItems(run_id, items),
+                        Target::Widget(widget_id),
+                    );
+                }
+            }),
+        );
+    }
+
+    fn get_lsp_logs(&self, ctx: &mut EventCtx) {
+        let run_id = self.palette.run_id.clone();
+        let widget_id = self.palette.widget_id;
+        let event_sink = ctx.get_external_handle();
+        self.palette
+            .proxy
+            .get_lsp_log_names(Box::new(move |result| {
+                if let Ok(Ok(names)) =
+                    result.map(serde_json::from_value::<Vec<String>>)
+                {
+                    let items = names
+                        .into_iter()
+                        .map(|name| PaletteItem {
+                            filter_text: name.clone(),
+                            content: PaletteItemContent::LspLog(name),
+                            score: 0,
+                            indices: vec![],
+                        })
+                        .collect();
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::UpdatePaletteItems(run_id, items),
+                        Target::Widget(widget_id),
+                    );
+                }
+            }));
+    }
+
     fn get_commands(&mut self, _ctx: &mut EventCtx) {
         const EXCLUDED_ITEMS: &[&str] = &["palette.command"];
 
@@ -980,6 +1529,72 @@ impl PaletteViewData {
         }
     }
 
+    fn get_workspace_symbols(&mut self, ctx: &mut EventCtx) {
+        let query = self.palette.get_input().to_string();
+        if query.is_empty() {
+            let palette = Arc::make_mut(&mut self.palette);
+            palette.items.clear();
+            palette.filtered_items.clear();
+            return;
+        }
+
+        let run_id = self.palette.run_id.clone();
+        let widget_id = self.palette.widget_id;
+        let workspace = self.workspace.clone();
+        let event_sink = ctx.get_external_handle();
+        self.palette.proxy.get_workspace_symbols(
+            self.palette.get_input(),
+            Box::new(move |result| {
+                if let Ok(res) = result {
+                    let resp: Result<Vec<SymbolInformation>, serde_json::Error> =
+                        serde_json::from_value(res);
+                    if let Ok(resp) = resp {
+                        let items: Vec<PaletteItem> = resp
+                            .into_iter()
+                            .filter_map(|s| {
+                                let full_path =
+                                    s.location.uri.to_file_path().ok()?;
+                                let mut path = full_path.clone();
+                                if let Some(workspace_path) = workspace.path.as_ref()
+                                {
+                                    path = path
+                                        .strip_prefix(workspace_path)
+                                        .unwrap_or(&full_path)
+                                        .to_path_buf();
+                                }
+                                Some(PaletteItem {
+                                    content: PaletteItemContent::WorkspaceSymbol {
+                                        kind: s.kind,
+                                        name: s.name.clone(),
+                                        container_name: s.container_name,
+                                        path,
+                                        location: EditorLocation {
+                                            path: full_path,
+                                            position: Some(s.location.range.start),
+                                            scroll_offset: None,
+                                            history: None,
+                                        },
+                                    },
+                                    filter_text: s.name,
+                                    score: 0,
+                                    indices: Vec::new(),
+                                })
+                            })
+                            .collect();
+                        let matcher = SkimMatcherV2::default().ignore_case();
+                        let items =
+                            Self::filter_items(&run_id, &query, items, &matcher);
+                        let _ = event_sink.submit_command(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::FilterPaletteItems(run_id, query, items),
+                            Target::Widget(widget_id),
+                        );
+                    }
+                }
+            }),
+        );
+    }
+
     pub fn update_process(
         receiver: Receiver<(String, String, Vec<PaletteItem>)>,
         widget_id: WidgetId,
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::BufReader;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
@@ -16,25 +17,32 @@ use lapce_proxy::dispatch::Dispatcher;
 use lapce_rpc::buffer::BufferId;
 use lapce_rpc::core::{CoreNotification, CoreRequest};
 use lapce_rpc::plugin::PluginDescription;
-use lapce_rpc::proxy::ProxyRequest;
-use lapce_rpc::source_control::FileDiff;
+use lapce_rpc::proxy::{HierarchyKind, LspSettings, ProxyRequest};
+use lapce_rpc::source_control::{self, FileDiff};
+use lapce_rpc::style::{LineStyle, Style};
 use lapce_rpc::terminal::TermId;
-use lapce_rpc::RpcHandler;
 use lapce_rpc::{stdio_transport, Callback};
 use lapce_rpc::{ControlFlow, Handler};
+use lapce_rpc::{RequestId, RpcHandler};
+use lsp_types::CallHierarchyItem;
+use lsp_types::CodeAction;
+use lsp_types::CodeLens;
 use lsp_types::CompletionItem;
+use lsp_types::MessageActionItem;
 use lsp_types::Position;
+use lsp_types::Range;
 use lsp_types::Url;
 use parking_lot::Mutex;
 use serde_json::json;
 use serde_json::Value;
-use xi_rope::spans::SpansBuilder;
+use xi_rope::spans::{Spans, SpansBuilder};
 use xi_rope::{Interval, Rope, RopeDelta};
 
 use crate::command::LapceUICommand;
 use crate::command::LAPCE_UI_COMMAND;
 use crate::config::Config;
 use crate::data::{LapceWorkspace, LapceWorkspaceType};
+use crate::signature::SignatureTriggers;
 use crate::terminal::RawTerminal;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -74,26 +82,33 @@ impl Handler for LapceProxy {
                 path,
                 styles,
                 len,
+                base_rev,
+                start,
+                end,
             } => {
                 let event_sink = self.event_sink.clone();
                 let tab_id = self.tab_id;
                 rayon::spawn(move || {
-                    let mut styles_span = SpansBuilder::new(len);
-                    for style in styles {
-                        styles_span.add_span(
-                            Interval::new(style.start, style.end),
-                            style.style,
-                        );
-                    }
-                    let styles_span = Arc::new(styles_span.build());
-                    let _ = event_sink.submit_command(
-                        LAPCE_UI_COMMAND,
-                        LapceUICommand::UpdateSemanticStyles(
+                    let command = match base_rev {
+                        Some(base_rev) => LapceUICommand::EditSemanticStyles {
                             buffer_id,
                             path,
                             rev,
-                            styles_span,
+                            base_rev: Some(base_rev),
+                            start,
+                            end,
+                            styles: styles_spans(&styles, start, end),
+                        },
+                        None => LapceUICommand::UpdateSemanticStyles(
+                            buffer_id,
+                            path,
+                            rev,
+                            Arc::new(styles_spans(&styles, 0, len)),
                         ),
+                    };
+                    let _ = event_sink.submit_command(
+                        LAPCE_UI_COMMAND,
+                        command,
                         Target::Widget(tab_id),
                     );
                 });
@@ -133,6 +148,37 @@ impl Handler for LapceProxy {
                     Target::Widget(self.tab_id),
                 );
             }
+            LspServerStatus { server, status } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateLspStatus(server, status),
+                    Target::Widget(self.tab_id),
+                );
+            }
+            LspLog { name, entry } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::AppendLspLog { name, entry },
+                    Target::Widget(self.tab_id),
+                );
+            }
+            SignatureHelpTriggers {
+                path,
+                trigger_characters,
+                retrigger_characters,
+            } => {
+                let _ = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateSignatureTriggers(
+                        path,
+                        SignatureTriggers {
+                            trigger_characters,
+                            retrigger_characters,
+                        },
+                    ),
+                    Target::Widget(self.tab_id),
+                );
+            }
             InstalledPlugins { plugins } => {
                 let _ = self.event_sink.submit_command(
                     LAPCE_UI_COMMAND,
@@ -186,8 +232,48 @@ impl Handler for LapceProxy {
         ControlFlow::Continue
     }
 
-    fn handle_request(&mut self, _rpc: Self::Request) -> Result<Value, Value> {
-        Err(json!("unimplemented"))
+    fn handle_request(&mut self, id: RequestId, rpc: Self::Request) {
+        use lapce_rpc::core::CoreRequest::*;
+        match rpc {
+            ApplyWorkspaceEdit { edit } => {
+                if let Err(e) = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ApplyWorkspaceEditRequest(id, edit),
+                    Target::Widget(self.tab_id),
+                ) {
+                    self.rpc.respond(
+                        id,
+                        Ok(json!({
+                            "applied": false,
+                            "failureReason": e.to_string(),
+                        })),
+                    );
+                }
+            }
+            ShowMessageRequest { params } => {
+                if let Err(e) = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::ShowMessageRequest(id, params),
+                    Target::Widget(self.tab_id),
+                ) {
+                    self.rpc.respond(id, Err(json!(e.to_string())));
+                }
+            }
+            GitCredentials { url, username, key } => {
+                if let Err(e) = self.event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::AskGitCredentials {
+                        id,
+                        url,
+                        username,
+                        key,
+                    },
+                    Target::Widget(self.tab_id),
+                ) {
+                    self.rpc.respond(id, Err(json!(e.to_string())));
+                }
+            }
+        }
     }
 }
 
@@ -195,6 +281,7 @@ impl LapceProxy {
     pub fn new(
         tab_id: WidgetId,
         workspace: LapceWorkspace,
+        lsp_settings: HashMap<String, LspSettings>,
         term_tx: Sender<(TermId, TermEvent)>,
         event_sink: ExtEventSink,
     ) -> Self {
@@ -215,7 +302,7 @@ impl LapceProxy {
                 LapceUICommand::ProxyUpdateStatus(ProxyStatus::Connecting),
                 Target::Widget(tab_id),
             );
-            let _ = local_proxy.start(workspace.clone());
+            let _ = local_proxy.start(workspace.clone(), lsp_settings);
             let _ = event_sink.submit_command(
                 LAPCE_UI_COMMAND,
                 LapceUICommand::ProxyUpdateStatus(ProxyStatus::Disconnected),
@@ -226,9 +313,13 @@ impl LapceProxy {
         proxy
     }
 
-    fn start(&self, workspace: LapceWorkspace) -> Result<()> {
+    fn start(
+        &self,
+        workspace: LapceWorkspace,
+        lsp_settings: HashMap<String, LspSettings>,
+    ) -> Result<()> {
         if let Some(path) = workspace.path.as_ref() {
-            self.initialize(path.clone());
+            self.initialize(path.clone(), lsp_settings);
         }
         let (core_sender, core_receiver) = crossbeam_channel::unbounded();
         match workspace.kind {
@@ -325,11 +416,25 @@ impl LapceProxy {
         Ok(())
     }
 
-    pub fn initialize(&self, workspace: PathBuf) {
+    pub fn initialize(
+        &self,
+        workspace: PathBuf,
+        lsp_settings: HashMap<String, LspSettings>,
+    ) {
         self.rpc.send_rpc_notification(
             "initialize",
             &json!({
                 "workspace": workspace,
+                "lsp": lsp_settings,
+            }),
+        )
+    }
+
+    pub fn update_lsp_settings(&self, lsp_settings: &HashMap<String, LspSettings>) {
+        self.rpc.send_rpc_notification(
+            "update_lsp_settings",
+            &json!({
+                "lsp": lsp_settings,
             }),
         )
     }
@@ -392,6 +497,237 @@ impl LapceProxy {
         )
     }
 
+    pub fn git_stage(&self, diffs: Vec<FileDiff>, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_stage",
+            &json!({
+                "diffs": diffs,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_unstage(&self, diffs: Vec<FileDiff>, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_unstage",
+            &json!({
+                "diffs": diffs,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_discard(&self, diffs: Vec<FileDiff>, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_discard",
+            &json!({
+                "diffs": diffs,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_stage_hunk(&self, path: &Path, line: usize, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_stage_hunk",
+            &json!({
+                "path": path,
+                "line": line,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_unstage_hunk(&self, path: &Path, line: usize, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_unstage_hunk",
+            &json!({
+                "path": path,
+                "line": line,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_discard_hunk(&self, path: &Path, line: usize, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_discard_hunk",
+            &json!({
+                "path": path,
+                "line": line,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_checkout(&self, reference: &str, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_checkout",
+            &json!({
+                "reference": reference,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_create_branch(
+        &self,
+        name: &str,
+        start_point: Option<&str>,
+        checkout: bool,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "git_create_branch",
+            &json!({
+                "name": name,
+                "start_point": start_point,
+                "checkout": checkout,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_delete_branch(&self, name: &str, force: bool, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_delete_branch",
+            &json!({
+                "name": name,
+                "force": force,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_rename_branch(
+        &self,
+        name: &str,
+        new_name: &str,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "git_rename_branch",
+            &json!({
+                "name": name,
+                "new_name": new_name,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_blame(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_blame",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_file_history(&self, path: &Path, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_file_history",
+            &json!({
+                "path": path,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_stash_save(
+        &self,
+        message: Option<&str>,
+        include_untracked: bool,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "git_stash_save",
+            &json!({
+                "message": message,
+                "include_untracked": include_untracked,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_stash_list(&self, f: Box<dyn Callback>) {
+        self.rpc
+            .send_rpc_request_async("git_stash_list", &json!({}), f);
+    }
+
+    pub fn git_stash_apply(&self, index: usize, pop: bool, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_stash_apply",
+            &json!({
+                "index": index,
+                "pop": pop,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_stash_drop(&self, index: usize, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_stash_drop",
+            &json!({
+                "index": index,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_fetch(&self, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async("git_fetch", &json!({}), f);
+    }
+
+    pub fn git_pull(&self, rebase: bool, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "git_pull",
+            &json!({
+                "rebase": rebase,
+            }),
+            f,
+        );
+    }
+
+    pub fn git_push(&self, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async("git_push", &json!({}), f);
+    }
+
+    /// Answers a `CoreRequest::ApplyWorkspaceEdit` with whether the edit
+    /// was applied, or why not
+    pub fn apply_workspace_edit_response(
+        &self,
+        id: RequestId,
+        result: Result<(), String>,
+    ) {
+        self.rpc.respond(
+            id,
+            Ok(json!({
+                "applied": result.is_ok(),
+                "failureReason": result.err(),
+            })),
+        );
+    }
+
+    /// Answers a `CoreRequest::GitCredentials`, with `None` when the user
+    /// gave up
+    pub fn git_credentials_response(
+        &self,
+        id: RequestId,
+        credentials: Option<source_control::GitCredentials>,
+    ) {
+        self.rpc.respond(id, Ok(json!(credentials)));
+    }
+
+    pub fn restart_lsp_servers(&self, buffer_id: Option<BufferId>) {
+        self.rpc.send_rpc_notification(
+            "restart_lsp_servers",
+            &json!({ "buffer_id": buffer_id }),
+        );
+    }
+
     pub fn install_plugin(&self, plugin: &PluginDescription) {
         self.rpc
             .send_rpc_notification("install_plugin", &json!({ "plugin": plugin }));
@@ -401,11 +737,12 @@ impl LapceProxy {
         &self,
         buffer_id: BufferId,
         path: PathBuf,
+        version: &str,
         f: Box<dyn Callback>,
     ) {
         self.rpc.send_rpc_request_async(
             "buffer_head",
-            &json!({ "buffer_id": buffer_id, "path": path, }),
+            &json!({ "buffer_id": buffer_id, "path": path, "version": version }),
             f,
         );
     }
@@ -470,6 +807,14 @@ impl LapceProxy {
         );
     }
 
+    pub fn show_message_response(
+        &self,
+        id: RequestId,
+        action: Option<MessageActionItem>,
+    ) {
+        self.rpc.respond(id, Ok(json!(action)));
+    }
+
     pub fn get_completion(
         &self,
         request_id: usize,
@@ -526,6 +871,8 @@ impl LapceProxy {
         &self,
         buffer_id: BufferId,
         position: Position,
+        trigger_character: Option<String>,
+        is_retrigger: bool,
         f: Box<dyn Callback>,
     ) {
         self.rpc.send_rpc_request_async(
@@ -533,6 +880,8 @@ impl LapceProxy {
             &json!({
                 "buffer_id": buffer_id,
                 "position": position,
+                "trigger_character": trigger_character,
+                "is_retrigger": is_retrigger,
             }),
             f,
         );
@@ -554,6 +903,40 @@ impl LapceProxy {
         );
     }
 
+    pub fn prepare_rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn rename(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "rename",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "new_name": new_name,
+            }),
+            f,
+        );
+    }
+
     pub fn get_files(&self, f: Box<dyn Callback>) {
         self.rpc.send_rpc_request_async(
             "get_files",
@@ -592,6 +975,88 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_type_definition(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_type_definition",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn prepare_hierarchy(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        kind: HierarchyKind,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "prepare_hierarchy",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "kind": kind,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_hierarchy_children(
+        &self,
+        kind: HierarchyKind,
+        item: &CallHierarchyItem,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_hierarchy_children",
+            &json!({
+                "kind": kind,
+                "item": item,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_implementation(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_implementation",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_declaration(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_declaration",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
     pub fn get_document_symbols(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
         self.rpc.send_rpc_request_async(
             "get_document_symbols",
@@ -602,6 +1067,16 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_workspace_symbols(&self, query: &str, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "get_workspace_symbols",
+            &json!({
+                "query": query,
+            }),
+            f,
+        );
+    }
+
     pub fn get_code_actions(
         &self,
         buffer_id: BufferId,
@@ -618,6 +1093,159 @@ impl LapceProxy {
         );
     }
 
+    pub fn resolve_code_action(&self, action: &CodeAction, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "resolve_code_action",
+            &json!({
+                "action": action,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_code_lens(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "get_code_lens",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+            f,
+        );
+    }
+
+    pub fn resolve_code_lens(&self, code_lens: &CodeLens, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "resolve_code_lens",
+            &json!({
+                "code_lens": code_lens,
+            }),
+            f,
+        );
+    }
+
+    pub fn execute_command(
+        &self,
+        buffer_id: BufferId,
+        command: &lsp_types::Command,
+        data: Option<&Value>,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "execute_command",
+            &json!({
+                "buffer_id": buffer_id,
+                "command": command,
+                "data": data,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_document_highlights(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_document_highlights",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_inlay_hints(
+        &self,
+        buffer_id: BufferId,
+        range: Range,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_inlay_hints",
+            &json!({
+                "buffer_id": buffer_id,
+                "range": range,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_lsp_log_names(&self, f: Box<dyn Callback>) {
+        self.rpc
+            .send_rpc_request_async("get_lsp_log_names", &json!({}), f);
+    }
+
+    pub fn get_lsp_log(&self, name: &str, f: Box<dyn Callback>) {
+        self.rpc
+            .send_rpc_request_async("get_lsp_log", &json!({ "name": name }), f);
+    }
+
+    pub fn toggle_lsp_trace(&self) {
+        self.rpc
+            .send_rpc_notification("toggle_lsp_trace", &json!({}));
+    }
+
+    pub fn close_buffer(&self, buffer_id: BufferId) {
+        self.rpc.send_rpc_notification(
+            "close_buffer",
+            &json!({ "buffer_id": buffer_id }),
+        );
+    }
+
+    pub fn get_semantic_styles_range(
+        &self,
+        buffer_id: BufferId,
+        range: Range,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_semantic_tokens_range",
+            &json!({
+                "buffer_id": buffer_id,
+                "range": range,
+            }),
+            f,
+        );
+    }
+
+    /// Asks for all the semantic styles again, when the ones that changed
+    /// were sent for styles that aren't the ones shown.
+    pub fn refresh_semantic_styles(&self, buffer_id: BufferId) {
+        self.rpc.send_rpc_notification(
+            "refresh_semantic_styles",
+            &json!({ "buffer_id": buffer_id }),
+        );
+    }
+
+    pub fn get_folding_ranges(&self, buffer_id: BufferId, f: Box<dyn Callback>) {
+        self.rpc.send_rpc_request_async(
+            "get_folding_ranges",
+            &json!({
+                "buffer_id": buffer_id,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_selection_ranges(
+        &self,
+        buffer_id: BufferId,
+        positions: &[Position],
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_selection_ranges",
+            &json!({
+                "buffer_id": buffer_id,
+                "positions": positions,
+            }),
+            f,
+        );
+    }
+
     pub fn get_document_formatting(
         &self,
         buffer_id: BufferId,
@@ -632,6 +1260,40 @@ impl LapceProxy {
         );
     }
 
+    pub fn get_range_formatting(
+        &self,
+        buffer_id: BufferId,
+        ranges: Vec<Range>,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_range_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "ranges": ranges,
+            }),
+            f,
+        );
+    }
+
+    pub fn get_on_type_formatting(
+        &self,
+        buffer_id: BufferId,
+        position: Position,
+        ch: &str,
+        f: Box<dyn Callback>,
+    ) {
+        self.rpc.send_rpc_request_async(
+            "get_on_type_formatting",
+            &json!({
+                "buffer_id": buffer_id,
+                "position": position,
+                "ch": ch,
+            }),
+            f,
+        );
+    }
+
     pub fn stop(&self) {
         self.rpc.send_rpc_notification("shutdown", &json!({}));
         // self.core_sender.send(json!({
@@ -641,6 +1303,24 @@ impl LapceProxy {
     }
 }
 
+/// Builds the spans of the styles in `start..end` of the buffer, relative to
+/// `start`.
+pub fn styles_spans(styles: &[LineStyle], start: usize, end: usize) -> Spans<Style> {
+    let len = end.saturating_sub(start);
+    let mut styles_span = SpansBuilder::new(len);
+    for style in styles {
+        let style_start = style.start.max(start) - start;
+        let style_end = style.end.min(end).saturating_sub(start);
+        if style_start < style_end {
+            styles_span.add_span(
+                Interval::new(style_start, style_end),
+                style.style.clone(),
+            );
+        }
+    }
+    styles_span.build()
+}
+
 fn new_command(program: &str) -> Command {
     #[allow(unused_mut)]
     let mut cmd = Command::new(program);
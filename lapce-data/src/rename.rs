@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+
+use druid::{Size, WidgetId};
+use lsp_types::Position;
+
+#[derive(Clone)]
+pub struct RenameData {
+    pub active: bool,
+    /// The view id of the inline input editor
+    pub view_id: WidgetId,
+    /// The editor view id that the rename was started from
+    pub from_editor: WidgetId,
+    /// The file that contains the symbol being renamed
+    pub path: PathBuf,
+    /// The buffer rev when the rename was prepared
+    pub rev: u64,
+    /// The offset the rename was requested at
+    pub offset: usize,
+    /// The position sent to the language server for the rename
+    pub position: Position,
+    /// The start offset of the symbol range, used to place the input box
+    pub start: usize,
+    pub size: Size,
+}
+
+impl RenameData {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            view_id: WidgetId::next(),
+            from_editor: WidgetId::next(),
+            path: PathBuf::new(),
+            rev: 0,
+            offset: 0,
+            position: Position::new(0, 0),
+            start: 0,
+            size: Size::new(200.0, 0.0),
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        from_editor: WidgetId,
+        path: PathBuf,
+        rev: u64,
+        offset: usize,
+        position: Position,
+        start: usize,
+    ) {
+        self.active = true;
+        self.from_editor = from_editor;
+        self.path = path;
+        self.rev = rev;
+        self.offset = offset;
+        self.position = position;
+        self.start = start;
+    }
+
+    pub fn cancel(&mut self) {
+        self.active = false;
+    }
+}
+
+impl Default for RenameData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
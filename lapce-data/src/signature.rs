@@ -1,65 +1,267 @@
-use lsp_types::ParameterLabel;
-use lsp_types::SignatureHelp;
+use std::sync::Arc;
+
+use druid::{ExtEventSink, Target, WidgetId};
+use lapce_rpc::buffer::BufferId;
+use lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, Position, SignatureHelp,
+    SignatureInformation,
+};
+
+use crate::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    proxy::LapceProxy,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    Inactive,
+    Started,
+    Done,
+}
+
+/// The characters that ask the language servers of a document for its
+/// signature help, either always or only while it's shown
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SignatureTriggers {
+    pub trigger_characters: Vec<String>,
+    pub retrigger_characters: Vec<String>,
+}
+
+impl SignatureTriggers {
+    /// Whether the typed character asks for the signature help, with the
+    /// retrigger characters only counting while it's shown.
+    fn is_trigger(&self, ch: &str, is_retrigger: bool) -> bool {
+        self.trigger_characters.iter().any(|c| c == ch)
+            || (is_retrigger && self.retrigger_characters.iter().any(|c| c == ch))
+    }
+}
 
 #[derive(Clone)]
-pub struct SignatureState {
-    pub offset: Option<usize>,
-    pub signature: Option<SignatureHelp>,
-    pub active: Option<(usize, usize)>,
+pub struct SignatureData {
+    pub id: WidgetId,
+    /// The editor view id that the signature help is displayed for
+    pub editor_view_id: WidgetId,
+    pub status: SignatureStatus,
+    /// The buffer that this signature help is for
+    pub buffer_id: BufferId,
+    /// The offset of the cursor when it was last asked for
+    pub offset: usize,
+    /// A counter to keep track of the active requests
+    pub request_id: usize,
+    /// Kept while the signature help is asked again, so that the popup
+    /// doesn't flicker on every keystroke
+    pub help: Option<Arc<SignatureHelp>>,
+    /// The overload that is shown
+    pub active_signature: usize,
 }
 
-impl SignatureState {
+impl SignatureData {
     pub fn new() -> Self {
         Self {
-            offset: None,
-            signature: None,
-            active: None,
+            id: WidgetId::next(),
+            editor_view_id: WidgetId::next(),
+            status: SignatureStatus::Inactive,
+            buffer_id: BufferId(0),
+            offset: 0,
+            request_id: 0,
+            help: None,
+            active_signature: 0,
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.status != SignatureStatus::Inactive && self.help.is_some()
+    }
+
+    /// The number of overloads
+    pub fn len(&self) -> usize {
+        self.help
+            .as_ref()
+            .map(|help| help.signatures.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn next(&mut self) {
+        if !self.is_empty() {
+            self.active_signature = (self.active_signature + 1) % self.len();
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.is_empty() {
+            self.active_signature =
+                (self.active_signature + self.len() - 1) % self.len();
         }
     }
 
-    pub fn update(&mut self, offset: usize, commas: Vec<usize>) -> Option<bool> {
-        let signature = self.signature.as_ref()?;
-        let params = signature.signatures[0].parameters.as_ref()?;
-        if params.is_empty() {
-            return None;
+    pub fn cancel(&mut self) {
+        if self.status == SignatureStatus::Inactive {
+            return;
         }
-        let mut index = commas.len();
-        for (i, c) in commas.iter().enumerate() {
-            if offset <= *c {
-                index = i;
-                break;
+
+        self.status = SignatureStatus::Inactive;
+        self.help = None;
+        self.active_signature = 0;
+    }
+
+    /// Asks for the signature help at the position. It's a retrigger when
+    /// it's shown for the same editor, in which case any character or a
+    /// cursor move asks for it, while otherwise only a trigger character of
+    /// the servers does.
+    #[allow(clippy::too_many_arguments)]
+    pub fn request(
+        &mut self,
+        proxy: &LapceProxy,
+        buffer_id: BufferId,
+        editor_view_id: WidgetId,
+        offset: usize,
+        position: Position,
+        typed: Option<&str>,
+        triggers: &SignatureTriggers,
+        event_sink: ExtEventSink,
+    ) {
+        let is_retrigger = self.is_shown()
+            && self.buffer_id == buffer_id
+            && self.editor_view_id == editor_view_id;
+        let trigger_character = typed
+            .filter(|ch| triggers.is_trigger(ch, is_retrigger))
+            .map(|ch| ch.to_string());
+        if !is_retrigger {
+            if trigger_character.is_none() {
+                return;
             }
+            self.cancel();
+            self.status = SignatureStatus::Started;
+            self.buffer_id = buffer_id;
+            self.editor_view_id = editor_view_id;
+        }
+        self.offset = offset;
+        self.request_id += 1;
+
+        let request_id = self.request_id;
+        let widget_id = self.id;
+        proxy.get_signature(
+            buffer_id,
+            position,
+            trigger_character,
+            is_retrigger,
+            Box::new(move |result| {
+                let help = result.ok().and_then(|value| {
+                    serde_json::from_value::<Option<SignatureHelp>>(value)
+                        .ok()
+                        .flatten()
+                });
+                let _ = event_sink.submit_command(
+                    LAPCE_UI_COMMAND,
+                    LapceUICommand::UpdateSignature(request_id, help),
+                    Target::Widget(widget_id),
+                );
+            }),
+        );
+    }
+
+    /// Receive the result of a signature help request, which closes the
+    /// popup when the cursor isn't in a call anymore.
+    pub fn receive(&mut self, request_id: usize, help: Option<SignatureHelp>) {
+        if self.status == SignatureStatus::Inactive || self.request_id != request_id
+        {
+            return;
         }
 
-        let label = signature.signatures[0].label.clone();
-        let active = if index >= params.len() {
-            None
-        } else {
-            match &params[index].label {
-                ParameterLabel::Simple(s) => {
-                    let start = label.find(s)?;
-                    Some((start, start + s.len()))
-                }
-                ParameterLabel::LabelOffsets(offsets) => {
-                    Some((offsets[0] as usize, offsets[1] as usize))
-                }
+        let help = match help {
+            Some(help) if !help.signatures.is_empty() => help,
+            _ => {
+                self.cancel();
+                return;
             }
         };
 
-        let changed = self.active != active;
-        self.active = active;
-        Some(changed)
+        // Keep the overload picked with the list commands while the
+        // signatures stay the same.
+        let same_signatures = self
+            .help
+            .as_ref()
+            .map(|old| old.signatures.len() == help.signatures.len())
+            .unwrap_or(false);
+        if !same_signatures {
+            self.active_signature = help
+                .active_signature
+                .map(|i| i as usize)
+                .filter(|i| *i < help.signatures.len())
+                .unwrap_or(0);
+        }
+        self.status = SignatureStatus::Done;
+        self.help = Some(Arc::new(help));
+    }
+
+    pub fn current_signature(&self) -> Option<&SignatureInformation> {
+        self.help
+            .as_ref()
+            .and_then(|help| help.signatures.get(self.active_signature))
     }
 
-    pub fn clear(&mut self) {
-        self.offset = None;
-        self.signature = None;
-        self.active = None;
+    fn current_parameter(&self) -> Option<&ParameterInformation> {
+        let help = self.help.as_ref()?;
+        let signature = self.current_signature()?;
+        let index = signature.active_parameter.or(help.active_parameter)?;
+        signature.parameters.as_ref()?.get(index as usize)
+    }
+
+    /// The byte range of the active parameter in the label of the shown
+    /// signature.
+    pub fn active_parameter(&self) -> Option<(usize, usize)> {
+        let label = &self.current_signature()?.label;
+        match &self.current_parameter()?.label {
+            ParameterLabel::Simple(s) => {
+                let start = label.find(s.as_str())?;
+                Some((start, start + s.len()))
+            }
+            // The offsets count UTF-16 code units.
+            ParameterLabel::LabelOffsets([start, end]) => Some((
+                utf16_to_byte(label, *start as usize),
+                utf16_to_byte(label, *end as usize),
+            )),
+        }
+    }
+
+    /// The documentation of the active parameter, if there's any, and then
+    /// the one of the shown signature.
+    pub fn documentation(&self) -> Vec<&str> {
+        let parameter_doc = self
+            .current_parameter()
+            .and_then(|parameter| parameter.documentation.as_ref());
+        let signature_doc = self
+            .current_signature()
+            .and_then(|signature| signature.documentation.as_ref());
+        parameter_doc
+            .into_iter()
+            .chain(signature_doc)
+            .map(|doc| match doc {
+                Documentation::String(s) => s.as_str(),
+                Documentation::MarkupContent(content) => content.value.as_str(),
+            })
+            .filter(|doc| !doc.trim().is_empty())
+            .collect()
     }
 }
 
-impl Default for SignatureState {
+impl Default for SignatureData {
     fn default() -> Self {
         Self::new()
     }
 }
+
+fn utf16_to_byte(s: &str, offset: usize) -> usize {
+    let mut utf16 = 0;
+    for (i, c) in s.char_indices() {
+        if utf16 >= offset {
+            return i;
+        }
+        utf16 += c.len_utf16();
+    }
+    s.len()
+}
@@ -4,10 +4,14 @@ use lapce_core::{
     mode::Mode,
     movement::Movement,
 };
-use lapce_rpc::source_control::FileDiff;
+use lapce_rpc::source_control::{BlameHunk, DiffInfo, FileDiff};
+use serde_json::json;
 
 use crate::{
-    command::{CommandExecuted, CommandKind, LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandExecuted, CommandKind, LapceCommand, LapceUICommand,
+        LapceWorkbenchCommand, LAPCE_COMMAND, LAPCE_UI_COMMAND,
+    },
     keypress::KeyPressFocus,
     split::{SplitDirection, SplitMoveDirection},
 };
@@ -21,12 +25,20 @@ pub struct SourceControlData {
     pub widget_id: WidgetId,
     pub split_id: WidgetId,
     pub split_direction: SplitDirection,
+    /// The list of the changes that aren't staged
     pub file_list_id: WidgetId,
     pub file_list_index: usize,
+    pub staged_list_id: WidgetId,
+    pub staged_list_index: usize,
     pub editor_view_id: WidgetId,
-    pub file_diffs: Vec<(FileDiff, bool)>,
+    /// The changes from the head to the working files
+    pub file_diffs: Vec<FileDiff>,
+    pub staged_diffs: Vec<FileDiff>,
+    pub unstaged_diffs: Vec<FileDiff>,
     pub branch: String,
     pub branches: Vec<String>,
+    /// The branches among `branches` that track a remote
+    pub remote_branches: Vec<String>,
 }
 
 impl SourceControlData {
@@ -39,11 +51,64 @@ impl SourceControlData {
             editor_view_id,
             file_list_id,
             file_list_index: 0,
+            staged_list_id: WidgetId::next(),
+            staged_list_index: 0,
             split_id: WidgetId::next(),
             split_direction: SplitDirection::Horizontal,
             file_diffs: Vec::new(),
+            staged_diffs: Vec::new(),
+            unstaged_diffs: Vec::new(),
             branch: "".to_string(),
             branches: Vec::new(),
+            remote_branches: Vec::new(),
+        }
+    }
+
+    pub fn diffs(&self, staged: bool) -> &[FileDiff] {
+        if staged {
+            &self.staged_diffs
+        } else {
+            &self.unstaged_diffs
+        }
+    }
+
+    pub fn list_index(&self, staged: bool) -> usize {
+        if staged {
+            self.staged_list_index
+        } else {
+            self.file_list_index
+        }
+    }
+
+    pub fn selected_diff(&self, staged: bool) -> Option<&FileDiff> {
+        self.diffs(staged).get(self.list_index(staged))
+    }
+
+    /// Keeps the selections in the lists after the diffs changed.
+    pub fn update_diffs(&mut self, diffs: &DiffInfo) {
+        self.file_diffs = diffs.diffs.clone();
+        self.staged_diffs = diffs.staged.clone();
+        self.unstaged_diffs = diffs.unstaged.clone();
+        self.file_list_index = self
+            .file_list_index
+            .min(self.unstaged_diffs.len().saturating_sub(1));
+        self.staged_list_index = self
+            .staged_list_index
+            .min(self.staged_diffs.len().saturating_sub(1));
+    }
+
+    fn is_staged_list_active(&self) -> bool {
+        self.active == self.staged_list_id
+    }
+
+    fn move_list_index(&mut self, movement: Movement) {
+        let staged = self.is_staged_list_active();
+        let len = self.diffs(staged).len();
+        let index = movement.update_index(self.list_index(staged), len, 1, true);
+        if staged {
+            self.staged_list_index = index;
+        } else {
+            self.file_list_index = index;
         }
     }
 }
@@ -62,7 +127,10 @@ impl KeyPressFocus for SourceControlData {
     fn check_condition(&self, condition: &str) -> bool {
         match condition {
             "source_control_focus" => true,
-            "list_focus" => self.active == self.file_list_id,
+            "list_focus" => {
+                self.active == self.file_list_id
+                    || self.active == self.staged_list_id
+            }
             _ => false,
         }
     }
@@ -155,36 +223,36 @@ impl KeyPressFocus for SourceControlData {
                     ));
                 }
                 FocusCommand::ListPrevious => {
-                    self.file_list_index = Movement::Up.update_index(
-                        self.file_list_index,
-                        self.file_diffs.len(),
-                        1,
-                        true,
-                    );
+                    self.move_list_index(Movement::Up);
                 }
                 FocusCommand::ListNext => {
-                    self.file_list_index = Movement::Down.update_index(
-                        self.file_list_index,
-                        self.file_diffs.len(),
-                        1,
-                        true,
-                    );
+                    self.move_list_index(Movement::Down);
                 }
                 FocusCommand::ListExpand => {
-                    if !self.file_diffs.is_empty() {
-                        self.file_diffs[self.file_list_index].1 =
-                            !self.file_diffs[self.file_list_index].1;
+                    let staged = self.is_staged_list_active();
+                    if let Some(diff) = self.selected_diff(staged) {
+                        let workbench_command = if staged {
+                            LapceWorkbenchCommand::SourceControlUnstage
+                        } else {
+                            LapceWorkbenchCommand::SourceControlStage
+                        };
+                        ctx.submit_command(Command::new(
+                            LAPCE_COMMAND,
+                            LapceCommand {
+                                kind: CommandKind::Workbench(workbench_command),
+                                data: Some(json!(diff)),
+                            },
+                            Target::Auto,
+                        ));
                     }
                 }
                 FocusCommand::ListSelect => {
-                    if !self.file_diffs.is_empty() {
+                    let staged = self.is_staged_list_active();
+                    if let Some(diff) = self.selected_diff(staged) {
                         ctx.submit_command(Command::new(
                             LAPCE_UI_COMMAND,
                             LapceUICommand::OpenFileDiff(
-                                self.file_diffs[self.file_list_index]
-                                    .0
-                                    .path()
-                                    .clone(),
+                                diff.path().clone(),
                                 "head".to_string(),
                             ),
                             Target::Auto,
@@ -195,20 +263,10 @@ impl KeyPressFocus for SourceControlData {
             },
             CommandKind::Move(cmd) => match cmd {
                 MoveCommand::Up => {
-                    self.file_list_index = Movement::Up.update_index(
-                        self.file_list_index,
-                        self.file_diffs.len(),
-                        1,
-                        true,
-                    );
+                    self.move_list_index(Movement::Up);
                 }
                 MoveCommand::Down => {
-                    self.file_list_index = Movement::Down.update_index(
-                        self.file_list_index,
-                        self.file_diffs.len(),
-                        1,
-                        true,
-                    );
+                    self.move_list_index(Movement::Down);
                 }
                 _ => return CommandExecuted::No,
             },
@@ -217,3 +275,30 @@ impl KeyPressFocus for SourceControlData {
         CommandExecuted::Yes
     }
 }
+
+/// How long ago the time, in seconds since the Unix epoch, is, like "3 days
+/// ago"
+pub fn time_ago(time: i64) -> String {
+    let (count, unit) = match (chrono::Utc::now().timestamp() - time).max(0) {
+        s if s < 60 => return "just now".to_string(),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s if s < 60 * 60 * 24 * 30 => (s / (60 * 60 * 24), "day"),
+        s if s < 60 * 60 * 24 * 365 => (s / (60 * 60 * 24 * 30), "month"),
+        s => (s / (60 * 60 * 24 * 365), "year"),
+    };
+    format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" })
+}
+
+/// Who last changed the lines, when and why
+pub fn blame_summary(hunk: &BlameHunk) -> String {
+    match hunk.commit.as_ref() {
+        Some(commit) => format!(
+            "{}, {} \u{2022} {}",
+            commit.author,
+            time_ago(commit.time),
+            commit.summary
+        ),
+        None => "Not committed yet".to_string(),
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use alacritty_terminal::{
     ansi,
@@ -522,6 +522,34 @@ impl LapceTerminalData {
         config: &Config,
     ) -> Self {
         let cwd = workspace.path.as_ref().cloned();
+        Self::new_in(cwd, None, split_id, event_sink, proxy, config)
+    }
+
+    /// A terminal whose shell starts in the directory and runs the command
+    /// line, named after the title.
+    pub fn new_with_command(
+        title: String,
+        cwd: Option<PathBuf>,
+        command: String,
+        split_id: WidgetId,
+        event_sink: ExtEventSink,
+        proxy: Arc<LapceProxy>,
+        config: &Config,
+    ) -> Self {
+        let mut terminal =
+            Self::new_in(cwd, Some(command), split_id, event_sink, proxy, config);
+        terminal.title = title;
+        terminal
+    }
+
+    fn new_in(
+        cwd: Option<PathBuf>,
+        command: Option<String>,
+        split_id: WidgetId,
+        event_sink: ExtEventSink,
+        proxy: Arc<LapceProxy>,
+        config: &Config,
+    ) -> Self {
         let widget_id = WidgetId::next();
         let view_id = WidgetId::next();
         let term_id = TermId::next();
@@ -536,6 +564,11 @@ impl LapceTerminalData {
         let shell = config.terminal.shell.clone();
         std::thread::spawn(move || {
             local_proxy.new_terminal(term_id, cwd, shell, local_raw);
+            // The command is typed in the shell rather than run in place of
+            // it, so that its output stays once it exits.
+            if let Some(command) = command {
+                local_proxy.terminal_write(term_id, &format!("{command}\r"));
+            }
         });
 
         Self {
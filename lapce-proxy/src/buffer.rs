@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
 use crossbeam_channel::Sender;
+use lapce_core::language::LapceLanguage;
 use lapce_rpc::buffer::BufferId;
 use std::ffi::OsString;
 use std::fs;
@@ -151,12 +152,17 @@ fn load_rope(path: &Path) -> Result<Rope> {
     Ok(Rope::from(std::str::from_utf8(&bytes)?))
 }
 
-fn language_id_from_path(path: &Path) -> Option<&str> {
-    Some(match path.extension()?.to_str()? {
-        "rs" => "rust",
-        "go" => "go",
-        _ => return None,
-    })
+/// The language id the language servers know the document by, for the
+/// languages Lapce highlights.
+pub fn language_id_from_path(path: &Path) -> Option<&'static str> {
+    // C headers aren't highlighted, and the embedded Elixir templates are
+    // highlighted as Elixir, but the servers have ids for both.
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "h" => return Some("c"),
+        "eex" | "heex" | "sface" => return Some("eelixir"),
+        _ => {}
+    }
+    LapceLanguage::from_path(path).map(|language| language.lsp_id())
 }
 
 fn get_document_content_changes(
@@ -1,6 +1,7 @@
 use crate::buffer::{get_mod_time, load_file, Buffer};
 use crate::lsp::LspCatalog;
 use crate::plugin::PluginCatalog;
+use crate::symbols::SymbolIndex;
 use crate::terminal::Terminal;
 use crate::watcher::{FileWatcher, Notify, WatchToken};
 use alacritty_terminal::event_loop::Msg;
@@ -8,24 +9,41 @@ use alacritty_terminal::term::SizeInfo;
 use anyhow::{anyhow, Context, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use directories::BaseDirs;
-use git2::{DiffOptions, Repository};
+use git2::{
+    BranchType, Commit, Cred, CredentialType, DiffOptions, ErrorCode, FetchOptions,
+    PushOptions, Reference, RemoteCallbacks, Repository, Sort, StashApplyOptions,
+    StashFlags, Status, StatusOptions,
+};
 use grep_matcher::Matcher;
 use grep_regex::RegexMatcherBuilder;
 use grep_searcher::sinks::UTF8;
 use grep_searcher::SearcherBuilder;
+use lapce_core::buffer::{rope_diff, DiffLines};
 use lapce_rpc::buffer::{BufferHeadResponse, BufferId, NewBufferResponse};
-use lapce_rpc::core::CoreNotification;
+use lapce_rpc::core::{CoreNotification, CoreRequest};
 use lapce_rpc::file::FileNodeItem;
 use lapce_rpc::proxy::{ProxyNotification, ProxyRequest, ReadDirResponse};
-use lapce_rpc::source_control::{DiffInfo, FileDiff};
+use lapce_rpc::source_control::{
+    BlameHunk, CommitInfo, DiffInfo, FileDiff, GitBranchError, GitCredentials,
+    StashEntry,
+};
 use lapce_rpc::terminal::TermId;
-use lapce_rpc::{self, Call, RequestId, RpcObject};
+use lapce_rpc::{self, Call, RequestId, RpcHandler, RpcObject};
+use lsp_types::{
+    ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressEnd, WorkDoneProgressReport,
+};
 use parking_lot::Mutex;
 use serde_json::json;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{hash_map::Entry, HashMap};
 use std::fs;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::thread;
 use std::{collections::HashSet, io::BufRead};
@@ -33,10 +51,13 @@ use xi_rope::Rope;
 
 const OPEN_FILE_EVENT_TOKEN: WatchToken = WatchToken(1);
 const WORKSPACE_EVENT_TOKEN: WatchToken = WatchToken(2);
+/// The most commits from the head looked at for the history of a file
+const GIT_FILE_HISTORY_DEPTH: usize = 10_000;
 
 #[derive(Clone)]
 pub struct Dispatcher {
     pub sender: Arc<Sender<Value>>,
+    rpc: RpcHandler,
     pub git_sender: Sender<(BufferId, u64)>,
     pub workspace: Arc<Mutex<Option<PathBuf>>>,
     pub buffers: Arc<Mutex<HashMap<BufferId, Buffer>>>,
@@ -47,6 +68,7 @@ pub struct Dispatcher {
     open_files: Arc<Mutex<HashMap<String, BufferId>>>,
     plugins: Arc<Mutex<PluginCatalog>>,
     pub lsp: Arc<Mutex<LspCatalog>>,
+    symbols: Arc<Mutex<SymbolIndex>>,
     pub file_watcher: Arc<Mutex<Option<FileWatcher>>>,
     last_diff: Arc<Mutex<DiffInfo>>,
 }
@@ -62,6 +84,7 @@ impl Dispatcher {
         let plugins = PluginCatalog::new();
         let (git_sender, git_receiver) = unbounded();
         let dispatcher = Dispatcher {
+            rpc: RpcHandler::new(sender.clone()),
             sender: Arc::new(sender),
             git_sender,
             workspace: Arc::new(Mutex::new(None)),
@@ -70,6 +93,7 @@ impl Dispatcher {
             terminals: Arc::new(Mutex::new(HashMap::new())),
             plugins: Arc::new(Mutex::new(plugins)),
             lsp: Arc::new(Mutex::new(LspCatalog::new())),
+            symbols: Arc::new(Mutex::new(SymbolIndex::new())),
             file_watcher: Arc::new(Mutex::new(None)),
             last_diff: Arc::new(Mutex::new(DiffInfo::default())),
         };
@@ -115,6 +139,15 @@ impl Dispatcher {
         for msg in receiver {
             let rpc: RpcObject = msg.into();
             if rpc.is_response() {
+                let id = rpc.get_id().unwrap();
+                match rpc.into_response() {
+                    Ok(resp) => {
+                        self.rpc.handle_response(id, resp);
+                    }
+                    Err(msg) => {
+                        self.rpc.handle_response(id, Err(json!(msg)));
+                    }
+                }
             } else {
                 match rpc.into_rpc::<ProxyNotification, ProxyRequest>() {
                     Ok(Call::Request(id, request)) => {
@@ -149,7 +182,10 @@ impl Dispatcher {
             match receiver.recv() {
                 Ok((buffer_id, rev)) => {
                     let buffers = buffers.lock();
-                    let buffer = buffers.get(&buffer_id).unwrap();
+                    let buffer = match buffers.get(&buffer_id) {
+                        Some(buffer) => buffer,
+                        None => continue,
+                    };
                     let (_path, _content) = if buffer.rev != rev {
                         continue;
                     } else {
@@ -205,6 +241,21 @@ impl Dispatcher {
         let _ = self.sender.send(resp);
     }
 
+    /// Runs `f` with the buffer of the request, or answers it with an error
+    /// when the buffer has been closed in the meantime.
+    fn with_buffer(
+        &self,
+        id: RequestId,
+        buffer_id: BufferId,
+        f: impl FnOnce(&Buffer),
+    ) {
+        let buffers = self.buffers.lock();
+        match buffers.get(&buffer_id) {
+            Some(buffer) => f(buffer),
+            None => self.respond(id, Err(anyhow!("buffer not found"))),
+        }
+    }
+
     pub fn respond_rpc<T: serde::Serialize>(
         &self,
         id: RequestId,
@@ -229,6 +280,14 @@ impl Dispatcher {
             .send(serde_json::to_value(notification).unwrap());
     }
 
+    pub fn send_rpc_request_async(
+        &self,
+        request: CoreRequest,
+        f: impl FnOnce(Result<Value, Value>) + Send + 'static,
+    ) {
+        self.rpc.send_rpc_request_value_async(request, Box::new(f));
+    }
+
     pub fn send_notification(&self, method: &str, params: Value) {
         let _ = self.sender.send(json!({
             "method": method,
@@ -236,6 +295,24 @@ impl Dispatcher {
         }));
     }
 
+    /// Sets the language of the buffer from the settings, and opens it in
+    /// its language servers, starting the one from the settings if needed.
+    /// It's done here rather than on another thread so that the didOpen
+    /// goes out before the changes that follow, and when the language
+    /// changes, the servers of the old one are told it's closed first.
+    fn open_buffer_in_lsp(&self, buffer_id: BufferId) {
+        let mut buffers = self.buffers.lock();
+        if let Some(buffer) = buffers.get_mut(&buffer_id) {
+            let lsp = self.lsp.lock();
+            let language_id = lsp.language_id(&buffer.path);
+            if language_id != buffer.language_id {
+                lsp.close_buffer(buffer);
+                buffer.language_id = language_id;
+            }
+            lsp.open_buffer(buffer);
+        }
+    }
+
     fn handle_fs_events(&self) {
         let mut events = {
             self.file_watcher
@@ -283,27 +360,278 @@ impl Dispatcher {
     }
 
     fn handle_workspace_fs_event(&self, event: notify::Event) {
-        if let Some(workspace) = self.workspace.lock().clone() {
+        if self.workspace.lock().is_some() {
+            self.symbols.lock().invalidate();
             self.send_rpc_notification(CoreNotification::FileChange { event });
-            if let Some(diff) = git_diff_new(&workspace) {
-                if diff != *self.last_diff.lock() {
-                    self.send_notification(
-                        "diff_info",
-                        json!({
-                            "diff": diff,
-                        }),
+            self.update_diff_info();
+        }
+    }
+
+    /// Sends the diff of the workspace when it's changed since it was last
+    /// sent.
+    fn update_diff_info(&self) {
+        let workspace = match self.workspace.lock().clone() {
+            Some(workspace) => workspace,
+            None => return,
+        };
+        if let Some(diff) = git_diff_new(&workspace) {
+            if diff != *self.last_diff.lock() {
+                self.send_notification(
+                    "diff_info",
+                    json!({
+                        "diff": diff,
+                    }),
+                );
+                *self.last_diff.lock() = diff;
+            }
+        }
+    }
+
+    /// Runs a git operation on the repository of the workspace, answers with
+    /// its result and sends the diff it left behind.
+    fn git_respond(&self, id: RequestId, f: impl FnOnce(&Path) -> Result<()>) {
+        let result = match self.workspace.lock().clone() {
+            Some(workspace) => f(&workspace),
+            None => Err(anyhow!("no workspace is opened")),
+        };
+        self.respond(id, result.map(|_| Value::Null));
+        self.update_diff_info();
+    }
+
+    /// Like `git_respond`, with the reason of a failure as the data of the
+    /// error.
+    fn git_branch_respond(
+        &self,
+        id: RequestId,
+        f: impl FnOnce(&Path) -> Result<(), GitBranchError>,
+    ) {
+        let result = match self.workspace.lock().clone() {
+            Some(workspace) => f(&workspace),
+            None => Err(GitBranchError::Git("no workspace is opened".to_string())),
+        };
+        let resp = match result {
+            Ok(()) => json!({ "id": id, "result": Value::Null }),
+            Err(e) => json!({
+                "id": id,
+                "error": {
+                    "code": 0,
+                    "message": e.to_string(),
+                    "data": e,
+                },
+            }),
+        };
+        let _ = self.sender.send(resp);
+        self.update_diff_info();
+    }
+
+    /// Runs a network operation of git on its own thread, showing its
+    /// progress under the title, then answers with its result and sends the
+    /// diff it left behind.
+    fn git_remote_respond(
+        &self,
+        id: RequestId,
+        title: &str,
+        f: impl FnOnce(&Path, RemoteCallbacks<'static>) -> Result<()> + Send + 'static,
+    ) {
+        let dispatcher = self.clone();
+        let title = title.to_string();
+        thread::spawn(move || {
+            let token = ProgressToken::String(format!("git_{id}"));
+            dispatcher.git_progress(
+                &token,
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title,
+                    cancellable: Some(false),
+                    message: None,
+                    percentage: None,
+                }),
+            );
+            let workspace = dispatcher.workspace.lock().clone();
+            let result = match workspace {
+                Some(workspace) => {
+                    f(&workspace, dispatcher.git_remote_callbacks(&token))
+                }
+                None => Err(anyhow!("no workspace is opened")),
+            };
+            dispatcher.git_progress(
+                &token,
+                WorkDoneProgress::End(WorkDoneProgressEnd { message: None }),
+            );
+            dispatcher.respond(id, result.map(|_| Value::Null));
+            dispatcher.update_diff_info();
+        });
+    }
+
+    fn git_progress(&self, token: &ProgressToken, progress: WorkDoneProgress) {
+        self.send_rpc_notification(CoreNotification::WorkDoneProgress {
+            progress: ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            },
+        });
+    }
+
+    /// Asks the user for the credentials of the remote, or for the passphrase
+    /// of the SSH key.
+    fn ask_git_credentials(
+        &self,
+        url: &str,
+        username: Option<&str>,
+        key: Option<&Path>,
+    ) -> Option<GitCredentials> {
+        self.rpc
+            .send_rpc_request_value(CoreRequest::GitCredentials {
+                url: url.to_string(),
+                username: username.map(|s| s.to_string()),
+                key: key.map(|key| key.to_path_buf()),
+            })
+            .ok()
+            .and_then(|value| {
+                serde_json::from_value::<Option<GitCredentials>>(value)
+                    .ok()
+                    .flatten()
+            })
+    }
+
+    /// The callbacks of a network operation of git, which report the objects
+    /// transferred and find the credentials: from the SSH agent, then the
+    /// keys in `~/.ssh`, or from the credential helpers, then the user.
+    fn git_remote_callbacks(
+        &self,
+        token: &ProgressToken,
+    ) -> RemoteCallbacks<'static> {
+        let mut callbacks = RemoteCallbacks::new();
+
+        let dispatcher = self.clone();
+        let mut tried_agent = false;
+        let mut ssh_keys: Option<std::vec::IntoIter<PathBuf>> = None;
+        let mut tried_helper = false;
+        let mut prompts = 0;
+        callbacks.credentials(move |url, username, allowed| {
+            if allowed.contains(CredentialType::USERNAME) {
+                return Cred::username(username.unwrap_or("git"));
+            }
+            if allowed.contains(CredentialType::SSH_KEY) {
+                let username = username.unwrap_or("git");
+                if !tried_agent {
+                    tried_agent = true;
+                    return Cred::ssh_key_from_agent(username);
+                }
+                // The agent didn't have the key the remote takes, so each of
+                // the keys ssh would look for is tried next.
+                if let Some(key) = ssh_keys.get_or_insert_with(git_ssh_keys).next() {
+                    let passphrase = if git_ssh_key_is_encrypted(&key) {
+                        match dispatcher.ask_git_credentials(
+                            url,
+                            Some(username),
+                            Some(&key),
+                        ) {
+                            Some(credentials) => Some(credentials.password),
+                            None => {
+                                return Err(git2::Error::from_str(
+                                    "no passphrase given",
+                                ))
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    return Cred::ssh_key(
+                        username,
+                        None,
+                        &key,
+                        passphrase.as_deref(),
                     );
-                    *self.last_diff.lock() = diff;
                 }
             }
-        }
+            if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+                if !tried_helper {
+                    tried_helper = true;
+                    if let Ok(cred) =
+                        git2::Config::open_default().and_then(|config| {
+                            Cred::credential_helper(&config, url, username)
+                        })
+                    {
+                        return Ok(cred);
+                    }
+                }
+                // Asked again when the remote turns them down, up to a
+                // point.
+                if prompts < 3 {
+                    prompts += 1;
+                    return match dispatcher.ask_git_credentials(url, username, None)
+                    {
+                        Some(credentials) => Cred::userpass_plaintext(
+                            &credentials.username,
+                            &credentials.password,
+                        ),
+                        None => Err(git2::Error::from_str("no credentials given")),
+                    };
+                }
+            }
+            Err(git2::Error::from_str("authentication failed"))
+        });
+
+        let dispatcher = self.clone();
+        let progress_token = token.clone();
+        let mut last_percentage = None;
+        callbacks.transfer_progress(move |stats| {
+            let (done, total, message) =
+                if stats.received_objects() < stats.total_objects() {
+                    (
+                        stats.received_objects(),
+                        stats.total_objects(),
+                        "Receiving objects",
+                    )
+                } else {
+                    (
+                        stats.indexed_deltas(),
+                        stats.total_deltas(),
+                        "Resolving deltas",
+                    )
+                };
+            let percentage = (done * 100).checked_div(total).unwrap_or(100) as u32;
+            if last_percentage != Some((message, percentage)) {
+                last_percentage = Some((message, percentage));
+                dispatcher.git_progress(
+                    &progress_token,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        cancellable: Some(false),
+                        message: Some(format!("{message} {done}/{total}")),
+                        percentage: Some(percentage),
+                    }),
+                );
+            }
+            true
+        });
+
+        let dispatcher = self.clone();
+        let progress_token = token.clone();
+        let mut last_percentage = None;
+        callbacks.push_transfer_progress(move |done, total, _bytes| {
+            let percentage = (done * 100).checked_div(total).unwrap_or(100) as u32;
+            if last_percentage != Some(percentage) {
+                last_percentage = Some(percentage);
+                dispatcher.git_progress(
+                    &progress_token,
+                    WorkDoneProgress::Report(WorkDoneProgressReport {
+                        cancellable: Some(false),
+                        message: Some(format!("Writing objects {done}/{total}")),
+                        percentage: Some(percentage),
+                    }),
+                );
+            }
+        });
+
+        callbacks
     }
 
     fn handle_notification(&self, rpc: ProxyNotification) {
         use ProxyNotification::*;
         match rpc {
-            Initialize { workspace } => {
+            Initialize { workspace, lsp } => {
                 *self.workspace.lock() = Some(workspace.clone());
+                self.lsp.lock().update_settings(lsp);
                 self.file_watcher.lock().as_mut().unwrap().watch(
                     &workspace,
                     true,
@@ -326,9 +654,10 @@ impl Dispatcher {
                 rev,
             } => {
                 let mut buffers = self.buffers.lock();
-                let buffer = buffers.get_mut(&buffer_id).unwrap();
-                if let Some(content_change) = buffer.update(&delta, rev) {
-                    self.lsp.lock().update(buffer, &content_change, buffer.rev);
+                if let Some(buffer) = buffers.get_mut(&buffer_id) {
+                    if let Some(content_change) = buffer.update(&delta, rev) {
+                        self.lsp.lock().update(buffer, &content_change, buffer.rev);
+                    }
                 }
             }
             InstallPlugin { plugin } => {
@@ -397,6 +726,45 @@ impl Dispatcher {
                     let _ = tx.send(Msg::Resize(size));
                 }
             }
+            RestartLspServers { buffer_id } => {
+                let buffers = self.buffers.lock();
+                let buffer = buffer_id.and_then(|buffer_id| buffers.get(&buffer_id));
+                self.lsp.lock().restart_servers(buffer);
+            }
+            RefreshSemanticStyles { buffer_id } => {
+                let buffers = self.buffers.lock();
+                if let Some(buffer) = buffers.get(&buffer_id) {
+                    self.lsp.lock().refresh_semantic_tokens(buffer);
+                }
+            }
+            ToggleLspTrace {} => {
+                self.lsp.lock().toggle_trace();
+            }
+            UpdateLspSettings { lsp } => {
+                self.lsp.lock().update_settings(lsp);
+                let buffer_ids: Vec<BufferId> =
+                    self.buffers.lock().keys().copied().collect();
+                for buffer_id in buffer_ids {
+                    self.open_buffer_in_lsp(buffer_id);
+                }
+            }
+            CloseBuffer { buffer_id } => {
+                let buffer = self.buffers.lock().remove(&buffer_id);
+                if let Some(buffer) = buffer {
+                    if let Some(path) = buffer.path.to_str() {
+                        let mut open_files = self.open_files.lock();
+                        if open_files.get(path) == Some(&buffer_id) {
+                            open_files.remove(path);
+                        }
+                    }
+                    self.file_watcher
+                        .lock()
+                        .as_mut()
+                        .unwrap()
+                        .unwatch(&buffer.path, OPEN_FILE_EVENT_TOKEN);
+                    self.lsp.lock().close_buffer(&buffer);
+                }
+            }
             GitCommit { message, diffs } => {
                 if let Some(workspace) = self.workspace.lock().clone() {
                     let _ = git_commit(&workspace, &message, diffs);
@@ -426,19 +794,20 @@ impl Dispatcher {
                     "id": id,
                     "result": resp,
                 }));
+                self.open_buffer_in_lsp(buffer_id);
             }
-            BufferHead { path, .. } => {
+            BufferHead { path, version, .. } => {
                 if let Some(workspace) = self.workspace.lock().clone() {
-                    let result = file_get_head(&workspace, &path);
-                    if let Ok((_blob_id, content)) = result {
-                        let resp = BufferHeadResponse {
-                            version: "head".to_string(),
-                            content,
-                        };
-                        let _ = self.sender.send(json!({
-                            "id": id,
-                            "result": resp,
-                        }));
+                    let result = file_get_head(&workspace, &path, &version);
+                    match result {
+                        Ok((_blob_id, content)) => {
+                            let resp = BufferHeadResponse { version, content };
+                            let _ = self.sender.send(json!({
+                                "id": id,
+                                "result": resp,
+                            }));
+                        }
+                        Err(e) => self.respond(id, Err(e)),
                     }
                 }
             }
@@ -447,75 +816,231 @@ impl Dispatcher {
                 position,
                 request_id,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp
-                    .lock()
-                    .get_completion(id, request_id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .get_completion(id, request_id, buffer, position);
+                });
             }
             CompletionResolve {
                 buffer_id,
                 completion_item,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp
-                    .lock()
-                    .completion_resolve(id, buffer, &completion_item);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .completion_resolve(id, buffer, &completion_item);
+                });
             }
             GetHover {
                 buffer_id,
                 position,
                 request_id,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_hover(id, request_id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_hover(id, request_id, buffer, position);
+                });
             }
             GetSignature {
                 buffer_id,
                 position,
+                trigger_character,
+                is_retrigger,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_signature(id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_signature(
+                        id,
+                        buffer,
+                        position,
+                        trigger_character,
+                        is_retrigger,
+                    );
+                });
             }
             GetReferences {
                 buffer_id,
                 position,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_references(id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_references(id, buffer, position);
+                });
             }
             GetDefinition {
                 buffer_id,
                 position,
                 request_id,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp
-                    .lock()
-                    .get_definition(id, request_id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .get_definition(id, request_id, buffer, position);
+                });
+            }
+            GetTypeDefinition {
+                buffer_id,
+                position,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_type_definition(id, buffer, position);
+                });
+            }
+            GetImplementation {
+                buffer_id,
+                position,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_implementation(id, buffer, position);
+                });
+            }
+            GetDeclaration {
+                buffer_id,
+                position,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_declaration(id, buffer, position);
+                });
             }
             GetCodeActions {
                 buffer_id,
                 position,
             } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_code_actions(id, buffer, position);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_code_actions(id, buffer, position);
+                });
+            }
+            ResolveCodeAction { action } => {
+                self.lsp.lock().resolve_code_action(id, &action);
+            }
+            GetCodeLens { buffer_id } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_code_lens(id, buffer);
+                });
+            }
+            ResolveCodeLens { code_lens } => {
+                self.lsp.lock().resolve_code_lens(id, &code_lens);
+            }
+            ExecuteCommand {
+                buffer_id,
+                command,
+                data,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().execute_command(id, buffer, &command, data);
+                });
+            }
+            GetDocumentHighlights {
+                buffer_id,
+                position,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .get_document_highlights(id, buffer, position);
+                });
+            }
+            GetInlayHints { buffer_id, range } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_inlay_hints(id, buffer, range);
+                });
+            }
+            GetSemanticTokensRange { buffer_id, range } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_semantic_tokens_range(id, buffer, range);
+                });
+            }
+            PrepareRename {
+                buffer_id,
+                position,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().prepare_rename(id, buffer, position);
+                });
+            }
+            Rename {
+                buffer_id,
+                position,
+                new_name,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().rename(id, buffer, position, new_name);
+                });
             }
             GetDocumentSymbols { buffer_id } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_document_symbols(id, buffer);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_document_symbols(id, buffer);
+                });
+            }
+            GetWorkspaceSymbols { query } => {
+                if self.lsp.lock().get_workspace_symbols(id, &query) {
+                    return;
+                }
+                match self.workspace.lock().clone() {
+                    Some(workspace) => {
+                        let local_dispatcher = self.clone();
+                        thread::spawn(move || {
+                            let symbols = {
+                                let mut index = local_dispatcher.symbols.lock();
+                                index.update(&workspace);
+                                index.query(&query)
+                            };
+                            local_dispatcher.respond(
+                                id,
+                                Ok(serde_json::to_value(symbols).unwrap()),
+                            );
+                        });
+                    }
+                    None => {
+                        self.respond(id, Ok(json!([])));
+                    }
+                }
+            }
+            PrepareHierarchy {
+                buffer_id,
+                position,
+                kind,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .prepare_hierarchy(id, buffer, position, kind);
+                });
+            }
+            GetHierarchyChildren { kind, item } => {
+                self.lsp.lock().get_hierarchy_children(id, kind, item);
             }
             GetDocumentFormatting { buffer_id } => {
-                let buffers = self.buffers.lock();
-                let buffer = buffers.get(&buffer_id).unwrap();
-                self.lsp.lock().get_document_formatting(id, buffer);
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_document_formatting(id, buffer);
+                });
+            }
+            GetFoldingRanges { buffer_id } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_folding_ranges(id, buffer);
+                });
+            }
+            GetSelectionRanges {
+                buffer_id,
+                positions,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_selection_ranges(id, buffer, positions);
+                });
+            }
+            GetRangeFormatting { buffer_id, ranges } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp.lock().get_range_formatting(id, buffer, ranges);
+                });
+            }
+            GetOnTypeFormatting {
+                buffer_id,
+                position,
+                ch,
+            } => {
+                self.with_buffer(id, buffer_id, |buffer| {
+                    self.lsp
+                        .lock()
+                        .get_on_type_formatting(id, buffer, position, &ch);
+                });
             }
             ReadDir { path } => {
                 let local_dispatcher = self.clone();
@@ -568,7 +1093,13 @@ impl Dispatcher {
             }
             Save { rev, buffer_id } => {
                 let mut buffers = self.buffers.lock();
-                let buffer = buffers.get_mut(&buffer_id).unwrap();
+                let buffer = match buffers.get_mut(&buffer_id) {
+                    Some(buffer) => buffer,
+                    None => {
+                        self.respond(id, Err(anyhow!("buffer not found")));
+                        return;
+                    }
+                };
                 let resp = buffer.save(rev).map(|_r| json!({}));
                 self.lsp.lock().save_buffer(buffer);
                 self.respond(id, resp);
@@ -584,7 +1115,8 @@ impl Dispatcher {
                 buffer.rope = Rope::from(content);
                 buffer.rev = rev;
                 let resp = buffer.save(rev).map(|_r| json!({}));
-                if resp.is_ok() {
+                let saved = resp.is_ok();
+                if saved {
                     self.buffers.lock().insert(buffer_id, buffer);
                     self.open_files
                         .lock()
@@ -592,6 +1124,147 @@ impl Dispatcher {
                     let _ = self.git_sender.send((buffer_id, 0));
                 }
                 self.respond(id, resp);
+                if saved {
+                    self.open_buffer_in_lsp(buffer_id);
+                }
+            }
+            GetLspLogNames {} => {
+                let names = self.lsp.lock().log_names();
+                self.respond(id, Ok(json!(names)));
+            }
+            GitStage { diffs } => {
+                self.git_respond(id, |workspace| git_stage(workspace, &diffs));
+            }
+            GitUnstage { diffs } => {
+                self.git_respond(id, |workspace| git_unstage(workspace, &diffs));
+            }
+            GitDiscard { diffs } => {
+                self.git_respond(id, |workspace| git_discard(workspace, &diffs));
+            }
+            GitStageHunk { path, line } => {
+                self.git_respond(id, |workspace| {
+                    git_stage_hunk(workspace, &path, line)
+                });
+            }
+            GitUnstageHunk { path, line } => {
+                self.git_respond(id, |workspace| {
+                    git_unstage_hunk(workspace, &path, line)
+                });
+            }
+            GitDiscardHunk { path, line } => {
+                self.git_respond(id, |workspace| {
+                    git_discard_hunk(workspace, &path, line)
+                });
+            }
+            GitCheckout { reference } => {
+                self.git_branch_respond(id, |workspace| {
+                    git_checkout(workspace, &reference)
+                });
+            }
+            GitCreateBranch {
+                name,
+                start_point,
+                checkout,
+            } => {
+                self.git_branch_respond(id, |workspace| {
+                    git_create_branch(
+                        workspace,
+                        &name,
+                        start_point.as_deref(),
+                        checkout,
+                    )
+                });
+            }
+            GitDeleteBranch { name, force } => {
+                self.git_branch_respond(id, |workspace| {
+                    git_delete_branch(workspace, &name, force)
+                });
+            }
+            GitRenameBranch { name, new_name } => {
+                self.git_branch_respond(id, |workspace| {
+                    git_rename_branch(workspace, &name, &new_name)
+                });
+            }
+            GitBlame { buffer_id } => {
+                let workspace = self.workspace.lock().clone();
+                let buffer = self
+                    .buffers
+                    .lock()
+                    .get(&buffer_id)
+                    .map(|buffer| (buffer.path.clone(), buffer.rope.clone()));
+                let dispatcher = self.clone();
+                // Blaming walks the history of the file, which can take a
+                // while.
+                thread::spawn(move || {
+                    let result = match (workspace, buffer) {
+                        (Some(workspace), Some((path, content))) => {
+                            git_blame(&workspace, &path, &content)
+                        }
+                        _ => Err(anyhow!("no file to blame")),
+                    };
+                    dispatcher.respond(id, result.map(|hunks| json!(hunks)));
+                });
+            }
+            GitFileHistory { path } => {
+                let workspace = self.workspace.lock().clone();
+                let dispatcher = self.clone();
+                thread::spawn(move || {
+                    let result = match workspace {
+                        Some(workspace) => git_file_history(&workspace, &path),
+                        None => Err(anyhow!("no workspace is opened")),
+                    };
+                    dispatcher.respond(id, result.map(|commits| json!(commits)));
+                });
+            }
+            GitStashSave {
+                message,
+                include_untracked,
+            } => {
+                self.git_respond(id, |workspace| {
+                    git_stash_save(workspace, message.as_deref(), include_untracked)
+                });
+            }
+            GitStashList {} => {
+                let result = match self.workspace.lock().clone() {
+                    Some(workspace) => git_stash_list(&workspace),
+                    None => Err(anyhow!("no workspace is opened")),
+                };
+                self.respond(id, result.map(|entries| json!(entries)));
+            }
+            GitStashApply { index, pop } => {
+                self.git_respond(id, |workspace| {
+                    git_stash_apply(workspace, index, pop)
+                });
+            }
+            GitStashDrop { index } => {
+                self.git_respond(id, |workspace| git_stash_drop(workspace, index));
+            }
+            GitFetch {} => {
+                self.git_remote_respond(id, "Fetching", |workspace, callbacks| {
+                    let repo = Repository::open(workspace)?;
+                    git_fetch(&repo, callbacks)
+                });
+            }
+            GitPull { rebase } => {
+                self.git_remote_respond(
+                    id,
+                    "Pulling",
+                    move |workspace, callbacks| {
+                        git_pull(workspace, rebase, callbacks)
+                    },
+                );
+            }
+            GitPush {} => {
+                self.git_remote_respond(id, "Pushing", git_push);
+            }
+            GetLspLog { name } => {
+                let result = self
+                    .lsp
+                    .lock()
+                    .log(&name)
+                    .map(|log| json!(log))
+                    .ok_or_else(|| anyhow!("no language server named {name}"));
+                self.respond(id, result);
             }
             GlobalSearch { pattern } => {
                 if let Some(workspace) = self.workspace.lock().clone() {
@@ -661,6 +1334,28 @@ fn git_commit(
             .ok_or_else(|| anyhow!("workspace path can't changed to str"))?,
     )?;
     let mut index = repo.index()?;
+    git_add_diffs(&mut index, workspace_path, &diffs)?;
+    index.write()?;
+    let tree = index.write_tree()?;
+    let tree = repo.find_tree(tree)?;
+    let signature = repo.signature()?;
+    let parent = repo.head()?.peel_to_commit()?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &[&parent],
+    )?;
+    Ok(())
+}
+
+fn git_add_diffs(
+    index: &mut git2::Index,
+    workspace_path: &Path,
+    diffs: &[FileDiff],
+) -> Result<()> {
     for diff in diffs {
         match diff {
             FileDiff::Modified(p) | FileDiff::Added(p) => {
@@ -675,22 +1370,824 @@ fn git_commit(
             }
         }
     }
+    Ok(())
+}
+
+/// The paths in the repository of the files of the diffs, with both sides
+/// of a rename.
+fn git_diff_paths(
+    workspace_path: &Path,
+    diffs: &[FileDiff],
+) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for diff in diffs {
+        if let FileDiff::Renamed(_, d) = diff {
+            paths.push(d.strip_prefix(workspace_path)?.to_path_buf());
+        }
+        paths.push(diff.path().strip_prefix(workspace_path)?.to_path_buf());
+    }
+    Ok(paths)
+}
+
+fn git_stage(workspace_path: &Path, diffs: &[FileDiff]) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let mut index = repo.index()?;
+    git_add_diffs(&mut index, workspace_path, diffs)?;
+    index.write()?;
+    Ok(())
+}
+
+fn git_unstage(workspace_path: &Path, diffs: &[FileDiff]) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let paths = git_diff_paths(workspace_path, diffs)?;
+    match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => {
+            repo.reset_default(
+                Some(commit.as_object()),
+                paths.iter().map(|p| p.as_path()),
+            )?;
+        }
+        // Nothing is committed yet, so the files are taken out of the index.
+        Err(_) => {
+            let mut index = repo.index()?;
+            for path in paths.iter() {
+                index.remove_path(path)?;
+            }
+            index.write()?;
+        }
+    }
+    Ok(())
+}
+
+fn git_discard(workspace_path: &Path, diffs: &[FileDiff]) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let index = repo.index()?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout.force();
+    let mut checkout_paths = false;
+    for path in git_diff_paths(workspace_path, diffs)? {
+        if index.get_path(&path, 0).is_some() {
+            checkout.path(path.as_path());
+            checkout_paths = true;
+        } else {
+            // An untracked file has nothing to go back to.
+            let path = workspace_path.join(path);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    // Without any path the whole index would be checked out.
+    if checkout_paths {
+        repo.checkout_index(None, Some(&mut checkout))?;
+    }
+    Ok(())
+}
+
+fn git_stage_hunk(workspace_path: &Path, path: &Path, line: usize) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let mut index = repo.index()?;
+    let entry = match index.get_path(path.strip_prefix(workspace_path)?, 0) {
+        Some(entry) => entry,
+        // A new file is staged as a whole.
+        None => {
+            return git_stage(workspace_path, &[FileDiff::Added(path.to_path_buf())])
+        }
+    };
+    let staged = git_blob_rope(&repo, entry.id)?;
+    let working = Rope::from(fs::read_to_string(path)?);
+    let (old, new) = git_hunk_at(&staged, &working, line)?;
+    let content = splice_lines(&staged, old, &working, new);
+    git_index_add_content(&mut index, entry, &content)?;
     index.write()?;
-    let tree = index.write_tree()?;
-    let tree = repo.find_tree(tree)?;
+    Ok(())
+}
+
+fn git_unstage_hunk(workspace_path: &Path, path: &Path, line: usize) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let repo_path = path.strip_prefix(workspace_path)?;
+    let mut index = repo.index()?;
+    let entry = index
+        .get_path(repo_path, 0)
+        .ok_or_else(|| anyhow!("{} isn't staged", repo_path.display()))?;
+    let head = match repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .and_then(|tree| tree.get_path(repo_path))
+    {
+        Ok(tree_entry) => git_blob_rope(&repo, tree_entry.id())?,
+        // A file that's new in the index is unstaged as a whole.
+        Err(_) => {
+            return git_unstage(
+                workspace_path,
+                &[FileDiff::Added(path.to_path_buf())],
+            )
+        }
+    };
+    let staged = git_blob_rope(&repo, entry.id)?;
+    let working = Rope::from(fs::read_to_string(path)?);
+    // The line is in the working file, so it's found in the staged one
+    // first.
+    let line = old_line(&git_hunks(&staged, &working), line);
+    let (old, new) = git_hunk_at(&head, &staged, line)?;
+    let content = splice_lines(&staged, new, &head, old);
+    git_index_add_content(&mut index, entry, &content)?;
+    index.write()?;
+    Ok(())
+}
+
+fn git_discard_hunk(workspace_path: &Path, path: &Path, line: usize) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let repo_path = path.strip_prefix(workspace_path)?;
+    let entry = repo
+        .index()?
+        .get_path(repo_path, 0)
+        .ok_or_else(|| anyhow!("{} is untracked", repo_path.display()))?;
+    let staged = git_blob_rope(&repo, entry.id)?;
+    let working = Rope::from(fs::read_to_string(path)?);
+    let (old, new) = git_hunk_at(&staged, &working, line)?;
+    fs::write(path, splice_lines(&working, new, &staged, old))?;
+    Ok(())
+}
+
+fn git_commit_info(commit: &Commit) -> CommitInfo {
+    CommitInfo {
+        id: commit.id().to_string(),
+        author: commit.author().name().unwrap_or("").to_string(),
+        time: commit.time().seconds(),
+        summary: commit.summary().unwrap_or("").to_string(),
+    }
+}
+
+/// Blames the file as committed at the head, and carries the commits over
+/// to the lines of `content` that are the same.
+fn git_blame(
+    workspace_path: &Path,
+    path: &Path,
+    content: &Rope,
+) -> Result<Vec<BlameHunk>> {
+    let repo = Repository::open(workspace_path)?;
+    let repo_path = path.strip_prefix(workspace_path)?;
+    let entry = match repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .and_then(|tree| tree.get_path(repo_path))
+    {
+        Ok(entry) => entry,
+        // None of the lines of a file that's new since the head are
+        // committed.
+        Err(_) => {
+            return Ok(vec![BlameHunk {
+                line: 0,
+                lines: content.line_of_offset(content.len()) + 1,
+                commit: None,
+            }])
+        }
+    };
+    let committed = git_blob_rope(&repo, entry.id())?;
+
+    let blame = repo.blame_file(repo_path, None)?;
+    let mut commits = HashMap::new();
+    let mut committed_lines =
+        vec![None; committed.line_of_offset(committed.len()) + 1];
+    for hunk in blame.iter() {
+        let id = hunk.final_commit_id();
+        if let Entry::Vacant(entry) = commits.entry(id) {
+            entry.insert(git_commit_info(&repo.find_commit(id)?));
+        }
+        let start = hunk.final_start_line().saturating_sub(1);
+        for line in committed_lines
+            .iter_mut()
+            .skip(start)
+            .take(hunk.lines_in_hunk())
+        {
+            *line = Some(id);
+        }
+    }
+
+    let changes =
+        rope_diff(committed, content.clone(), 0, Arc::new(AtomicU64::new(0)))
+            .unwrap_or_default();
+    let mut hunks: Vec<BlameHunk> = Vec::new();
+    let mut push = |line: usize, id: Option<git2::Oid>| {
+        let commit = id.and_then(|id| commits.get(&id)).cloned();
+        match hunks.last_mut() {
+            Some(hunk)
+                if hunk.line + hunk.lines == line && hunk.commit == commit =>
+            {
+                hunk.lines += 1;
+            }
+            _ => hunks.push(BlameHunk {
+                line,
+                lines: 1,
+                commit,
+            }),
+        }
+    };
+    for change in changes {
+        match change {
+            DiffLines::Left(_) => {}
+            DiffLines::Both(l, r) | DiffLines::Skip(l, r) => {
+                for (old, new) in l.zip(r) {
+                    push(new, committed_lines.get(old).copied().flatten());
+                }
+            }
+            DiffLines::Right(r) => {
+                for new in r {
+                    push(new, None);
+                }
+            }
+        }
+    }
+    Ok(hunks)
+}
+
+/// The commits from the head that changed the file, the ones the same as a
+/// parent for it left out, like `git log` does. Only the latest commits are
+/// looked at, for a long history not to hold up the answer.
+fn git_file_history(workspace_path: &Path, path: &Path) -> Result<Vec<CommitInfo>> {
+    let repo = Repository::open(workspace_path)?;
+    let repo_path = path.strip_prefix(workspace_path)?;
+    let entry_id = |commit: &Commit| {
+        commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.get_path(repo_path).ok())
+            .map(|entry| entry.id())
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    let mut commits = Vec::new();
+    for id in revwalk.take(GIT_FILE_HISTORY_DEPTH) {
+        let commit = repo.find_commit(id?)?;
+        let id = entry_id(&commit);
+        let changed = if commit.parent_count() == 0 {
+            id.is_some()
+        } else {
+            commit.parents().all(|parent| entry_id(&parent) != id)
+        };
+        if changed {
+            commits.push(git_commit_info(&commit));
+        }
+    }
+    Ok(commits)
+}
+
+fn git_branch_error(e: git2::Error) -> GitBranchError {
+    GitBranchError::Git(e.message().to_string())
+}
+
+/// Refuses to go on when tracked files have changes that a checkout could
+/// lose.
+fn git_check_worktree(
+    repo: &Repository,
+    workspace_path: &Path,
+) -> Result<(), GitBranchError> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(git_branch_error)?;
+    let paths: Vec<PathBuf> = statuses
+        .iter()
+        .filter(|entry| entry.status() != Status::CURRENT)
+        .filter_map(|entry| entry.path().map(|p| workspace_path.join(p)))
+        .collect();
+    if !paths.is_empty() {
+        return Err(GitBranchError::DirtyWorktree(paths));
+    }
+    Ok(())
+}
+
+fn git_checkout_reference(
+    repo: &Repository,
+    reference: &Reference,
+) -> Result<(), GitBranchError> {
+    let commit = reference.peel_to_commit().map_err(git_branch_error)?;
+    repo.checkout_tree(commit.as_object(), None)
+        .map_err(git_branch_error)?;
+    let name = reference
+        .name()
+        .ok_or_else(|| GitBranchError::Git("invalid branch name".to_string()))?;
+    repo.set_head(name).map_err(git_branch_error)
+}
+
+fn git_checkout(
+    workspace_path: &Path,
+    reference: &str,
+) -> Result<(), GitBranchError> {
+    let repo = Repository::open(workspace_path).map_err(git_branch_error)?;
+    git_check_worktree(&repo, workspace_path)?;
+
+    if let Ok(branch) = repo.find_branch(reference, BranchType::Local) {
+        return git_checkout_reference(&repo, branch.get());
+    }
+
+    if let Ok(remote) = repo.find_branch(reference, BranchType::Remote) {
+        // "origin/feature" is checked out as "feature", tracking it.
+        let name = reference
+            .split_once('/')
+            .map(|(_, name)| name)
+            .unwrap_or(reference);
+        if let Ok(branch) = repo.find_branch(name, BranchType::Local) {
+            return git_checkout_reference(&repo, branch.get());
+        }
+        let commit = remote.get().peel_to_commit().map_err(git_branch_error)?;
+        let mut branch = repo
+            .branch(name, &commit, false)
+            .map_err(git_branch_error)?;
+        branch
+            .set_upstream(Some(reference))
+            .map_err(git_branch_error)?;
+        return git_checkout_reference(&repo, branch.get());
+    }
+
+    let commit = repo
+        .revparse_single(reference)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| GitBranchError::BranchNotFound(reference.to_string()))?;
+    repo.checkout_tree(commit.as_object(), None)
+        .map_err(git_branch_error)?;
+    repo.set_head_detached(commit.id())
+        .map_err(git_branch_error)
+}
+
+fn git_create_branch(
+    workspace_path: &Path,
+    name: &str,
+    start_point: Option<&str>,
+    checkout: bool,
+) -> Result<(), GitBranchError> {
+    let repo = Repository::open(workspace_path).map_err(git_branch_error)?;
+    if repo.find_branch(name, BranchType::Local).is_ok() {
+        return Err(GitBranchError::BranchExists(name.to_string()));
+    }
+
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(git_branch_error)?;
+    let commit = match start_point {
+        Some(start_point) => repo
+            .revparse_single(start_point)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| GitBranchError::BranchNotFound(start_point.to_string()))?,
+        None => head.clone(),
+    };
+    // A branch at the head keeps the working files as they are, changes
+    // included, the others need a clean worktree to be checked out.
+    let switch_files = checkout && commit.id() != head.id();
+    if switch_files {
+        git_check_worktree(&repo, workspace_path)?;
+    }
+
+    let branch = repo
+        .branch(name, &commit, false)
+        .map_err(git_branch_error)?;
+    if switch_files {
+        git_checkout_reference(&repo, branch.get())?;
+    } else if checkout {
+        let name = branch
+            .get()
+            .name()
+            .ok_or_else(|| GitBranchError::Git("invalid branch name".to_string()))?;
+        repo.set_head(name).map_err(git_branch_error)?;
+    }
+    Ok(())
+}
+
+fn git_delete_branch(
+    workspace_path: &Path,
+    name: &str,
+    force: bool,
+) -> Result<(), GitBranchError> {
+    let repo = Repository::open(workspace_path).map_err(git_branch_error)?;
+    let mut branch = repo
+        .find_branch(name, BranchType::Local)
+        .map_err(|_| GitBranchError::BranchNotFound(name.to_string()))?;
+    if branch.is_head() {
+        return Err(GitBranchError::CurrentBranch(name.to_string()));
+    }
+
+    if !force {
+        // Like git, a branch is merged when the head or its upstream has
+        // its commits.
+        let tip = branch
+            .get()
+            .peel_to_commit()
+            .map_err(git_branch_error)?
+            .id();
+        let contains = |reference: Reference| match reference.peel_to_commit() {
+            Ok(commit) => {
+                commit.id() == tip
+                    || repo.graph_descendant_of(commit.id(), tip).unwrap_or(false)
+            }
+            Err(_) => false,
+        };
+        let merged = repo.head().map(contains).unwrap_or(false)
+            || branch
+                .upstream()
+                .map(|upstream| contains(upstream.into_reference()))
+                .unwrap_or(false);
+        if !merged {
+            return Err(GitBranchError::BranchNotMerged(name.to_string()));
+        }
+    }
+
+    branch.delete().map_err(git_branch_error)
+}
+
+fn git_rename_branch(
+    workspace_path: &Path,
+    name: &str,
+    new_name: &str,
+) -> Result<(), GitBranchError> {
+    let repo = Repository::open(workspace_path).map_err(git_branch_error)?;
+    let mut branch = repo
+        .find_branch(name, BranchType::Local)
+        .map_err(|_| GitBranchError::BranchNotFound(name.to_string()))?;
+    if repo.find_branch(new_name, BranchType::Local).is_ok() {
+        return Err(GitBranchError::BranchExists(new_name.to_string()));
+    }
+    branch.rename(new_name, false).map_err(git_branch_error)?;
+    Ok(())
+}
+
+fn git_stash_save(
+    workspace_path: &Path,
+    message: Option<&str>,
+    include_untracked: bool,
+) -> Result<()> {
+    let mut repo = Repository::open(workspace_path)?;
     let signature = repo.signature()?;
-    let parent = repo.head()?.peel_to_commit()?;
-    repo.commit(
-        Some("HEAD"),
-        &signature,
-        &signature,
-        message,
-        &tree,
-        &[&parent],
-    )?;
+    let flags = if include_untracked {
+        StashFlags::INCLUDE_UNTRACKED
+    } else {
+        StashFlags::DEFAULT
+    };
+    repo.stash_save2(&signature, message, Some(flags))?;
+    Ok(())
+}
+
+fn git_stash_list(workspace_path: &Path) -> Result<Vec<StashEntry>> {
+    let mut repo = Repository::open(workspace_path)?;
+    let mut entries = Vec::new();
+    repo.stash_foreach(|index, message, id| {
+        entries.push(StashEntry {
+            index,
+            message: message.to_string(),
+            id: id.to_string(),
+        });
+        true
+    })?;
+    Ok(entries)
+}
+
+fn git_stash_apply(workspace_path: &Path, index: usize, pop: bool) -> Result<()> {
+    let mut repo = Repository::open(workspace_path)?;
+    // What was staged when stashing is staged again.
+    let mut options = StashApplyOptions::new();
+    options.reinstantiate_index();
+    if pop {
+        repo.stash_pop(index, Some(&mut options))?;
+    } else {
+        repo.stash_apply(index, Some(&mut options))?;
+    }
+    Ok(())
+}
+
+fn git_stash_drop(workspace_path: &Path, index: usize) -> Result<()> {
+    let mut repo = Repository::open(workspace_path)?;
+    repo.stash_drop(index)?;
+    Ok(())
+}
+
+/// The current branch, which has to be there for pulling and pushing
+fn git_current_branch(repo: &Repository) -> Result<String> {
+    let head = repo.head()?;
+    if !head.is_branch() {
+        return Err(anyhow!("the head is detached, check out a branch first"));
+    }
+    head.shorthand()
+        .map(|name| name.to_string())
+        .ok_or_else(|| anyhow!("invalid branch name"))
+}
+
+/// The remote of the upstream of the current branch, or "origin"
+fn git_remote_name(repo: &Repository) -> String {
+    repo.head()
+        .ok()
+        .and_then(|head| {
+            let name = head.name()?;
+            let remote = repo.branch_upstream_remote(name).ok()?;
+            remote.as_str().map(|remote| remote.to_string())
+        })
+        .unwrap_or_else(|| "origin".to_string())
+}
+
+fn git_fetch(repo: &Repository, callbacks: RemoteCallbacks) -> Result<()> {
+    let mut remote = repo.find_remote(&git_remote_name(repo))?;
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    // Without refspecs, the ones configured for the remote are fetched.
+    remote.fetch::<&str>(&[], Some(&mut options), None)?;
     Ok(())
 }
 
+fn git_pull(
+    workspace_path: &Path,
+    rebase: bool,
+    callbacks: RemoteCallbacks,
+) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let name = git_current_branch(&repo)?;
+    git_fetch(&repo, callbacks)?;
+
+    let branch = repo.find_branch(&name, BranchType::Local)?;
+    let upstream = branch
+        .upstream()
+        .map_err(|_| anyhow!("the branch '{}' has no upstream", name))?;
+    let upstream = repo.reference_to_annotated_commit(upstream.get())?;
+    let (analysis, _) = repo.merge_analysis(&[&upstream])?;
+    if analysis.is_up_to_date() {
+        return Ok(());
+    }
+
+    if analysis.is_fast_forward() {
+        // The safe checkout fails rather than overwrite changed files.
+        let target = repo.find_object(upstream.id(), None)?;
+        repo.checkout_tree(&target, None)?;
+        branch
+            .into_reference()
+            .set_target(upstream.id(), "pull: fast-forward")?;
+        return Ok(());
+    }
+
+    if !rebase {
+        return Err(anyhow!(
+            "the branch '{}' and its upstream have diverged, pull with rebase instead",
+            name
+        ));
+    }
+    git_check_worktree(&repo, workspace_path).map_err(|e| anyhow!(e.to_string()))?;
+    let head = repo.reference_to_annotated_commit(&repo.head()?)?;
+    let signature = repo.signature()?;
+    let mut rebase = repo.rebase(Some(&head), Some(&upstream), None, None)?;
+    while let Some(operation) = rebase.next() {
+        if let Err(e) = operation {
+            rebase.abort()?;
+            return Err(e.into());
+        }
+        if repo.index()?.has_conflicts() {
+            rebase.abort()?;
+            return Err(anyhow!(
+                "rebasing on the upstream ran into conflicts, nothing was changed"
+            ));
+        }
+        match rebase.commit(None, &signature, None) {
+            // The upstream already has the change of the commit.
+            Err(e) if e.code() == ErrorCode::Applied => {}
+            Err(e) => {
+                rebase.abort()?;
+                return Err(e.into());
+            }
+            Ok(_) => {}
+        }
+    }
+    rebase.finish(Some(&signature))?;
+    Ok(())
+}
+
+fn git_push(workspace_path: &Path, mut callbacks: RemoteCallbacks) -> Result<()> {
+    let repo = Repository::open(workspace_path)?;
+    let name = git_current_branch(&repo)?;
+    let mut branch = repo.find_branch(&name, BranchType::Local)?;
+    let local_ref = format!("refs/heads/{name}");
+    let (remote_name, remote_ref, has_upstream) = match repo
+        .branch_upstream_remote(&local_ref)
+        .ok()
+        .zip(branch.upstream().ok())
+    {
+        Some((remote, upstream)) => {
+            let remote = remote.as_str().unwrap_or("origin").to_string();
+            // "origin/feature" is "refs/heads/feature" on the remote.
+            let merge = upstream
+                .name()?
+                .and_then(|name| name.strip_prefix(&format!("{}/", remote)))
+                .map(|name| format!("refs/heads/{}", name))
+                .unwrap_or_else(|| local_ref.clone());
+            (remote, merge, true)
+        }
+        None => ("origin".to_string(), local_ref.clone(), false),
+    };
+
+    let rejected = Rc::new(RefCell::new(None));
+    let push_rejected = rejected.clone();
+    callbacks.push_update_reference(move |refname, status| {
+        if let Some(status) = status {
+            *push_rejected.borrow_mut() = Some(format!("{refname}: {status}"));
+        }
+        Ok(())
+    });
+    let mut remote = repo.find_remote(&remote_name)?;
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    remote.push(&[format!("{local_ref}:{remote_ref}")], Some(&mut options))?;
+    if let Some(rejected) = rejected.take() {
+        return Err(anyhow!("the remote rejected {}", rejected));
+    }
+
+    if !has_upstream {
+        let upstream = format!("{}/{}", remote_name, name);
+        branch.set_upstream(Some(&upstream))?;
+    }
+    Ok(())
+}
+
+/// The private keys in `~/.ssh` named like the ones ssh looks for, like
+/// `id_ed25519` or `id_rsa`.
+fn git_ssh_keys() -> std::vec::IntoIter<PathBuf> {
+    let mut keys: Vec<PathBuf> = BaseDirs::new()
+        .and_then(|dirs| fs::read_dir(dirs.home_dir().join(".ssh")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str());
+            match name {
+                Some(name) => name.starts_with("id_") && !name.ends_with(".pub"),
+                None => false,
+            }
+        })
+        .filter(|path| path.is_file())
+        .collect();
+    keys.sort();
+    keys.into_iter()
+}
+
+/// Whether the private key takes a passphrase, which is when it's a PEM key
+/// marked as encrypted, or an OpenSSH key with a cipher.
+fn git_ssh_key_is_encrypted(key: &Path) -> bool {
+    let content = match fs::read_to_string(key) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    if content.contains("ENCRYPTED") {
+        return true;
+    }
+    let body: String = content
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let bytes = match base64::decode(body) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+    // The magic of the format, then the name of the cipher as its length
+    // and its bytes
+    let rest = match bytes.strip_prefix(b"openssh-key-v1\0") {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let len = match rest.get(..4) {
+        Some(len) => u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize,
+        None => return false,
+    };
+    match rest.get(4..4 + len) {
+        Some(cipher) => cipher != b"none",
+        None => false,
+    }
+}
+
+fn git_blob_rope(repo: &Repository, id: git2::Oid) -> Result<Rope> {
+    let blob = repo.find_blob(id)?;
+    let content = std::str::from_utf8(blob.content())
+        .with_context(|| "content bytes to string")?;
+    Ok(Rope::from(content))
+}
+
+/// Stages the content in place of the entry. The stat data of the entry is
+/// the one of the working file, which the content isn't the same as anymore,
+/// so it's cleared for git to compare the file again instead of taking it
+/// for what's staged.
+fn git_index_add_content(
+    index: &mut git2::Index,
+    mut entry: git2::IndexEntry,
+    content: &str,
+) -> Result<()> {
+    entry.ctime = git2::IndexTime::new(0, 0);
+    entry.mtime = git2::IndexTime::new(0, 0);
+    entry.dev = 0;
+    entry.ino = 0;
+    entry.uid = 0;
+    entry.gid = 0;
+    entry.file_size = 0;
+    index.add_frombuffer(&entry, content.as_bytes())?;
+    Ok(())
+}
+
+/// The changed lines between two texts, as the range of lines in the old one
+/// and the range of lines that took their place in the new one.
+fn git_hunks(old: &Rope, new: &Rope) -> Vec<(Range<usize>, Range<usize>)> {
+    let changes =
+        rope_diff(old.clone(), new.clone(), 0, Arc::new(AtomicU64::new(0)))
+            .unwrap_or_default();
+    let mut hunks = Vec::new();
+    let mut hunk: Option<(Range<usize>, Range<usize>)> = None;
+    let mut old_line = 0;
+    let mut new_line = 0;
+    for change in changes {
+        match change {
+            DiffLines::Left(r) => {
+                hunk.get_or_insert((old_line..old_line, new_line..new_line))
+                    .0
+                    .end = r.end;
+                old_line = r.end;
+            }
+            DiffLines::Right(r) => {
+                hunk.get_or_insert((old_line..old_line, new_line..new_line))
+                    .1
+                    .end = r.end;
+                new_line = r.end;
+            }
+            DiffLines::Both(l, r) | DiffLines::Skip(l, r) => {
+                hunks.extend(hunk.take());
+                old_line = l.end;
+                new_line = r.end;
+            }
+        }
+    }
+    hunks.extend(hunk.take());
+    hunks
+}
+
+/// The hunk with the line of the new text in it, or right at the line for
+/// lines that were only deleted.
+fn git_hunk_at(
+    old: &Rope,
+    new: &Rope,
+    line: usize,
+) -> Result<(Range<usize>, Range<usize>)> {
+    git_hunks(old, new)
+        .into_iter()
+        .find(|(_, new)| {
+            new.contains(&line) || (new.is_empty() && new.start == line)
+        })
+        .ok_or_else(|| anyhow!("there's no change at line {}", line + 1))
+}
+
+/// The line of the old text where the line of the new text was.
+fn old_line(hunks: &[(Range<usize>, Range<usize>)], line: usize) -> usize {
+    let mut last = (0, 0);
+    for (old, new) in hunks {
+        if line < new.start {
+            break;
+        }
+        if line < new.end {
+            return old.start;
+        }
+        last = (old.end, new.end);
+    }
+    last.0 + (line - last.1)
+}
+
+/// The text with its lines in the range replaced by the lines of the other
+/// text in the other range.
+fn splice_lines(
+    text: &Rope,
+    range: Range<usize>,
+    other: &Rope,
+    other_range: Range<usize>,
+) -> String {
+    let lines: Vec<Cow<str>> = text.lines_raw(..).collect();
+    let other_lines: Vec<Cow<str>> = other.lines_raw(..).collect();
+    let line_ending = match lines
+        .iter()
+        .chain(other_lines.iter())
+        .find(|line| line.ends_with('\n'))
+    {
+        Some(line) if line.ends_with("\r\n") => "\r\n",
+        _ => "\n",
+    };
+    let mut content = String::new();
+    for line in lines[..range.start]
+        .iter()
+        .chain(other_lines[other_range].iter())
+        .chain(lines[range.end..].iter())
+    {
+        // The last line of a text might have lost its line ending, which
+        // gets the one the text ends its other lines with.
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push_str(line_ending);
+        }
+        content.push_str(line);
+    }
+    content
+}
+
 fn git_delta_format(
     workspace_path: &Path,
     delta: &git2::DiffDelta,
@@ -721,20 +2218,27 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
     let name = head.shorthand()?.to_string();
 
     let mut branches = Vec::new();
+    let mut remote_branches = Vec::new();
     for branch in repo.branches(None).ok()? {
-        branches.push(branch.ok()?.0.name().ok()??.to_string());
+        let (branch, branch_type) = branch.ok()?;
+        let name = branch.name().ok()??.to_string();
+        if branch_type == BranchType::Remote {
+            remote_branches.push(name.clone());
+        }
+        branches.push(name);
     }
 
-    let mut deltas = Vec::new();
+    let mut unstaged = Vec::new();
     let mut diff_options = DiffOptions::new();
     let diff = repo
         .diff_index_to_workdir(None, Some(diff_options.include_untracked(true)))
         .ok()?;
     for delta in diff.deltas() {
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
-            deltas.push(delta);
+            unstaged.push(delta);
         }
     }
+    let mut staged = Vec::new();
     let cached_diff = repo
         .diff_tree_to_index(
             repo.find_tree(repo.revparse_single("HEAD^{tree}").ok()?.id())
@@ -746,9 +2250,24 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         .ok()?;
     for delta in cached_diff.deltas() {
         if let Some(delta) = git_delta_format(workspace_path, &delta) {
-            deltas.push(delta);
+            staged.push(delta);
         }
     }
+    let deltas: Vec<_> = unstaged.iter().chain(staged.iter()).cloned().collect();
+
+    Some(DiffInfo {
+        head: name,
+        branches,
+        remote_branches,
+        diffs: git_file_diffs(&deltas),
+        staged: git_file_diffs(&staged),
+        unstaged: git_file_diffs(&unstaged),
+    })
+}
+
+/// The file diffs of the deltas, where a file that was added with the same
+/// content as a deleted one is a rename of it.
+fn git_file_diffs(deltas: &[(git2::Delta, git2::Oid, PathBuf)]) -> Vec<FileDiff> {
     let mut renames = Vec::new();
     let mut renamed_deltas = HashSet::new();
 
@@ -790,26 +2309,42 @@ fn git_diff_new(workspace_path: &Path) -> Option<DiffInfo> {
         | FileDiff::Renamed(p, _)
         | FileDiff::Deleted(p) => p.clone(),
     });
-    Some(DiffInfo {
-        head: name,
-        branches,
-        diffs: file_diffs,
-    })
+    file_diffs
 }
 
-fn file_get_head(workspace_path: &Path, path: &Path) -> Result<(String, String)> {
+fn file_get_head(
+    workspace_path: &Path,
+    path: &Path,
+    version: &str,
+) -> Result<(String, String)> {
     let repo = Repository::open(
         workspace_path
             .to_str()
             .ok_or_else(|| anyhow!("can't to str"))?,
     )?;
-    let head = repo.head()?;
-    let tree = head.peel_to_tree()?;
-    let tree_entry = tree.get_path(path.strip_prefix(workspace_path)?)?;
-    let blob = repo.find_blob(tree_entry.id())?;
+    let repo_path = path.strip_prefix(workspace_path)?;
+    let blob_id = match version {
+        "index" => {
+            repo.index()?
+                .get_path(repo_path, 0)
+                .ok_or_else(|| anyhow!("{} isn't staged", repo_path.display()))?
+                .id
+        }
+        _ => {
+            let tree = match version {
+                "head" => repo.head()?.peel_to_tree()?,
+                _ => repo.revparse_single(version)?.peel_to_tree()?,
+            };
+            tree.get_path(repo_path)?.id()
+        }
+    };
+    let blob = repo.find_blob(blob_id)?;
     let id = blob.id().to_string();
     let content = std::str::from_utf8(blob.content())
         .with_context(|| "content bytes to string")?
         .to_string();
     Ok((id, content))
 }
+
+#[cfg(test)]
+mod test;
@@ -2,6 +2,7 @@ pub mod buffer;
 pub mod dispatch;
 pub mod lsp;
 pub mod plugin;
+pub mod symbols;
 pub mod terminal;
 pub mod watcher;
 
@@ -1,34 +1,56 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     io::BufRead,
     io::{BufReader, BufWriter, Write},
-    process::{self, Child, ChildStdout, Command, Stdio},
-    sync::{mpsc::channel, Arc},
+    path::{Path, PathBuf},
+    process::{self, Child, ChildStderr, ChildStdout, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::channel,
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
 use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
 use jsonrpc_lite::{Id, JsonRpc, Params};
 use lapce_rpc::{
     buffer::BufferId,
+    core::{CoreNotification, CoreRequest, LspStatus},
+    proxy::{HierarchyKind, InlayHint, LspSettings, TaggedData},
     style::{LineStyle, Style},
     RequestId,
 };
 use lsp_types::*;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, to_value, Value};
 
-use crate::buffer::Buffer;
+use crate::buffer::{language_id_from_path, Buffer};
 use crate::dispatch::Dispatcher;
 
 pub type Callback = Box<dyn Callable>;
 const HEADER_CONTENT_LENGTH: &str = "content-length";
 const HEADER_CONTENT_TYPE: &str = "content-type";
 
+/// How long a server waits before its first restart after a crash
+const LSP_RESTART_BACKOFF: Duration = Duration::from_millis(500);
+/// The number of crashes in a row after which a server is given up on
+const LSP_MAX_RESTARTS: u32 = 5;
+/// A server that stayed up that long no longer counts its earlier crashes
+const LSP_STABLE_DURATION: Duration = Duration::from_secs(60);
+const LSP_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+/// The number of entries kept in the log of a server, the oldest ones are
+/// dropped first
+const LSP_LOG_CAPACITY: usize = 2000;
+/// How long after the last change of a document its diagnostics are pulled
+const LSP_DIAGNOSTIC_PULL_DELAY: Duration = Duration::from_millis(300);
+
 pub trait Callable: Send {
     fn call(self: Box<Self>, client: &LspClient, result: Result<Value>);
 }
@@ -41,120 +63,562 @@ impl<F: Send + FnOnce(&LspClient, Result<Value>)> Callable for F {
 
 pub struct LspCatalog {
     pub dispatcher: Option<Dispatcher>,
-    clients: HashMap<String, Arc<LspClient>>,
+    clients: IndexMap<LspServerId, Arc<LspClient>>,
+    /// The servers being initialized, which join `clients` once they are
+    starting: HashSet<LspServerId>,
+    diagnostics: DiagnosticsStore,
+    /// The servers from the settings, by language id
+    settings: HashMap<String, LspSettings>,
+    /// If the messages exchanged with the servers go to their logs too
+    trace: Arc<AtomicBool>,
+}
+
+/// Identifies a language server. The same executable gets its own client
+/// for every language and workspace folder it is started for.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LspServerId {
+    pub exec_path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub language_id: String,
+    pub root: Option<PathBuf>,
+}
+
+impl LspServerId {
+    /// The name shown to the user: the executable, and the workspace folder
+    /// the server is limited to if any.
+    pub fn name(&self) -> String {
+        let name = Path::new(&self.exec_path)
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.exec_path.clone());
+        match self.root.as_ref().and_then(|root| root.file_name()) {
+            Some(folder) => format!("{} ({})", name, folder.to_string_lossy()),
+            None => name,
+        }
+    }
+
+    /// The name of the log of the server, which tells apart the clients of
+    /// an executable started for several languages.
+    pub fn log_name(&self) -> String {
+        format!("{} [{}]", self.name(), self.language_id)
+    }
 }
 
+/// The latest diagnostics each server published, per document, so that a
+/// document gets the union of what all its servers reported.
+type DiagnosticsStore =
+    Arc<Mutex<HashMap<Url, HashMap<LspServerId, Vec<Diagnostic>>>>>;
+
 pub struct LspState {
     next_id: u64,
     writer: Box<dyn Write + Send>,
     process: Child,
     pending: HashMap<u64, Callback>,
     pub server_capabilities: Option<ServerCapabilities>,
+    /// The capabilities as the server sent them, for the ones lsp-types
+    /// doesn't know about yet
+    pub capabilities: Value,
     pub opened_documents: HashMap<BufferId, Url>,
+    semantic_tokens: HashMap<BufferId, SemanticTokensState>,
+    /// The id of the diagnostics last pulled for each document, which the
+    /// server can then answer as unchanged against
+    diagnostic_result_ids: HashMap<Url, String>,
+    /// When to pull the diagnostics of the documents that changed, pushed
+    /// back by every change so that only the last of a burst pulls them
+    diagnostic_pulls: HashMap<Url, Instant>,
+    /// Set while the thread pulling the diagnostics of `diagnostic_pulls`
+    /// runs, so that there's only one for the server
+    diagnostic_puller: bool,
+    /// Set while a `workspace/diagnostic` request is in flight, so that
+    /// they don't pile up
+    pulling_workspace_diagnostics: bool,
     pub is_initialized: bool,
+    /// Set when we stop the server, so that its exit isn't taken as a crash
+    stopping: bool,
+    /// The number of crashes in a row, reset once the server stays up
+    restarts: u32,
+    started_at: Instant,
+    /// Bumped on every restart, so that the exit of a previous process
+    /// isn't taken for the current one
+    generation: u64,
+}
+
+/// The semantic tokens last received for a document, which the next ones
+/// are asked as a delta of.
+struct SemanticTokensState {
+    rev: u64,
+    result_id: Option<String>,
+    tokens: Vec<SemanticToken>,
+    /// If the styles of the tokens were sent, so that the next ones can be
+    /// sent as the ones that changed only
+    sent: bool,
 }
 
 #[derive(Clone)]
 pub struct LspClient {
-    exec_path: String,
+    server_id: LspServerId,
     options: Option<Value>,
     state: Arc<Mutex<LspState>>,
     dispatcher: Dispatcher,
+    diagnostics: DiagnosticsStore,
+    /// What the server wrote to stderr, and the messages when tracing is
+    /// on. It's kept across restarts, so that the reason of a crash can be
+    /// looked up.
+    log: Arc<Mutex<VecDeque<String>>>,
+    trace: Arc<AtomicBool>,
+}
+
+/// Gathers the answers to a request sent to several servers, and hands them
+/// over together once the last server has replied.
+struct MergedResults {
+    pending: usize,
+    results: Vec<(LspServerId, Value)>,
+    on_done: Option<MergedCallback>,
+}
+
+type MergedCallback = Box<dyn FnOnce(Vec<(LspServerId, Value)>) + Send>;
+
+impl MergedResults {
+    fn new<F>(pending: usize, on_done: F) -> Arc<Mutex<MergedResults>>
+    where
+        F: 'static + Send + FnOnce(Vec<(LspServerId, Value)>),
+    {
+        Arc::new(Mutex::new(MergedResults {
+            pending,
+            results: Vec::new(),
+            on_done: Some(Box::new(on_done)),
+        }))
+    }
+
+    fn add(
+        merged: &Arc<Mutex<MergedResults>>,
+        server_id: &LspServerId,
+        result: Result<Value>,
+    ) {
+        let done = {
+            let mut merged = merged.lock();
+            merged.pending = merged.pending.saturating_sub(1);
+            if let Ok(value) = result {
+                if !value.is_null() {
+                    merged.results.push((server_id.clone(), value));
+                }
+            }
+            if merged.pending == 0 {
+                let results = std::mem::take(&mut merged.results);
+                merged.on_done.take().map(|f| (f, results))
+            } else {
+                None
+            }
+        };
+        if let Some((f, results)) = done {
+            f(results);
+        }
+    }
 }
 
 impl LspCatalog {
     pub fn new() -> LspCatalog {
         LspCatalog {
             dispatcher: None,
-            clients: HashMap::new(),
+            clients: IndexMap::new(),
+            starting: HashSet::new(),
+            diagnostics: Arc::new(Mutex::new(HashMap::new())),
+            settings: HashMap::new(),
+            trace: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub fn stop(&mut self) {
-        for (_, client) in self.clients.iter() {
-            client.stop();
+        let shutdowns: Vec<_> = self
+            .clients
+            .drain(..)
+            .map(|(_, client)| thread::spawn(move || client.shutdown()))
+            .collect();
+        for shutdown in shutdowns {
+            let _ = shutdown.join();
         }
-        self.clients.clear();
+        self.starting.clear();
+        self.diagnostics.lock().clear();
         self.dispatcher.take();
     }
 
+    /// The id of the server for `language_id`. A relative `root` is resolved
+    /// against the workspace, and the server then only sees the documents
+    /// inside that folder.
+    fn server_id(
+        dispatcher: &Dispatcher,
+        exec_path: &str,
+        args: Vec<String>,
+        language_id: &str,
+        root: Option<PathBuf>,
+    ) -> LspServerId {
+        let root = root.map(|root| match dispatcher.workspace.lock().as_ref() {
+            Some(workspace) => workspace.join(root),
+            None => root,
+        });
+        LspServerId {
+            exec_path: exec_path.to_string(),
+            args,
+            language_id: language_id.to_string(),
+            root,
+        }
+    }
+
+    fn is_started(&self, server_id: &LspServerId) -> bool {
+        self.clients.contains_key(server_id) || self.starting.contains(server_id)
+    }
+
+    /// Start a server for `language_id` unless it's already running or
+    /// starting, and then open the documents it handles in it. Neither the
+    /// catalog nor the buffers are held while the server initializes, so
+    /// this blocks until it has.
     pub fn start_server(
-        &mut self,
+        dispatcher: &Dispatcher,
         exec_path: &str,
+        args: Vec<String>,
         language_id: &str,
+        root: Option<PathBuf>,
         options: Option<Value>,
     ) {
+        let server_id =
+            Self::server_id(dispatcher, exec_path, args, language_id, root);
+        let (diagnostics, trace) = {
+            let mut lsp = dispatcher.lsp.lock();
+            if lsp.is_started(&server_id) {
+                return;
+            }
+            lsp.starting.insert(server_id.clone());
+            (lsp.diagnostics.clone(), lsp.trace.clone())
+        };
+
         let client = LspClient::new(
-            language_id.to_string(),
-            exec_path,
+            server_id.clone(),
             options,
-            self.dispatcher.clone().unwrap(),
+            dispatcher.clone(),
+            diagnostics,
+            trace,
         );
-        self.clients.insert(language_id.to_string(), client);
+
+        let buffers = dispatcher.buffers.lock();
+        let mut lsp = dispatcher.lsp.lock();
+        let stopped = !lsp.starting.remove(&server_id);
+        match client {
+            Ok(client) if stopped => {
+                thread::spawn(move || client.shutdown());
+            }
+            Ok(client) => {
+                lsp.clients.insert(server_id, client.clone());
+                for buffer in buffers.values() {
+                    if client.handles(&buffer.language_id, &buffer.path) {
+                        lsp.open_buffer(buffer);
+                    }
+                }
+            }
+            Err(_) => {
+                send_server_status(dispatcher, &server_id, LspStatus::Crashed);
+            }
+        }
+    }
+
+    pub fn log_names(&self) -> Vec<String> {
+        self.clients
+            .keys()
+            .map(|server_id| server_id.log_name())
+            .collect()
+    }
+
+    pub fn log(&self, name: &str) -> Option<String> {
+        self.clients
+            .iter()
+            .find(|(server_id, _)| server_id.log_name() == name)
+            .map(|(_, client)| client.log_text())
+    }
+
+    /// Starts or stops adding the messages exchanged with the servers to
+    /// their logs.
+    pub fn toggle_trace(&self) {
+        let enabled = !self.trace.fetch_xor(true, Ordering::Relaxed);
+        for client in self.clients.values() {
+            client.log(format!(
+                "[lapce] Message trace {}",
+                if enabled { "on" } else { "off" }
+            ));
+        }
+    }
+
+    /// Restart the servers for `buffer`, or all of them without one.
+    pub fn restart_servers(&self, buffer: Option<&Buffer>) {
+        let clients: Vec<Arc<LspClient>> = match buffer {
+            Some(buffer) => self
+                .clients_for(&buffer.language_id, &buffer.path)
+                .into_iter()
+                .cloned()
+                .collect(),
+            None => self.clients.values().cloned().collect(),
+        };
+        for client in clients {
+            thread::spawn(move || {
+                client.shutdown();
+                client.state.lock().restarts = 0;
+                client.restart();
+            });
+        }
+    }
+
+    /// All the clients a document should be sent to. When the same server
+    /// runs for nested workspace folders, only the innermost one is used.
+    fn clients_for(&self, language_id: &str, path: &Path) -> Vec<&Arc<LspClient>> {
+        let mut clients: Vec<&Arc<LspClient>> = Vec::new();
+        for client in self.clients.values() {
+            if !client.handles(language_id, path) {
+                continue;
+            }
+            match clients
+                .iter()
+                .position(|c| c.server_id.exec_path == client.server_id.exec_path)
+            {
+                Some(i) => {
+                    if client.root_depth() > clients[i].root_depth() {
+                        clients[i] = client;
+                    }
+                }
+                None => clients.push(client),
+            }
+        }
+        clients
     }
 
-    pub fn new_buffer(
+    /// The client a request with a single answer goes to: the first one
+    /// whose server has the capability, or else the first one for the
+    /// document.
+    fn client_for(
         &self,
-        buffer_id: &BufferId,
-        path: &str,
-        language_id: &str,
-        text: String,
-    ) {
-        let document_uri = Url::from_file_path(path).unwrap();
-        if let Some(client) = self.clients.get(language_id) {
-            client.send_did_open(buffer_id, document_uri, language_id, text);
+        buffer: &Buffer,
+        capability: fn(&ServerCapabilities) -> bool,
+    ) -> Option<&Arc<LspClient>> {
+        let clients = self.clients_for(&buffer.language_id, &buffer.path);
+        clients
+            .iter()
+            .find(|client| client.has_capability(capability))
+            .or_else(|| clients.first())
+            .copied()
+    }
+
+    /// Replaces the servers from the settings. The running ones whose
+    /// settings changed are stopped, and started again once a document
+    /// needs them.
+    pub fn update_settings(&mut self, settings: HashMap<String, LspSettings>) {
+        let outdated: Vec<LspServerId> = self
+            .clients
+            .keys()
+            .filter(
+                |server_id| match self.settings.get(&server_id.language_id) {
+                    Some(old) => {
+                        old.command == server_id.exec_path
+                            && old.args == server_id.args
+                            && settings.get(&server_id.language_id) != Some(old)
+                    }
+                    None => false,
+                },
+            )
+            .cloned()
+            .collect();
+        self.settings = settings;
+        for server_id in outdated {
+            if let Some(client) = self.clients.shift_remove(&server_id) {
+                thread::spawn(move || client.shutdown());
+            }
+        }
+    }
+
+    /// The language id of the document, which the file extensions from the
+    /// settings take precedence for.
+    pub fn language_id(&self, path: &Path) -> String {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        extension
+            .and_then(|extension| {
+                self.settings
+                    .iter()
+                    .find(|(_, settings)| {
+                        settings.file_extensions.iter().any(|e| {
+                            e.trim_start_matches('.').to_lowercase() == extension
+                        })
+                    })
+                    .map(|(language_id, _)| language_id.clone())
+            })
+            .or_else(|| language_id_from_path(path).map(|id| id.to_string()))
+            .unwrap_or_default()
+    }
+
+    /// Opens the document in all the running servers for it, and starts the
+    /// one the settings have for its language on its own thread if it isn't
+    /// running yet, which opens the document once it's initialized.
+    pub fn open_buffer(&self, buffer: &Buffer) {
+        let dispatcher = match self.dispatcher.as_ref() {
+            Some(dispatcher) => dispatcher,
+            None => return,
+        };
+        if let Some(settings) = self.settings.get(&buffer.language_id).cloned() {
+            if !settings.command.is_empty() {
+                let workspace = dispatcher.workspace.lock().clone();
+                let root = find_root(
+                    &buffer.path,
+                    &settings.root_markers,
+                    workspace.as_deref(),
+                );
+                let server_id = Self::server_id(
+                    dispatcher,
+                    &settings.command,
+                    settings.args.clone(),
+                    &buffer.language_id,
+                    root.clone(),
+                );
+                if !self.is_started(&server_id) {
+                    let dispatcher = dispatcher.clone();
+                    let language_id = buffer.language_id.clone();
+                    thread::spawn(move || {
+                        Self::start_server(
+                            &dispatcher,
+                            &settings.command,
+                            settings.args,
+                            &language_id,
+                            root,
+                            settings.initialization_options,
+                        );
+                    });
+                }
+            }
+        }
+        let mut trigger_characters = Vec::new();
+        let mut retrigger_characters = Vec::new();
+        for client in self.clients_for(&buffer.language_id, &buffer.path) {
+            client.get_uri(buffer);
+            if let Some((triggers, retriggers)) = client.signature_help_characters()
+            {
+                trigger_characters.extend(triggers);
+                retrigger_characters.extend(retriggers);
+            }
+        }
+        dispatcher.send_rpc_notification(CoreNotification::SignatureHelpTriggers {
+            path: buffer.path.clone(),
+            trigger_characters,
+            retrigger_characters,
+        });
+    }
+
+    /// Tells every server that had the document open that it's closed,
+    /// including the ones that don't handle its path anymore.
+    pub fn close_buffer(&self, buffer: &Buffer) {
+        for client in self.clients.values() {
+            client.send_did_close(&buffer.id);
         }
     }
 
     pub fn save_buffer(&self, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        for client in self.clients_for(&buffer.language_id, &buffer.path) {
             let uri = client.get_uri(buffer);
             client.send_did_save(uri);
         }
     }
 
+    /// Asks for the semantic tokens of the whole document, as a delta of the
+    /// previous ones when the server supports it, and sends the styles of
+    /// the ones that changed.
     pub fn get_semantic_tokens(&self, buffer: &Buffer) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.semantic_tokens_provider.is_some())
+        {
+            let uri = client.get_uri(buffer);
+            let result_id = client.semantic_tokens_result_id(buffer.id);
+            let cb = semantic_tokens_callback(
+                self.dispatcher.clone().unwrap(),
+                buffer,
+                result_id.is_some(),
+            );
+            match result_id {
+                Some(result_id) => {
+                    client.request_semantic_tokens_delta(uri, result_id, cb)
+                }
+                None => client.request_semantic_tokens(uri, cb),
+            }
+        }
+    }
+
+    /// Sends the styles of all the semantic tokens again, for when the ones
+    /// that changed couldn't be applied.
+    pub fn refresh_semantic_tokens(&self, buffer: &Buffer) {
+        for client in self.clients_for(&buffer.language_id, &buffer.path) {
+            client.state.lock().semantic_tokens.remove(&buffer.id);
+        }
+        self.get_semantic_tokens(buffer);
+    }
+
+    /// The styles of the semantic tokens in the range, for the part of the
+    /// document that is shown before the ones of the whole document come.
+    pub fn get_semantic_tokens_range(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        range: Range,
+    ) {
         let buffer_id = buffer.id;
-        let path = buffer.path.clone();
         let rev = buffer.rev;
-        let len = buffer.len();
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| client.has_capability(semantic_tokens_range_support))
+        {
             let uri = client.get_uri(buffer);
             let local_dispatcher = self.dispatcher.clone().unwrap();
-            client.request_semantic_tokens(uri, move |lsp_client, result| {
-                if let Ok(res) = result {
-                    let buffers = local_dispatcher.buffers.lock();
-                    let buffer = buffers.get(&buffer_id).unwrap();
-                    if buffer.rev != rev {
-                        return;
-                    }
-                    let lsp_state = lsp_client.state.lock();
-                    let semantic_tokens_provider = &lsp_state
-                        .server_capabilities
-                        .as_ref()
-                        .unwrap()
-                        .semantic_tokens_provider;
-                    if let Some(styles) =
-                        format_semantic_styles(buffer, semantic_tokens_provider, res)
-                    {
-                        local_dispatcher.send_notification(
-                            "semantic_styles",
-                            json!({
-                                "rev": rev,
-                                "buffer_id": buffer_id,
-                                "path": path,
-                                "styles": styles,
-                                "len": len,
-                            }),
-                        )
-                    }
-                }
-            });
+            client.request_semantic_tokens_range(
+                uri,
+                range,
+                move |lsp_client, result| {
+                    let result = result.and_then(|value| {
+                        let tokens: SemanticTokensRangeResult =
+                            serde_json::from_value(value)?;
+                        let tokens = match tokens {
+                            SemanticTokensRangeResult::Tokens(tokens) => tokens.data,
+                            SemanticTokensRangeResult::Partial(partial) => {
+                                partial.data
+                            }
+                        };
+                        let buffers = local_dispatcher.buffers.lock();
+                        let buffer = buffers
+                            .get(&buffer_id)
+                            .filter(|buffer| buffer.rev == rev)
+                            .ok_or_else(|| anyhow!("buffer changed"))?;
+                        let legend = lsp_client
+                            .semantic_tokens_legend()
+                            .ok_or_else(|| anyhow!("no semantic tokens legend"))?;
+                        let (_, _, styles) = format_semantic_styles(
+                            buffer,
+                            &legend,
+                            &tokens,
+                            0,
+                            tokens.len(),
+                        );
+                        Ok(serde_json::to_value(styles)?)
+                    });
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no semantic tokens range")));
         }
     }
 
     pub fn get_document_symbols(&self, id: RequestId, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.document_symbol_provider.is_some())
+        {
             let uri = client.get_uri(buffer);
             client.request_document_symbols(uri, move |lsp_client, result| {
                 lsp_client.dispatcher.respond(id, result);
@@ -162,8 +626,51 @@ impl LspCatalog {
         }
     }
 
+    /// Asks every server with workspace symbols and merges their answers.
+    /// Returns false when there's no such server, so that the caller can
+    /// fall back to the symbols it indexed itself.
+    pub fn get_workspace_symbols(&self, id: RequestId, query: &str) -> bool {
+        let clients: Vec<&Arc<LspClient>> = self
+            .clients
+            .values()
+            .filter(|client| {
+                client.has_capability(|c| {
+                    !matches!(
+                        c.workspace_symbol_provider,
+                        None | Some(OneOf::Left(false))
+                    )
+                })
+            })
+            .collect();
+        if clients.is_empty() {
+            return false;
+        }
+
+        let dispatcher = self.dispatcher.clone().unwrap();
+        let merged = MergedResults::new(clients.len(), move |results| {
+            let symbols: Vec<Value> = results
+                .into_iter()
+                .filter_map(|(_, value)| match value {
+                    Value::Array(symbols) => Some(symbols),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            dispatcher.respond(id, Ok(Value::Array(symbols)));
+        });
+        for client in clients {
+            let merged = merged.clone();
+            client.request_workspace_symbols(query, move |lsp_client, result| {
+                MergedResults::add(&merged, &lsp_client.server_id, result);
+            });
+        }
+        true
+    }
+
     pub fn get_document_formatting(&self, id: RequestId, buffer: &Buffer) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.document_formatting_provider.is_some())
+        {
             let uri = client.get_uri(buffer);
             client.request_document_formatting(uri, move |lsp_client, result| {
                 lsp_client.dispatcher.respond(id, result);
@@ -176,6 +683,151 @@ impl LspCatalog {
         }
     }
 
+    /// Answers with an error when no server has folding ranges, so that the
+    /// editor falls back to the ones from the syntax tree.
+    pub fn get_folding_ranges(&self, id: RequestId, buffer: &Buffer) {
+        if let Some(client) = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| {
+                client.has_capability(|c| {
+                    !matches!(
+                        c.folding_range_provider,
+                        None | Some(FoldingRangeProviderCapability::Simple(false))
+                    )
+                })
+            })
+        {
+            let uri = client.get_uri(buffer);
+            client.request_folding_ranges(uri, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no folding ranges")));
+        }
+    }
+
+    /// Answers with an error when no server has selection ranges, so that
+    /// the editor falls back to the nodes of the syntax tree.
+    pub fn get_selection_ranges(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        positions: Vec<Position>,
+    ) {
+        if let Some(client) = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| {
+                client.has_capability(|c| {
+                    !matches!(
+                        c.selection_range_provider,
+                        None | Some(SelectionRangeProviderCapability::Simple(false))
+                    )
+                })
+            })
+        {
+            let uri = client.get_uri(buffer);
+            client.request_selection_ranges(
+                uri,
+                positions,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no selection ranges")));
+        }
+    }
+
+    /// Each range is formatted on its own, and the edits are merged into a
+    /// single list as they all apply to the same version of the document.
+    pub fn get_range_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        ranges: Vec<Range>,
+    ) {
+        let client = match self.client_for(buffer, |c| {
+            !matches!(
+                c.document_range_formatting_provider,
+                None | Some(OneOf::Left(false))
+            )
+        }) {
+            Some(client) if !ranges.is_empty() => client,
+            _ => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Err(anyhow!("no range formatting")));
+                return;
+            }
+        };
+
+        let dispatcher = self.dispatcher.clone().unwrap();
+        let merged = MergedResults::new(ranges.len(), move |results| {
+            let edits: Vec<Value> = results
+                .into_iter()
+                .filter_map(|(_, value)| match value {
+                    Value::Array(edits) => Some(edits),
+                    _ => None,
+                })
+                .flatten()
+                .collect();
+            dispatcher.respond(id, Ok(Value::Array(edits)));
+        });
+        let uri = client.get_uri(buffer);
+        for range in ranges {
+            let merged = merged.clone();
+            client.request_range_formatting(
+                uri.clone(),
+                range,
+                move |lsp_client, result| {
+                    MergedResults::add(&merged, &lsp_client.server_id, result);
+                },
+            );
+        }
+    }
+
+    /// Only the servers that declared the typed character as one of their
+    /// trigger characters are asked.
+    pub fn get_on_type_formatting(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        ch: &str,
+    ) {
+        if let Some(client) = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| client.is_on_type_formatting_trigger(ch))
+        {
+            let uri = client.get_uri(buffer);
+            client.request_on_type_formatting(
+                uri,
+                position,
+                ch,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no on type formatting")));
+        }
+    }
+
+    /// Completion is asked from every server for the document, and the
+    /// items are merged into a single list.
     pub fn get_completion(
         &self,
         id: RequestId,
@@ -183,20 +835,40 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        let clients = self.clients_for(&buffer.language_id, &buffer.path);
+        if clients.is_empty() {
+            return;
+        }
+        let dispatcher = self.dispatcher.clone().unwrap();
+        let merged = MergedResults::new(clients.len(), move |results| {
+            let mut is_incomplete = false;
+            let mut items = Vec::new();
+            for (server_id, value) in results {
+                let (incomplete, server_items) =
+                    match serde_json::from_value::<CompletionResponse>(value) {
+                        Ok(CompletionResponse::Array(items)) => (false, items),
+                        Ok(CompletionResponse::List(list)) => {
+                            (list.is_incomplete, list.items)
+                        }
+                        Err(_) => continue,
+                    };
+                is_incomplete |= incomplete;
+                items.extend(server_items.into_iter().map(|mut item| {
+                    tag_data(&mut item.data, &server_id);
+                    item
+                }));
+            }
+            let resp = CompletionResponse::List(CompletionList {
+                is_incomplete,
+                items,
+            });
+            dispatcher.respond(id, Ok(json!(resp)));
+        });
+        for client in clients {
             let uri = client.get_uri(buffer);
+            let merged = merged.clone();
             client.request_completion(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
+                MergedResults::add(&merged, &lsp_client.server_id, result);
             });
         }
     }
@@ -207,20 +879,34 @@ impl LspCatalog {
         buffer: &Buffer,
         completion_item: &CompletionItem,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
-            client.completion_resolve(completion_item, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
+        let mut untagged = completion_item.clone();
+        let client = match untag_data(&mut untagged.data) {
+            Some(server_id) => self.clients.get(&server_id),
+            None => self.client_for(buffer, |c| {
+                c.completion_provider
+                    .as_ref()
+                    .and_then(|c| c.resolve_provider)
+                    .unwrap_or(false)
+            }),
+        };
+        if let Some(client) = client {
+            client.completion_resolve(&untagged, move |lsp_client, result| {
+                let result = result.map(|value| {
+                    match serde_json::from_value::<CompletionItem>(value.clone()) {
+                        Ok(mut item) => {
+                            tag_data(&mut item.data, &lsp_client.server_id);
+                            json!(item)
+                        }
+                        Err(_) => value,
                     }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
+                });
+                lsp_client.dispatcher.respond(id, result);
             });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Ok(json!(completion_item)));
         }
     }
 
@@ -231,7 +917,8 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) = self.client_for(buffer, |c| c.hover_provider.is_some())
+        {
             let uri = client.get_uri(buffer);
             client.request_hover(uri, position, move |lsp_client, result| {
                 let mut resp = json!({ "id": id });
@@ -249,22 +936,50 @@ impl LspCatalog {
         }
     }
 
-    pub fn get_signature(&self, id: RequestId, buffer: &Buffer, position: Position) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+    /// Signature help is only asked for when a trigger character of the
+    /// server was typed, or when it's already shown and might have changed.
+    pub fn get_signature(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        trigger_character: Option<String>,
+        is_retrigger: bool,
+    ) {
+        if let Some(client) = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| {
+                client.is_signature_help_trigger(
+                    trigger_character.as_deref(),
+                    is_retrigger,
+                )
+            })
+        {
             let uri = client.get_uri(buffer);
-            client.request_signature(uri, position, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
-                }
-                let _ = lsp_client.dispatcher.sender.send(resp);
-            });
+            let context = SignatureHelpContext {
+                trigger_kind: if trigger_character.is_some() {
+                    SignatureHelpTriggerKind::TriggerCharacter
+                } else {
+                    SignatureHelpTriggerKind::ContentChange
+                },
+                trigger_character,
+                is_retrigger,
+                active_signature_help: None,
+            };
+            client.request_signature(
+                uri,
+                position,
+                context,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no signature help")));
         }
     }
 
@@ -274,7 +989,9 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.references_provider.is_some())
+        {
             let uri = client.get_uri(buffer);
             client.request_references(uri, position, move |lsp_client, result| {
                 let mut resp = json!({ "id": id });
@@ -292,34 +1009,353 @@ impl LspCatalog {
         }
     }
 
+    /// Code actions are asked from every server for the document, and the
+    /// actions are merged into a single list, each one tagged with its
+    /// server for `codeAction/resolve` and for running its command.
     pub fn get_code_actions(
         &self,
         id: RequestId,
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        let clients = self.clients_for(&buffer.language_id, &buffer.path);
+        if clients.is_empty() {
+            return;
+        }
+        let dispatcher = self.dispatcher.clone().unwrap();
+        let merged = MergedResults::new(clients.len(), move |results| {
+            let mut actions = Vec::new();
+            for (server_id, value) in results {
+                if let Ok(server_actions) =
+                    serde_json::from_value::<CodeActionResponse>(value)
+                {
+                    actions.extend(server_actions.into_iter().map(|action| {
+                        // A bare command becomes an action that runs it, which
+                        // has data to keep the server in.
+                        let mut action = match action {
+                            CodeActionOrCommand::CodeAction(action) => action,
+                            CodeActionOrCommand::Command(command) => CodeAction {
+                                title: command.title.clone(),
+                                command: Some(command),
+                                ..Default::default()
+                            },
+                        };
+                        tag_data(&mut action.data, &server_id);
+                        CodeActionOrCommand::CodeAction(action)
+                    }));
+                }
+            }
+            dispatcher.respond(id, Ok(json!(actions)));
+        });
+        let range = Range {
+            start: position,
+            end: position,
+        };
+        for client in clients {
             let uri = client.get_uri(buffer);
-            let range = Range {
-                start: position,
-                end: position,
-            };
+            let merged = merged.clone();
             client.request_code_actions(uri, range, move |lsp_client, result| {
-                let mut resp = json!({ "id": id });
-                match result {
-                    Ok(v) => resp["result"] = v,
-                    Err(e) => {
-                        resp["error"] = json!({
-                            "code": 0,
-                            "message": format!("{}",e),
-                        })
-                    }
+                MergedResults::add(&merged, &lsp_client.server_id, result);
+            });
+        }
+    }
+
+    /// Fills in the edit of a code action with the server it came from, or
+    /// answers the code action as it is when that server can't.
+    pub fn resolve_code_action(&self, id: RequestId, action: &CodeAction) {
+        let mut untagged = action.clone();
+        let client = untag_data(&mut untagged.data)
+            .and_then(|server_id| self.clients.get(&server_id))
+            .filter(|client| {
+                client.has_capability(|c| {
+                    matches!(
+                        c.code_action_provider,
+                        Some(CodeActionProviderCapability::Options(
+                            CodeActionOptions {
+                                resolve_provider: Some(true),
+                                ..
+                            }
+                        ))
+                    )
+                })
+            });
+        match client {
+            Some(client) => {
+                client.code_action_resolve(&untagged, move |lsp_client, result| {
+                    let result = result.map(|value| {
+                        match serde_json::from_value::<CodeAction>(value.clone()) {
+                            Ok(mut action) => {
+                                tag_data(&mut action.data, &lsp_client.server_id);
+                                json!(action)
+                            }
+                            Err(_) => value,
+                        }
+                    });
+                    lsp_client.dispatcher.respond(id, result);
+                });
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(json!(action)));
+            }
+        }
+    }
+
+    /// Code lenses come from every server for the document that has them,
+    /// each one tagged with its server for `codeLens/resolve`.
+    pub fn get_code_lens(&self, id: RequestId, buffer: &Buffer) {
+        let clients: Vec<&Arc<LspClient>> = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .filter(|client| {
+                client.has_capability(|c| c.code_lens_provider.is_some())
+            })
+            .collect();
+        let dispatcher = self.dispatcher.clone().unwrap();
+        if clients.is_empty() {
+            dispatcher.respond(id, Ok(Value::Array(Vec::new())));
+            return;
+        }
+        let merged = MergedResults::new(clients.len(), move |results| {
+            let mut code_lenses = Vec::new();
+            for (server_id, value) in results {
+                if let Ok(server_code_lenses) =
+                    serde_json::from_value::<Vec<CodeLens>>(value)
+                {
+                    code_lenses.extend(server_code_lenses.into_iter().map(
+                        |mut code_lens| {
+                            tag_data(&mut code_lens.data, &server_id);
+                            code_lens
+                        },
+                    ));
                 }
-                let _ = lsp_client.dispatcher.sender.send(resp);
+            }
+            dispatcher.respond(id, Ok(json!(code_lenses)));
+        });
+        for client in clients {
+            let uri = client.get_uri(buffer);
+            let merged = merged.clone();
+            client.request_code_lens(uri, move |lsp_client, result| {
+                MergedResults::add(&merged, &lsp_client.server_id, result);
             });
         }
     }
 
+    /// Fills in the command of a code lens with the server it came from, or
+    /// answers the code lens as it is when that server can't.
+    pub fn resolve_code_lens(&self, id: RequestId, code_lens: &CodeLens) {
+        let mut untagged = code_lens.clone();
+        let client = untag_data(&mut untagged.data)
+            .and_then(|server_id| self.clients.get(&server_id))
+            .filter(|client| {
+                client.has_capability(|c| {
+                    c.code_lens_provider
+                        .as_ref()
+                        .and_then(|c| c.resolve_provider)
+                        .unwrap_or(false)
+                })
+            });
+        match client {
+            Some(client) => {
+                client.code_lens_resolve(&untagged, move |lsp_client, result| {
+                    let result = result.map(|value| {
+                        match serde_json::from_value::<CodeLens>(value.clone()) {
+                            Ok(mut code_lens) => {
+                                tag_data(&mut code_lens.data, &lsp_client.server_id);
+                                json!(code_lens)
+                            }
+                            Err(_) => value,
+                        }
+                    });
+                    lsp_client.dispatcher.respond(id, result);
+                });
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(json!(code_lens)));
+            }
+        }
+    }
+
+    /// Runs the command with the server the item it came with is tagged
+    /// with. Without one, it goes to the server for the document that
+    /// registered it, or else the first one for the document.
+    pub fn execute_command(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        command: &lsp_types::Command,
+        mut data: Option<Value>,
+    ) {
+        let clients = self.clients_for(&buffer.language_id, &buffer.path);
+        let client = untag_data(&mut data)
+            .and_then(|server_id| self.clients.get(&server_id))
+            .or_else(|| {
+                clients
+                    .iter()
+                    .find(|client| client.provides_command(&command.command))
+                    .or_else(|| clients.first())
+                    .copied()
+            });
+        match client {
+            Some(client) => {
+                client.execute_command(command, move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                });
+            }
+            None => {
+                self.dispatcher.as_ref().unwrap().respond(
+                    id,
+                    Err(anyhow!("no language server for {}", command.command)),
+                );
+            }
+        }
+    }
+
+    /// Answers null when no server for the document has document
+    /// highlights, so that the editor can fall back to its own.
+    pub fn get_document_highlights(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        let client = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| {
+                client.has_capability(|c| {
+                    !matches!(
+                        c.document_highlight_provider,
+                        None | Some(OneOf::Left(false))
+                    )
+                })
+            });
+        match client {
+            Some(client) => {
+                let uri = client.get_uri(buffer);
+                client.request_document_highlights(
+                    uri,
+                    position,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(Value::Null));
+            }
+        }
+    }
+
+    /// Inlay hints come from the first server for the document that has
+    /// them, through either the standard request or rust-analyzer's own.
+    pub fn get_inlay_hints(&self, id: RequestId, buffer: &Buffer, range: Range) {
+        let client = self
+            .clients_for(&buffer.language_id, &buffer.path)
+            .into_iter()
+            .find(|client| client.inlay_hints_method().is_some());
+        let client = match client {
+            Some(client) => client,
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Ok(Value::Array(Vec::new())));
+                return;
+            }
+        };
+        let uri = client.get_uri(buffer);
+        client.request_inlay_hints(uri, range, move |lsp_client, result| {
+            let hints =
+                result.map(|value| to_value(parse_inlay_hints(value)).unwrap());
+            lsp_client.dispatcher.respond(id, hints);
+        });
+    }
+
+    /// Answers the items the hierarchy starts from, usually the one symbol
+    /// at the position.
+    pub fn prepare_hierarchy(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        kind: HierarchyKind,
+    ) {
+        match self.hierarchy_client(&buffer.language_id, &buffer.path, kind) {
+            Some(client) => {
+                let uri = client.get_uri(buffer);
+                client.request_prepare_hierarchy(
+                    uri,
+                    position,
+                    kind,
+                    move |lsp_client, result| {
+                        lsp_client.dispatcher.respond(id, result);
+                    },
+                );
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Err(anyhow!("no hierarchy for {:?}", kind)));
+            }
+        }
+    }
+
+    /// Answers the callers, callees, supertypes or subtypes of the item, as
+    /// items themselves. The item must come from a server of the document.
+    pub fn get_hierarchy_children(
+        &self,
+        id: RequestId,
+        kind: HierarchyKind,
+        item: CallHierarchyItem,
+    ) {
+        // The item may live in a document that has been closed since, so
+        // the servers are picked by its own path rather than a buffer.
+        let client = item.uri.to_file_path().ok().and_then(|path| {
+            self.hierarchy_client(&self.language_id(&path), &path, kind)
+        });
+        match client {
+            Some(client) => {
+                client.request_hierarchy_children(
+                    kind,
+                    item,
+                    move |lsp_client, result| {
+                        let items = result.map(|value| {
+                            to_value(parse_hierarchy_children(kind, value)).unwrap()
+                        });
+                        lsp_client.dispatcher.respond(id, items);
+                    },
+                );
+            }
+            None => {
+                self.dispatcher
+                    .as_ref()
+                    .unwrap()
+                    .respond(id, Err(anyhow!("no hierarchy for {:?}", kind)));
+            }
+        }
+    }
+
+    fn hierarchy_client(
+        &self,
+        language_id: &str,
+        path: &Path,
+        kind: HierarchyKind,
+    ) -> Option<&Arc<LspClient>> {
+        self.clients_for(language_id, path)
+            .into_iter()
+            .find(|client| client.has_hierarchy(kind))
+    }
+
     pub fn get_definition(
         &self,
         id: RequestId,
@@ -327,7 +1363,9 @@ impl LspCatalog {
         buffer: &Buffer,
         position: Position,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.definition_provider.is_some())
+        {
             let uri = client.get_uri(buffer);
             client.request_definition(uri, position, move |lsp_client, result| {
                 let mut resp = json!({ "id": id });
@@ -345,13 +1383,154 @@ impl LspCatalog {
         }
     }
 
+    pub fn get_type_definition(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.type_definition_provider.is_some())
+        {
+            let uri = client.get_uri(buffer);
+            client.request_type_definition(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no type definition")));
+        }
+    }
+
+    pub fn get_implementation(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.implementation_provider.is_some())
+        {
+            let uri = client.get_uri(buffer);
+            client.request_implementation(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    lsp_client.dispatcher.respond(id, result);
+                },
+            );
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no implementation")));
+        }
+    }
+
+    pub fn get_declaration(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.declaration_provider.is_some())
+        {
+            let uri = client.get_uri(buffer);
+            client.request_declaration(uri, position, move |lsp_client, result| {
+                lsp_client.dispatcher.respond(id, result);
+            });
+        } else {
+            self.dispatcher
+                .as_ref()
+                .unwrap()
+                .respond(id, Err(anyhow!("no declaration")));
+        }
+    }
+
+    pub fn prepare_rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+    ) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.rename_provider.is_some())
+        {
+            if !client.prepare_rename_support() {
+                // Let the editor fall back to the word under the cursor
+                let _ = client
+                    .dispatcher
+                    .sender
+                    .send(json!({ "id": id, "result": Value::Null }));
+                return;
+            }
+            let uri = client.get_uri(buffer);
+            client.request_prepare_rename(
+                uri,
+                position,
+                move |lsp_client, result| {
+                    let mut resp = json!({ "id": id });
+                    match result {
+                        Ok(v) => resp["result"] = v,
+                        Err(e) => {
+                            resp["error"] = json!({
+                                "code": 0,
+                                "message": format!("{}",e),
+                            })
+                        }
+                    }
+                    let _ = lsp_client.dispatcher.sender.send(resp);
+                },
+            );
+        }
+    }
+
+    pub fn rename(
+        &self,
+        id: RequestId,
+        buffer: &Buffer,
+        position: Position,
+        new_name: String,
+    ) {
+        if let Some(client) =
+            self.client_for(buffer, |c| c.rename_provider.is_some())
+        {
+            let uri = client.get_uri(buffer);
+            client.request_rename(
+                uri,
+                position,
+                new_name,
+                move |lsp_client, result| {
+                    let mut resp = json!({ "id": id });
+                    match result {
+                        Ok(v) => resp["result"] = v,
+                        Err(e) => {
+                            resp["error"] = json!({
+                                "code": 0,
+                                "message": format!("{}",e),
+                            })
+                        }
+                    }
+                    let _ = lsp_client.dispatcher.sender.send(resp);
+                },
+            );
+        }
+    }
+
     pub fn update(
         &self,
         buffer: &Buffer,
         content_change: &TextDocumentContentChangeEvent,
         rev: u64,
     ) {
-        if let Some(client) = self.clients.get(&buffer.language_id) {
+        for client in self.clients_for(&buffer.language_id, &buffer.path) {
             client.update(buffer, content_change, rev);
         }
     }
@@ -365,38 +1544,56 @@ impl Default for LspCatalog {
 
 impl LspClient {
     pub fn new(
-        _language_id: String,
-        exec_path: &str,
+        server_id: LspServerId,
         options: Option<Value>,
         dispatcher: Dispatcher,
-    ) -> Arc<LspClient> {
-        let mut process = Self::process(exec_path);
+        diagnostics: DiagnosticsStore,
+        trace: Arc<AtomicBool>,
+    ) -> Result<Arc<LspClient>> {
+        let mut process = Self::process(&server_id.exec_path, &server_id.args)?;
         let writer = Box::new(BufWriter::new(process.stdin.take().unwrap()));
         let stdout = process.stdout.take().unwrap();
+        let stderr = process.stderr.take().unwrap();
 
         let lsp_client = Arc::new(LspClient {
             dispatcher,
-            exec_path: exec_path.to_string(),
+            server_id,
             options,
+            diagnostics,
+            log: Arc::new(Mutex::new(VecDeque::new())),
+            trace,
             state: Arc::new(Mutex::new(LspState {
                 next_id: 0,
                 writer,
                 process,
                 pending: HashMap::new(),
                 server_capabilities: None,
+                capabilities: Value::Null,
                 opened_documents: HashMap::new(),
+                semantic_tokens: HashMap::new(),
+                diagnostic_result_ids: HashMap::new(),
+                diagnostic_pulls: HashMap::new(),
+                diagnostic_puller: false,
+                pulling_workspace_diagnostics: false,
                 is_initialized: false,
+                stopping: false,
+                restarts: 0,
+                started_at: Instant::now(),
+                generation: 0,
             })),
         });
 
+        lsp_client.send_status(LspStatus::Starting);
         lsp_client.handle_stdout(stdout);
+        lsp_client.handle_stderr(stderr);
         lsp_client.initialize();
 
-        lsp_client
+        Ok(lsp_client)
     }
 
     fn handle_stdout(&self, stdout: ChildStdout) {
         let local_lsp_client = self.clone();
+        let generation = self.state.lock().generation;
         thread::spawn(move || {
             let mut reader = Box::new(BufReader::new(stdout));
             loop {
@@ -405,8 +1602,7 @@ impl LspClient {
                         local_lsp_client.handle_message(message_str.as_ref());
                     }
                     Err(_err) => {
-                        local_lsp_client.stop();
-                        local_lsp_client.reload();
+                        local_lsp_client.handle_exit(generation);
                         return;
                     }
                 };
@@ -414,37 +1610,447 @@ impl LspClient {
         });
     }
 
-    fn process(exec_path: &str) -> Child {
+    fn handle_stderr(&self, stderr: ChildStderr) {
+        let local_lsp_client = self.clone();
+        thread::spawn(move || {
+            let reader = BufReader::new(stderr);
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => local_lsp_client.log(format!("[stderr] {line}")),
+                    Err(_) => return,
+                }
+            }
+        });
+    }
+
+    /// Adds an entry to the log of the server, dropping the oldest one once
+    /// it's full.
+    fn log(&self, entry: String) {
+        let mut log = self.log.lock();
+        // Sent while the log is held, so that an entry is either in the log
+        // the UI asked for or comes after it, but not both.
+        self.dispatcher
+            .send_rpc_notification(CoreNotification::LspLog {
+                name: self.server_id.log_name(),
+                entry: entry.clone(),
+            });
+        if log.len() >= LSP_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+
+    pub fn log_text(&self) -> String {
+        let log = self.log.lock();
+        log.iter()
+            .map(|entry| entry.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn process(exec_path: &str, args: &[String]) -> Result<Child> {
         let mut process = Command::new(exec_path);
+        process.args(args);
         #[cfg(target_os = "windows")]
         let process = process.creation_flags(0x08000000);
-        process
+        let process = process
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .spawn()
-            .expect("Error Occurred")
+            .stderr(Stdio::piped())
+            .spawn()?;
+        Ok(process)
+    }
+
+    /// Called when the server's stdout closes. Unless we asked the server
+    /// to stop, it has crashed, and is restarted after a delay that doubles
+    /// with every crash in a row.
+    fn handle_exit(&self, generation: u64) {
+        let attempt = {
+            let mut state = self.state.lock();
+            if state.generation != generation {
+                return;
+            }
+            let _ = state.process.kill();
+            let _ = state.process.wait();
+            if state.stopping {
+                return;
+            }
+            if state.started_at.elapsed() > LSP_STABLE_DURATION {
+                state.restarts = 0;
+            }
+            state.restarts += 1;
+            state.restarts
+        };
+        self.log("[lapce] The server exited unexpectedly".to_string());
+        self.clear_diagnostics();
+
+        if attempt > LSP_MAX_RESTARTS {
+            self.send_status(LspStatus::Crashed);
+            return;
+        }
+        self.send_status(LspStatus::Restarting);
+        thread::sleep(LSP_RESTART_BACKOFF * 2u32.pow(attempt - 1));
+        if self.state.lock().stopping {
+            return;
+        }
+        self.restart();
     }
 
-    fn reload(&self) {
-        let mut process = Self::process(&self.exec_path);
+    /// Start a new server process, and open the documents the previous one
+    /// had open again.
+    fn restart(&self) {
+        let mut process =
+            match Self::process(&self.server_id.exec_path, &self.server_id.args) {
+                Ok(process) => process,
+                Err(_) => {
+                    self.send_status(LspStatus::Crashed);
+                    return;
+                }
+            };
         let writer = Box::new(BufWriter::new(process.stdin.take().unwrap()));
         let stdout = process.stdout.take().unwrap();
+        let stderr = process.stderr.take().unwrap();
 
-        let mut state = self.state.lock();
-        state.next_id = 0;
-        state.pending.clear();
-        state.opened_documents.clear();
-        state.server_capabilities = None;
-        state.is_initialized = false;
-        state.writer = writer;
-        state.process = process;
+        let (opened_documents, pending) = {
+            let mut state = self.state.lock();
+            state.next_id = 0;
+            let pending = std::mem::take(&mut state.pending);
+            state.server_capabilities = None;
+            state.capabilities = Value::Null;
+            state.semantic_tokens.clear();
+            state.diagnostic_result_ids.clear();
+            state.diagnostic_pulls.clear();
+            state.pulling_workspace_diagnostics = false;
+            state.is_initialized = false;
+            state.stopping = false;
+            state.started_at = Instant::now();
+            state.generation += 1;
+            state.writer = writer;
+            state.process = process;
+            (std::mem::take(&mut state.opened_documents), pending)
+        };
+        // The requests the previous process never answered still get an
+        // answer, so that nothing waits on them forever.
+        for (_, callback) in pending {
+            callback.call(self, Err(anyhow!("server restarted")));
+        }
 
+        self.send_status(LspStatus::Starting);
         self.handle_stdout(stdout);
+        self.handle_stderr(stderr);
         self.initialize();
+
+        let documents: Vec<(BufferId, Url, String, String)> = {
+            let buffers = self.dispatcher.buffers.lock();
+            opened_documents
+                .into_iter()
+                .filter_map(|(buffer_id, uri)| {
+                    let buffer = buffers.get(&buffer_id)?;
+                    Some((
+                        buffer_id,
+                        uri,
+                        buffer.language_id.clone(),
+                        buffer.get_document(),
+                    ))
+                })
+                .collect()
+        };
+        for (buffer_id, uri, language_id, text) in documents {
+            self.send_did_open(&buffer_id, uri, &language_id, text);
+        }
+    }
+
+    /// The `shutdown` request and `exit` notification handshake. The
+    /// process is killed if it doesn't exit in time.
+    fn shutdown(&self) {
+        self.state.lock().stopping = true;
+
+        let (sender, receiver) = channel();
+        self.send_request(
+            "shutdown",
+            Params::None(()),
+            Box::new(move |_: &LspClient, _| {
+                let _ = sender.send(true);
+            }),
+        );
+        let _ = receiver.recv_timeout(LSP_SHUTDOWN_TIMEOUT);
+        let exit = JsonRpc::notification("exit");
+        self.send_rpc(&to_value(&exit).unwrap());
+
+        let deadline = Instant::now() + LSP_SHUTDOWN_TIMEOUT;
+        loop {
+            let mut state = self.state.lock();
+            match state.process.try_wait() {
+                Ok(None) if Instant::now() < deadline => {}
+                Ok(Some(_)) => break,
+                _ => {
+                    let _ = state.process.kill();
+                    let _ = state.process.wait();
+                    break;
+                }
+            }
+            drop(state);
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        self.clear_diagnostics();
+        self.send_status(LspStatus::Stopped);
+    }
+
+    fn send_status(&self, status: LspStatus) {
+        send_server_status(&self.dispatcher, &self.server_id, status);
+    }
+
+    fn handles(&self, language_id: &str, path: &Path) -> bool {
+        self.server_id.language_id == language_id
+            && self
+                .server_id
+                .root
+                .as_ref()
+                .map(|root| path.starts_with(root))
+                .unwrap_or(true)
+    }
+
+    fn root_depth(&self) -> usize {
+        self.server_id
+            .root
+            .as_ref()
+            .map(|root| root.components().count())
+            .unwrap_or(0)
+    }
+
+    fn has_capability(&self, capability: fn(&ServerCapabilities) -> bool) -> bool {
+        self.state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .map(capability)
+            .unwrap_or(false)
+    }
+
+    /// Whether the server registered the command for
+    /// `workspace/executeCommand`.
+    fn provides_command(&self, command: &str) -> bool {
+        self.state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .and_then(|c| c.execute_command_provider.as_ref())
+            .map(|provider| provider.commands.iter().any(|c| c == command))
+            .unwrap_or(false)
+    }
+
+    fn semantic_tokens_legend(&self) -> Option<SemanticTokensLegend> {
+        self.state
+            .lock()
+            .server_capabilities
+            .as_ref()?
+            .semantic_tokens_provider
+            .as_ref()
+            .map(semantic_tokens_lengend)
+    }
+
+    /// The result id of the tokens last received for the document, if the
+    /// server can send the next ones as a delta of them.
+    fn semantic_tokens_result_id(&self, buffer_id: BufferId) -> Option<String> {
+        if !self.has_capability(semantic_tokens_delta_support) {
+            return None;
+        }
+        self.state
+            .lock()
+            .semantic_tokens
+            .get(&buffer_id)?
+            .result_id
+            .clone()
+    }
+
+    /// Stores the tokens of the whole document or applies the delta to the
+    /// previous ones, and returns the styles to send: those of the tokens
+    /// that changed along with the rev of the styles they replace some of
+    /// and the offsets they cover, or those of all the tokens.
+    fn update_semantic_tokens(
+        &self,
+        buffer: &Buffer,
+        rev: u64,
+        value: Value,
+    ) -> Option<(Option<u64>, usize, usize, Vec<LineStyle>)> {
+        let result: SemanticTokensFullDeltaResult =
+            serde_json::from_value(value).ok()?;
+        let legend = self.semantic_tokens_legend()?;
+        let mut state = self.state.lock();
+        let previous = state.semantic_tokens.remove(&buffer.id);
+
+        let (result_id, tokens, changed) = match result {
+            SemanticTokensFullDeltaResult::Tokens(tokens) => {
+                (tokens.result_id, tokens.data, None)
+            }
+            SemanticTokensFullDeltaResult::TokensDelta(delta) => {
+                let previous = previous?;
+                let (tokens, start, end) =
+                    apply_semantic_tokens_edits(previous.tokens, delta.edits)?;
+                let changed = if previous.sent {
+                    Some((previous.rev, start, end))
+                } else {
+                    None
+                };
+                (delta.result_id, tokens, changed)
+            }
+            SemanticTokensFullDeltaResult::PartialTokensDelta { edits } => {
+                let previous = previous?;
+                let (tokens, start, end) =
+                    apply_semantic_tokens_edits(previous.tokens, edits)?;
+                let changed = if previous.sent {
+                    Some((previous.rev, start, end))
+                } else {
+                    None
+                };
+                (None, tokens, changed)
+            }
+        };
+
+        // Tokens for an older rev are kept for the next delta, but their
+        // styles can't be used anymore.
+        let sent = buffer.rev == rev;
+        let styles = if sent {
+            Some(match changed {
+                Some((base_rev, start, end)) => {
+                    let (start, end, styles) =
+                        format_semantic_styles(buffer, &legend, &tokens, start, end);
+                    (Some(base_rev), start, end, styles)
+                }
+                None => {
+                    let (_, _, styles) = format_semantic_styles(
+                        buffer,
+                        &legend,
+                        &tokens,
+                        0,
+                        tokens.len(),
+                    );
+                    (None, 0, buffer.len(), styles)
+                }
+            })
+        } else {
+            None
+        };
+        state.semantic_tokens.insert(
+            buffer.id,
+            SemanticTokensState {
+                rev,
+                result_id,
+                tokens,
+                sent,
+            },
+        );
+        styles
+    }
+
+    /// The characters that trigger the signature help and the ones that
+    /// only trigger it again, None when the server has no signature help.
+    fn signature_help_characters(&self) -> Option<(Vec<String>, Vec<String>)> {
+        let state = self.state.lock();
+        let options = state
+            .server_capabilities
+            .as_ref()?
+            .signature_help_provider
+            .as_ref()?;
+        Some((
+            options.trigger_characters.clone().unwrap_or_default(),
+            options.retrigger_characters.clone().unwrap_or_default(),
+        ))
+    }
+
+    /// Once signature help is shown, any edit or cursor move asks for it
+    /// again, and the server decides whether it still applies.
+    fn is_signature_help_trigger(
+        &self,
+        ch: Option<&str>,
+        is_retrigger: bool,
+    ) -> bool {
+        let state = self.state.lock();
+        let options = match state
+            .server_capabilities
+            .as_ref()
+            .and_then(|c| c.signature_help_provider.as_ref())
+        {
+            Some(options) => options,
+            None => return false,
+        };
+        if is_retrigger {
+            return true;
+        }
+        match ch {
+            Some(ch) => options
+                .trigger_characters
+                .as_ref()
+                .map(|chars| chars.iter().any(|c| c == ch))
+                .unwrap_or(false),
+            None => false,
+        }
     }
 
-    fn stop(&self) {
-        let _ = self.state.lock().process.kill();
+    fn is_on_type_formatting_trigger(&self, ch: &str) -> bool {
+        self.state
+            .lock()
+            .server_capabilities
+            .as_ref()
+            .and_then(|c| c.document_on_type_formatting_provider.as_ref())
+            .map(|options| {
+                options.first_trigger_character == ch
+                    || options
+                        .more_trigger_character
+                        .as_ref()
+                        .map(|chars| chars.iter().any(|c| c == ch))
+                        .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    /// The request inlay hints are asked with, if the server has them.
+    /// rust-analyzer had its own request before the standard one existed.
+    fn inlay_hints_method(&self) -> Option<&'static str> {
+        let state = self.state.lock();
+        if !state.is_initialized {
+            return None;
+        }
+        match state.capabilities.get("inlayHintProvider") {
+            Some(Value::Null) | Some(Value::Bool(false)) | None => {}
+            Some(_) => return Some("textDocument/inlayHint"),
+        }
+        let is_rust_analyzer = Path::new(&self.server_id.exec_path)
+            .file_stem()
+            .map(|name| name.to_string_lossy().starts_with("rust-analyzer"))
+            .unwrap_or(false);
+        if is_rust_analyzer {
+            Some("rust-analyzer/inlayHints")
+        } else {
+            None
+        }
+    }
+
+    /// Call hierarchies are known to lsp-types, type hierarchies only to the
+    /// raw capabilities.
+    fn has_hierarchy(&self, kind: HierarchyKind) -> bool {
+        let state = self.state.lock();
+        if !state.is_initialized {
+            return false;
+        }
+        if kind.is_call() {
+            state
+                .server_capabilities
+                .as_ref()
+                .map(|c| {
+                    !matches!(
+                        c.call_hierarchy_provider,
+                        None | Some(CallHierarchyServerCapability::Simple(false))
+                    )
+                })
+                .unwrap_or(false)
+        } else {
+            !matches!(
+                state.capabilities.get("typeHierarchyProvider"),
+                Some(Value::Null) | Some(Value::Bool(false)) | None
+            )
+        }
     }
 
     pub fn get_uri(&self, buffer: &Buffer) -> Url {
@@ -473,9 +2079,16 @@ impl LspClient {
     }
 
     pub fn handle_message(&self, message: &str) {
+        if self.trace.load(Ordering::Relaxed) {
+            self.log(format!("[recv] {message}"));
+        }
         match JsonRpc::parse(message) {
-            Ok(JsonRpc::Request(_obj)) => {
-                // trace!("client received unexpected request: {:?}", obj)
+            Ok(value @ JsonRpc::Request(_)) => {
+                self.handle_request(
+                    value.get_id().unwrap(),
+                    value.get_method().unwrap(),
+                    value.get_params().unwrap_or(Params::None(())),
+                );
             }
             Ok(value @ JsonRpc::Notification(_)) => {
                 self.handle_notification(
@@ -500,12 +2113,17 @@ impl LspClient {
     pub fn handle_notification(&self, method: &str, params: Params) {
         match method {
             "textDocument/publishDiagnostics" => {
-                self.dispatcher.send_notification(
-                    "publish_diagnostics",
-                    json!({
-                        "diagnostics": params,
-                    }),
-                );
+                if let Ok(diagnostics) = serde_json::from_value::<
+                    PublishDiagnosticsParams,
+                >(to_value(params).unwrap())
+                {
+                    self.dispatcher.send_notification(
+                        "publish_diagnostics",
+                        json!({
+                            "diagnostics": self.merge_diagnostics(diagnostics),
+                        }),
+                    );
+                }
             }
             "$/progress" => {
                 self.dispatcher.send_notification(
@@ -519,6 +2137,212 @@ impl LspClient {
         }
     }
 
+    /// Answer a request sent by the language server. Requests that need
+    /// the user or the editor state are forwarded to the core, and the
+    /// reply is sent back once the core responds.
+    pub fn handle_request(&self, id: Id, method: &str, params: Params) {
+        let params = serde_json::to_value(params).unwrap_or(Value::Null);
+        match method {
+            "workspace/configuration" => {
+                let result =
+                    match serde_json::from_value::<ConfigurationParams>(params) {
+                        Ok(params) => Ok(Value::Array(
+                            params
+                                .items
+                                .iter()
+                                .map(|item| {
+                                    self.configuration(item.section.as_deref())
+                                })
+                                .collect(),
+                        )),
+                        Err(_) => Err(jsonrpc_lite::Error::invalid_params()),
+                    };
+                self.send_response(id, result);
+            }
+            "workspace/workspaceFolders" => {
+                let folders = self.dispatcher.workspace.lock().as_ref().and_then(
+                    |workspace| {
+                        let uri = Url::from_directory_path(workspace).ok()?;
+                        let name = workspace
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        Some(vec![WorkspaceFolder { uri, name }])
+                    },
+                );
+                self.send_response(id, Ok(json!(folders)));
+            }
+            "workspace/diagnostic/refresh" => {
+                self.send_response(id, Ok(Value::Null));
+                let uris: Vec<Url> = self
+                    .state
+                    .lock()
+                    .opened_documents
+                    .values()
+                    .cloned()
+                    .collect();
+                for uri in uris {
+                    self.pull_diagnostics(uri);
+                }
+                self.pull_workspace_diagnostics();
+            }
+            "client/registerCapability"
+            | "client/unregisterCapability"
+            | "window/workDoneProgress/create" => {
+                self.send_response(id, Ok(Value::Null));
+            }
+            "workspace/applyEdit" => {
+                let params =
+                    match serde_json::from_value::<ApplyWorkspaceEditParams>(params)
+                    {
+                        Ok(params) => params,
+                        Err(_) => {
+                            self.send_response(
+                                id,
+                                Err(jsonrpc_lite::Error::invalid_params()),
+                            );
+                            return;
+                        }
+                    };
+                let local_lsp_client = self.clone();
+                self.dispatcher.send_rpc_request_async(
+                    CoreRequest::ApplyWorkspaceEdit { edit: params.edit },
+                    move |result| {
+                        let response = match result {
+                            Ok(value) => serde_json::from_value(value).unwrap_or(
+                                ApplyWorkspaceEditResponse {
+                                    applied: false,
+                                    failure_reason: None,
+                                    failed_change: None,
+                                },
+                            ),
+                            Err(e) => ApplyWorkspaceEditResponse {
+                                applied: false,
+                                failure_reason: Some(e.to_string()),
+                                failed_change: None,
+                            },
+                        };
+                        local_lsp_client.send_response(id, Ok(json!(response)));
+                    },
+                );
+            }
+            "window/showMessageRequest" => {
+                let params =
+                    match serde_json::from_value::<ShowMessageRequestParams>(params)
+                    {
+                        Ok(params) => params,
+                        Err(_) => {
+                            self.send_response(
+                                id,
+                                Err(jsonrpc_lite::Error::invalid_params()),
+                            );
+                            return;
+                        }
+                    };
+                let local_lsp_client = self.clone();
+                self.dispatcher.send_rpc_request_async(
+                    CoreRequest::ShowMessageRequest { params },
+                    move |result| {
+                        // The user not picking any action is answered with null
+                        let action = result.unwrap_or(Value::Null);
+                        local_lsp_client.send_response(id, Ok(action));
+                    },
+                );
+            }
+            _ => {
+                self.send_response(id, Err(jsonrpc_lite::Error::method_not_found()));
+            }
+        }
+    }
+
+    /// Look up the settings for a `workspace/configuration` item in the
+    /// options the server was started with. Servers usually ask for their
+    /// own top level section, which is what the options already hold.
+    fn configuration(&self, section: Option<&str>) -> Value {
+        let options = match self.options.as_ref() {
+            Some(options) => options,
+            None => return Value::Null,
+        };
+        let section = match section {
+            Some(section) => section,
+            None => return options.clone(),
+        };
+        let mut value = options;
+        for key in section.split('.') {
+            match value.get(key) {
+                Some(v) => value = v,
+                None => {
+                    return if section.contains('.') {
+                        Value::Null
+                    } else {
+                        options.clone()
+                    };
+                }
+            }
+        }
+        value.clone()
+    }
+
+    /// Drop everything this server published, and update the documents it
+    /// had diagnostics for.
+    fn clear_diagnostics(&self) {
+        let cleared: Vec<PublishDiagnosticsParams> = {
+            let mut store = self.diagnostics.lock();
+            let uris: Vec<Url> = store
+                .iter()
+                .filter(|(_, servers)| servers.contains_key(&self.server_id))
+                .map(|(uri, _)| uri.clone())
+                .collect();
+            uris.into_iter()
+                .map(|uri| {
+                    let servers = store.get_mut(&uri).unwrap();
+                    servers.remove(&self.server_id);
+                    let diagnostics = servers.values().flatten().cloned().collect();
+                    if servers.is_empty() {
+                        store.remove(&uri);
+                    }
+                    PublishDiagnosticsParams {
+                        uri,
+                        diagnostics,
+                        version: None,
+                    }
+                })
+                .collect()
+        };
+        for diagnostics in cleared {
+            self.dispatcher.send_notification(
+                "publish_diagnostics",
+                json!({
+                    "diagnostics": diagnostics,
+                }),
+            );
+        }
+    }
+
+    /// Record what this server published for a document, and return the
+    /// diagnostics of all servers for it.
+    fn merge_diagnostics(
+        &self,
+        params: PublishDiagnosticsParams,
+    ) -> PublishDiagnosticsParams {
+        let mut store = self.diagnostics.lock();
+        let servers = store.entry(params.uri.clone()).or_default();
+        if params.diagnostics.is_empty() {
+            servers.remove(&self.server_id);
+        } else {
+            servers.insert(self.server_id.clone(), params.diagnostics);
+        }
+        let diagnostics = servers.values().flatten().cloned().collect();
+        if servers.is_empty() {
+            store.remove(&params.uri);
+        }
+        PublishDiagnosticsParams {
+            uri: params.uri,
+            diagnostics,
+            version: params.version,
+        }
+    }
+
     pub fn handle_response(&self, id: u64, result: Result<Value>) {
         let callback =
             {
@@ -541,10 +2365,25 @@ impl LspClient {
             Ok(r) => r,
             Err(err) => panic!("Encoding Error {:?}", err),
         };
+        if self.trace.load(Ordering::Relaxed) {
+            self.log(format!("[send] {value}"));
+        }
 
         let _ = self.write(rpc.as_ref());
     }
 
+    fn send_response(
+        &self,
+        id: Id,
+        result: std::result::Result<Value, jsonrpc_lite::Error>,
+    ) {
+        let response = match result {
+            Ok(result) => JsonRpc::success(id, &result),
+            Err(error) => JsonRpc::error(id, error),
+        };
+        self.send_rpc(&to_value(&response).unwrap());
+    }
+
     pub fn send_notification(&self, method: &str, params: Params) {
         let notification = JsonRpc::notification_with_params(method, params);
         let res = to_value(&notification).unwrap();
@@ -565,19 +2404,30 @@ impl LspClient {
     }
 
     fn initialize(&self) {
-        if let Some(workspace) = self.dispatcher.workspace.lock().clone() {
-            let root_url = Url::from_directory_path(workspace).unwrap();
+        let root = self
+            .server_id
+            .root
+            .clone()
+            .or_else(|| self.dispatcher.workspace.lock().clone());
+        if let Some(root) = root {
+            // A root that isn't absolute has no URL, and the server is
+            // started without one rather than not at all.
+            let root_url = Url::from_directory_path(root).ok();
             let (sender, receiver) = channel();
-            self.send_initialize(Some(root_url), move |lsp_client, result| {
+            self.send_initialize(root_url, move |lsp_client, result| {
                 if let Ok(result) = result {
                     {
+                        let capabilities = result["capabilities"].clone();
                         let init_result: InitializeResult =
                             serde_json::from_value(result).unwrap();
                         let mut state = lsp_client.state.lock();
                         state.server_capabilities = Some(init_result.capabilities);
+                        state.capabilities = capabilities;
                         state.is_initialized = true;
                     }
                     lsp_client.send_initialized();
+                    lsp_client.send_status(LspStatus::Running);
+                    lsp_client.pull_workspace_diagnostics();
                 }
                 let _ = sender.send(true);
             });
@@ -607,7 +2457,7 @@ impl LspClient {
         let text_document_did_open_params = DidOpenTextDocumentParams {
             text_document: TextDocumentItem {
                 language_id: language_id.to_string(),
-                uri: document_uri,
+                uri: document_uri.clone(),
                 version: 0,
                 text: document_text,
             },
@@ -616,15 +2466,234 @@ impl LspClient {
             serde_json::to_value(text_document_did_open_params).unwrap(),
         );
         self.send_notification("textDocument/didOpen", params);
+        self.pull_diagnostics(document_uri);
+    }
+
+    pub fn send_did_close(&self, buffer_id: &BufferId) {
+        let uri = {
+            let mut state = self.state.lock();
+            state.semantic_tokens.remove(buffer_id);
+            let uri = state.opened_documents.remove(buffer_id);
+            if let Some(uri) = uri.as_ref() {
+                state.diagnostic_result_ids.remove(uri);
+                state.diagnostic_pulls.remove(uri);
+            }
+            uri
+        };
+        if let Some(uri) = uri {
+            let params = DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri },
+            };
+            let params = Params::from(serde_json::to_value(params).unwrap());
+            self.send_notification("textDocument/didClose", params);
+        }
     }
 
     pub fn send_did_save(&self, uri: Url) {
         let params = DidSaveTextDocumentParams {
-            text_document: TextDocumentIdentifier { uri },
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
             text: None,
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
         self.send_notification("textDocument/didSave", params);
+
+        // Saving can change the diagnostics of the other documents too when
+        // they depend on each other.
+        let inter_file_dependencies = self
+            .diagnostic_provider()
+            .map(|provider| provider["interFileDependencies"] == json!(true))
+            .unwrap_or(false);
+        let uris: Vec<Url> = if inter_file_dependencies {
+            self.state
+                .lock()
+                .opened_documents
+                .values()
+                .cloned()
+                .collect()
+        } else {
+            vec![uri]
+        };
+        for uri in uris {
+            self.pull_diagnostics(uri);
+        }
+        self.pull_workspace_diagnostics();
+    }
+
+    /// The `diagnosticProvider` capability of the server, if it has
+    /// diagnostics to be pulled.
+    fn diagnostic_provider(&self) -> Option<Value> {
+        let state = self.state.lock();
+        match &state.capabilities["diagnosticProvider"] {
+            Value::Null | Value::Bool(false) => None,
+            provider => Some(provider.clone()),
+        }
+    }
+
+    /// Asks the server for the diagnostics of a document, for the servers
+    /// that don't push them.
+    pub fn pull_diagnostics(&self, uri: Url) {
+        let provider = match self.diagnostic_provider() {
+            Some(provider) => provider,
+            None => return,
+        };
+        let mut params = json!({
+            "textDocument": { "uri": uri },
+        });
+        if let Some(identifier) = provider["identifier"].as_str() {
+            params["identifier"] = json!(identifier);
+        }
+        if let Some(result_id) = self.state.lock().diagnostic_result_ids.get(&uri) {
+            params["previousResultId"] = json!(result_id);
+        }
+        self.send_request(
+            "textDocument/diagnostic",
+            Params::from(params),
+            Box::new(move |lsp_client: &LspClient, result: Result<Value>| {
+                if let Ok(report) = result {
+                    lsp_client.receive_diagnostic_report(uri, &report);
+                }
+            }),
+        );
+    }
+
+    /// Pulls the diagnostics of the document once it stopped changing for a
+    /// while.
+    fn schedule_diagnostics_pull(&self, uri: Url) {
+        if self.diagnostic_provider().is_none() {
+            return;
+        }
+        {
+            let mut state = self.state.lock();
+            state
+                .diagnostic_pulls
+                .insert(uri, Instant::now() + LSP_DIAGNOSTIC_PULL_DELAY);
+            if state.diagnostic_puller {
+                return;
+            }
+            state.diagnostic_puller = true;
+        }
+        let local_lsp_client = self.clone();
+        thread::spawn(move || local_lsp_client.pull_scheduled_diagnostics());
+    }
+
+    /// Pulls the diagnostics of the documents as their time comes, until
+    /// there are none left to pull.
+    fn pull_scheduled_diagnostics(&self) {
+        loop {
+            let (due, next) = {
+                let mut state = self.state.lock();
+                let now = Instant::now();
+                let due: Vec<Url> = state
+                    .diagnostic_pulls
+                    .iter()
+                    .filter(|(_, at)| **at <= now)
+                    .map(|(uri, _)| uri.clone())
+                    .collect();
+                for uri in &due {
+                    state.diagnostic_pulls.remove(uri);
+                }
+                let next = state.diagnostic_pulls.values().min().copied();
+                if due.is_empty() && next.is_none() {
+                    state.diagnostic_puller = false;
+                    return;
+                }
+                (due, next)
+            };
+            for uri in due {
+                self.pull_diagnostics(uri);
+            }
+            if let Some(next) = next {
+                thread::sleep(next.saturating_duration_since(Instant::now()));
+            }
+        }
+    }
+
+    /// Asks the server for the diagnostics of the whole workspace, if it
+    /// has them.
+    pub fn pull_workspace_diagnostics(&self) {
+        let provider = match self.diagnostic_provider() {
+            Some(provider) => provider,
+            None => return,
+        };
+        if provider["workspaceDiagnostics"] != json!(true) {
+            return;
+        }
+        let previous_result_ids: Vec<Value> = {
+            let mut state = self.state.lock();
+            if state.pulling_workspace_diagnostics {
+                return;
+            }
+            state.pulling_workspace_diagnostics = true;
+            state
+                .diagnostic_result_ids
+                .iter()
+                .map(|(uri, result_id)| json!({ "uri": uri, "value": result_id }))
+                .collect()
+        };
+        let mut params = json!({
+            "previousResultIds": previous_result_ids,
+        });
+        if let Some(identifier) = provider["identifier"].as_str() {
+            params["identifier"] = json!(identifier);
+        }
+        self.send_request(
+            "workspace/diagnostic",
+            Params::from(params),
+            Box::new(move |lsp_client: &LspClient, result: Result<Value>| {
+                lsp_client.state.lock().pulling_workspace_diagnostics = false;
+                if let Ok(result) = result {
+                    for report in result["items"].as_array().into_iter().flatten() {
+                        if let Some(uri) = report["uri"]
+                            .as_str()
+                            .and_then(|uri| Url::parse(uri).ok())
+                        {
+                            lsp_client.receive_diagnostic_report(uri, report);
+                        }
+                    }
+                }
+            }),
+        );
+    }
+
+    /// Handles a full or unchanged diagnostic report, as the pushed
+    /// diagnostics are.
+    fn receive_diagnostic_report(&self, uri: Url, report: &Value) {
+        if report["kind"] == json!("full") {
+            let diagnostics: Vec<Diagnostic> =
+                serde_json::from_value(report["items"].clone()).unwrap_or_default();
+            {
+                let mut state = self.state.lock();
+                match report["resultId"].as_str() {
+                    Some(result_id) => {
+                        state
+                            .diagnostic_result_ids
+                            .insert(uri.clone(), result_id.to_string());
+                    }
+                    None => {
+                        state.diagnostic_result_ids.remove(&uri);
+                    }
+                }
+            }
+            let params = PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            };
+            self.dispatcher.send_notification(
+                "publish_diagnostics",
+                json!({
+                    "diagnostics": self.merge_diagnostics(params),
+                }),
+            );
+        }
+
+        if let Some(related) = report["relatedDocuments"].as_object() {
+            for (uri, report) in related {
+                if let Ok(uri) = Url::parse(uri) {
+                    self.receive_diagnostic_report(uri, report);
+                }
+            }
+        }
     }
 
     pub fn send_initialized(&self) {
@@ -649,19 +2718,17 @@ impl LspClient {
                     }),
                     ..Default::default()
                 }),
-                // signature_help: Some(SignatureHelpCapability {
-                //     signature_information: Some(SignatureInformationSettings {
-                //         parameter_information: Some(ParameterInformationSettings {
-                //             label_offset_support: Some(true),
-                //         }),
-                //         active_parameter_support: Some(true),
-                //         documentation_format: Some(vec![
-                //             MarkupKind::Markdown,
-                //             MarkupKind::PlainText,
-                //         ]),
-                //     }),
-                //     ..Default::default()
-                // }),
+                signature_help: Some(SignatureHelpClientCapabilities {
+                    signature_information: Some(SignatureInformationSettings {
+                        parameter_information: Some(ParameterInformationSettings {
+                            label_offset_support: Some(true),
+                        }),
+                        active_parameter_support: Some(true),
+                        documentation_format: Some(vec![MarkupKind::PlainText]),
+                    }),
+                    context_support: Some(true),
+                    ..Default::default()
+                }),
                 hover: Some(HoverClientCapabilities {
                     content_format: Some(vec![
                         MarkupKind::Markdown,
@@ -690,14 +2757,68 @@ impl LspClient {
                             ],
                         },
                     }),
+                    data_support: Some(true),
+                    resolve_support: Some(CodeActionCapabilityResolveSupport {
+                        properties: vec!["edit".to_string()],
+                    }),
                     ..Default::default()
                 }),
                 semantic_tokens: Some(SemanticTokensClientCapabilities {
+                    requests: SemanticTokensClientCapabilitiesRequests {
+                        range: Some(true),
+                        full: Some(SemanticTokensFullOptions::Delta {
+                            delta: Some(true),
+                        }),
+                    },
+                    formats: vec![TokenFormat::RELATIVE],
+                    ..Default::default()
+                }),
+                rename: Some(RenameClientCapabilities {
+                    prepare_support: Some(true),
+                    ..Default::default()
+                }),
+                call_hierarchy: Some(CallHierarchyClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
+                range_formatting: Some(DocumentRangeFormattingClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
+                on_type_formatting: Some(
+                    DocumentOnTypeFormattingClientCapabilities {
+                        dynamic_registration: Some(false),
+                    },
+                ),
+                folding_range: Some(FoldingRangeClientCapabilities {
+                    line_folding_only: Some(true),
+                    ..Default::default()
+                }),
+                selection_range: Some(SelectionRangeClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
+                publish_diagnostics: Some(PublishDiagnosticsClientCapabilities {
+                    related_information: Some(true),
+                    tag_support: Some(TagSupport {
+                        value_set: vec![
+                            DiagnosticTag::Unnecessary,
+                            DiagnosticTag::Deprecated,
+                        ],
+                    }),
+                    code_description_support: Some(true),
                     ..Default::default()
                 }),
+                code_lens: Some(CodeLensClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
 
                 ..Default::default()
             }),
+            workspace: Some(WorkspaceClientCapabilities {
+                apply_edit: Some(true),
+                execute_command: Some(DynamicRegistrationClientCapabilities {
+                    dynamic_registration: Some(false),
+                }),
+                ..Default::default()
+            }),
             window: Some(WindowClientCapabilities {
                 work_done_progress: Some(true),
                 show_message: Some(ShowMessageRequestClientCapabilities {
@@ -725,67 +2846,318 @@ impl LspClient {
             root_path: None,
             locale: None,
         };
-
-        let params = Params::from(serde_json::to_value(init_params).unwrap());
-        self.send_request("initialize", params, Box::new(on_init));
+
+        let mut params = serde_json::to_value(init_params).unwrap();
+        // lsp-types doesn't have the inlay hint, type hierarchy and pulled
+        // diagnostics capabilities yet
+        params["capabilities"]["textDocument"]["inlayHint"] = json!({
+            "dynamicRegistration": false,
+        });
+        params["capabilities"]["textDocument"]["typeHierarchy"] = json!({
+            "dynamicRegistration": false,
+        });
+        params["capabilities"]["textDocument"]["diagnostic"] = json!({
+            "dynamicRegistration": false,
+            "relatedDocumentSupport": true,
+        });
+        params["capabilities"]["workspace"]["diagnostics"] = json!({
+            "refreshSupport": true,
+        });
+        let params = Params::from(params);
+        self.send_request("initialize", params, Box::new(on_init));
+    }
+
+    pub fn request_document_symbols<CB>(&self, document_uri: Url, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/documentSymbol", params, Box::new(cb));
+    }
+
+    pub fn request_folding_ranges<CB>(&self, document_uri: Url, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = FoldingRangeParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/foldingRange", params, Box::new(cb));
+    }
+
+    pub fn request_selection_ranges<CB>(
+        &self,
+        document_uri: Url,
+        positions: Vec<Position>,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = SelectionRangeParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            positions,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/selectionRange", params, Box::new(cb));
+    }
+
+    pub fn request_prepare_hierarchy<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        kind: HierarchyKind,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        // The type hierarchy request takes the same parameters.
+        let params = CallHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let method = if kind.is_call() {
+            "textDocument/prepareCallHierarchy"
+        } else {
+            "textDocument/prepareTypeHierarchy"
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request(method, params, Box::new(cb));
+    }
+
+    pub fn request_hierarchy_children<CB>(
+        &self,
+        kind: HierarchyKind,
+        item: CallHierarchyItem,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let (method, params) = match kind {
+            HierarchyKind::IncomingCalls => (
+                "callHierarchy/incomingCalls",
+                serde_json::to_value(CallHierarchyIncomingCallsParams {
+                    item,
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                }),
+            ),
+            HierarchyKind::OutgoingCalls => (
+                "callHierarchy/outgoingCalls",
+                serde_json::to_value(CallHierarchyOutgoingCallsParams {
+                    item,
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                }),
+            ),
+            HierarchyKind::Supertypes => {
+                ("typeHierarchy/supertypes", Ok(json!({ "item": item })))
+            }
+            HierarchyKind::Subtypes => {
+                ("typeHierarchy/subtypes", Ok(json!({ "item": item })))
+            }
+        };
+        let params = Params::from(params.unwrap());
+        self.send_request(method, params, Box::new(cb));
+    }
+
+    pub fn request_workspace_symbols<CB>(&self, query: &str, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = WorkspaceSymbolParams {
+            query: query.to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("workspace/symbol", params, Box::new(cb));
+    }
+
+    pub fn request_document_formatting<CB>(&self, document_uri: Url, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/formatting", params, Box::new(cb));
+    }
+
+    pub fn request_range_formatting<CB>(
+        &self,
+        document_uri: Url,
+        range: Range,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            range,
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rangeFormatting", params, Box::new(cb));
+    }
+
+    pub fn request_on_type_formatting<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        ch: &str,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentOnTypeFormattingParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            ch: ch.to_string(),
+            options: FormattingOptions {
+                tab_size: 4,
+                insert_spaces: true,
+                ..Default::default()
+            },
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/onTypeFormatting", params, Box::new(cb));
+    }
+
+    pub fn request_semantic_tokens<CB>(&self, document_uri: Url, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = SemanticTokensParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/semanticTokens/full", params, Box::new(cb));
+    }
+
+    pub fn request_semantic_tokens_delta<CB>(
+        &self,
+        document_uri: Url,
+        previous_result_id: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = SemanticTokensDeltaParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            previous_result_id,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request(
+            "textDocument/semanticTokens/full/delta",
+            params,
+            Box::new(cb),
+        );
     }
 
-    pub fn request_document_symbols<CB>(&self, document_uri: Url, cb: CB)
-    where
+    pub fn request_semantic_tokens_range<CB>(
+        &self,
+        document_uri: Url,
+        range: Range,
+        cb: CB,
+    ) where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
-        let params = DocumentSymbolParams {
+        let params = SemanticTokensRangeParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
+            range,
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/documentSymbol", params, Box::new(cb));
+        self.send_request("textDocument/semanticTokens/range", params, Box::new(cb));
     }
 
-    pub fn request_document_formatting<CB>(&self, document_uri: Url, cb: CB)
+    pub fn request_code_actions<CB>(&self, document_uri: Url, range: Range, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
-        let params = DocumentFormattingParams {
+        let params = CodeActionParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
-            options: FormattingOptions {
-                tab_size: 4,
-                insert_spaces: true,
-                ..Default::default()
-            },
+            range,
+            context: CodeActionContext::default(),
             work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/formatting", params, Box::new(cb));
+        self.send_request("textDocument/codeAction", params, Box::new(cb));
     }
 
-    pub fn request_semantic_tokens<CB>(&self, document_uri: Url, cb: CB)
+    pub fn request_code_lens<CB>(&self, document_uri: Url, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
-        let params = SemanticTokensParams {
+        let params = CodeLensParams {
             text_document: TextDocumentIdentifier { uri: document_uri },
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/semanticTokens/full", params, Box::new(cb));
+        self.send_request("textDocument/codeLens", params, Box::new(cb));
     }
 
-    pub fn request_code_actions<CB>(&self, document_uri: Url, range: Range, cb: CB)
+    pub fn code_action_resolve<CB>(&self, action: &CodeAction, cb: CB)
     where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
     {
-        let params = CodeActionParams {
-            text_document: TextDocumentIdentifier { uri: document_uri },
-            range,
-            context: CodeActionContext::default(),
+        let params = Params::from(serde_json::to_value(action).unwrap());
+        self.send_request("codeAction/resolve", params, Box::new(cb));
+    }
+
+    pub fn code_lens_resolve<CB>(&self, code_lens: &CodeLens, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = Params::from(serde_json::to_value(code_lens).unwrap());
+        self.send_request("codeLens/resolve", params, Box::new(cb));
+    }
+
+    pub fn execute_command<CB>(&self, command: &lsp_types::Command, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = ExecuteCommandParams {
+            command: command.command.clone(),
+            arguments: command.arguments.clone().unwrap_or_default(),
             work_done_progress_params: WorkDoneProgressParams::default(),
-            partial_result_params: PartialResultParams::default(),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
-        self.send_request("textDocument/codeAction", params, Box::new(cb));
+        self.send_request("workspace/executeCommand", params, Box::new(cb));
     }
 
     pub fn request_references<CB>(
@@ -831,6 +3203,66 @@ impl LspClient {
         self.send_request("textDocument/definition", params, Box::new(cb));
     }
 
+    pub fn request_type_definition<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = request::GotoTypeDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/typeDefinition", params, Box::new(cb));
+    }
+
+    pub fn request_implementation<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = request::GotoImplementationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/implementation", params, Box::new(cb));
+    }
+
+    pub fn request_declaration<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = request::GotoDeclarationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/declaration", params, Box::new(cb));
+    }
+
     pub fn request_completion<CB>(
         &self,
         document_uri: Url,
@@ -886,6 +3318,7 @@ impl LspClient {
         &self,
         document_uri: Url,
         position: Position,
+        context: SignatureHelpContext,
         cb: CB,
     ) where
         CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
@@ -896,12 +3329,101 @@ impl LspClient {
                 position,
             },
             work_done_progress_params: WorkDoneProgressParams::default(),
-            context: None,
+            context: Some(context),
         };
         let params = Params::from(serde_json::to_value(params).unwrap());
         self.send_request("textDocument/signatureHelp", params, Box::new(cb));
     }
 
+    fn prepare_rename_support(&self) -> bool {
+        let state = self.state.lock();
+        matches!(
+            state
+                .server_capabilities
+                .as_ref()
+                .and_then(|c| c.rename_provider.as_ref()),
+            Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                ..
+            }))
+        )
+    }
+
+    pub fn request_document_highlights<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = DocumentHighlightParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/documentHighlight", params, Box::new(cb));
+    }
+
+    pub fn request_inlay_hints<CB>(&self, document_uri: Url, range: Range, cb: CB)
+    where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let method = match self.inlay_hints_method() {
+            Some(method) => method,
+            None => {
+                cb(self, Ok(Value::Array(Vec::new())));
+                return;
+            }
+        };
+        let params = json!({
+            "textDocument": TextDocumentIdentifier { uri: document_uri },
+            "range": range,
+        });
+        self.send_request(method, Params::from(params), Box::new(cb));
+    }
+
+    pub fn request_prepare_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: document_uri },
+            position,
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/prepareRename", params, Box::new(cb));
+    }
+
+    pub fn request_rename<CB>(
+        &self,
+        document_uri: Url,
+        position: Position,
+        new_name: String,
+        cb: CB,
+    ) where
+        CB: 'static + Send + FnOnce(&LspClient, Result<Value>),
+    {
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: document_uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let params = Params::from(serde_json::to_value(params).unwrap());
+        self.send_request("textDocument/rename", params, Box::new(cb));
+    }
+
     pub fn send_did_change(
         &self,
         buffer: &Buffer,
@@ -941,10 +3463,17 @@ impl LspClient {
         content_change: &TextDocumentContentChangeEvent,
         rev: u64,
     ) {
+        // A document the server doesn't have yet goes out whole with its
+        // didOpen, so the changes until then aren't sent on their own.
+        if !self.state.lock().opened_documents.contains_key(&buffer.id) {
+            self.get_uri(buffer);
+            return;
+        }
         let sync_kind = self.get_sync_kind().unwrap_or(TextDocumentSyncKind::Full);
         let changes = get_change_for_sync_kind(sync_kind, buffer, content_change);
         if let Some(changes) = changes {
             self.send_did_change(buffer, changes, rev);
+            self.schedule_diagnostics_pull(self.get_uri(buffer));
         }
     }
 }
@@ -954,6 +3483,105 @@ pub enum LspHeader {
     ContentLength(usize),
 }
 
+/// The closest folder above the document with one of the root markers in
+/// it, if it's inside the workspace and not the workspace itself.
+fn find_root(
+    path: &Path,
+    root_markers: &[String],
+    workspace: Option<&Path>,
+) -> Option<PathBuf> {
+    if root_markers.is_empty() {
+        return None;
+    }
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| {
+            workspace
+                .map(|workspace| dir.starts_with(workspace) && *dir != workspace)
+                .unwrap_or(true)
+        })
+        .find(|dir| root_markers.iter().any(|marker| dir.join(marker).exists()))
+        .map(|dir| dir.to_path_buf())
+}
+
+/// Sends the styles of the semantic tokens the server answered with, or asks
+/// for all of them again when the delta it sent for a `delta` request
+/// couldn't be applied to the previous ones.
+fn semantic_tokens_callback(
+    dispatcher: Dispatcher,
+    buffer: &Buffer,
+    delta: bool,
+) -> impl FnOnce(&LspClient, Result<Value>) + Send + 'static {
+    let buffer_id = buffer.id;
+    let path = buffer.path.clone();
+    let rev = buffer.rev;
+    let len = buffer.len();
+    move |lsp_client: &LspClient, result: Result<Value>| {
+        if let Ok(res) = result {
+            let buffers = dispatcher.buffers.lock();
+            let buffer = match buffers.get(&buffer_id) {
+                Some(buffer) => buffer,
+                None => return,
+            };
+            if let Some((base_rev, start, end, styles)) =
+                lsp_client.update_semantic_tokens(buffer, rev, res)
+            {
+                dispatcher.send_notification(
+                    "semantic_styles",
+                    json!({
+                        "rev": rev,
+                        "buffer_id": buffer_id,
+                        "path": path,
+                        "styles": styles,
+                        "len": len,
+                        "base_rev": base_rev,
+                        "start": start,
+                        "end": end,
+                    }),
+                )
+            } else if delta
+                && !lsp_client
+                    .state
+                    .lock()
+                    .semantic_tokens
+                    .contains_key(&buffer_id)
+            {
+                let uri = lsp_client.get_uri(buffer);
+                let cb = semantic_tokens_callback(dispatcher.clone(), buffer, false);
+                lsp_client.request_semantic_tokens(uri, cb);
+            }
+        }
+    }
+}
+
+fn send_server_status(
+    dispatcher: &Dispatcher,
+    server_id: &LspServerId,
+    status: LspStatus,
+) {
+    dispatcher.send_rpc_notification(CoreNotification::LspServerStatus {
+        server: server_id.log_name(),
+        status,
+    });
+}
+
+/// Completion items, code actions and code lenses from several servers end
+/// up in one list, so each of them keeps the server it came from next to its
+/// own data, for resolving them and running their commands later.
+fn tag_data(data: &mut Option<Value>, server_id: &LspServerId) {
+    *data = Some(json!(TaggedData {
+        server: server_id.clone(),
+        data: data.take(),
+    }));
+}
+
+fn untag_data(data: &mut Option<Value>) -> Option<LspServerId> {
+    let tagged =
+        serde_json::from_value::<TaggedData<LspServerId>>(data.clone()?).ok()?;
+    *data = tagged.data;
+    Some(tagged.server)
+}
+
 fn number_from_id(id: &Id) -> u64 {
     match *id {
         Id::Num(n) => n as u64,
@@ -988,6 +3616,80 @@ fn parse_header(s: &str) -> Result<LspHeader> {
     }
 }
 
+/// Reads the items out of a hierarchy answer. Calls are answered with the
+/// item on the other end of the call and the ranges of the call, of which
+/// only the item is kept.
+fn parse_hierarchy_children(
+    kind: HierarchyKind,
+    value: Value,
+) -> Vec<CallHierarchyItem> {
+    match kind {
+        HierarchyKind::IncomingCalls => {
+            serde_json::from_value::<Vec<CallHierarchyIncomingCall>>(value)
+                .map(|calls| calls.into_iter().map(|call| call.from).collect())
+        }
+        HierarchyKind::OutgoingCalls => {
+            serde_json::from_value::<Vec<CallHierarchyOutgoingCall>>(value)
+                .map(|calls| calls.into_iter().map(|call| call.to).collect())
+        }
+        HierarchyKind::Supertypes | HierarchyKind::Subtypes => {
+            serde_json::from_value(value)
+        }
+    }
+    .unwrap_or_default()
+}
+
+/// Reads the hints out of either a `textDocument/inlayHint` answer, or a
+/// `rust-analyzer/inlayHints` one, whose hints have a range and a kind name
+/// instead of a position.
+fn parse_inlay_hints(value: Value) -> Vec<InlayHint> {
+    let hints = match value {
+        Value::Array(hints) => hints,
+        _ => return Vec::new(),
+    };
+    hints
+        .iter()
+        .filter_map(|hint| {
+            let label = match hint.get("label")? {
+                Value::String(label) => label.clone(),
+                Value::Array(parts) => parts
+                    .iter()
+                    .filter_map(|part| part.get("value")?.as_str())
+                    .collect(),
+                _ => return None,
+            };
+            if let Some(position) = hint.get("position") {
+                let padding = |key: &str| {
+                    hint.get(key).and_then(Value::as_bool).unwrap_or(false)
+                };
+                return Some(InlayHint {
+                    position: serde_json::from_value(position.clone()).ok()?,
+                    label,
+                    padding_left: padding("paddingLeft"),
+                    padding_right: padding("paddingRight"),
+                });
+            }
+            let range: Range =
+                serde_json::from_value(hint.get("range")?.clone()).ok()?;
+            let hint = match hint.get("kind")?.as_str()? {
+                "ParameterHint" => InlayHint {
+                    position: range.start,
+                    label: format!("{}:", label),
+                    padding_left: false,
+                    padding_right: true,
+                },
+                _ => InlayHint {
+                    position: range.end,
+                    label: format!(": {}", label),
+                    padding_left: false,
+                    padding_right: false,
+                },
+            };
+            Some(hint)
+        })
+        .collect()
+}
+
 pub fn read_message<T: BufRead>(reader: &mut T) -> Result<String> {
     let mut buffer = String::new();
     let mut content_length: Option<usize> = None;
@@ -1037,43 +3739,131 @@ pub fn get_change_for_sync_kind(
     }
 }
 
+/// The styles of the tokens from the `start`th one to before the `end`th
+/// one, and the offsets they cover: from the end of the token before them
+/// to the start of the token after them.
 fn format_semantic_styles(
     buffer: &Buffer,
-    semantic_tokens_provider: &Option<SemanticTokensServerCapabilities>,
-    value: Value,
-) -> Option<Vec<LineStyle>> {
-    let semantic_tokens: SemanticTokens = serde_json::from_value(value).ok()?;
-    let semantic_tokens_provider = semantic_tokens_provider.as_ref()?;
-    let semantic_lengends = semantic_tokens_lengend(semantic_tokens_provider);
-
+    legend: &SemanticTokensLegend,
+    tokens: &[SemanticToken],
+    start: usize,
+    end: usize,
+) -> (usize, usize, Vec<LineStyle>) {
     let mut highlights = Vec::new();
+    let mut from = 0;
+    let mut to = buffer.len();
     let mut line = 0;
-    let mut start = 0;
+    let mut character = 0;
     let mut last_start = 0;
-    for semantic_token in &semantic_tokens.data {
+    for (i, semantic_token) in tokens.iter().enumerate() {
+        // The positions are relative to the previous token, so they are
+        // followed from the first one.
         if semantic_token.delta_line > 0 {
             line += semantic_token.delta_line as usize;
-            start = buffer.offset_of_line(line);
-        }
-        start += semantic_token.delta_start as usize;
-        let end = start + semantic_token.length as usize;
-        let kind = semantic_lengends.token_types[semantic_token.token_type as usize]
-            .as_str()
-            .to_string();
-        if start < last_start {
+            character = 0;
+        }
+        character += semantic_token.delta_start as usize;
+        if i + 1 < start {
+            continue;
+        }
+
+        let offset = buffer.offset_of_line(line) + character;
+        if i + 1 == start {
+            from = offset + semantic_token.length as usize;
+            last_start = offset;
             continue;
         }
-        last_start = start;
+        if i >= end {
+            to = offset;
+            break;
+        }
+
+        if offset < last_start {
+            continue;
+        }
+        last_start = offset;
+        let kind = match legend.token_types.get(semantic_token.token_type as usize) {
+            Some(kind) => kind.as_str().to_string(),
+            None => continue,
+        };
         highlights.push(LineStyle {
-            start,
-            end,
+            start: offset,
+            end: offset + semantic_token.length as usize,
             style: Style {
                 fg_color: Some(kind),
             },
         });
     }
 
-    Some(highlights)
+    (from.min(to), to, highlights)
+}
+
+/// Applies the edits of a delta to the previous tokens, and returns the
+/// tokens with the range of the ones that changed in them. The edits count
+/// the integers the tokens are sent as, five for each token.
+fn apply_semantic_tokens_edits(
+    mut tokens: Vec<SemanticToken>,
+    mut edits: Vec<SemanticTokensEdit>,
+) -> Option<(Vec<SemanticToken>, usize, usize)> {
+    if edits.is_empty() {
+        return Some((tokens, 0, 0));
+    }
+    edits.sort_by_key(|edit| edit.start);
+
+    let mut start = usize::MAX;
+    let mut end = 0;
+    let mut shift: isize = 0;
+    let mut last_end = 0;
+    let mut spliced = Vec::new();
+    for edit in edits.iter() {
+        if edit.start % 5 != 0 || edit.delete_count % 5 != 0 {
+            return None;
+        }
+        let edit_start = edit.start as usize / 5;
+        let edit_end = edit_start + edit.delete_count as usize / 5;
+        if edit_start < last_end || edit_end > tokens.len() {
+            return None;
+        }
+        last_end = edit_end;
+        let inserted = edit.data.as_ref().map(|data| data.len()).unwrap_or(0);
+        let new_start = (edit_start as isize + shift) as usize;
+        start = start.min(new_start);
+        end = new_start + inserted;
+        shift += inserted as isize - (edit_end - edit_start) as isize;
+        spliced.push((edit_start, edit_end, edit.data.clone().unwrap_or_default()));
+    }
+
+    // The edits are applied from the last one, so that the indices of the
+    // ones before it stay the same.
+    for (edit_start, edit_end, data) in spliced.into_iter().rev() {
+        tokens.splice(edit_start..edit_end, data);
+    }
+
+    Some((tokens, start, end))
+}
+
+fn semantic_tokens_options(
+    capabilities: &ServerCapabilities,
+) -> Option<&SemanticTokensOptions> {
+    match capabilities.semantic_tokens_provider.as_ref()? {
+        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
+            Some(options)
+        }
+        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
+            options,
+        ) => Some(&options.semantic_tokens_options),
+    }
+}
+
+fn semantic_tokens_delta_support(capabilities: &ServerCapabilities) -> bool {
+    matches!(
+        semantic_tokens_options(capabilities).and_then(|o| o.full.as_ref()),
+        Some(SemanticTokensFullOptions::Delta { delta: Some(true) })
+    )
+}
+
+fn semantic_tokens_range_support(capabilities: &ServerCapabilities) -> bool {
+    semantic_tokens_options(capabilities).and_then(|o| o.range) == Some(true)
 }
 
 fn semantic_tokens_lengend(
@@ -1088,3 +3878,125 @@ fn semantic_tokens_lengend(
         ) => options.semantic_tokens_options.legend.clone(),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crossbeam_channel::unbounded;
+    use lapce_rpc::buffer::BufferId;
+    use xi_rope::Rope;
+
+    use super::*;
+
+    fn token(delta_line: u32, delta_start: u32, token_type: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length: if token_type == 0 { 3 } else { 1 },
+            token_type,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    fn edit(
+        start: u32,
+        delete_count: u32,
+        data: Option<Vec<SemanticToken>>,
+    ) -> SemanticTokensEdit {
+        SemanticTokensEdit {
+            start,
+            delete_count,
+            data,
+        }
+    }
+
+    /// `let a = 1;` on each line, a keyword and a variable per line
+    fn tokens(lines: u32) -> Vec<SemanticToken> {
+        (0..lines)
+            .flat_map(|line| {
+                [token(if line == 0 { 0 } else { 1 }, 0, 0), token(0, 4, 1)]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_semantic_tokens_edits() {
+        let old = tokens(2);
+
+        // The edits come unsorted, and the first one replaces a token with
+        // two while the second one only deletes.
+        let (new, start, end) = apply_semantic_tokens_edits(
+            old.clone(),
+            vec![
+                edit(15, 5, None),
+                edit(5, 5, Some(vec![token(0, 4, 0), token(0, 4, 1)])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(new, vec![old[0], token(0, 4, 0), token(0, 4, 1), old[2]]);
+        assert_eq!((start, end), (1, 4));
+
+        let (new, start, end) =
+            apply_semantic_tokens_edits(old.clone(), vec![edit(5, 5, None)])
+                .unwrap();
+        assert_eq!(new, vec![old[0], old[2], old[3]]);
+        assert_eq!((start, end), (1, 1));
+
+        let (new, start, end) =
+            apply_semantic_tokens_edits(old.clone(), Vec::new()).unwrap();
+        assert_eq!(new, old);
+        assert_eq!((start, end), (0, 0));
+    }
+
+    #[test]
+    fn test_apply_invalid_semantic_tokens_edits() {
+        for edits in [
+            vec![edit(0, 10, None), edit(5, 5, None)],
+            vec![edit(3, 5, None)],
+            vec![edit(5, 3, None)],
+            vec![edit(15, 10, None)],
+        ] {
+            assert!(apply_semantic_tokens_edits(tokens(2), edits).is_none());
+        }
+    }
+
+    #[test]
+    fn test_format_semantic_styles() {
+        let (sender, _) = unbounded();
+        let mut buffer = Buffer::new(BufferId(1), PathBuf::from("/a.rs"), sender);
+        buffer.rope = Rope::from("let a = 1;\nlet b = 2;\n");
+        let legend = SemanticTokensLegend {
+            token_types: vec![
+                SemanticTokenType::KEYWORD,
+                SemanticTokenType::VARIABLE,
+            ],
+            token_modifiers: Vec::new(),
+        };
+        let styles = |start: usize, end: usize| {
+            let (from, to, styles) =
+                format_semantic_styles(&buffer, &legend, &tokens(2), start, end);
+            let styles: Vec<(usize, usize, String)> = styles
+                .into_iter()
+                .map(|s| (s.start, s.end, s.style.fg_color.unwrap()))
+                .collect();
+            (from, to, styles)
+        };
+
+        let (from, to, all) = styles(0, 4);
+        assert_eq!((from, to), (0, buffer.len()));
+        assert_eq!(
+            all,
+            vec![
+                (0, 3, "keyword".to_string()),
+                (4, 5, "variable".to_string()),
+                (11, 14, "keyword".to_string()),
+                (15, 16, "variable".to_string()),
+            ]
+        );
+
+        // Only the third token changed, so the offsets run from the end of
+        // the second token to the start of the fourth.
+        let (from, to, changed) = styles(2, 3);
+        assert_eq!((from, to), (5, 15));
+        assert_eq!(changed, vec![(11, 14, "keyword".to_string())]);
+    }
+}
@@ -23,6 +23,7 @@ use wasmer_wasi::WasiEnv;
 use wasmer_wasi::WasiState;
 
 use crate::dispatch::Dispatcher;
+use crate::lsp::LspCatalog;
 
 pub type PluginName = String;
 
@@ -246,6 +247,10 @@ pub enum PluginNotification {
     StartLspServer {
         exec_path: String,
         language_id: String,
+        /// The workspace folder the server is limited to, relative to the
+        /// workspace root. The server covers the whole workspace without it.
+        #[serde(default)]
+        root: Option<PathBuf>,
         options: Option<Value>,
     },
     DownloadFile {
@@ -268,9 +273,11 @@ fn host_handle_notification(plugin_env: &PluginEnv) {
             PluginNotification::StartLspServer {
                 exec_path,
                 language_id,
+                root,
                 options,
             } => {
-                plugin_env.dispatcher.lsp.lock().start_server(
+                LspCatalog::start_server(
+                    &plugin_env.dispatcher,
                     plugin_env
                         .desc
                         .dir
@@ -279,7 +286,9 @@ fn host_handle_notification(plugin_env: &PluginEnv) {
                         .join(&exec_path)
                         .to_str()
                         .unwrap(),
+                    Vec::new(),
                     &language_id,
+                    root,
                     options,
                 );
             }
@@ -0,0 +1,220 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use lapce_core::{language::LapceLanguage, syntax::find_symbols};
+use lsp_types::{Location, Position, Range, SymbolInformation, Url};
+use xi_rope::Rope;
+
+use crate::buffer::{get_mod_time, load_file};
+
+/// The most symbols a query answers with, so that a short query over a big
+/// workspace doesn't flood the palette.
+const MAX_SYMBOLS: usize = 1000;
+/// The least time between two walks of the workspace, so that a burst of
+/// file changes doesn't make every query walk it again.
+const MIN_WALK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The symbols tree-sitter finds in the workspace files, used for workspace
+/// symbols when no language server provides them.
+#[derive(Default)]
+pub struct SymbolIndex {
+    workspace: Option<PathBuf>,
+    files: HashMap<PathBuf, (SystemTime, Vec<SymbolInformation>)>,
+    /// Whether files of the workspace changed since the last walk
+    dirty: bool,
+    last_walk: Option<Instant>,
+}
+
+impl SymbolIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tells the index that files of the workspace changed, for the file
+    /// watcher.
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Reparses the files modified since the last update, and drops the
+    /// ones that are gone. The workspace is only walked again after the
+    /// file watcher saw changes.
+    pub fn update(&mut self, workspace: &Path) {
+        if self.workspace.as_deref() != Some(workspace) {
+            self.workspace = Some(workspace.to_path_buf());
+            self.files.clear();
+            self.dirty = true;
+            self.last_walk = None;
+        }
+        if !self.dirty
+            || self
+                .last_walk
+                .map(|last_walk| last_walk.elapsed() < MIN_WALK_INTERVAL)
+                .unwrap_or(false)
+        {
+            return;
+        }
+        self.dirty = false;
+        self.last_walk = Some(Instant::now());
+
+        let mut seen = HashSet::new();
+        for entry in ignore::Walk::new(workspace).flatten() {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let path = entry.into_path();
+            let language = match LapceLanguage::from_path(&path) {
+                Some(language) => language,
+                None => continue,
+            };
+            let mod_time = match get_mod_time(&path) {
+                Some(mod_time) => mod_time,
+                None => continue,
+            };
+            seen.insert(path.clone());
+            if self
+                .files
+                .get(&path)
+                .map(|(indexed, _)| *indexed == mod_time)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let symbols = file_symbols(&path, language).unwrap_or_default();
+            self.files.insert(path, (mod_time, symbols));
+        }
+        self.files.retain(|path, _| seen.contains(path));
+    }
+
+    /// The symbols whose name contains the characters of the query in
+    /// order, ignoring case.
+    pub fn query(&self, query: &str) -> Vec<SymbolInformation> {
+        let query = query.to_lowercase();
+        self.files
+            .values()
+            .flat_map(|(_, symbols)| symbols.iter())
+            .filter(|symbol| is_subsequence(&query, &symbol.name.to_lowercase()))
+            .take(MAX_SYMBOLS)
+            .cloned()
+            .collect()
+    }
+}
+
+#[allow(deprecated)]
+fn file_symbols(
+    path: &Path,
+    language: LapceLanguage,
+) -> Option<Vec<SymbolInformation>> {
+    let uri = Url::from_file_path(path).ok()?;
+    let text = load_file(path).ok()?;
+    let rope = Rope::from(text.as_str());
+    let symbols = find_symbols(language, &text)
+        .into_iter()
+        .map(|symbol| SymbolInformation {
+            name: symbol.name,
+            kind: symbol.kind,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: uri.clone(),
+                range: Range {
+                    start: lsp_position(&rope, symbol.start),
+                    end: lsp_position(&rope, symbol.end),
+                },
+            },
+            container_name: symbol.container_name,
+        })
+        .collect();
+    Some(symbols)
+}
+
+/// The position of a byte offset, in the UTF-16 code units LSP counts
+fn lsp_position(rope: &Rope, offset: usize) -> Position {
+    let line = rope.line_of_offset(offset);
+    let line_start = rope.offset_of_line(line);
+    Position {
+        line: line as u32,
+        character: rope.slice_to_cow(line_start..offset).encode_utf16().count()
+            as u32,
+    }
+}
+
+fn is_subsequence(query: &str, name: &str) -> bool {
+    let mut chars = name.chars();
+    query.chars().all(|c| chars.any(|n| n == c))
+}
+
+#[cfg(test)]
+mod test {
+    use lsp_types::SymbolKind;
+
+    use super::*;
+
+    #[allow(deprecated)]
+    fn symbol(name: &str) -> SymbolInformation {
+        SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::Function,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: Url::parse("file:///a.rs").unwrap(),
+                range: Range::default(),
+            },
+            container_name: None,
+        }
+    }
+
+    #[test]
+    fn test_is_subsequence() {
+        assert!(is_subsequence("", "anything"));
+        assert!(is_subsequence("gws", "get_workspace_symbols"));
+        assert!(is_subsequence("symbols", "get_workspace_symbols"));
+        assert!(!is_subsequence("swg", "get_workspace_symbols"));
+        assert!(!is_subsequence("getx", "get"));
+    }
+
+    #[test]
+    fn test_query() {
+        let mut index = SymbolIndex::new();
+        index.files.insert(
+            PathBuf::from("/a.rs"),
+            (
+                SystemTime::UNIX_EPOCH,
+                vec![symbol("SymbolIndex"), symbol("file_symbols")],
+            ),
+        );
+        index.files.insert(
+            PathBuf::from("/b.rs"),
+            (SystemTime::UNIX_EPOCH, vec![symbol("is_subsequence")]),
+        );
+
+        let names = |query: &str| {
+            let mut names: Vec<String> =
+                index.query(query).into_iter().map(|s| s.name).collect();
+            names.sort();
+            names
+        };
+        // The query is lowercased along with the names.
+        assert_eq!(names("symidx"), vec!["SymbolIndex"]);
+        assert_eq!(names("FILESYM"), vec!["file_symbols"]);
+        assert_eq!(
+            names("s"),
+            vec!["SymbolIndex", "file_symbols", "is_subsequence"]
+        );
+        assert!(names("zzz").is_empty());
+    }
+
+    #[test]
+    fn test_lsp_position() {
+        let rope = Rope::from("fn a() {}\n/* \u{1F600}\u{e9} */ fn b() {}\n");
+        assert_eq!(lsp_position(&rope, 3), Position::new(0, 3));
+        // The emoji is two UTF-16 code units and four bytes, and the accent
+        // one code unit and two bytes.
+        let offset = rope.to_string().find("fn b").unwrap();
+        assert_eq!(lsp_position(&rope, offset), Position::new(1, 10));
+    }
+}
@@ -147,6 +147,7 @@ impl FileWatcher {
     /// Does not stop watching this path, if it is associated with
     /// other tokens.
     pub fn unwatch(&mut self, path: &Path, token: WatchToken) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
         let mut state = self.state.lock();
 
         let idx = state
@@ -1,4 +1,7 @@
-use lsp_types::{ProgressParams, PublishDiagnosticsParams};
+use lsp_types::{
+    ProgressParams, PublishDiagnosticsParams, ShowMessageRequestParams,
+    WorkspaceEdit,
+};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, path::PathBuf};
 
@@ -17,6 +20,11 @@ pub enum CoreNotification {
         buffer_id: BufferId,
         path: PathBuf,
         len: usize,
+        /// Set when only the styles between `start` and `end` changed since
+        /// the ones sent for that rev, and None when these are all of them
+        base_rev: Option<u64>,
+        start: usize,
+        end: usize,
         styles: Vec<LineStyle>,
     },
     OpenFileChanged {
@@ -37,6 +45,22 @@ pub enum CoreNotification {
     WorkDoneProgress {
         progress: ProgressParams,
     },
+    LspServerStatus {
+        server: String,
+        status: LspStatus,
+    },
+    /// An entry added to the log of a language server since it was asked for
+    LspLog {
+        name: String,
+        entry: String,
+    },
+    /// The characters that ask the servers of the document for its
+    /// signature help, sent once the document is open in them
+    SignatureHelpTriggers {
+        path: PathBuf,
+        trigger_characters: Vec<String>,
+        retrigger_characters: Vec<String>,
+    },
     HomeDir {
         path: PathBuf,
     },
@@ -61,5 +85,37 @@ pub enum CoreNotification {
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LspStatus {
+    Starting,
+    Running,
+    /// The server crashed and is about to be started again
+    Restarting,
+    Stopped,
+    /// The server crashed too many times in a row, or could not be started
+    Crashed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum CoreRequest {}
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "method", content = "params")]
+pub enum CoreRequest {
+    ApplyWorkspaceEdit {
+        edit: WorkspaceEdit,
+    },
+    ShowMessageRequest {
+        params: ShowMessageRequestParams,
+    },
+    /// Asks for the user name and password of a git remote, when neither the
+    /// SSH agent nor the credential helpers have them, or for the passphrase
+    /// of the SSH key when there's one, which comes back as the password.
+    /// It's answered with a `GitCredentials`, or with null when the user
+    /// gives up.
+    GitCredentials {
+        url: String,
+        username: Option<String>,
+        #[serde(default)]
+        key: Option<PathBuf>,
+    },
+}
@@ -76,7 +76,7 @@ pub trait Handler {
     type Request: DeserializeOwned;
 
     fn handle_notification(&mut self, rpc: Self::Notification) -> ControlFlow;
-    fn handle_request(&mut self, rpc: Self::Request) -> Result<Value, Value>;
+    fn handle_request(&mut self, id: RequestId, rpc: Self::Request);
 }
 
 #[derive(Clone)]
@@ -114,8 +114,7 @@ impl RpcHandler {
             } else {
                 match rpc.into_rpc::<H::Notification, H::Request>() {
                     Ok(Call::Request(id, request)) => {
-                        let result = handler.handle_request(request);
-                        self.respond(id, result);
+                        handler.handle_request(id, request);
                     }
                     Ok(Call::Notification(notification)) => {
                         if handler.handle_notification(notification)
@@ -220,7 +219,7 @@ impl RpcHandler {
         self.send_rpc_request_common(method, params, ResponseHandler::Callback(f));
     }
 
-    fn handle_response(&self, id: u64, resp: Result<Value, Value>) {
+    pub fn handle_response(&self, id: u64, resp: Result<Value, Value>) {
         let handler = {
             let mut pending = self.pending.lock();
             pending.remove(&id)
@@ -230,7 +229,7 @@ impl RpcHandler {
         }
     }
 
-    fn respond(&self, id: u64, result: Result<Value, Value>) {
+    pub fn respond(&self, id: u64, result: Result<Value, Value>) {
         let mut response = json!({ "id": id });
         match result {
             Ok(result) => response["result"] = result,
@@ -1,7 +1,11 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use lsp_types::{CompletionItem, Position};
+use lsp_types::{
+    CallHierarchyItem, CodeAction, CodeLens, Command, CompletionItem, Position,
+    Range,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use xi_rope::RopeDelta;
 
 use crate::{
@@ -15,6 +19,12 @@ use crate::{
 pub enum ProxyNotification {
     Initialize {
         workspace: PathBuf,
+        /// The language servers from the settings, by language id
+        #[serde(default)]
+        lsp: HashMap<String, LspSettings>,
+    },
+    UpdateLspSettings {
+        lsp: HashMap<String, LspSettings>,
     },
     Shutdown {},
     Update {
@@ -46,6 +56,40 @@ pub enum ProxyNotification {
     TerminalClose {
         term_id: TermId,
     },
+    RestartLspServers {
+        buffer_id: Option<BufferId>,
+    },
+    RefreshSemanticStyles {
+        buffer_id: BufferId,
+    },
+    /// The document isn't shown anymore, so the proxy can forget about it
+    CloseBuffer {
+        buffer_id: BufferId,
+    },
+    /// Starts or stops logging the messages exchanged with the language
+    /// servers
+    ToggleLspTrace {},
+}
+
+/// A language server from a `[lsp.<language>]` section of the settings,
+/// started when a document of the language is opened.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LspSettings {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The extensions of the files of the language, on top of the ones
+    /// Lapce already knows
+    #[serde(default)]
+    pub file_extensions: Vec<String>,
+    /// Files like `Cargo.toml` that mark the root of a project. The server
+    /// is started in the closest folder above the document that has one,
+    /// or else in the workspace.
+    #[serde(default)]
+    pub root_markers: Vec<String>,
+    #[serde(default)]
+    pub initialization_options: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,9 +100,13 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         path: PathBuf,
     },
+    /// Gets the content of the file at the version, which is "head",
+    /// "index", or any revision git understands, like a commit, a branch
+    /// or a stash
     BufferHead {
         buffer_id: BufferId,
         path: PathBuf,
+        version: String,
     },
     GetCompletion {
         request_id: usize,
@@ -80,6 +128,10 @@ pub enum ProxyRequest {
     GetSignature {
         buffer_id: BufferId,
         position: Position,
+        /// The character just typed, None when the signature help already
+        /// shown is asked again after the cursor moved
+        trigger_character: Option<String>,
+        is_retrigger: bool,
     },
     GetReferences {
         buffer_id: BufferId,
@@ -90,16 +142,94 @@ pub enum ProxyRequest {
         buffer_id: BufferId,
         position: Position,
     },
+    GetTypeDefinition {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetImplementation {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetDeclaration {
+        buffer_id: BufferId,
+        position: Position,
+    },
     GetCodeActions {
         buffer_id: BufferId,
         position: Position,
     },
+    ResolveCodeAction {
+        action: Box<CodeAction>,
+    },
+    GetCodeLens {
+        buffer_id: BufferId,
+    },
+    ResolveCodeLens {
+        code_lens: Box<CodeLens>,
+    },
+    /// Runs the command with the server that the item it came with is
+    /// tagged with, when there's such an item
+    ExecuteCommand {
+        buffer_id: BufferId,
+        command: Command,
+        #[serde(default)]
+        data: Option<Value>,
+    },
     GetDocumentSymbols {
         buffer_id: BufferId,
     },
+    GetWorkspaceSymbols {
+        query: String,
+    },
     GetDocumentFormatting {
         buffer_id: BufferId,
     },
+    GetFoldingRanges {
+        buffer_id: BufferId,
+    },
+    GetSelectionRanges {
+        buffer_id: BufferId,
+        positions: Vec<Position>,
+    },
+    GetRangeFormatting {
+        buffer_id: BufferId,
+        ranges: Vec<Range>,
+    },
+    GetOnTypeFormatting {
+        buffer_id: BufferId,
+        position: Position,
+        ch: String,
+    },
+    GetDocumentHighlights {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    GetInlayHints {
+        buffer_id: BufferId,
+        range: Range,
+    },
+    GetSemanticTokensRange {
+        buffer_id: BufferId,
+        range: Range,
+    },
+    PrepareHierarchy {
+        buffer_id: BufferId,
+        position: Position,
+        kind: HierarchyKind,
+    },
+    GetHierarchyChildren {
+        kind: HierarchyKind,
+        item: CallHierarchyItem,
+    },
+    PrepareRename {
+        buffer_id: BufferId,
+        position: Position,
+    },
+    Rename {
+        buffer_id: BufferId,
+        position: Position,
+        new_name: String,
+    },
     GetFiles {
         path: String,
     },
@@ -116,6 +246,142 @@ pub enum ProxyRequest {
         rev: u64,
         content: String,
     },
+    /// The names of the logs of the running language servers
+    GetLspLogNames {},
+    GetLspLog {
+        name: String,
+    },
+    GitStage {
+        diffs: Vec<FileDiff>,
+    },
+    GitUnstage {
+        diffs: Vec<FileDiff>,
+    },
+    /// Takes the working files back to what's staged for them
+    GitDiscard {
+        diffs: Vec<FileDiff>,
+    },
+    /// The hunk requests pick the change that has the line of the working
+    /// file in it.
+    GitStageHunk {
+        path: PathBuf,
+        line: usize,
+    },
+    GitUnstageHunk {
+        path: PathBuf,
+        line: usize,
+    },
+    GitDiscardHunk {
+        path: PathBuf,
+        line: usize,
+    },
+    /// Checks out a local or remote branch, or any revision as a detached
+    /// head. A remote branch gets a local branch that tracks it.
+    GitCheckout {
+        reference: String,
+    },
+    /// Creates a branch at `start_point`, or at the head when there's none
+    GitCreateBranch {
+        name: String,
+        start_point: Option<String>,
+        checkout: bool,
+    },
+    GitDeleteBranch {
+        name: String,
+        force: bool,
+    },
+    GitRenameBranch {
+        name: String,
+        new_name: String,
+    },
+    /// Blames the lines of the buffer as it is, edits included
+    GitBlame {
+        buffer_id: BufferId,
+    },
+    /// The commits that changed the file, the latest first
+    GitFileHistory {
+        path: PathBuf,
+    },
+    /// Stashes the changes of the index and of the working files
+    GitStashSave {
+        message: Option<String>,
+        include_untracked: bool,
+    },
+    GitStashList {},
+    /// Applies the entry of the stash, and drops it if `pop` is set
+    GitStashApply {
+        index: usize,
+        pop: bool,
+    },
+    GitStashDrop {
+        index: usize,
+    },
+    /// The network requests work with the remote of the upstream of the
+    /// current branch, or "origin" when it has none.
+    GitFetch {},
+    /// Fetches, then brings the current branch up to its upstream by
+    /// fast-forwarding it, or by rebasing its own commits if `rebase` is set
+    GitPull {
+        rebase: bool,
+    },
+    /// Pushes the current branch to its upstream, which is set to the branch
+    /// of the same name on the remote if there's none yet
+    GitPush {},
+}
+
+/// An inlay hint as the editor shows it. The proxy turns both the standard
+/// `textDocument/inlayHint` answer and rust-analyzer's own one into this.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InlayHint {
+    pub position: Position,
+    pub label: String,
+    #[serde(default)]
+    pub padding_left: bool,
+    #[serde(default)]
+    pub padding_right: bool,
+}
+
+/// The data of a completion item, code action or code lens. The items of all
+/// the servers for a document end up in one list, so the proxy tags each one
+/// with the server it came from, next to the server's own data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaggedData<S> {
+    pub server: S,
+    pub data: Option<Value>,
+}
+
+impl TaggedData<Value> {
+    /// Whether the server gave the item data of its own, which is what it
+    /// does for the items it can fill in when they're resolved.
+    pub fn has_server_data(data: Option<&Value>) -> bool {
+        match data {
+            Some(data) => match TaggedData::<Value>::deserialize(data) {
+                Ok(tagged) => tagged.data.is_some(),
+                Err(_) => true,
+            },
+            None => false,
+        }
+    }
+}
+
+/// What the children of a node in a call or type hierarchy are. The items of
+/// a type hierarchy have the same shape as the ones of a call hierarchy, so
+/// `CallHierarchyItem` is used for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HierarchyKind {
+    IncomingCalls,
+    OutgoingCalls,
+    Supertypes,
+    Subtypes,
+}
+
+impl HierarchyKind {
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self,
+            HierarchyKind::IncomingCalls | HierarchyKind::OutgoingCalls
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
@@ -6,7 +7,18 @@ use serde::{Deserialize, Serialize};
 pub struct DiffInfo {
     pub head: String,
     pub branches: Vec<String>,
+    /// The branches that track a remote, like `origin/main`, which are in
+    /// `branches` too
+    #[serde(default)]
+    pub remote_branches: Vec<String>,
+    /// The changes from the head to the working files
     pub diffs: Vec<FileDiff>,
+    /// The changes from the head to the index
+    #[serde(default)]
+    pub staged: Vec<FileDiff>,
+    /// The changes from the index to the working files
+    #[serde(default)]
+    pub unstaged: Vec<FileDiff>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -27,3 +39,80 @@ impl FileDiff {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author: String,
+    /// Seconds since the Unix epoch
+    pub time: i64,
+    pub summary: String,
+}
+
+/// The commit that last changed a range of lines
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BlameHunk {
+    /// The first line of the hunk in the blamed text
+    pub line: usize,
+    pub lines: usize,
+    /// `None` when the lines aren't committed yet
+    pub commit: Option<CommitInfo>,
+}
+
+/// An entry of the stash, where 0 is the latest
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StashEntry {
+    pub index: usize,
+    pub message: String,
+    pub id: String,
+}
+
+/// The answer to a `CoreRequest::GitCredentials`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GitCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Why a branch operation failed, sent as the `data` of the error so that
+/// the editor can offer a way out.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GitBranchError {
+    /// The tracked files that have uncommitted changes
+    DirtyWorktree(Vec<PathBuf>),
+    BranchExists(String),
+    BranchNotFound(String),
+    /// The branch has commits that the head doesn't have
+    BranchNotMerged(String),
+    /// The branch is the one that's checked out
+    CurrentBranch(String),
+    Git(String),
+}
+
+impl fmt::Display for GitBranchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitBranchError::DirtyWorktree(paths) => {
+                write!(
+                    f,
+                    "{} file(s) have uncommitted changes, commit or discard them first",
+                    paths.len()
+                )
+            }
+            GitBranchError::BranchExists(name) => {
+                write!(f, "a branch named '{name}' already exists")
+            }
+            GitBranchError::BranchNotFound(name) => {
+                write!(f, "no branch or revision named '{name}'")
+            }
+            GitBranchError::BranchNotMerged(name) => {
+                write!(f, "the branch '{name}' is not fully merged")
+            }
+            GitBranchError::CurrentBranch(name) => {
+                write!(f, "the branch '{name}' is checked out")
+            }
+            GitBranchError::Git(message) => write!(f, "{message}"),
+        }
+    }
+}
@@ -168,6 +168,7 @@ impl Widget<LapceTabData> for AlertBoxContent {
                 if self.cancel_rect.contains(self.mouse_down_point)
                     && self.cancel_rect.contains(mouse_event.pos)
                 {
+                    data.alert.content.cancel(ctx);
                     ctx.submit_command(Command::new(
                         LAPCE_COMMAND,
                         LapceCommand {
@@ -9,7 +9,7 @@ use druid::{
     LayoutCtx, LifeCycle, LifeCycleCtx, MouseButton, MouseEvent, PaintCtx, Point,
     Rect, RenderContext, Size, Target, UpdateCtx, Widget, WidgetId,
 };
-use lapce_core::buffer::DiffLines;
+use lapce_core::buffer::{DiffBlock, DiffLines};
 use lapce_core::command::EditCommand;
 use lapce_core::{
     command::FocusCommand,
@@ -31,8 +31,12 @@ use lapce_data::{
     editor::{LapceEditorBufferData, Syntax},
     menu::MenuItem,
     panel::PanelPosition,
+    source_control::blame_summary,
+};
+use lsp_types::{
+    CodeActionOrCommand, CodeLens, DiagnosticSeverity, DiagnosticTag,
+    DocumentHighlightKind,
 };
-use lsp_types::{CodeActionOrCommand, DiagnosticSeverity};
 
 pub mod container;
 pub mod gutter;
@@ -55,6 +59,8 @@ pub struct LapceEditor {
 }
 
 impl LapceEditor {
+    const CODE_LENS_SEPARATOR: &'static str = " | ";
+
     pub fn new(view_id: WidgetId, editor_id: WidgetId) -> Self {
         Self {
             view_id,
@@ -158,11 +164,10 @@ impl LapceEditor {
         }
 
         if ctx.is_active() {
-            let (new_offset, _) = editor_data.doc.offset_of_point(
+            let (new_offset, _) = editor_data.offset_of_point(
                 ctx.text(),
                 editor_data.get_mode(),
                 mouse_pos,
-                config.editor.font_size,
                 config,
             );
             let editor = Arc::make_mut(&mut editor_data.editor);
@@ -170,13 +175,8 @@ impl LapceEditor {
             return;
         }
 
-        let (offset, is_inside) = editor_data.doc.offset_of_point(
-            ctx.text(),
-            Mode::Insert,
-            mouse_pos,
-            config.editor.font_size,
-            config,
-        );
+        let (offset, is_inside) =
+            editor_data.offset_of_point(ctx.text(), Mode::Insert, mouse_pos, config);
         let within_scroll = self.mouse_within_scroll(editor_data, mouse_pos);
         if !editor_data.check_hover(ctx, offset, is_inside, within_scroll)
             && is_inside
@@ -199,6 +199,18 @@ impl LapceEditor {
         ctx.set_handled();
         match mouse_event.button {
             MouseButton::Left => {
+                if let Some(code_lens) =
+                    Self::code_lens_at(editor_data, ctx.text(), mouse_event.pos)
+                {
+                    if let Some(command) = code_lens.command.as_ref() {
+                        editor_data.run_lsp_command(
+                            ctx,
+                            command,
+                            code_lens.data.as_ref(),
+                        );
+                    }
+                    return;
+                }
                 self.left_click(ctx, mouse_event, editor_data, config);
                 editor_data.get_code_actions(ctx);
                 editor_data.cancel_completion();
@@ -246,11 +258,10 @@ impl LapceEditor {
         mouse_event: &MouseEvent,
         config: &Config,
     ) {
-        let (offset, _) = editor_data.doc.offset_of_point(
+        let (offset, _) = editor_data.offset_of_point(
             ctx.text(),
             editor_data.get_mode(),
             mouse_event.pos,
-            config.editor.font_size,
             config,
         );
 
@@ -271,6 +282,13 @@ impl LapceEditor {
                     data: None,
                 },
             }),
+            MenuKind::Item(MenuItem {
+                desc: None,
+                command: LapceCommand {
+                    kind: CommandKind::Focus(FocusCommand::RenameSymbol),
+                    data: None,
+                },
+            }),
             MenuKind::Item(MenuItem {
                 desc: None,
                 command: LapceCommand {
@@ -326,6 +344,7 @@ impl LapceEditor {
         match &data.editor.content {
             BufferContent::File(_)
             | BufferContent::Scratch(..)
+            | BufferContent::LspLog(_)
             | BufferContent::Local(LocalBufferKind::Empty) => {
                 if data.editor.code_lens {
                     if let Some(syntax) = data.doc.syntax() {
@@ -355,6 +374,32 @@ impl LapceEditor {
                             },
                         )
                     }
+                } else if let EditorView::SideBySide(version) = &data.editor.view {
+                    let (rows, max_len) = data
+                        .doc
+                        .get_history(version)
+                        .map(|history| {
+                            (
+                                history
+                                    .diff_blocks()
+                                    .iter()
+                                    .map(|block| block.rows())
+                                    .sum(),
+                                history.max_len(),
+                            )
+                        })
+                        .unwrap_or((0, 0));
+                    let max_len = max_len.max(data.doc.buffer().max_len());
+                    Size::new(
+                        (data.editor.side_by_side_offset() + width * max_len as f64)
+                            .max(editor_size.width),
+                        if data.config.editor.scroll_beyond_last_line {
+                            (line_height * rows as f64 - line_height).max(0.0)
+                                + editor_size.height
+                        } else {
+                            (line_height * rows as f64).max(editor_size.height)
+                        },
+                    )
                 } else if let Some(compare) = data.editor.compare.as_ref() {
                     let mut lines = 0;
                     if let Some(history) = data.doc.get_history(compare) {
@@ -378,17 +423,15 @@ impl LapceEditor {
                         },
                     )
                 } else {
+                    let lines = data.doc.folding().rows();
                     Size::new(
                         (width * data.doc.buffer().max_len() as f64)
                             .max(editor_size.width),
                         if data.config.editor.scroll_beyond_last_line {
-                            (line_height * data.doc.buffer().num_lines() as f64
-                                - line_height)
-                                .max(0.0)
+                            (line_height * lines as f64 - line_height).max(0.0)
                                 + editor_size.height
                         } else {
-                            (line_height * data.doc.buffer().num_lines() as f64)
-                                .max(editor_size.height)
+                            (line_height * lines as f64).max(editor_size.height)
                         },
                     )
                 }
@@ -586,6 +629,18 @@ impl LapceEditor {
 
         if !data.editor.content.is_input() && data.editor.code_lens {
             Self::paint_code_lens_content(data, ctx, is_focused);
+        } else if let EditorView::SideBySide(version) = &data.editor.view {
+            Self::paint_side_by_side_diff(
+                data,
+                ctx,
+                is_focused,
+                version,
+                char_width,
+                line_height,
+                font_size,
+                y_shift,
+            );
+            return;
         } else if let EditorView::Diff(version) = &data.editor.view {
             if let Some(history) = data.doc.get_history(version) {
                 let cursor_line = data
@@ -787,6 +842,13 @@ impl LapceEditor {
             return;
         } else {
             let last_line = data.doc.buffer().last_line();
+            let cursor_line = data
+                .doc
+                .buffer()
+                .line_of_offset(data.editor.cursor.offset());
+            let folding = data.doc.folding();
+            let (start_line, _) = folding.line_of_row(start_line);
+            let (end_line, _) = folding.line_of_row(end_line);
 
             Self::paint_cursor(
                 data,
@@ -798,11 +860,15 @@ impl LapceEditor {
                 env,
             );
             Self::paint_find(data, ctx, char_width, env);
+            Self::paint_document_highlights(data, ctx);
 
             for line in start_line..end_line + 1 {
                 if line > last_line {
                     break;
                 }
+                if folding.is_hidden(line) {
+                    continue;
+                }
 
                 let text_layout = data.doc.get_text_layout(
                     ctx.text(),
@@ -810,15 +876,50 @@ impl LapceEditor {
                     font_size,
                     &data.config,
                 );
+                let y = Self::line_y(data, line, line_height);
                 ctx.draw_text(
                     &text_layout,
                     Point::new(
                         0.0,
-                        line_height * line as f64
-                            + (line_height - text_layout.size().height) / 2.0
+                        y + (line_height - text_layout.size().height) / 2.0
                             + line_padding,
                     ),
                 );
+                if folding.has_code_lens(line) {
+                    Self::paint_code_lenses(
+                        data,
+                        ctx,
+                        line,
+                        y - line_height,
+                        line_height,
+                    );
+                }
+                let folded =
+                    folding.range_at(line).map(|r| r.folded).unwrap_or(false);
+                if folded {
+                    Self::paint_fold_placeholder(
+                        data,
+                        ctx,
+                        text_layout.size().width + char_width,
+                        y + line_padding,
+                        line_height,
+                        char_width,
+                    );
+                }
+                if is_focused
+                    && line == cursor_line
+                    && data.config.editor.inline_blame
+                {
+                    let spacing = if folded { 7.0 } else { 4.0 };
+                    Self::paint_inline_blame(
+                        data,
+                        ctx,
+                        line,
+                        text_layout.size().width + char_width * spacing,
+                        y + line_padding,
+                        line_height,
+                    );
+                }
             }
         }
 
@@ -845,6 +946,168 @@ impl LapceEditor {
         }
     }
 
+    /// Paints the version on the left and the working tree on the right, each
+    /// clipped to its half of the viewport, so that both sides scroll
+    /// together and the unchanged lines stay next to each other.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_side_by_side_diff(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        is_focused: bool,
+        version: &str,
+        char_width: f64,
+        line_height: f64,
+        font_size: usize,
+        y_shift: f64,
+    ) {
+        let history = match data.doc.get_history(version) {
+            Some(history) => history,
+            None => return,
+        };
+        let blocks = history.diff_blocks();
+        let rect = ctx.region().bounding_box();
+        let start_row = (rect.y0 / line_height).floor() as usize;
+        let end_row = (rect.y1 / line_height).ceil() as usize;
+        let scroll_x = data.editor.scroll_offset.x;
+        let offset = data.editor.side_by_side_offset();
+        let width = data.editor.size.borrow().width;
+        let cursor_line = data
+            .doc
+            .buffer()
+            .line_of_offset(data.editor.cursor.offset());
+
+        for is_right in [false, true] {
+            let x = if is_right { offset } else { 0.0 };
+            let clip = Rect::new(
+                scroll_x + x,
+                rect.y0,
+                scroll_x + if is_right { width } else { offset },
+                rect.y1,
+            );
+            ctx.with_save(|ctx| {
+                ctx.clip(clip);
+                let mut row = 0;
+                for block in blocks.iter() {
+                    if row > end_row {
+                        break;
+                    }
+                    let rows = block.rows();
+                    if row + rows < start_row {
+                        row += rows;
+                        continue;
+                    }
+                    let (range, is_changed) = match block {
+                        DiffBlock::Both(l, r) => {
+                            (if is_right { r } else { l }, false)
+                        }
+                        DiffBlock::Changed(l, r) => {
+                            (if is_right { r } else { l }, true)
+                        }
+                        DiffBlock::Skip(left, right) => {
+                            let rect = Rect::new(
+                                clip.x0,
+                                line_height * row as f64,
+                                clip.x1,
+                                line_height * (row + 1) as f64,
+                            );
+                            ctx.fill(
+                                rect,
+                                data.config.get_color_unchecked(
+                                    LapceTheme::PANEL_BACKGROUND,
+                                ),
+                            );
+                            let text_layout = ctx
+                                .text()
+                                .new_text_layout(if is_right {
+                                    format!(" +{}", right.end + 1)
+                                } else {
+                                    format!(" -{}", left.end + 1)
+                                })
+                                .font(
+                                    data.config.editor.font_family(),
+                                    font_size as f64,
+                                )
+                                .text_color(
+                                    data.config
+                                        .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                                        .clone(),
+                                )
+                                .build()
+                                .unwrap();
+                            ctx.draw_text(
+                                &text_layout,
+                                Point::new(clip.x0, rect.y0 + y_shift),
+                            );
+                            row += 1;
+                            continue;
+                        }
+                    };
+
+                    for current_row in
+                        start_row.max(row)..(row + rows).min(end_row + 1)
+                    {
+                        let y = line_height * current_row as f64;
+                        let i = current_row - row;
+                        if i >= range.len() {
+                            // The shorter side of a change is padded, to keep
+                            // what follows aligned.
+                            ctx.fill(
+                                Rect::new(clip.x0, y, clip.x1, y + line_height),
+                                data.config.get_color_unchecked(
+                                    LapceTheme::PANEL_BACKGROUND,
+                                ),
+                            );
+                            continue;
+                        }
+                        if is_changed {
+                            ctx.fill(
+                                Rect::new(clip.x0, y, clip.x1, y + line_height),
+                                data.config.get_color_unchecked(if is_right {
+                                    LapceTheme::SOURCE_CONTROL_ADDED
+                                } else {
+                                    LapceTheme::SOURCE_CONTROL_REMOVED
+                                }),
+                            );
+                        }
+                        let line = range.start + i;
+                        let text_layout = if is_right {
+                            Self::paint_cursor_on_line(
+                                data,
+                                ctx,
+                                is_focused,
+                                cursor_line,
+                                line,
+                                x,
+                                y,
+                                char_width,
+                                line_height,
+                            );
+                            data.doc.get_text_layout(
+                                ctx.text(),
+                                line,
+                                font_size,
+                                &data.config,
+                            )
+                        } else {
+                            history.get_text_layout(ctx.text(), line, &data.config)
+                        };
+                        ctx.draw_text(&text_layout, Point::new(x, y + y_shift));
+                    }
+                    row += rows;
+                }
+            });
+        }
+
+        ctx.stroke(
+            Line::new(
+                Point::new(scroll_x + offset, rect.y0),
+                Point::new(scroll_x + offset, rect.y1),
+            ),
+            data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+            1.0,
+        );
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn paint_cursor_on_line(
         data: &LapceEditorBufferData,
@@ -1062,12 +1325,7 @@ impl LapceEditor {
     ) {
         let line_height = Self::line_height(data, env);
         let line_padding = Self::line_padding(data, env);
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let (start_line, end_line) = Self::view_lines(data, line_height);
         match &data.editor.cursor.mode {
             CursorMode::Normal(offset) => {
                 let line = data.doc.buffer().line_of_offset(*offset);
@@ -1107,7 +1365,7 @@ impl LapceEditor {
                         Rect::ZERO
                             .with_origin(Point::new(
                                 x0,
-                                line as f64 * line_height + line_padding,
+                                Self::line_y(data, line, line_height) + line_padding,
                             ))
                             .with_size(Size::new(char_width, line_height)),
                         data.config.get_color_unchecked(LapceTheme::EDITOR_CARET),
@@ -1122,7 +1380,10 @@ impl LapceEditor {
                 let (end_line, end_col) =
                     data.doc.buffer().offset_to_line_col(*start.max(end));
                 for line in paint_start_line..paint_end_line {
-                    if line < start_line || line > end_line {
+                    if line < start_line
+                        || line > end_line
+                        || data.doc.folding().is_hidden(line)
+                    {
                         continue;
                     }
                     let left_col = match mode {
@@ -1188,7 +1449,7 @@ impl LapceEditor {
                         x1 += width;
                     }
 
-                    let y0 = line as f64 * line_height + line_padding;
+                    let y0 = Self::line_y(data, line, line_height) + line_padding;
                     let y1 = y0 + line_height;
                     ctx.fill(
                         Rect::new(x0, y0, x1, y1),
@@ -1232,7 +1493,8 @@ impl LapceEditor {
                             Rect::ZERO
                                 .with_origin(Point::new(
                                     x0,
-                                    line as f64 * line_height + line_padding,
+                                    Self::line_y(data, line, line_height)
+                                        + line_padding,
                                 ))
                                 .with_size(Size::new(char_width, line_height)),
                             data.config
@@ -1271,7 +1533,10 @@ impl LapceEditor {
                         let (end_line, end_col) =
                             data.doc.buffer().offset_to_line_col(start.max(end));
                         for line in paint_start_line..paint_end_line + 1 {
-                            if line < start_line || line > end_line {
+                            if line < start_line
+                                || line > end_line
+                                || data.doc.folding().is_hidden(line)
+                            {
                                 continue;
                             }
 
@@ -1310,7 +1575,8 @@ impl LapceEditor {
                                         &data.config,
                                     )
                                     .x;
-                                let y0 = line as f64 * line_height + line_padding;
+                                let y0 = Self::line_y(data, line, line_height)
+                                    + line_padding;
                                 let y1 = y0 + line_height;
                                 ctx.fill(
                                     Rect::new(x0, y0, x1, y1),
@@ -1337,7 +1603,7 @@ impl LapceEditor {
                                 &data.config,
                             )
                             .x;
-                        let y = line as f64 * line_height + line_padding;
+                        let y = Self::line_y(data, line, line_height) + line_padding;
                         ctx.stroke(
                             Line::new(
                                 Point::new(x, y),
@@ -1370,7 +1636,7 @@ impl LapceEditor {
         let size = ctx.size();
         ctx.fill(
             Rect::ZERO
-                .with_origin(Point::new(0.0, line as f64 * line_height))
+                .with_origin(Point::new(0.0, Self::line_y(data, line, line_height)))
                 .with_size(Size::new(size.width, line_height)),
             data.config
                 .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
@@ -1390,12 +1656,7 @@ impl LapceEditor {
             return;
         }
         let line_height = Self::line_height(data, env);
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let (start_line, end_line) = Self::view_lines(data, line_height);
         let start_offset = data.doc.buffer().offset_of_line(start_line);
         let end_offset = data.doc.buffer().offset_of_line(end_line + 1);
         let cursor_offset = data.editor.cursor.offset();
@@ -1416,21 +1677,36 @@ impl LapceEditor {
                     data.doc.buffer().offset_to_line_col(start);
                 let (end_line, end_col) = data.doc.buffer().offset_to_line_col(end);
                 for line in start_line..end_line + 1 {
+                    if data.doc.folding().is_hidden(line) {
+                        continue;
+                    }
                     let left_col = if line == start_line { start_col } else { 0 };
                     let right_col = if line == end_line {
                         end_col
                     } else {
                         data.doc.buffer().line_end_col(line, true) + 1
                     };
-                    let text_layout = data.doc.get_text_layout(
-                        ctx.text(),
-                        line,
-                        data.config.editor.font_size,
-                        &data.config,
-                    );
-                    let x0 = text_layout.hit_test_text_position(left_col).point.x;
-                    let x1 = text_layout.hit_test_text_position(right_col).point.x;
-                    let y0 = line as f64 * line_height;
+                    let x0 = data
+                        .doc
+                        .point_of_line_col(
+                            ctx.text(),
+                            line,
+                            left_col,
+                            data.config.editor.font_size,
+                            &data.config,
+                        )
+                        .x;
+                    let x1 = data
+                        .doc
+                        .point_of_line_col(
+                            ctx.text(),
+                            line,
+                            right_col,
+                            data.config.editor.font_size,
+                            &data.config,
+                        )
+                        .x;
+                    let y0 = Self::line_y(data, line, line_height);
                     let y1 = y0 + line_height;
                     let rect = Rect::new(x0, y0, x1, y1);
                     if active {
@@ -1454,14 +1730,74 @@ impl LapceEditor {
         }
     }
 
+    fn paint_document_highlights(data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
+        let highlights = match data.editor.document_highlights.as_ref() {
+            Some(highlights)
+                if highlights
+                    .is_for(data.doc.rev(), data.editor.cursor.offset()) =>
+            {
+                highlights
+            }
+            _ => return,
+        };
+        let line_height = data.config.editor.line_height as f64;
+        let (start_line, end_line) = Self::view_lines(data, line_height);
+
+        for (start, end, kind) in highlights.highlights.iter() {
+            let (first_line, first_col) =
+                data.doc.buffer().offset_to_line_col(*start);
+            let (last_line, last_col) = data.doc.buffer().offset_to_line_col(*end);
+            let color = match kind {
+                DocumentHighlightKind::Write => {
+                    LapceTheme::EDITOR_DOCUMENT_HIGHLIGHT_WRITE
+                }
+                _ => LapceTheme::EDITOR_DOCUMENT_HIGHLIGHT,
+            };
+
+            // A range across lines is painted on each of them, up to the end
+            // of all but its last line.
+            for line in first_line.max(start_line)..=last_line.min(end_line) {
+                if data.doc.folding().is_hidden(line) {
+                    continue;
+                }
+                let start_col = if line == first_line { first_col } else { 0 };
+                let end_col = if line == last_line {
+                    last_col
+                } else {
+                    data.doc.buffer().line_end_col(line, true)
+                };
+                let x0 = data
+                    .doc
+                    .point_of_line_col(
+                        ctx.text(),
+                        line,
+                        start_col,
+                        data.config.editor.font_size,
+                        &data.config,
+                    )
+                    .x;
+                let x1 = data
+                    .doc
+                    .point_of_line_col(
+                        ctx.text(),
+                        line,
+                        end_col,
+                        data.config.editor.font_size,
+                        &data.config,
+                    )
+                    .x;
+                let y0 = Self::line_y(data, line, line_height);
+                ctx.fill(
+                    Rect::new(x0, y0, x1, y0 + line_height),
+                    data.config.get_color_unchecked(color),
+                );
+            }
+        }
+    }
+
     fn paint_snippet(data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
         let line_height = data.config.editor.line_height as f64;
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let (start_line, end_line) = Self::view_lines(data, line_height);
         let width = data.config.editor_char_width(ctx.text());
         if let Some(snippet) = data.editor.snippet.as_ref() {
             for (_, (start, end)) in snippet {
@@ -1472,7 +1808,10 @@ impl LapceEditor {
                 let (end_line, end_col) =
                     data.doc.buffer().offset_to_line_col(*start.max(end));
                 for line in paint_start_line..paint_end_line {
-                    if line < start_line || line > end_line {
+                    if line < start_line
+                        || line > end_line
+                        || data.doc.folding().is_hidden(line)
+                    {
                         continue;
                     }
                     let line_content = data.doc.buffer().line_content(line);
@@ -1491,7 +1830,7 @@ impl LapceEditor {
                     };
                     if !line_content.is_empty() {
                         let x1 = right_col as f64 * width;
-                        let y0 = line as f64 * line_height;
+                        let y0 = Self::line_y(data, line, line_height);
                         let y1 = y0 + line_height;
                         ctx.stroke(
                             Rect::new(x0, y0, x1, y1).inflate(1.0, -0.5),
@@ -1507,12 +1846,7 @@ impl LapceEditor {
 
     fn paint_diagnostics(data: &LapceEditorBufferData, ctx: &mut PaintCtx) {
         let line_height = data.config.editor.line_height as f64;
-        let start_line =
-            (data.editor.scroll_offset.y / line_height).floor() as usize;
-        let end_line = ((data.editor.size.borrow().height
-            + data.editor.scroll_offset.y)
-            / line_height)
-            .ceil() as usize;
+        let (start_line, end_line) = Self::view_lines(data, line_height);
 
         let mut current = None;
         let cursor_offset = data.editor.cursor.offset();
@@ -1532,50 +1866,88 @@ impl LapceEditor {
                         current = Some(diagnostic.clone());
                     }
                     for line in start.line as usize..end.line as usize + 1 {
-                        if line < start_line {
+                        if line < start_line || data.doc.folding().is_hidden(line) {
                             continue;
                         }
                         if line > end_line {
                             break;
                         }
 
-                        let text_layout = data.doc.get_text_layout(
-                            ctx.text(),
-                            line,
-                            data.config.editor.font_size,
-                            &data.config,
-                        );
-                        let x0 = if line == start.line as usize {
-                            text_layout
-                                .hit_test_text_position(start.character as usize)
-                                .point
-                                .x
+                        let start_col = if line == start.line as usize {
+                            start.character as usize
                         } else {
                             let (_, col) = data.doc.buffer().offset_to_line_col(
                                 data.doc
                                     .buffer()
                                     .first_non_blank_character_on_line(line),
                             );
-                            text_layout.hit_test_text_position(col).point.x
+                            col
                         };
-                        let x1 = if line == end.line as usize {
-                            text_layout
-                                .hit_test_text_position(end.character as usize)
-                                .point
-                                .x
+                        let x0 = data
+                            .doc
+                            .point_of_line_col(
+                                ctx.text(),
+                                line,
+                                start_col,
+                                data.config.editor.font_size,
+                                &data.config,
+                            )
+                            .x;
+                        let end_col = if line == end.line as usize {
+                            end.character as usize
                         } else {
-                            let col =
-                                data.doc.buffer().line_end_col(line, false) + 1;
-                            text_layout.hit_test_text_position(col).point.x
+                            data.doc.buffer().line_end_col(line, false) + 1
                         };
-                        let _y1 = (line + 1) as f64 * line_height;
-                        let y0 = (line + 1) as f64 * line_height - 4.0;
+                        let x1 = data
+                            .doc
+                            .point_of_line_col(
+                                ctx.text(),
+                                line,
+                                end_col,
+                                data.config.editor.font_size,
+                                &data.config,
+                            )
+                            .x;
+                        let line_y = Self::line_y(data, line, line_height);
+                        let y0 = line_y + line_height - 4.0;
+
+                        // Unnecessary code, like an unused variable, is faded
+                        // out, and deprecated code is struck through.
+                        let tags =
+                            diagnostic.diagnostic.tags.as_deref().unwrap_or(&[]);
+                        if tags.contains(&DiagnosticTag::Unnecessary) {
+                            ctx.fill(
+                                Rect::new(x0, line_y, x1, line_y + line_height),
+                                &data
+                                    .config
+                                    .get_color_unchecked(
+                                        LapceTheme::EDITOR_BACKGROUND,
+                                    )
+                                    .clone()
+                                    .with_alpha(0.5),
+                            );
+                        }
+                        if tags.contains(&DiagnosticTag::Deprecated) {
+                            let y = line_y + line_height / 2.0;
+                            ctx.stroke(
+                                Line::new(Point::new(x0, y), Point::new(x1, y)),
+                                data.config.get_color_unchecked(
+                                    LapceTheme::EDITOR_FOREGROUND,
+                                ),
+                                1.0,
+                            );
+                        }
 
                         let severity = diagnostic
                             .diagnostic
                             .severity
                             .as_ref()
                             .unwrap_or(&DiagnosticSeverity::Information);
+                        // A tag is all there is to show for a hint.
+                        if *severity == DiagnosticSeverity::Hint && !tags.is_empty()
+                        {
+                            continue;
+                        }
                         let color = match severity {
                             DiagnosticSeverity::Error => data
                                 .config
@@ -1655,7 +2027,8 @@ impl LapceEditor {
                 let rect = Rect::ZERO
                     .with_origin(Point::new(
                         0.0,
-                        (start.line + 1) as f64 * line_height,
+                        Self::line_y(data, start.line as usize, line_height)
+                            + line_height,
                     ))
                     .with_size(Size::new(
                         data.editor.size.borrow().width,
@@ -1686,7 +2059,9 @@ impl LapceEditor {
                     &text_layout,
                     Point::new(
                         10.0 + data.editor.scroll_offset.x,
-                        (start.line + 1) as f64 * line_height + 10.0,
+                        Self::line_y(data, start.line as usize, line_height)
+                            + line_height
+                            + 10.0,
                     ),
                 );
                 let mut text_height = text_size.height;
@@ -1697,7 +2072,8 @@ impl LapceEditor {
                         &text,
                         Point::new(
                             10.0 + data.editor.scroll_offset.x,
-                            (start.line + 1) as f64 * line_height
+                            Self::line_y(data, start.line as usize, line_height)
+                                + line_height
                                 + 10.0
                                 + text_height,
                         ),
@@ -1708,6 +2084,187 @@ impl LapceEditor {
         }
     }
 
+    /// The first and the last lines in view, where the folded lines don't
+    /// take any room.
+    fn view_lines(data: &LapceEditorBufferData, line_height: f64) -> (usize, usize) {
+        let folding = data.doc.folding();
+        let start_line =
+            (data.editor.scroll_offset.y / line_height).floor() as usize;
+        let end_line = ((data.editor.size.borrow().height
+            + data.editor.scroll_offset.y)
+            / line_height)
+            .ceil() as usize;
+        (
+            folding.line_of_row(start_line).0,
+            folding.line_of_row(end_line).0,
+        )
+    }
+
+    /// The top of the text of the line, below the rows shown before it.
+    fn line_y(data: &LapceEditorBufferData, line: usize, line_height: f64) -> f64 {
+        data.doc.folding().row_of_line(line) as f64 * line_height
+    }
+
+    /// The box after the first line of a folded range, standing in for the
+    /// lines it hides.
+    fn paint_fold_placeholder(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        x: f64,
+        y: f64,
+        line_height: f64,
+        char_width: f64,
+    ) {
+        let text_layout = ctx
+            .text()
+            .new_text_layout("\u{22ef}")
+            .font(
+                data.config.editor.font_family(),
+                data.config.editor.font_size as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let width = text_layout.size().width.max(char_width) + char_width;
+        let rect = Rect::new(x, y + 2.0, x + width, y + line_height - 2.0);
+        ctx.fill(
+            rect.to_rounded_rect(3.0),
+            data.config
+                .get_color_unchecked(LapceTheme::EDITOR_CURRENT_LINE),
+        );
+        ctx.draw_text(
+            &text_layout,
+            Point::new(
+                x + (width - text_layout.size().width) / 2.0,
+                y + (line_height - text_layout.size().height) / 2.0,
+            ),
+        );
+    }
+
+    /// Who last changed the line and when, after its end.
+    fn paint_inline_blame(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        line: usize,
+        x: f64,
+        y: f64,
+        line_height: f64,
+    ) {
+        let hunk = match data.doc.line_blame(line) {
+            Some(hunk) => hunk,
+            None => return,
+        };
+        let text_layout = ctx
+            .text()
+            .new_text_layout(blame_summary(hunk))
+            .font(
+                data.config.editor.font_family(),
+                data.config.editor.font_size as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        ctx.draw_text(
+            &text_layout,
+            Point::new(x, y + (line_height - text_layout.size().height) / 2.0),
+        );
+    }
+
+    /// The code lenses of the line that have been resolved, with the
+    /// horizontal span of their titles, which are lined up with the
+    /// indentation of the line and separated by bars.
+    fn code_lens_spans<'a>(
+        data: &'a LapceEditorBufferData,
+        text: &mut PietText,
+        line: usize,
+    ) -> Vec<(f64, f64, &'a CodeLens, &'a lsp_types::Command)> {
+        let buffer = data.doc.buffer();
+        let indent = buffer.first_non_blank_character_on_line(line);
+        let (_, col) = buffer.offset_to_line_col(indent);
+        let mut x = data
+            .doc
+            .point_of_line_col(
+                text,
+                line,
+                col,
+                data.config.editor.font_size,
+                &data.config,
+            )
+            .x;
+        let separator = data
+            .config
+            .editor_text_width(text, Self::CODE_LENS_SEPARATOR);
+        let mut spans = Vec::new();
+        for (_, code_lens) in data.doc.line_code_lenses(line) {
+            let command = match code_lens.command.as_ref() {
+                Some(command) if !command.title.is_empty() => command,
+                _ => continue,
+            };
+            let width = data.config.editor_text_width(text, &command.title);
+            spans.push((x, x + width, code_lens, command));
+            x += width + separator;
+        }
+        spans
+    }
+
+    /// The code lens under the mouse, if it's on the row of the code lenses
+    /// of a line.
+    fn code_lens_at(
+        data: &LapceEditorBufferData,
+        text: &mut PietText,
+        pos: Point,
+    ) -> Option<CodeLens> {
+        let line_height = data.config.editor.line_height as f64;
+        let row = (pos.y / line_height).floor().max(0.0) as usize;
+        let (line, is_code_lens) = data.doc.folding().line_of_row(row);
+        if !is_code_lens {
+            return None;
+        }
+        Self::code_lens_spans(data, text, line)
+            .into_iter()
+            .find(|(start, end, ..)| *start <= pos.x && pos.x < *end)
+            .map(|(_, _, code_lens, _)| code_lens.clone())
+    }
+
+    /// The titles of the code lenses of the line, on the row above it.
+    fn paint_code_lenses(
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        line: usize,
+        y: f64,
+        line_height: f64,
+    ) {
+        let spans = Self::code_lens_spans(data, ctx.text(), line);
+        let dim = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+        for (i, (x, _, _, command)) in spans.iter().enumerate() {
+            let title = if i + 1 < spans.len() {
+                format!("{}{}", command.title, Self::CODE_LENS_SEPARATOR)
+            } else {
+                command.title.clone()
+            };
+            let text_layout = ctx
+                .text()
+                .new_text_layout(title)
+                .font(
+                    data.config.editor.font_family(),
+                    data.config.editor.font_size as f64,
+                )
+                .text_color(dim.clone())
+                .build()
+                .unwrap();
+            let text_y = y + (line_height - text_layout.size().height) / 2.0;
+            ctx.draw_text(&text_layout, Point::new(*x, text_y));
+        }
+    }
+
     fn line_height(data: &LapceEditorBufferData, env: &Env) -> f64 {
         if data.editor.content.is_input() {
             env.get(LapceTheme::INPUT_LINE_HEIGHT)
@@ -1763,11 +2320,17 @@ impl Widget<LapceTabData> for LapceEditor {
         match event {
             Event::MouseMove(mouse_event) => {
                 ctx.set_handled();
-                ctx.set_cursor(&druid::Cursor::IBeam);
                 let doc = data.main_split.editor_doc(self.view_id);
                 let editor =
                     data.main_split.editors.get(&self.view_id).unwrap().clone();
                 let mut editor_data = data.editor_view_content(self.view_id);
+                if Self::code_lens_at(&editor_data, ctx.text(), mouse_event.pos)
+                    .is_some()
+                {
+                    ctx.set_cursor(&druid::Cursor::Pointer);
+                } else {
+                    ctx.set_cursor(&druid::Cursor::IBeam);
+                }
                 self.mouse_move(
                     ctx,
                     mouse_event.pos,
@@ -1858,6 +2421,8 @@ impl Widget<LapceTabData> for LapceEditor {
                                         &editor_data.config,
                                     )
                                     .x;
+                                let line =
+                                    editor_data.doc.folding().row_of_line(line);
                                 let y = editor_data.config.editor.line_height as f64
                                     * (line + 1) as f64;
                                 ctx.to_window(Point::new(x, y))
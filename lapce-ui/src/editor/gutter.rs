@@ -1,18 +1,25 @@
 use crate::svg::get_svg;
+use chrono::NaiveDateTime;
 use druid::{
     piet::{PietText, Text, TextLayout, TextLayoutBuilder},
     BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
     LifeCycleCtx, PaintCtx, Point, Rect, RenderContext, Size, Target, UpdateCtx,
     Widget, WidgetId,
 };
-use lapce_core::buffer::DiffLines;
+use lapce_core::buffer::{DiffBlock, DiffLines};
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
     config::LapceTheme,
     data::{EditorView, LapceTabData},
+    document::BufferContent,
     editor::{LapceEditorBufferData, Syntax},
 };
 
+/// The width of the column of fold markers, right of the code actions
+const FOLD_MARKER_WIDTH: f64 = 16.0;
+/// The width in characters of the blame column, right of the fold markers
+const BLAME_WIDTH: usize = 24;
+
 pub struct LapceEditorGutter {
     view_id: WidgetId,
     width: f64,
@@ -43,6 +50,28 @@ impl Widget<LapceTabData> for LapceEditorGutter {
             }
             Event::MouseUp(mouse_event) => {
                 let data = data.editor_view_content(self.view_id);
+                if let Some(line) =
+                    self.fold_marker_line(ctx.text(), &data, mouse_event.pos)
+                {
+                    ctx.submit_command(Command::new(
+                        LAPCE_UI_COMMAND,
+                        LapceUICommand::ToggleFold(line),
+                        Target::Widget(self.view_id),
+                    ));
+                    return;
+                }
+                if let Some(commit) =
+                    self.blame_commit(ctx.text(), &data, mouse_event.pos)
+                {
+                    if let BufferContent::File(path) = data.doc.content() {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::OpenFileDiff(path.clone(), commit),
+                            Target::Auto,
+                        ));
+                    }
+                    return;
+                }
                 if let Some(actions) = data.current_code_actions() {
                     if !actions.is_empty() {
                         let rect = self.code_actions_rect(ctx.text(), &data);
@@ -53,6 +82,7 @@ impl Widget<LapceTabData> for LapceEditorGutter {
                             let offset = data.editor.cursor.offset();
                             let (line, _) =
                                 data.doc.buffer().offset_to_line_col(offset);
+                            let line = data.doc.folding().row_of_line(line);
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
                                 LapceUICommand::ShowCodeActions(Some(
@@ -130,6 +160,11 @@ impl Widget<LapceTabData> for LapceEditorGutter {
         let mut width = self.width + 16.0 + char_width * 2.0;
         if data.editor.compare.is_some() {
             width += self.width + char_width * 2.0;
+        } else {
+            width += FOLD_MARKER_WIDTH;
+            if data.editor.blame {
+                width += char_width * (BLAME_WIDTH + 1) as f64;
+            }
         }
         Size::new(width.ceil(), bc.max().height)
     }
@@ -141,6 +176,115 @@ impl Widget<LapceTabData> for LapceEditorGutter {
 }
 
 impl LapceEditorGutter {
+    /// Paints the line numbers of both sides of a side-by-side diff, on the
+    /// rows they're on.
+    fn paint_gutter_side_by_side(
+        &self,
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        version: &str,
+    ) {
+        let history = match data.doc.get_history(version) {
+            Some(history) => history,
+            None => return,
+        };
+        let self_size = ctx.size();
+        let line_height = data.config.editor.line_height as f64;
+        let scroll_offset = data.editor.scroll_offset;
+        let start_row = (scroll_offset.y / line_height).floor() as usize;
+        let end_row =
+            ((scroll_offset.y + self_size.height) / line_height).ceil() as usize;
+        let current_line = data
+            .doc
+            .buffer()
+            .line_of_offset(data.editor.cursor.offset());
+        let char_width = data.config.editor_char_width(ctx.text());
+        let label_len = (data.doc.buffer().last_line() + 1).to_string().len();
+
+        let mut row = 0;
+        for block in history.diff_blocks() {
+            if row > end_row {
+                break;
+            }
+            let rows = block.rows();
+            let (left, right, is_changed) = match block {
+                DiffBlock::Both(l, r) => (l, r, false),
+                DiffBlock::Changed(l, r) => (l, r, true),
+                DiffBlock::Skip(_, _) => {
+                    let rect = Size::new(self_size.width, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(
+                            0.0,
+                            line_height * row as f64 - scroll_offset.y,
+                        ));
+                    ctx.fill(
+                        rect,
+                        data.config
+                            .get_color_unchecked(LapceTheme::PANEL_BACKGROUND),
+                    );
+                    row += 1;
+                    continue;
+                }
+            };
+            for current_row in start_row.max(row)..(row + rows).min(end_row + 1) {
+                let i = current_row - row;
+                let y = line_height * current_row as f64 - scroll_offset.y;
+                let columns = [
+                    (left, false, 0.0, LapceTheme::SOURCE_CONTROL_REMOVED),
+                    (
+                        right,
+                        true,
+                        self.width + 2.0 * char_width,
+                        LapceTheme::SOURCE_CONTROL_ADDED,
+                    ),
+                ];
+                for (range, is_right, x, color) in columns {
+                    if i >= range.len() {
+                        continue;
+                    }
+                    let line = range.start + i;
+                    if is_changed {
+                        ctx.fill(
+                            Rect::new(
+                                x,
+                                y,
+                                x + self.width + 2.0 * char_width,
+                                y + line_height,
+                            ),
+                            data.config.get_color_unchecked(color),
+                        );
+                    }
+                    let content = (line + 1).to_string();
+                    let is_current = is_right && line == current_line;
+                    let text_layout = ctx
+                        .text()
+                        .new_text_layout(
+                            " ".repeat(label_len.saturating_sub(content.len()))
+                                + &content,
+                        )
+                        .font(
+                            data.config.editor.font_family(),
+                            data.config.editor.font_size as f64,
+                        )
+                        .text_color(
+                            data.config
+                                .get_color_unchecked(if is_current {
+                                    LapceTheme::EDITOR_FOREGROUND
+                                } else {
+                                    LapceTheme::EDITOR_DIM
+                                })
+                                .clone(),
+                        )
+                        .build()
+                        .unwrap();
+                    let text_y = y + (line_height - text_layout.size().height) / 2.0;
+                    ctx.draw_text(&text_layout, Point::new(x, text_y));
+                }
+            }
+            row += rows;
+        }
+    }
+
     fn paint_gutter_inline_diff(
         &self,
         data: &LapceEditorBufferData,
@@ -481,6 +625,7 @@ impl LapceEditorGutter {
         let line_height = data.config.editor.line_height as f64;
         let offset = data.editor.cursor.offset();
         let (line, _) = data.doc.buffer().offset_to_line_col(offset);
+        let line = data.doc.folding().row_of_line(line);
 
         let width = 16.0;
         let height = 16.0;
@@ -493,6 +638,121 @@ impl LapceEditorGutter {
         rect
     }
 
+    fn fold_marker_x(
+        &self,
+        text: &mut PietText,
+        data: &LapceEditorBufferData,
+    ) -> f64 {
+        self.width + 16.0 + data.config.editor_char_width(text) * 2.0
+    }
+
+    /// The line whose fold marker is at the position, if any.
+    fn fold_marker_line(
+        &self,
+        text: &mut PietText,
+        data: &LapceEditorBufferData,
+        pos: Point,
+    ) -> Option<usize> {
+        if data.editor.compare.is_some() || data.editor.code_lens {
+            return None;
+        }
+        let x = self.fold_marker_x(text, data);
+        if pos.x < x || pos.x > x + FOLD_MARKER_WIDTH {
+            return None;
+        }
+        let line_height = data.config.editor.line_height as f64;
+        let row = |y: f64| {
+            ((y + data.editor.scroll_offset.y) / line_height).floor() as usize
+        };
+        if row(pos.y) != row(self.mouse_down_pos.y) {
+            return None;
+        }
+        let (line, is_code_lens) = data.doc.folding().line_of_row(row(pos.y));
+        if is_code_lens {
+            return None;
+        }
+        data.doc.folding().range_at(line).map(|_| line)
+    }
+
+    /// The commit the blame under the mouse is about, for the lines that
+    /// are committed.
+    fn blame_commit(
+        &self,
+        text: &mut PietText,
+        data: &LapceEditorBufferData,
+        pos: Point,
+    ) -> Option<String> {
+        if !data.editor.blame
+            || data.editor.compare.is_some()
+            || data.editor.code_lens
+            || pos.x < self.fold_marker_x(text, data) + FOLD_MARKER_WIDTH
+        {
+            return None;
+        }
+        let line_height = data.config.editor.line_height as f64;
+        let row = ((pos.y + data.editor.scroll_offset.y) / line_height).floor();
+        let (line, is_code_lens) = data.doc.folding().line_of_row(row as usize);
+        if is_code_lens {
+            return None;
+        }
+        let commit = data.doc.line_blame(line)?.commit.as_ref()?;
+        Some(commit.id.clone())
+    }
+
+    /// The date and author of the commit that last changed the lines, on
+    /// the first line of the hunk, or the first line in view.
+    fn paint_blame(
+        &self,
+        data: &LapceEditorBufferData,
+        ctx: &mut PaintCtx,
+        line: usize,
+        first_row: bool,
+        y: f64,
+        current_line: usize,
+    ) {
+        let hunk = match data.doc.line_blame(line) {
+            Some(hunk) if hunk.line == line || first_row => hunk,
+            _ => return,
+        };
+        let current =
+            hunk.line <= current_line && current_line < hunk.line + hunk.lines;
+        let label = match hunk.commit.as_ref() {
+            Some(commit) => {
+                let date =
+                    NaiveDateTime::from_timestamp(commit.time, 0).format("%Y-%m-%d");
+                format!("{date} {}", commit.author)
+            }
+            None => "Not committed yet".to_string(),
+        };
+        let label: String = label.chars().take(BLAME_WIDTH).collect();
+        let line_height = data.config.editor.line_height as f64;
+        let text_layout = ctx
+            .text()
+            .new_text_layout(label)
+            .font(
+                data.config.editor.font_family(),
+                data.config.editor.font_size as f64,
+            )
+            .text_color(
+                data.config
+                    .get_color_unchecked(if current {
+                        LapceTheme::EDITOR_FOREGROUND
+                    } else {
+                        LapceTheme::EDITOR_DIM
+                    })
+                    .clone(),
+            )
+            .build()
+            .unwrap();
+        let x = self.fold_marker_x(ctx.text(), data)
+            + FOLD_MARKER_WIDTH
+            + data.config.editor_char_width(ctx.text()) / 2.0;
+        ctx.draw_text(
+            &text_layout,
+            Point::new(x, y + (line_height - text_layout.size().height) / 2.0),
+        );
+    }
+
     fn paint_code_actions_hint(
         &self,
         data: &LapceEditorBufferData,
@@ -520,13 +780,19 @@ impl LapceEditorGutter {
                 self.paint_gutter_inline_diff(data, ctx, version);
                 return;
             }
+            if let EditorView::SideBySide(version) = &data.editor.view {
+                self.paint_gutter_side_by_side(data, ctx, version);
+                return;
+            }
             if data.editor.code_lens {
                 self.paint_gutter_code_lens(data, ctx);
                 return;
             }
             let line_height = data.config.editor.line_height as f64;
             let scroll_offset = data.editor.scroll_offset;
-            let start_line = (scroll_offset.y / line_height).floor() as usize;
+            let folding = data.doc.folding();
+            let start_row = (scroll_offset.y / line_height).floor() as usize;
+            let (start_line, _) = folding.line_of_row(start_row);
             let num_lines = (ctx.size().height / line_height).floor() as usize;
             let last_line = data.doc.buffer().last_line();
             let current_line = data
@@ -537,7 +803,7 @@ impl LapceEditorGutter {
 
             let line_label_length =
                 (last_line + 1).to_string().len() as f64 * char_width;
-            let last_displayed_line = (start_line + num_lines + 1).min(last_line);
+            let fold_marker_x = self.fold_marker_x(ctx.text(), data);
 
             let sequential_line_numbers = *data.main_split.active
                 != Some(data.view_id)
@@ -545,7 +811,14 @@ impl LapceEditorGutter {
 
             let font_family = data.config.editor.font_family();
 
-            for line in start_line..last_displayed_line + 1 {
+            for row in start_row..start_row + num_lines + 2 {
+                let (line, is_code_lens) = folding.line_of_row(row);
+                if line > last_line {
+                    break;
+                }
+                if is_code_lens {
+                    continue;
+                }
                 let line_no = if sequential_line_numbers || line == current_line {
                     line + 1
                 } else {
@@ -579,15 +852,48 @@ impl LapceEditorGutter {
                 let x = line_label_length as f64 - text_layout.size().width;
 
                 // Vertically centered
-                let y = line_height * line as f64 - scroll_offset.y
-                    + (line_height - text_layout.size().height) / 2.0;
+                let line_y = line_height * row as f64 - scroll_offset.y;
+                let y = line_y + (line_height - text_layout.size().height) / 2.0;
 
                 ctx.draw_text(&text_layout, Point::new(x, y));
+
+                if data.editor.blame {
+                    self.paint_blame(
+                        data,
+                        ctx,
+                        line,
+                        row == start_row,
+                        line_y,
+                        current_line,
+                    );
+                }
+
+                if let Some(range) = folding.range_at(line) {
+                    let icon_name = if range.folded {
+                        "chevron-right.svg"
+                    } else {
+                        "chevron-down.svg"
+                    };
+                    let padding = (line_height - FOLD_MARKER_WIDTH) / 2.0 + 2.0;
+                    let rect = Size::new(FOLD_MARKER_WIDTH, line_height)
+                        .to_rect()
+                        .with_origin(Point::new(fold_marker_x, line_y))
+                        .inflate(-2.0, -padding);
+                    ctx.draw_svg(
+                        &get_svg(icon_name).unwrap(),
+                        rect,
+                        Some(
+                            data.config.get_color_unchecked(LapceTheme::EDITOR_DIM),
+                        ),
+                    );
+                }
             }
 
             if let Some(history) = data.doc.get_history("head") {
-                let end_line =
-                    (scroll_offset.y + rect.height() / line_height).ceil() as usize;
+                let (end_line, _) = folding.line_of_row(
+                    ((scroll_offset.y + rect.height()) / line_height).ceil()
+                        as usize,
+                );
 
                 let mut line = 0;
                 let mut last_change = None;
@@ -631,8 +937,9 @@ impl LapceEditorGutter {
                     if let Some(color) = color.cloned() {
                         let removed_height = 10.0;
                         let x = self.width + char_width;
-                        let mut y =
-                            (line - len) as f64 * line_height - scroll_offset.y;
+                        let start = folding.row_of_line(line - len);
+                        let visual_len = folding.row_of_line(line) - start;
+                        let mut y = start as f64 * line_height - scroll_offset.y;
                         if len == 0 {
                             y -= removed_height / 2.0;
                         }
@@ -655,7 +962,7 @@ impl LapceEditorGutter {
                                 if len == 0 {
                                     removed_height
                                 } else {
-                                    line_height * len as f64
+                                    line_height * visual_len as f64
                                 },
                             ),
                         );
@@ -135,14 +135,16 @@ impl LapceEditorHeader {
                 clip_rect.x1 = icon.rect.x0;
             }
         }
-        if let BufferContent::File(_) | BufferContent::Scratch(..) =
-            data.doc.content()
+        if let BufferContent::File(_)
+        | BufferContent::Scratch(..)
+        | BufferContent::LspLog(_) = data.doc.content()
         {
             let mut path = match data.doc.content() {
                 BufferContent::File(path) => path.to_path_buf(),
                 BufferContent::Scratch(_, scratch_doc_name) => {
                     scratch_doc_name.into()
                 }
+                BufferContent::LspLog(name) => name.into(),
                 _ => PathBuf::from(""),
             };
 
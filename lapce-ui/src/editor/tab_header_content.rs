@@ -364,7 +364,9 @@ impl Widget<LapceTabData> for LapceEditorTabHeaderContent {
                                 text = s.to_string();
                             }
                         }
-                    } else if let BufferContent::Scratch(..) = &editor.content {
+                    } else if let BufferContent::Scratch(..)
+                    | BufferContent::LspLog(_) = &editor.content
+                    {
                         text = editor.content.file_name().to_string();
                     }
                 }
@@ -43,6 +43,13 @@ pub struct LapceEditorView {
     pub find: Option<WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>>,
     cursor_blink_timer: TimerToken,
     last_idle_timer: TimerToken,
+    inlay_hints_timer: TimerToken,
+    folding_ranges_timer: TimerToken,
+    code_lens_timer: TimerToken,
+    code_lens_resolve_timer: TimerToken,
+    blame_timer: TimerToken,
+    semantic_styles_timer: TimerToken,
+    document_highlight_timer: TimerToken,
 }
 
 pub fn editor_tab_child_widget(
@@ -78,6 +85,13 @@ impl LapceEditorView {
             find,
             cursor_blink_timer: TimerToken::INVALID,
             last_idle_timer: TimerToken::INVALID,
+            inlay_hints_timer: TimerToken::INVALID,
+            folding_ranges_timer: TimerToken::INVALID,
+            code_lens_timer: TimerToken::INVALID,
+            code_lens_resolve_timer: TimerToken::INVALID,
+            blame_timer: TimerToken::INVALID,
+            semantic_styles_timer: TimerToken::INVALID,
+            document_highlight_timer: TimerToken::INVALID,
         }
     }
 
@@ -131,7 +145,9 @@ impl LapceEditorView {
             ));
         }
         match &editor.content {
-            BufferContent::File(_) | BufferContent::Scratch(..) => {
+            BufferContent::File(_)
+            | BufferContent::Scratch(..)
+            | BufferContent::LspLog(_) => {
                 data.focus_area = FocusArea::Editor;
                 data.main_split.active = Arc::new(Some(self.view_id));
                 data.main_split.active_tab = Arc::new(editor.tab_id);
@@ -139,6 +155,7 @@ impl LapceEditorView {
             BufferContent::Local(kind) => match kind {
                 LocalBufferKind::Keymap => {}
                 LocalBufferKind::Settings => {}
+                LocalBufferKind::Rename => {}
                 LocalBufferKind::Palette => {
                     data.focus_area = FocusArea::Palette;
                 }
@@ -172,7 +189,22 @@ impl LapceEditorView {
     ) {
         match cmd {
             LapceUICommand::RunCodeAction(action) => {
-                data.run_code_action(action);
+                data.run_code_action(ctx, action);
+            }
+            LapceUICommand::ApplyCodeAction(action) => {
+                data.apply_code_action(ctx, action);
+            }
+            LapceUICommand::ToggleFold(line) => {
+                data.toggle_fold(ctx, *line);
+            }
+            LapceUICommand::ExpandSelection {
+                rev,
+                offset,
+                ranges,
+            } => {
+                if data.doc.rev() == *rev && data.editor.cursor.offset() == *offset {
+                    data.apply_expand_selection(ranges.as_deref());
+                }
             }
             LapceUICommand::EnsureCursorVisible(position) => {
                 self.ensure_cursor_visible(
@@ -201,9 +233,11 @@ impl LapceEditorView {
                 }
                 let offset = data.editor.cursor.offset();
                 let line = data.doc.buffer().line_of_offset(offset);
-                let _ = data.apply_completion_item(item);
+                let line = data.doc.folding().row_of_line(line);
+                let _ = data.apply_completion_item(ctx, item);
                 let new_offset = data.editor.cursor.offset();
                 let new_line = data.doc.buffer().line_of_offset(new_offset);
+                let new_line = data.doc.folding().row_of_line(new_line);
                 if line != new_line {
                     self.editor
                         .widget_mut()
@@ -477,18 +511,29 @@ impl LapceEditorView {
             }
             y
         } else {
-            let line = if let EditorView::Diff(version) = &data.editor.view {
-                data.doc.history_visual_line(version, line)
-            } else {
-                line
+            let line = match &data.editor.view {
+                EditorView::Diff(version) => {
+                    data.doc.history_visual_line(version, line)
+                }
+                EditorView::SideBySide(version) => {
+                    data.doc.side_by_side_row(version, line)
+                }
+                _ => data.doc.folding().row_of_line(line),
             };
             line as f64 * line_height
         };
 
-        Rect::ZERO
+        let rect = Rect::ZERO
             .with_size(Size::new(width, line_height))
             .with_origin(Point::new(cursor_x, y))
-            .inflate(width, line_height)
+            .inflate(width, line_height);
+        if let EditorView::SideBySide(_) = &data.editor.view {
+            // The right side scrolls with the left one, so the cursor has to
+            // be visible at both of its columns.
+            rect.union(rect + Vec2::new(data.editor.side_by_side_offset(), 0.0))
+        } else {
+            rect
+        }
     }
 }
 
@@ -552,6 +597,45 @@ impl Widget<LapceTabData> for LapceEditorView {
                     self.cursor_blink_timer = TimerToken::INVALID;
                 }
             }
+            Event::Timer(id) if self.inlay_hints_timer == *id => {
+                ctx.set_handled();
+                self.inlay_hints_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id).get_inlay_hints(ctx);
+            }
+            Event::Timer(id) if self.folding_ranges_timer == *id => {
+                ctx.set_handled();
+                self.folding_ranges_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id)
+                    .get_folding_ranges(ctx);
+            }
+            Event::Timer(id) if self.code_lens_timer == *id => {
+                ctx.set_handled();
+                self.code_lens_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id).get_code_lens(ctx);
+            }
+            Event::Timer(id) if self.code_lens_resolve_timer == *id => {
+                ctx.set_handled();
+                self.code_lens_resolve_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id)
+                    .resolve_code_lenses(ctx);
+            }
+            Event::Timer(id) if self.blame_timer == *id => {
+                ctx.set_handled();
+                self.blame_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id).get_blame(ctx);
+            }
+            Event::Timer(id) if self.semantic_styles_timer == *id => {
+                ctx.set_handled();
+                self.semantic_styles_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id)
+                    .get_semantic_styles_range(ctx);
+            }
+            Event::Timer(id) if self.document_highlight_timer == *id => {
+                ctx.set_handled();
+                self.document_highlight_timer = TimerToken::INVALID;
+                data.editor_view_content(self.view_id)
+                    .get_document_highlights(ctx);
+            }
             Event::Timer(id) if self.last_idle_timer == *id => {
                 ctx.set_handled();
                 let editor_data = data.editor_view_content(self.view_id);
@@ -670,6 +754,16 @@ impl Widget<LapceTabData> for LapceEditorView {
 
         match event {
             LifeCycle::WidgetAdded => {
+                self.inlay_hints_timer =
+                    ctx.request_timer(Duration::from_millis(300), None);
+                self.folding_ranges_timer =
+                    ctx.request_timer(Duration::from_millis(500), None);
+                self.code_lens_timer =
+                    ctx.request_timer(Duration::from_millis(500), None);
+                self.blame_timer =
+                    ctx.request_timer(Duration::from_millis(500), None);
+                self.semantic_styles_timer =
+                    ctx.request_timer(Duration::from_millis(100), None);
                 let editor = data.main_split.editors.get(&self.view_id).unwrap();
                 if editor.scroll_offset.x > 0.0 || editor.scroll_offset.y > 0.0 {
                     ctx.submit_command(Command::new(
@@ -821,7 +915,12 @@ impl Widget<LapceTabData> for LapceEditorView {
         if editor_data.editor.code_lens != old_editor_data.editor.code_lens {
             ctx.request_layout();
         }
-        if let EditorView::Diff(version) = &editor_data.editor.view {
+        if editor_data.editor.view != old_editor_data.editor.view {
+            ctx.request_layout();
+        }
+        if let EditorView::Diff(version) | EditorView::SideBySide(version) =
+            &editor_data.editor.view
+        {
             let old_history = old_editor_data.doc.get_history(version);
             let history = editor_data.doc.get_history(version);
             match (history, old_history) {
@@ -869,6 +968,95 @@ impl Widget<LapceTabData> for LapceEditorView {
             ctx.request_paint();
         }
 
+        if data.focus == self.view_id
+            && doc.content().is_file()
+            && (editor_data.editor.cursor.offset()
+                != old_editor_data.editor.cursor.offset()
+                || doc.rev() != old_doc.rev()
+                || old_data.focus != self.view_id)
+        {
+            self.document_highlight_timer =
+                ctx.request_timer(Duration::from_millis(200), None);
+        }
+        match (
+            editor_data.editor.document_highlights.as_ref(),
+            old_editor_data.editor.document_highlights.as_ref(),
+        ) {
+            (None, None) => {}
+            (Some(new), Some(old)) if Arc::ptr_eq(new, old) => {}
+            _ => ctx.request_paint(),
+        }
+
+        if !Arc::ptr_eq(doc.inlay_hints(), old_doc.inlay_hints())
+            || doc.show_inlay_hints() != old_doc.show_inlay_hints()
+        {
+            ctx.request_paint();
+        }
+
+        if doc.content().is_file()
+            && doc.show_inlay_hints()
+            && (doc.rev() != old_doc.rev()
+                || doc.loaded() != old_doc.loaded()
+                || !old_doc.show_inlay_hints()
+                || editor_data.editor.scroll_offset
+                    != old_editor_data.editor.scroll_offset)
+        {
+            self.inlay_hints_timer =
+                ctx.request_timer(Duration::from_millis(300), None);
+        }
+
+        if !Arc::ptr_eq(doc.folding(), old_doc.folding()) {
+            ctx.request_layout();
+        }
+
+        if doc.content().is_file()
+            && (doc.rev() != old_doc.rev() || doc.loaded() != old_doc.loaded())
+        {
+            self.folding_ranges_timer =
+                ctx.request_timer(Duration::from_millis(500), None);
+            self.code_lens_timer =
+                ctx.request_timer(Duration::from_millis(500), None);
+        }
+
+        if !Arc::ptr_eq(doc.code_lenses(), old_doc.code_lenses()) {
+            ctx.request_paint();
+        }
+
+        if editor_data.editor.blame != old_editor_data.editor.blame {
+            ctx.request_layout();
+        }
+        if !Arc::ptr_eq(doc.blame(), old_doc.blame()) {
+            ctx.request_paint();
+        }
+        // Committing changes the blame without changing the document.
+        if doc.content().is_file()
+            && (doc.rev() != old_doc.rev()
+                || doc.loaded() != old_doc.loaded()
+                || editor_data.editor.blame != old_editor_data.editor.blame
+                || !Arc::ptr_eq(&data.source_control, &old_data.source_control))
+        {
+            self.blame_timer = ctx.request_timer(Duration::from_millis(500), None);
+        }
+        // The code lenses are resolved once they're in view.
+        if !doc.code_lenses().is_empty()
+            && (doc.code_lenses_rev() != old_doc.code_lenses_rev()
+                || editor_data.editor.scroll_offset
+                    != old_editor_data.editor.scroll_offset)
+        {
+            self.code_lens_resolve_timer =
+                ctx.request_timer(Duration::from_millis(300), None);
+        }
+
+        if doc.content().is_file()
+            && !doc.has_semantic_styles()
+            && (doc.loaded() != old_doc.loaded()
+                || editor_data.editor.scroll_offset
+                    != old_editor_data.editor.scroll_offset)
+        {
+            self.semantic_styles_timer =
+                ctx.request_timer(Duration::from_millis(100), None);
+        }
+
         if old_editor_data.current_code_actions().is_some()
             != editor_data.current_code_actions().is_some()
         {
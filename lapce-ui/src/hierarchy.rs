@@ -0,0 +1,310 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
+    BoxConstraints, Command, Cursor, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    LifeCycleCtx, MouseEvent, PaintCtx, Point, RenderContext, Size, Target,
+    UpdateCtx, Widget, WidgetExt,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::{LapceTabData, PanelKind},
+    editor::EditorLocation,
+    hierarchy::{HierarchyData, HierarchyNode, HierarchyRow},
+    split::SplitDirection,
+};
+use lapce_rpc::proxy::HierarchyKind;
+
+use crate::{
+    panel::{LapcePanel, PanelHeaderKind},
+    svg::{get_svg, symbol_svg},
+};
+
+pub fn new_hierarchy_panel(data: &HierarchyData) -> LapcePanel {
+    LapcePanel::new(
+        PanelKind::Hierarchy,
+        data.widget_id,
+        data.split_id,
+        SplitDirection::Vertical,
+        PanelHeaderKind::Simple("Hierarchy".into()),
+        vec![(
+            data.split_id,
+            PanelHeaderKind::None,
+            HierarchyContent::new().boxed(),
+            None,
+        )],
+    )
+}
+
+fn kind_name(kind: HierarchyKind) -> &'static str {
+    match kind {
+        HierarchyKind::IncomingCalls => "Incoming Calls",
+        HierarchyKind::OutgoingCalls => "Outgoing Calls",
+        HierarchyKind::Supertypes => "Supertypes",
+        HierarchyKind::Subtypes => "Subtypes",
+    }
+}
+
+struct HierarchyContent {
+    mouse_pos: Point,
+    line_height: f64,
+    content_height: f64,
+}
+
+impl HierarchyContent {
+    pub fn new() -> Self {
+        Self {
+            mouse_pos: Point::ZERO,
+            line_height: 25.0,
+            content_height: 0.0,
+        }
+    }
+
+    /// A click on the chevron of a node opens or closes it, and a click
+    /// anywhere else on it jumps to its symbol.
+    fn mouse_down(
+        &self,
+        ctx: &mut EventCtx,
+        mouse_event: &MouseEvent,
+        data: &mut LapceTabData,
+    ) {
+        let n = (mouse_event.pos.y / self.line_height).floor() as usize;
+        let (tree, path, location) =
+            match data.hierarchy.rows_between(n, n + 1).into_iter().next() {
+                Some(HierarchyRow::Node { tree, path, node }) => {
+                    let location = node.item.uri.to_file_path().ok().map(|path| {
+                        EditorLocation {
+                            path,
+                            position: Some(node.item.selection_range.start),
+                            scroll_offset: None,
+                            history: None,
+                        }
+                    });
+                    (tree, path, location)
+                }
+                _ => return,
+            };
+
+        let chevron_x = self.line_height * path.len() as f64;
+        if mouse_event.pos.x < chevron_x {
+            Arc::make_mut(&mut data.hierarchy).toggle(
+                tree,
+                &path,
+                &data.proxy,
+                ctx.get_external_handle(),
+            );
+        } else if let Some(location) = location {
+            ctx.submit_command(Command::new(
+                LAPCE_UI_COMMAND,
+                LapceUICommand::JumpToLocation(None, location),
+                Target::Widget(data.id),
+            ));
+        }
+    }
+
+    /// Paints the chevron and the symbol icon of the node, and returns where
+    /// its name starts.
+    fn paint_node_icons(
+        &self,
+        ctx: &mut PaintCtx,
+        data: &LapceTabData,
+        y: f64,
+        level: usize,
+        node: &HierarchyNode,
+    ) -> f64 {
+        let line_height = self.line_height;
+        let padding = (line_height - 14.0) / 2.0;
+        let color = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND);
+
+        let has_children = node
+            .children
+            .as_ref()
+            .map(|children| !children.is_empty())
+            .unwrap_or(true);
+        if has_children {
+            let icon_name = if node.open {
+                "chevron-down.svg"
+            } else {
+                "chevron-right.svg"
+            };
+            let rect = Size::new(line_height, line_height)
+                .to_rect()
+                .with_origin(Point::new(line_height * (level - 1) as f64, y))
+                .inflate(-padding, -padding);
+            ctx.draw_svg(&get_svg(icon_name).unwrap(), rect, Some(color));
+        }
+
+        let x = line_height * level as f64;
+        if let Some(svg) = symbol_svg(&node.item.kind) {
+            let rect = Size::new(line_height, line_height)
+                .to_rect()
+                .with_origin(Point::new(x, y))
+                .inflate(-padding, -padding);
+            ctx.draw_svg(&svg, rect, Some(color));
+        }
+        x + line_height
+    }
+}
+
+/// The detail of the symbol, like its signature, and the file it's in.
+fn node_hint(node: &HierarchyNode) -> String {
+    let file_name = node
+        .item
+        .uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .and_then(|f| f.to_str())
+                .map(|f| f.to_string())
+        })
+        .unwrap_or_default();
+    match node.item.detail.as_ref() {
+        Some(detail) => format!("{detail} {file_name}"),
+        None => file_name,
+    }
+}
+
+impl Widget<LapceTabData> for HierarchyContent {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::MouseMove(mouse_event) => {
+                self.mouse_pos = mouse_event.pos;
+
+                if mouse_event.pos.y < self.content_height {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+
+                ctx.request_paint();
+            }
+            Event::MouseDown(mouse_event) => {
+                self.mouse_down(ctx, mouse_event, data);
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !Arc::ptr_eq(&data.hierarchy, &old_data.hierarchy) {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        self.line_height = data.config.editor.line_height as f64;
+        self.content_height = self.line_height * data.hierarchy.rows() as f64;
+
+        Size::new(bc.max().width, self.content_height.max(bc.max().height))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        let line_height = self.line_height;
+        let size = ctx.size();
+        let mouse_line = (self.mouse_pos.y / line_height).floor() as usize;
+
+        let rect = ctx.region().bounding_box();
+        let min = (rect.y0 / line_height).floor() as usize;
+        let max = (rect.y1 / line_height) as usize + 2;
+
+        let rows = data.hierarchy.rows_between(min, max);
+        for (i, row) in rows.into_iter().enumerate() {
+            let line = min + i;
+            let y = line_height * line as f64;
+            let (x, text, hint) = match row {
+                HierarchyRow::Header(kind) => {
+                    (10.0, kind_name(kind).to_string(), None)
+                }
+                HierarchyRow::Node { path, node, .. } => {
+                    if ctx.is_hot() && line == mouse_line {
+                        ctx.fill(
+                            Size::new(size.width, line_height)
+                                .to_rect()
+                                .with_origin(Point::new(0.0, y)),
+                            data.config.get_color_unchecked(
+                                LapceTheme::EDITOR_CURRENT_LINE,
+                            ),
+                        );
+                    }
+                    let x = self.paint_node_icons(ctx, data, y, path.len(), node);
+                    (x, node.item.name.clone(), Some(node_hint(node)))
+                }
+            };
+
+            let text_layout = ctx
+                .text()
+                .new_text_layout(text)
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            let text_width = text_layout.size().width;
+            ctx.draw_text(
+                &text_layout,
+                Point::new(x, y + (line_height - text_layout.size().height) / 2.0),
+            );
+
+            if let Some(hint) = hint {
+                let text_layout = ctx
+                    .text()
+                    .new_text_layout(hint)
+                    .font(
+                        data.config.ui.font_family(),
+                        data.config.ui.font_size() as f64,
+                    )
+                    .text_color(
+                        data.config
+                            .get_color_unchecked(LapceTheme::EDITOR_DIM)
+                            .clone(),
+                    )
+                    .build()
+                    .unwrap();
+                ctx.draw_text(
+                    &text_layout,
+                    Point::new(
+                        x + text_width + 5.0,
+                        y + (line_height - text_layout.size().height) / 2.0,
+                    ),
+                );
+            }
+        }
+    }
+}
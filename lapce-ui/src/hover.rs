@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
 use druid::{
-    kurbo::Line, theme, ArcStr, BoxConstraints, Command, Data, Env, Event, EventCtx,
-    FontDescriptor, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, Point,
-    RenderContext, Size, Target, TextLayout, UpdateCtx, Widget, WidgetId, WidgetPod,
+    kurbo::Line, text::Link, theme, ArcStr, BoxConstraints, Command, Cursor, Data,
+    Env, Event, EventCtx, FontDescriptor, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, Target, TextLayout, UpdateCtx, Widget,
+    WidgetId, WidgetPod,
 };
 use lapce_data::{
     command::{LapceUICommand, LAPCE_UI_COMMAND},
@@ -212,17 +213,55 @@ impl Hover {
             },
         }
     }
+
+    /// The height taken by the diagnostics and the separator below them.
+    fn diagnostic_height(&self, data: &LapceTabData) -> f64 {
+        if self.active_diagnostic_layout.size().is_empty() {
+            0.0
+        } else {
+            self.active_diagnostic_layout.layout_metrics().size.height
+                + data.config.editor.line_height as f64
+        }
+    }
+
+    /// The link of the diagnostics or the documentation under the mouse,
+    /// like a related location of a diagnostic.
+    fn link_at(&self, pos: Point, data: &LapceTabData) -> Option<&Link> {
+        let diagnostic_origin = Point::new(Self::STARTING_X, Self::STARTING_Y);
+        if let Some(link) = self
+            .active_diagnostic_layout
+            .link_for_pos(pos - diagnostic_origin.to_vec2())
+        {
+            return Some(link);
+        }
+        let doc_origin = diagnostic_origin + (0.0, self.diagnostic_height(data));
+        self.active_layout.link_for_pos(pos - doc_origin.to_vec2())
+    }
 }
 impl Widget<LapceTabData> for Hover {
     fn event(
         &mut self,
         ctx: &mut EventCtx,
         event: &Event,
-        _data: &mut LapceTabData,
+        data: &mut LapceTabData,
         _env: &Env,
     ) {
-        if let Event::MouseMove(_) = event {
-            ctx.set_handled();
+        match event {
+            Event::MouseMove(mouse_event) => {
+                if self.link_at(mouse_event.pos, data).is_some() {
+                    ctx.set_cursor(&Cursor::Pointer);
+                } else {
+                    ctx.clear_cursor();
+                }
+                ctx.set_handled();
+            }
+            Event::MouseDown(mouse_event) => {
+                if let Some(link) = self.link_at(mouse_event.pos, data) {
+                    ctx.submit_command(link.command.clone());
+                    ctx.set_handled();
+                }
+            }
+            _ => {}
         }
     }
 
@@ -316,15 +355,7 @@ impl Widget<LapceTabData> for Hover {
             text_metrics.size.height - text_metrics.first_baseline,
         );
 
-        let diagnostic_height = if self.active_diagnostic_layout.size().is_empty() {
-            0.0
-        } else {
-            let diagnostic_text_metrics =
-                self.active_diagnostic_layout.layout_metrics();
-
-            diagnostic_text_metrics.size.height
-                + data.config.editor.line_height as f64
-        };
+        let diagnostic_height = self.diagnostic_height(data);
 
         Size::new(
             width,
@@ -5,6 +5,7 @@ pub mod completion;
 pub mod editor;
 pub mod explorer;
 pub mod find;
+pub mod hierarchy;
 pub mod hover;
 pub mod keymap;
 mod logging;
@@ -13,9 +14,11 @@ pub mod panel;
 pub mod picker;
 pub mod plugin;
 pub mod problem;
+pub mod rename;
 pub mod scroll;
 pub mod search;
 pub mod settings;
+pub mod signature;
 pub mod source_control;
 pub mod split;
 pub mod status;
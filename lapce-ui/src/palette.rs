@@ -20,7 +20,8 @@ use lapce_data::{
     config::LapceTheme,
     data::LapceTabData,
     keypress::KeyPressFocus,
-    palette::{PaletteStatus, PaletteType, PaletteViewData, PaletteViewLens},
+    palette::{revision_name, PaletteStatus, PaletteViewData, PaletteViewLens},
+    source_control::time_ago,
 };
 
 use crate::{
@@ -479,14 +480,17 @@ impl Widget<PaletteViewData> for PaletteInput {
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &PaletteViewData, _env: &Env) {
-        let text = data.palette.input.clone();
+        let text = data.palette.display_input();
         let cursor = data.palette.cursor;
 
-        let text_layout = if text.is_empty()
-            && data.palette.palette_type == PaletteType::SshHost
-        {
+        let placeholder = data
+            .palette
+            .palette_type
+            .placeholder()
+            .filter(|_| text.is_empty());
+        let text_layout = if let Some(placeholder) = placeholder {
             ctx.text()
-                .new_text_layout("Enter your SSH details, like user@host")
+                .new_text_layout(placeholder)
                 .font(
                     data.config.ui.font_family(),
                     data.config.ui.font_size() as f64,
@@ -589,6 +593,26 @@ impl PaletteContent {
                         .collect();
                     (symbol_svg(kind), text, text_indices, hint, hint_indices)
                 }
+                PaletteItemContent::WorkspaceSymbol {
+                    kind,
+                    name,
+                    container_name,
+                    path,
+                    ..
+                } => {
+                    let path = path.to_str().unwrap_or("");
+                    let hint = match container_name {
+                        Some(container_name) => format!("{container_name} {path}"),
+                        None => path.to_string(),
+                    };
+                    (
+                        symbol_svg(kind),
+                        name.to_string(),
+                        indices.to_vec(),
+                        hint,
+                        vec![],
+                    )
+                }
                 PaletteItemContent::Line(_, text) => {
                     (None, text.clone(), indices.to_vec(), "".to_string(), vec![])
                 }
@@ -626,6 +650,49 @@ impl PaletteContent {
                     "".to_string(),
                     vec![],
                 ),
+                PaletteItemContent::LspLog(name)
+                | PaletteItemContent::GitBranch(_, name) => (
+                    None,
+                    name.to_string(),
+                    indices.to_vec(),
+                    "".to_string(),
+                    vec![],
+                ),
+                PaletteItemContent::FileCommit(_, commit) => (
+                    None,
+                    commit.summary.clone(),
+                    indices
+                        .iter()
+                        .filter(|i| **i < commit.summary.len())
+                        .copied()
+                        .collect(),
+                    format!(
+                        "{}, {} {}",
+                        commit.author,
+                        time_ago(commit.time),
+                        &commit.id[..commit.id.len().min(7)]
+                    ),
+                    vec![],
+                ),
+                PaletteItemContent::DiffRevision(_, revision) => (
+                    None,
+                    revision_name(revision).to_string(),
+                    indices.to_vec(),
+                    match revision.as_str() {
+                        "index" => "Staged changes",
+                        "head" => "Last commit",
+                        _ => "Branch",
+                    }
+                    .to_string(),
+                    vec![],
+                ),
+                PaletteItemContent::GitStash(_, entry) => (
+                    None,
+                    entry.message.clone(),
+                    indices.to_vec(),
+                    format!("stash@{{{}}}", entry.index),
+                    vec![],
+                ),
                 PaletteItemContent::TerminalLine(_line, content) => (
                     None,
                     content.clone(),
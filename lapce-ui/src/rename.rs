@@ -0,0 +1,105 @@
+use druid::{
+    BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
+    PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget, WidgetExt, WidgetId,
+    WidgetPod,
+};
+use lapce_data::{config::LapceTheme, data::LapceTabData};
+
+use crate::editor::view::LapceEditorView;
+
+/// The inline input box shown at the symbol when renaming it
+pub struct RenameBox {
+    input: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+}
+
+impl RenameBox {
+    pub fn new(data: &LapceTabData) -> Self {
+        let input =
+            LapceEditorView::new(data.rename.view_id, WidgetId::next(), None)
+                .hide_header()
+                .hide_gutter()
+                .padding((10.0, 5.0));
+        Self {
+            input: WidgetPod::new(input.boxed()),
+        }
+    }
+}
+
+impl Widget<LapceTabData> for RenameBox {
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        env: &Env,
+    ) {
+        self.input.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        self.input.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        env: &Env,
+    ) {
+        if old_data.rename.active != data.rename.active {
+            ctx.request_layout();
+        }
+        self.input.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        env: &Env,
+    ) -> Size {
+        let size = data.rename.size;
+        self.input
+            .layout(ctx, &BoxConstraints::tight(size), data, env);
+        self.input.set_origin(ctx, data, env, Point::ZERO);
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, env: &Env) {
+        if !data.rename.active {
+            return;
+        }
+
+        let rect = ctx.size().to_rect();
+        let shadow_width = data.config.ui.drop_shadow_width() as f64;
+        if shadow_width > 0.0 {
+            ctx.blurred_rect(
+                rect,
+                shadow_width,
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+            );
+        } else {
+            ctx.stroke(
+                rect.inflate(0.5, 0.5),
+                data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                1.0,
+            );
+        }
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::EDITOR_BACKGROUND),
+        );
+
+        self.input.paint(ctx, data, env);
+    }
+}
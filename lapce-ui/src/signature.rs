@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use druid::{
+    piet::{PietTextLayout, Text, TextAttribute, TextLayout, TextLayoutBuilder},
+    BoxConstraints, Data, Env, Event, EventCtx, FontWeight, LayoutCtx, LifeCycle,
+    LifeCycleCtx, PaintCtx, Point, RenderContext, Size, UpdateCtx, Widget, WidgetId,
+};
+use lapce_data::{
+    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    config::LapceTheme,
+    data::LapceTabData,
+    signature::SignatureData,
+};
+
+/// The popup above the cursor with the signature of the call it's in, and
+/// the parameter being typed highlighted.
+pub struct SignatureContainer {
+    id: WidgetId,
+    label_layout: Option<PietTextLayout>,
+    documentation_layout: Option<PietTextLayout>,
+}
+
+impl SignatureContainer {
+    const PADDING_X: f64 = 10.0;
+    const PADDING_Y: f64 = 5.0;
+    const MAX_WIDTH: f64 = 600.0;
+
+    pub fn new(data: &SignatureData) -> Self {
+        Self {
+            id: data.id,
+            label_layout: None,
+            documentation_layout: None,
+        }
+    }
+}
+
+impl Widget<LapceTabData> for SignatureContainer {
+    fn id(&self) -> Option<WidgetId> {
+        Some(self.id)
+    }
+
+    fn event(
+        &mut self,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut LapceTabData,
+        _env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(LAPCE_UI_COMMAND) => {
+                let command = cmd.get_unchecked(LAPCE_UI_COMMAND);
+                if let LapceUICommand::UpdateSignature(request_id, help) = command {
+                    let signature = Arc::make_mut(&mut data.signature);
+                    signature.receive(*request_id, help.clone());
+                    ctx.set_handled();
+                }
+            }
+            Event::MouseMove(_) => {
+                ctx.set_handled();
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &LapceTabData,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &LapceTabData,
+        data: &LapceTabData,
+        _env: &Env,
+    ) {
+        if !old_data.signature.same(&data.signature) {
+            ctx.request_layout();
+            return;
+        }
+
+        if data.signature.is_shown() {
+            let old_editor = old_data
+                .main_split
+                .editors
+                .get(&old_data.signature.editor_view_id);
+            let editor = data.main_split.editors.get(&data.signature.editor_view_id);
+            if let (Some(old_editor), Some(editor)) = (old_editor, editor) {
+                if old_editor.window_origin != editor.window_origin
+                    || old_editor.scroll_offset != editor.scroll_offset
+                {
+                    ctx.request_layout();
+                }
+            }
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        _bc: &BoxConstraints,
+        data: &LapceTabData,
+        _env: &Env,
+    ) -> Size {
+        self.label_layout = None;
+        self.documentation_layout = None;
+        let signature = match data.signature.current_signature() {
+            Some(signature) => signature,
+            None => return Size::ZERO,
+        };
+
+        let foreground = data
+            .config
+            .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+            .clone();
+        let dim = data.config.get_color_unchecked(LapceTheme::EDITOR_DIM);
+        let focus = data.config.get_color_unchecked(LapceTheme::EDITOR_FOCUS);
+        let max_width = Self::MAX_WIDTH - Self::PADDING_X * 2.0;
+
+        // The overload counter goes before the label when there are several.
+        let counter = if data.signature.len() > 1 {
+            format!(
+                "{}/{} ",
+                data.signature.active_signature + 1,
+                data.signature.len()
+            )
+        } else {
+            String::new()
+        };
+        let mut label_layout = ctx
+            .text()
+            .new_text_layout(format!("{counter}{}", signature.label))
+            .font(
+                data.config.editor.font_family(),
+                data.config.editor.font_size as f64,
+            )
+            .text_color(foreground.clone())
+            .max_width(max_width)
+            .range_attribute(
+                0..counter.len(),
+                TextAttribute::TextColor(dim.clone()),
+            );
+        if let Some((start, end)) = data.signature.active_parameter() {
+            let range = counter.len() + start..counter.len() + end;
+            label_layout = label_layout
+                .range_attribute(
+                    range.clone(),
+                    TextAttribute::TextColor(focus.clone()),
+                )
+                .range_attribute(range, TextAttribute::Weight(FontWeight::BOLD));
+        }
+        let label_layout = label_layout.build().unwrap();
+        let mut width = label_layout.size().width;
+        let mut height = label_layout.size().height;
+        self.label_layout = Some(label_layout);
+
+        let documentation = data.signature.documentation();
+        if !documentation.is_empty() {
+            let documentation_layout = ctx
+                .text()
+                .new_text_layout(documentation.join("\n\n"))
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(foreground)
+                .max_width(max_width)
+                .build()
+                .unwrap();
+            width = width.max(documentation_layout.size().width);
+            height += Self::PADDING_Y * 2.0 + documentation_layout.size().height;
+            self.documentation_layout = Some(documentation_layout);
+        }
+
+        ctx.set_paint_insets((10.0, 10.0, 10.0, 10.0));
+        Size::new(
+            width + Self::PADDING_X * 2.0,
+            height + Self::PADDING_Y * 2.0,
+        )
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
+        if !data.signature.is_shown() {
+            return;
+        }
+        let label_layout = match self.label_layout.as_ref() {
+            Some(layout) => layout,
+            None => return,
+        };
+
+        let rect = ctx.size().to_rect();
+        let shadow_width = data.config.ui.drop_shadow_width() as f64;
+        if shadow_width > 0.0 {
+            ctx.blurred_rect(
+                rect,
+                shadow_width,
+                data.config
+                    .get_color_unchecked(LapceTheme::LAPCE_DROPDOWN_SHADOW),
+            );
+        } else {
+            ctx.stroke(
+                rect.inflate(0.5, 0.5),
+                data.config.get_color_unchecked(LapceTheme::LAPCE_BORDER),
+                1.0,
+            );
+        }
+        ctx.fill(
+            rect,
+            data.config
+                .get_color_unchecked(LapceTheme::HOVER_BACKGROUND),
+        );
+
+        ctx.draw_text(label_layout, Point::new(Self::PADDING_X, Self::PADDING_Y));
+        if let Some(documentation_layout) = self.documentation_layout.as_ref() {
+            ctx.draw_text(
+                documentation_layout,
+                Point::new(
+                    Self::PADDING_X,
+                    label_layout.size().height + Self::PADDING_Y * 3.0,
+                ),
+            );
+        }
+    }
+}
@@ -1,18 +1,22 @@
 use std::sync::Arc;
 
 use druid::{
-    kurbo::BezPath,
+    kurbo::Line,
     piet::{Text, TextLayout as PietTextLayout, TextLayoutBuilder},
-    BoxConstraints, Color, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
+    BoxConstraints, Command, Env, Event, EventCtx, LayoutCtx, LifeCycle,
     LifeCycleCtx, PaintCtx, Point, RenderContext, Size, Target, UpdateCtx, Widget,
     WidgetExt, WidgetId,
 };
 use lapce_data::{
-    command::{LapceUICommand, LAPCE_UI_COMMAND},
+    command::{
+        CommandKind, LapceCommand, LapceUICommand, LapceWorkbenchCommand,
+        LAPCE_COMMAND, LAPCE_UI_COMMAND,
+    },
     config::LapceTheme,
     data::{FocusArea, LapceTabData, PanelKind},
 };
 use lapce_rpc::source_control::FileDiff;
+use serde_json::json;
 
 use crate::{
     editor::view::LapceEditorView,
@@ -32,7 +36,10 @@ pub fn new_source_control_panel(data: &LapceTabData) -> LapcePanel {
             .hide_gutter()
             .set_placeholder("Commit Message".to_string())
             .padding((15.0, 15.0));
-    let content = SourceControlFileList::new(data.source_control.file_list_id);
+    let staged =
+        SourceControlFileList::new(data.source_control.staged_list_id, true);
+    let content =
+        SourceControlFileList::new(data.source_control.file_list_id, false);
     LapcePanel::new(
         PanelKind::SourceControl,
         data.source_control.widget_id,
@@ -46,6 +53,12 @@ pub fn new_source_control_panel(data: &LapceTabData) -> LapcePanel {
                 input.boxed(),
                 Some(300.0),
             ),
+            (
+                data.source_control.staged_list_id,
+                PanelHeaderKind::Simple("Staged Changes".into()),
+                staged.boxed(),
+                None,
+            ),
             (
                 data.source_control.file_list_id,
                 PanelHeaderKind::Simple("Changes".into()),
@@ -56,21 +69,46 @@ pub fn new_source_control_panel(data: &LapceTabData) -> LapcePanel {
     )
 }
 
+/// The staged or the unstaged changes, with a button in front of each file
+/// to move it to the other list.
 struct SourceControlFileList {
     widget_id: WidgetId,
+    staged: bool,
     mouse_down: Option<usize>,
     line_height: f64,
 }
 
 impl SourceControlFileList {
-    pub fn new(widget_id: WidgetId) -> Self {
+    pub fn new(widget_id: WidgetId, staged: bool) -> Self {
         Self {
             widget_id,
+            staged,
             mouse_down: None,
             line_height: 25.0,
         }
     }
 
+    fn toggle_stage(
+        &self,
+        ctx: &mut EventCtx,
+        data: &LapceTabData,
+        diff: &FileDiff,
+    ) {
+        let command = if self.staged {
+            LapceWorkbenchCommand::SourceControlUnstage
+        } else {
+            LapceWorkbenchCommand::SourceControlStage
+        };
+        ctx.submit_command(Command::new(
+            LAPCE_COMMAND,
+            LapceCommand {
+                kind: CommandKind::Workbench(command),
+                data: Some(json!(diff)),
+            },
+            Target::Widget(data.id),
+        ));
+    }
+
     pub fn request_focus(&self, ctx: &mut EventCtx, data: &mut LapceTabData) {
         ctx.request_focus();
         let source_control = Arc::make_mut(&mut data.source_control);
@@ -101,16 +139,13 @@ impl Widget<LapceTabData> for SourceControlFileList {
                 let y = mouse_event.pos.y;
                 if y > 0.0 {
                     let line = (y / self.line_height).floor() as usize;
-                    if line < data.source_control.file_diffs.len()
-                        && mouse_event.pos.x < self.line_height
+                    if let Some(diff) =
+                        data.source_control.diffs(self.staged).get(line)
                     {
-                        if let Some(mouse_down) = self.mouse_down {
-                            if mouse_down == line {
-                                let source_control =
-                                    Arc::make_mut(&mut data.source_control);
-                                source_control.file_diffs[line].1 =
-                                    !source_control.file_diffs[line].1;
-                            }
+                        if mouse_event.pos.x < self.line_height
+                            && self.mouse_down == Some(line)
+                        {
+                            self.toggle_stage(ctx, data, diff);
                         }
                     }
                 }
@@ -123,15 +158,21 @@ impl Widget<LapceTabData> for SourceControlFileList {
                 let y = mouse_event.pos.y;
                 if y > 0.0 {
                     let line = (y / self.line_height).floor() as usize;
-                    if line < source_control.file_diffs.len() {
-                        source_control.file_list_index = line;
+                    if line < source_control.diffs(self.staged).len() {
+                        if self.staged {
+                            source_control.staged_list_index = line;
+                        } else {
+                            source_control.file_list_index = line;
+                        }
                         if mouse_event.pos.x < self.line_height {
                             self.mouse_down = Some(line);
                         } else {
                             ctx.submit_command(Command::new(
                                 LAPCE_UI_COMMAND,
                                 LapceUICommand::OpenFileDiff(
-                                    source_control.file_diffs[line].0.path().clone(),
+                                    source_control.diffs(self.staged)[line]
+                                        .path()
+                                        .clone(),
                                     "head".to_string(),
                                 ),
                                 Target::Widget(data.id),
@@ -186,8 +227,8 @@ impl Widget<LapceTabData> for SourceControlFileList {
         data: &LapceTabData,
         _env: &Env,
     ) {
-        if data.source_control.file_diffs.len()
-            != old_data.source_control.file_diffs.len()
+        if data.source_control.diffs(self.staged)
+            != old_data.source_control.diffs(self.staged)
         {
             ctx.request_layout();
         }
@@ -200,21 +241,23 @@ impl Widget<LapceTabData> for SourceControlFileList {
         data: &LapceTabData,
         _env: &Env,
     ) -> Size {
-        let height = self.line_height * data.source_control.file_diffs.len() as f64;
+        let height =
+            self.line_height * data.source_control.diffs(self.staged).len() as f64;
         Size::new(bc.max().width, height)
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &LapceTabData, _env: &Env) {
         let self_size = ctx.size();
 
-        let diffs = &data.source_control.file_diffs;
+        let diffs = data.source_control.diffs(self.staged);
 
         if ctx.is_focused() && !diffs.is_empty() {
             let rect = Size::new(ctx.size().width, self.line_height)
                 .to_rect()
                 .with_origin(Point::new(
                     0.0,
-                    data.source_control.file_list_index as f64 * self.line_height,
+                    data.source_control.list_index(self.staged) as f64
+                        * self.line_height,
                 ));
             ctx.fill(
                 rect,
@@ -230,7 +273,7 @@ impl Widget<LapceTabData> for SourceControlFileList {
                 break;
             }
             let y = self.line_height * line as f64;
-            let (diff, checked) = diffs[line].clone();
+            let diff = diffs[line].clone();
             let mut path = diff.path().clone();
             if let Some(workspace_path) = data.workspace.path.as_ref() {
                 path = path
@@ -245,15 +288,30 @@ impl Widget<LapceTabData> for SourceControlFileList {
                     (self.line_height - width) / 2.0 + 5.0,
                     (self.line_height - height) / 2.0 + y,
                 );
-                let rect = Size::new(width, height).to_rect().with_origin(origin);
-                ctx.stroke(rect, &Color::rgb8(0, 0, 0), 1.0);
-
-                if checked {
-                    let mut path = BezPath::new();
-                    path.move_to((origin.x + 3.0, origin.y + 7.0));
-                    path.line_to((origin.x + 6.0, origin.y + 9.5));
-                    path.line_to((origin.x + 10.0, origin.y + 3.0));
-                    ctx.stroke(path, &Color::rgb8(0, 0, 0), 2.0);
+                let color = data
+                    .config
+                    .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND);
+                let center =
+                    Point::new(origin.x + width / 2.0, origin.y + height / 2.0);
+                // A minus to unstage a staged file, and a plus to stage the
+                // others.
+                ctx.stroke(
+                    Line::new(
+                        (origin.x + 2.0, center.y),
+                        (origin.x + width - 2.0, center.y),
+                    ),
+                    color,
+                    1.5,
+                );
+                if !self.staged {
+                    ctx.stroke(
+                        Line::new(
+                            (center.x, origin.y + 2.0),
+                            (center.x, origin.y + height - 2.0),
+                        ),
+                        color,
+                        1.5,
+                    );
                 }
             }
             let svg = file_svg(&path);
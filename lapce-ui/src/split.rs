@@ -370,7 +370,7 @@ impl LapceSplit {
             ));
         }
         let view_id = self.children[index].widget.id();
-        data.main_split.editors.remove(&view_id);
+        data.main_split.remove_editor(ctx, &view_id);
         self.children.remove(index);
         self.children_ids.remove(index);
 
@@ -928,6 +928,42 @@ impl Widget<LapceTabData> for LapceSplit {
                             ctx.children_changed();
                         }
                     }
+                    LapceUICommand::NewTerminal {
+                        title,
+                        cwd,
+                        command,
+                    } => {
+                        let terminal_data =
+                            Arc::new(LapceTerminalData::new_with_command(
+                                title.clone(),
+                                cwd.clone(),
+                                command.clone(),
+                                data.terminal.split_id,
+                                ctx.get_external_handle(),
+                                data.proxy.clone(),
+                                &data.config,
+                            ));
+                        let terminal = LapceTerminalView::new(&terminal_data);
+                        self.insert_flex_child(
+                            self.children.len(),
+                            terminal.boxed(),
+                            Some(terminal_data.widget_id),
+                            1.0,
+                        );
+                        self.even_flex_children();
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::Focus,
+                            Target::Widget(terminal_data.widget_id),
+                        ));
+                        let terminal_panel = Arc::make_mut(&mut data.terminal);
+                        terminal_panel.active = terminal_data.widget_id;
+                        terminal_panel.active_term_id = terminal_data.term_id;
+                        terminal_panel
+                            .terminals
+                            .insert(terminal_data.term_id, terminal_data);
+                        ctx.children_changed();
+                    }
                     _ => (),
                 }
                 return;
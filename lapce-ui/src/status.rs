@@ -11,6 +11,7 @@ use lapce_data::{
     data::{FocusArea, LapceTabData, PanelKind},
     panel::PanelPosition,
 };
+use lapce_rpc::core::LspStatus;
 
 use crate::{svg::get_svg, tab::LapceIcon};
 
@@ -63,6 +64,9 @@ impl LapceStatus {
                     }
                     PanelKind::Search => LapceWorkbenchCommand::ToggleSearchVisual,
                     PanelKind::Problem => LapceWorkbenchCommand::ToggleProblemVisual,
+                    PanelKind::Hierarchy => {
+                        LapceWorkbenchCommand::ToggleHierarchyVisual
+                    }
                 };
 
                 LapceIcon {
@@ -213,7 +217,9 @@ impl Widget<LapceTabData> for LapceStatus {
             return;
         }
 
-        if !old_data.progresses.ptr_eq(&data.progresses) {
+        if !old_data.progresses.ptr_eq(&data.progresses)
+            || !old_data.lsp_status.ptr_eq(&data.lsp_status)
+        {
             ctx.request_paint();
         }
     }
@@ -348,6 +354,38 @@ impl Widget<LapceTabData> for LapceStatus {
             left += 10.0 + text_layout.size().width;
         }
 
+        for (server, status) in data.lsp_status.iter() {
+            let status = match status {
+                LspStatus::Running => continue,
+                LspStatus::Starting => "Starting",
+                LspStatus::Restarting => "Restarting",
+                LspStatus::Stopped => "Stopped",
+                LspStatus::Crashed => "Crashed",
+            };
+            let text_layout = ctx
+                .text()
+                .new_text_layout(format!("{server}: {status}"))
+                .font(
+                    data.config.ui.font_family(),
+                    data.config.ui.font_size() as f64,
+                )
+                .text_color(
+                    data.config
+                        .get_color_unchecked(LapceTheme::EDITOR_FOREGROUND)
+                        .clone(),
+                )
+                .build()
+                .unwrap();
+            ctx.draw_text(
+                &text_layout,
+                Point::new(
+                    left + 10.0,
+                    (size.height - text_layout.size().height) / 2.0,
+                ),
+            );
+            left += 10.0 + text_layout.size().width;
+        }
+
         let icon_padding = (size.height - self.icon_size) / 2.0;
         for icon in self.panel_icons.iter() {
             if icon.rect.contains(self.mouse_pos) {
@@ -25,11 +25,11 @@ use lapce_data::{
         LapceWorkspace, LapceWorkspaceType, PanelKind, WorkProgress,
     },
     document::{BufferContent, LocalBufferKind},
-    editor::EditorLocation,
+    editor::{DocumentHighlights, EditorLocation},
     hover::HoverStatus,
     keypress::{DefaultKeyPressHandler, KeyPressData},
     menu::MenuKind,
-    palette::PaletteStatus,
+    palette::{PaletteStatus, PaletteType},
     panel::{PanelPosition, PanelResizePosition},
     proxy::path_from_url,
 };
@@ -39,9 +39,10 @@ use xi_rope::Rope;
 
 use crate::{
     activity::ActivityBar, alert::AlertBox, completion::CompletionContainer,
-    explorer::FileExplorer, hover::HoverContainer, palette::Palette,
-    picker::FilePicker, plugin::Plugin, problem::new_problem_panel,
-    search::new_search_panel, settings::LapceSettingsPanel,
+    explorer::FileExplorer, hierarchy::new_hierarchy_panel, hover::HoverContainer,
+    palette::Palette, picker::FilePicker, plugin::Plugin,
+    problem::new_problem_panel, rename::RenameBox, search::new_search_panel,
+    settings::LapceSettingsPanel, signature::SignatureContainer,
     source_control::new_source_control_panel, split::split_data_widget,
     status::LapceStatus, svg::get_svg, terminal::TerminalPanel,
 };
@@ -64,6 +65,8 @@ pub struct LapceTab {
     main_split: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     completion: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     hover: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    signature: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
+    rename: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     palette: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     status: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
     picker: WidgetPod<LapceTabData, Box<dyn Widget<LapceTabData>>>,
@@ -90,6 +93,8 @@ impl LapceTab {
         let activity = ActivityBar::new();
         let completion = CompletionContainer::new(&data.completion);
         let hover = HoverContainer::new(&data.hover);
+        let signature = SignatureContainer::new(&data.signature);
+        let rename = RenameBox::new(data);
         let palette = Palette::new(data);
         let status = LapceStatus::new();
 
@@ -118,6 +123,9 @@ impl LapceTab {
         let problem = new_problem_panel(&data.problem);
         panels.insert(PanelKind::Problem, WidgetPod::new(problem.boxed()));
 
+        let hierarchy = new_hierarchy_panel(&data.hierarchy);
+        panels.insert(PanelKind::Hierarchy, WidgetPod::new(hierarchy.boxed()));
+
         let picker = FilePicker::new(data);
 
         let settings =
@@ -131,6 +139,8 @@ impl LapceTab {
             main_split: WidgetPod::new(main_split.boxed()),
             completion: WidgetPod::new(completion.boxed()),
             hover: WidgetPod::new(hover.boxed()),
+            signature: WidgetPod::new(signature.boxed()),
+            rename: WidgetPod::new(rename.boxed()),
             picker: WidgetPod::new(picker.boxed()),
             palette: WidgetPod::new(palette.boxed()),
             status: WidgetPod::new(status.boxed()),
@@ -403,15 +413,17 @@ impl LapceTab {
                         content,
                         locations,
                     } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc).init_content(content.to_owned());
-                        for (view_id, location) in locations {
-                            data.main_split.go_to_location(
-                                ctx,
-                                Some(*view_id),
-                                location.clone(),
-                                &data.config,
-                            );
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).init_content(content.to_owned());
+                            data.main_split.apply_pending_edits(ctx, path);
+                            for (view_id, location) in locations {
+                                data.main_split.go_to_location(
+                                    ctx,
+                                    Some(*view_id),
+                                    location.clone(),
+                                    &data.config,
+                                );
+                            }
                         }
                         ctx.set_handled();
                     }
@@ -521,9 +533,10 @@ impl LapceTab {
                         version,
                         content,
                     } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        let doc = Arc::make_mut(doc);
-                        doc.load_history(version, content.clone());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            let doc = Arc::make_mut(doc);
+                            doc.load_history(version, content.clone());
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateTerminalTitle(term_id, title) => {
@@ -574,23 +587,12 @@ impl LapceTab {
                         let source_control = Arc::make_mut(&mut data.source_control);
                         source_control.branch = diff.head.to_string();
                         source_control.branches = diff.branches.clone();
-                        source_control.file_diffs = diff
-                            .diffs
-                            .iter()
-                            .map(|diff| {
-                                let mut checked = true;
-                                for (p, c) in source_control.file_diffs.iter() {
-                                    if p == diff {
-                                        checked = *c;
-                                        break;
-                                    }
-                                }
-                                (diff.clone(), checked)
-                            })
-                            .collect();
+                        source_control.remote_branches =
+                            diff.remote_branches.clone();
+                        source_control.update_diffs(diff);
 
                         for (_path, doc) in data.main_split.open_docs.iter() {
-                            doc.reload_history("head");
+                            doc.reload_histories();
                         }
                         ctx.set_handled();
                     }
@@ -629,6 +631,17 @@ impl LapceTab {
                             }
                         }
                     }
+                    LapceUICommand::UpdateLspStatus(server, status) => {
+                        data.lsp_status.insert(server.to_owned(), *status);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateSignatureTriggers(path, triggers) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc)
+                                .set_signature_triggers(triggers.clone());
+                        }
+                        ctx.set_handled();
+                    }
                     LapceUICommand::PublishDiagnostics(diagnostics) => {
                         let path = path_from_url(&diagnostics.uri);
                         let diagnostics = diagnostics
@@ -694,6 +707,9 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::ShowAlert(content) => {
+                        if data.alert.active {
+                            data.alert.content.cancel(ctx);
+                        }
                         let alert = Arc::make_mut(&mut data.alert);
                         alert.active = true;
                         alert.content = content.to_owned();
@@ -704,9 +720,37 @@ impl LapceTab {
                         ));
                         ctx.set_handled();
                     }
+                    LapceUICommand::ShowMessageRequest(id, params) => {
+                        data.show_message_request(ctx, *id, params);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::AskGitCredentials {
+                        id,
+                        url,
+                        username,
+                        key,
+                    } => {
+                        ctx.submit_command(Command::new(
+                            LAPCE_UI_COMMAND,
+                            LapceUICommand::RunPalette(Some(
+                                PaletteType::GitCredentials {
+                                    id: *id,
+                                    url: url.clone(),
+                                    username: username.clone(),
+                                    key: key.clone(),
+                                },
+                            )),
+                            Target::Widget(data.palette.widget_id),
+                        ));
+                        ctx.set_handled();
+                    }
                     LapceUICommand::BufferSave(path, rev, exit_widget_id) => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() == *rev {
+                        if let Some(doc) = data
+                            .main_split
+                            .open_docs
+                            .get_mut(path)
+                            .filter(|doc| doc.rev() == *rev)
+                        {
                             Arc::make_mut(doc).buffer_mut().set_pristine();
                             if let Some(widget_id) = exit_widget_id {
                                 ctx.submit_command(Command::new(
@@ -729,14 +773,15 @@ impl LapceTab {
                         editor_view_id,
                         location,
                     } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc).reload(Rope::from(content), true);
-                        data.main_split.go_to_location(
-                            ctx,
-                            Some(*editor_view_id),
-                            location.clone(),
-                            &data.config,
-                        );
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).reload(Rope::from(content), true);
+                            data.main_split.go_to_location(
+                                ctx,
+                                Some(*editor_view_id),
+                                location.clone(),
+                                &data.config,
+                            );
+                        }
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateSettingsFile(parent, key, value) => {
@@ -908,6 +953,83 @@ impl LapceTab {
                             }
                         }
                     }
+                    LapceUICommand::UpdateDocumentHighlights {
+                        view_id,
+                        path,
+                        rev,
+                        offset,
+                        highlights,
+                    } => {
+                        if let Some(doc) = data.main_split.open_docs.get(path) {
+                            if doc.rev() == *rev {
+                                let highlights = DocumentHighlights::new(
+                                    doc,
+                                    *offset,
+                                    highlights.as_deref(),
+                                );
+                                if let Some(editor) =
+                                    data.main_split.editors.get_mut(view_id)
+                                {
+                                    Arc::make_mut(editor).document_highlights =
+                                        Some(Arc::new(highlights));
+                                }
+                            }
+                        }
+                    }
+                    LapceUICommand::UpdateInlayHints(path, rev, hints) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).set_inlay_hints(hints);
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateCodeLens(path, rev, code_lenses) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_code_lenses(code_lenses.clone());
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateBlame(path, rev, blame) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc).set_blame(*rev, blame.clone());
+                            }
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ResolveCodeLens(path, code_lens) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc)
+                                .set_resolved_code_lens(code_lens.clone());
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::RunInTerminal {
+                        title,
+                        cwd,
+                        command,
+                    } => {
+                        data.run_in_terminal(
+                            ctx,
+                            title.clone(),
+                            cwd.clone(),
+                            command.clone(),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateFoldingRanges(path, rev, ranges) => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                Arc::make_mut(doc)
+                                    .set_folding_ranges(ranges.clone());
+                            }
+                        }
+                        ctx.set_handled();
+                    }
                     LapceUICommand::PaletteReferences(offset, locations) => {
                         if let Some(editor) = data.main_split.active_editor() {
                             if *offset == editor.cursor.offset() {
@@ -929,6 +1051,60 @@ impl LapceTab {
                         }
                         ctx.set_handled();
                     }
+                    LapceUICommand::PrepareRename {
+                        editor_view_id,
+                        path,
+                        rev,
+                        offset,
+                        position,
+                        response,
+                    } => {
+                        data.prepare_rename(
+                            ctx,
+                            *editor_view_id,
+                            path,
+                            *rev,
+                            *offset,
+                            *position,
+                            response.as_ref(),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ShowHierarchy { is_call, items } => {
+                        data.show_hierarchy(ctx, *is_call, items.clone());
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateHierarchyChildren {
+                        id,
+                        tree,
+                        path,
+                        items,
+                    } => {
+                        Arc::make_mut(&mut data.hierarchy).set_children(
+                            *id,
+                            *tree,
+                            path,
+                            items.clone(),
+                        );
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ConfirmRename => {
+                        data.confirm_rename(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::CancelRename => {
+                        data.cancel_rename(ctx);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ApplyWorkspaceEdit(edit) => {
+                        let _ = data.main_split.apply_workspace_edit(ctx, edit);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::ApplyWorkspaceEditRequest(id, edit) => {
+                        let result = data.main_split.apply_workspace_edit(ctx, edit);
+                        data.proxy.apply_workspace_edit_response(*id, result);
+                        ctx.set_handled();
+                    }
                     LapceUICommand::SaveAs(content, path, view_id, exit) => {
                         data.main_split.save_as(ctx, content, path, *view_id, *exit);
                         ctx.set_handled();
@@ -946,13 +1122,18 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::OpenFileChanged { path, content } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        let doc = Arc::make_mut(doc);
-                        doc.handle_file_changed(content.to_owned());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            let doc = Arc::make_mut(doc);
+                            doc.handle_file_changed(content.to_owned());
+                        }
                     }
                     LapceUICommand::ReloadBuffer { path, rev, content } => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() + 1 == *rev {
+                        if let Some(doc) = data
+                            .main_split
+                            .open_docs
+                            .get_mut(path)
+                            .filter(|doc| doc.rev() + 1 == *rev)
+                        {
                             let doc = Arc::make_mut(doc);
                             doc.reload(content.to_owned(), true);
 
@@ -990,14 +1171,64 @@ impl LapceTab {
                         ctx.set_handled();
                     }
                     LapceUICommand::UpdateSemanticStyles(_id, path, rev, styles) => {
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        if doc.rev() == *rev {
+                        if let Some(doc) = data
+                            .main_split
+                            .open_docs
+                            .get_mut(path)
+                            .filter(|doc| doc.rev() == *rev)
+                        {
                             let doc = Arc::make_mut(doc);
                             doc.set_semantic_styles(Some(styles.clone()));
                         }
 
                         ctx.set_handled();
                     }
+                    LapceUICommand::OpenLspLog(name) => {
+                        data.main_split.open_lsp_log(ctx, name, &data.config);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::UpdateLspLog { name, content } => {
+                        data.main_split.update_lsp_log(ctx, name, content.clone());
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::AppendLspLog { name, entry } => {
+                        data.main_split.append_lsp_log(ctx, name, entry);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::OpenURI(uri) => {
+                        if let Err(e) = open_uri(uri) {
+                            log::error!("failed to open {uri}: {e}");
+                        }
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::CloseBuffers(buffer_ids) => {
+                        data.main_split.close_buffers(buffer_ids);
+                        ctx.set_handled();
+                    }
+                    LapceUICommand::EditSemanticStyles {
+                        buffer_id,
+                        path,
+                        rev,
+                        base_rev,
+                        start,
+                        end,
+                        styles,
+                    } => {
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            if doc.rev() == *rev {
+                                let doc = Arc::make_mut(doc);
+                                if !doc.edit_semantic_styles(
+                                    *base_rev,
+                                    *start,
+                                    *end,
+                                    styles.clone(),
+                                ) {
+                                    data.proxy.refresh_semantic_styles(*buffer_id);
+                                }
+                            }
+                        }
+                        ctx.set_handled();
+                    }
                     LapceUICommand::Focus => {
                         let dir = data
                             .workspace
@@ -1063,7 +1294,10 @@ impl LapceTab {
                         ctx.set_handled();
                         let doc = match content {
                             BufferContent::File(path) => {
-                                data.main_split.open_docs.get_mut(path).unwrap()
+                                match data.main_split.open_docs.get_mut(path) {
+                                    Some(doc) => doc,
+                                    None => return,
+                                }
                             }
                             BufferContent::Local(kind) => {
                                 data.main_split.local_docs.get_mut(kind).unwrap()
@@ -1074,6 +1308,12 @@ impl LapceTab {
                             BufferContent::Scratch(id, _) => {
                                 data.main_split.scratch_docs.get_mut(id).unwrap()
                             }
+                            BufferContent::LspLog(name) => {
+                                match data.main_split.log_docs.get_mut(name) {
+                                    Some(doc) => doc,
+                                    None => return,
+                                }
+                            }
                         };
                         let doc = Arc::make_mut(doc);
                         if doc.rev() == *rev {
@@ -1090,12 +1330,13 @@ impl LapceTab {
                         ..
                     } => {
                         ctx.set_handled();
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc).update_history_changes(
-                            *rev,
-                            history,
-                            changes.clone(),
-                        );
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).update_history_changes(
+                                *rev,
+                                history,
+                                changes.clone(),
+                            );
+                        }
                     }
                     LapceUICommand::UpdateHistoryStyle {
                         path,
@@ -1104,9 +1345,12 @@ impl LapceTab {
                         ..
                     } => {
                         ctx.set_handled();
-                        let doc = data.main_split.open_docs.get_mut(path).unwrap();
-                        Arc::make_mut(doc)
-                            .update_history_styles(history, highlights.to_owned());
+                        if let Some(doc) = data.main_split.open_docs.get_mut(path) {
+                            Arc::make_mut(doc).update_history_styles(
+                                history,
+                                highlights.to_owned(),
+                            );
+                        }
                     }
                     LapceUICommand::UpdatePickerPwd(path) => {
                         Arc::make_mut(&mut data.picker).pwd = path.clone();
@@ -1173,6 +1417,12 @@ impl Widget<LapceTabData> for LapceTab {
         {
             self.hover.event(ctx, event, data, env);
         }
+        if data.signature.is_shown() || event.should_propagate_to_hidden() {
+            self.signature.event(ctx, event, data, env);
+        }
+        if data.rename.active || event.should_propagate_to_hidden() {
+            self.rename.event(ctx, event, data, env);
+        }
 
         if !event.should_propagate_to_hidden() && !ctx.is_handled() {
             self.handle_event(ctx, event, data, env);
@@ -1210,6 +1460,10 @@ impl Widget<LapceTabData> for LapceTab {
             }
             _ => (),
         }
+
+        if data.rename.active && data.focus != data.rename.view_id {
+            Arc::make_mut(&mut data.rename).cancel();
+        }
     }
 
     fn lifecycle(
@@ -1232,6 +1486,8 @@ impl Widget<LapceTabData> for LapceTab {
         self.status.lifecycle(ctx, event, data, env);
         self.completion.lifecycle(ctx, event, data, env);
         self.hover.lifecycle(ctx, event, data, env);
+        self.signature.lifecycle(ctx, event, data, env);
+        self.rename.lifecycle(ctx, event, data, env);
         self.picker.lifecycle(ctx, event, data, env);
         self.settings.lifecycle(ctx, event, data, env);
         self.alert.lifecycle(ctx, event, data, env);
@@ -1289,6 +1545,8 @@ impl Widget<LapceTabData> for LapceTab {
         self.main_split.update(ctx, data, env);
         self.completion.update(ctx, data, env);
         self.hover.update(ctx, data, env);
+        self.signature.update(ctx, data, env);
+        self.rename.update(ctx, data, env);
         self.status.update(ctx, data, env);
         self.picker.update(ctx, data, env);
         self.settings.update(ctx, data, env);
@@ -1561,6 +1819,24 @@ impl Widget<LapceTabData> for LapceTab {
             self.hover.set_origin(ctx, data, env, hover_origin);
         }
 
+        if data.signature.is_shown() {
+            let signature_size = self.signature.layout(ctx, bc, data, env);
+            let signature_origin = data.signature_origin(
+                ctx.text(),
+                self_size,
+                signature_size,
+                &data.config,
+            );
+            self.signature.set_origin(ctx, data, env, signature_origin);
+        }
+
+        if data.rename.active {
+            self.rename.layout(ctx, bc, data, env);
+            let rename_origin =
+                data.rename_origin(ctx.text(), self_size, &data.config);
+            self.rename.set_origin(ctx, data, env, rename_origin);
+        }
+
         if data.palette.status != PaletteStatus::Inactive {
             let palette_size = self.palette.layout(ctx, bc, data, env);
             self.palette.set_origin(
@@ -1668,8 +1944,10 @@ impl Widget<LapceTabData> for LapceTab {
         //     //     .paint_svg(ctx, data, active_index, kind);
         // }
         self.status.paint(ctx, data, env);
+        self.signature.paint(ctx, data, env);
         self.completion.paint(ctx, data, env);
         self.hover.paint(ctx, data, env);
+        self.rename.paint(ctx, data, env);
         self.palette.paint(ctx, data, env);
         self.picker.paint(ctx, data, env);
         self.settings.paint(ctx, data, env);
@@ -1843,3 +2121,24 @@ impl Widget<LapceTabData> for LapceTabHeader {
         }
     }
 }
+
+/// Opens the link with the application the system has for it.
+fn open_uri(uri: &str) -> std::io::Result<()> {
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = std::process::Command::new("xdg-open");
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    // `cmd /C start` would parse the link as a command line, so it goes to
+    // ShellExecute through url.dll instead, which takes it as it is.
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = std::process::Command::new("rundll32.exe");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+
+    let mut child = command.arg(uri).spawn()?;
+    // The opener exits once it has handed the link over.
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
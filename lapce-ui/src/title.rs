@@ -411,7 +411,7 @@ impl Widget<LapceWindowData> for Title {
 
             let command_rect =
                 command_rect.with_size(Size::new(x - command_rect.x0, size.height));
-            let menu_items = tab
+            let mut menu_items: Vec<MenuKind> = tab
                 .source_control
                 .branches
                 .iter()
@@ -427,6 +427,36 @@ impl Widget<LapceWindowData> for Title {
                     })
                 })
                 .collect();
+            for commands in [
+                &[
+                    LapceWorkbenchCommand::CreateBranch,
+                    LapceWorkbenchCommand::RenameBranch,
+                    LapceWorkbenchCommand::DeleteBranch,
+                ][..],
+                &[
+                    LapceWorkbenchCommand::Fetch,
+                    LapceWorkbenchCommand::Pull,
+                    LapceWorkbenchCommand::PullRebase,
+                    LapceWorkbenchCommand::Push,
+                ],
+                &[
+                    LapceWorkbenchCommand::StashChanges,
+                    LapceWorkbenchCommand::ApplyStash,
+                    LapceWorkbenchCommand::PopStash,
+                    LapceWorkbenchCommand::DropStash,
+                ],
+            ] {
+                menu_items.push(MenuKind::Separator);
+                for command in commands {
+                    menu_items.push(MenuKind::Item(MenuItem {
+                        desc: None,
+                        command: LapceCommand {
+                            kind: CommandKind::Workbench(command.clone()),
+                            data: None,
+                        },
+                    }));
+                }
+            }
             self.commands.push((
                 command_rect,
                 Command::new(
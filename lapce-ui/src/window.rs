@@ -211,10 +211,14 @@ impl Widget<LapceWindowData> for LapceWindow {
                                 .unwrap_or_default(),
                         );
                         for (_, tab) in data.tabs.iter_mut() {
-                            tab.config = Arc::new(
+                            let config = Arc::new(
                                 Config::load(&tab.workspace.clone())
                                     .unwrap_or_default(),
                             );
+                            if config.lsp != tab.config.lsp {
+                                tab.proxy.update_lsp_settings(&config.lsp);
+                            }
+                            tab.config = config;
                         }
                         Arc::make_mut(&mut data.keypress)
                             .update_keymaps(&data.config);